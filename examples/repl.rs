@@ -0,0 +1,22 @@
+//! Type raw CDP commands against a page
+//!
+//! ```sh
+//! cargo run --example repl --features repl -- https://example.com
+//! ```
+
+use chrome_cdp::{CdpBrowser, Repl, Result};
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let url = std::env::args().nth(1);
+    let browser = CdpBrowser::launch(None, vec![], true, false).await?;
+    let page = browser.new_page_connected(url.as_deref()).await?;
+    let schema = browser.protocol_schema().await?;
+
+    let repl = Repl::new(page.connection().clone(), &schema);
+    println!(
+        "{} commands available; type `help` for usage",
+        repl.methods().len()
+    );
+    repl.run().await
+}
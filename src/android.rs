@@ -0,0 +1,214 @@
+//! Chrome on Android devices, reached through adb port forwarding
+
+use crate::{Error, Result};
+use std::path::PathBuf;
+use std::process::Stdio;
+
+/// DevTools socket of Chrome on Android
+pub const CHROME_DEVTOOLS_SOCKET: &str = "chrome_devtools_remote";
+
+/// Options for [`CdpBrowser::connect_android`](crate::CdpBrowser::connect_android)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AndroidOptions {
+    /// Serial of the device, as listed by [`android_devices`]; required when several are connected
+    pub serial: Option<String>,
+    /// Abstract socket to forward (default: [`CHROME_DEVTOOLS_SOCKET`])
+    ///
+    /// Other Chromium browsers and WebViews use their own names, e.g.
+    /// `webview_devtools_remote_<pid>`.
+    pub socket: String,
+    /// Local port to forward from; 0 lets adb pick a free one
+    pub local_port: u16,
+    /// adb executable; falls back to `ADB`, then `$ANDROID_HOME/platform-tools/adb`, then `adb` on the `PATH`
+    pub adb_path: Option<PathBuf>,
+}
+
+impl Default for AndroidOptions {
+    fn default() -> Self {
+        Self {
+            serial: None,
+            socket: CHROME_DEVTOOLS_SOCKET.to_string(),
+            local_port: 0,
+            adb_path: None,
+        }
+    }
+}
+
+impl AndroidOptions {
+    /// Target the device with the given serial
+    pub fn device(serial: impl Into<String>) -> Self {
+        Self {
+            serial: Some(serial.into()),
+            ..Default::default()
+        }
+    }
+}
+
+/// Device listed by `adb devices`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AndroidDevice {
+    /// Serial to pass as [`AndroidOptions::serial`]
+    pub serial: String,
+    /// Connection state, `device` once it is ready; `unauthorized` until USB debugging is allowed
+    pub state: String,
+    /// Model name, if adb reported one
+    pub model: Option<String>,
+}
+
+/// List the Android devices adb can see
+pub async fn android_devices() -> Result<Vec<AndroidDevice>> {
+    let output = Adb::new(None, None).run(&["devices", "-l"]).await?;
+    Ok(parse_devices(&output))
+}
+
+/// adb invocation for one device
+#[derive(Debug, Clone)]
+struct Adb {
+    executable: PathBuf,
+    serial: Option<String>,
+}
+
+impl Adb {
+    fn new(executable: Option<PathBuf>, serial: Option<String>) -> Self {
+        let executable = executable
+            .or_else(|| std::env::var_os("ADB").map(PathBuf::from))
+            .or_else(|| {
+                let home = std::env::var_os("ANDROID_HOME")?;
+                let adb = PathBuf::from(home).join("platform-tools").join("adb");
+                adb.exists().then_some(adb)
+            })
+            .unwrap_or_else(|| PathBuf::from("adb"));
+        Self { executable, serial }
+    }
+
+    fn command(&self, args: &[&str]) -> std::process::Command {
+        let mut command = std::process::Command::new(&self.executable);
+        if let Some(serial) = &self.serial {
+            command.args(["-s", serial]);
+        }
+        command.args(args);
+        command
+    }
+
+    /// Run adb, returning its stdout
+    async fn run(&self, args: &[&str]) -> Result<String> {
+        let mut command = tokio::process::Command::from(self.command(args));
+        let output = command.stdin(Stdio::null()).output().await.map_err(|e| {
+            Error::Browser(format!(
+                "Failed to run {}: {}",
+                self.executable.display(),
+                e
+            ))
+        })?;
+        if !output.status.success() {
+            return Err(Error::Browser(format!(
+                "adb {} failed: {}",
+                args.join(" "),
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+}
+
+/// Local port forwarded to a device socket, removed on drop
+#[derive(Debug)]
+pub(crate) struct AdbForward {
+    adb: Adb,
+    port: u16,
+}
+
+impl AdbForward {
+    /// Forward a local TCP port to the device's abstract socket
+    pub(crate) async fn open(options: &AndroidOptions) -> Result<Self> {
+        let adb = Adb::new(options.adb_path.clone(), options.serial.clone());
+        let local = format!("tcp:{}", options.local_port);
+        let remote = format!("localabstract:{}", options.socket);
+        let output = adb.run(&["forward", &local, &remote]).await?;
+        // adb prints the port it picked for tcp:0, and nothing otherwise
+        let port = match options.local_port {
+            0 => output.trim().parse().map_err(|_| {
+                Error::Browser(format!("adb forward reported no port: {:?}", output.trim()))
+            })?,
+            port => port,
+        };
+        Ok(Self { adb, port })
+    }
+
+    pub(crate) fn port(&self) -> u16 {
+        self.port
+    }
+}
+
+impl Drop for AdbForward {
+    fn drop(&mut self) {
+        // Best effort: a stale forward only occupies a local port until adb restarts
+        let _ = self
+            .adb
+            .command(&["forward", "--remove", &format!("tcp:{}", self.port)])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status();
+    }
+}
+
+/// Parse the output of `adb devices -l`
+fn parse_devices(output: &str) -> Vec<AndroidDevice> {
+    output
+        .lines()
+        .filter(|line| !line.starts_with("List of devices") && !line.starts_with('*'))
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let serial = fields.next()?.to_string();
+            let state = fields.next()?.to_string();
+            let model = fields
+                .find_map(|field| field.strip_prefix("model:"))
+                .map(|model| model.replace('_', " "));
+            Some(AndroidDevice {
+                serial,
+                state,
+                model,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_devices() {
+        let output = "* daemon started successfully\n\
+                      List of devices attached\n\
+                      R58M123ABC     device usb:1-1 product:beyond1 model:SM_G973F device:beyond1 transport_id:1\n\
+                      emulator-5554  unauthorized transport_id:2\n\n";
+        assert_eq!(
+            parse_devices(output),
+            [
+                AndroidDevice {
+                    serial: "R58M123ABC".to_string(),
+                    state: "device".to_string(),
+                    model: Some("SM G973F".to_string()),
+                },
+                AndroidDevice {
+                    serial: "emulator-5554".to_string(),
+                    state: "unauthorized".to_string(),
+                    model: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_adb_targets_serial() {
+        let adb = Adb::new(
+            Some(PathBuf::from("/opt/adb")),
+            Some("emulator-5554".into()),
+        );
+        let command = adb.command(&["forward", "--list"]);
+        assert_eq!(command.get_program(), "/opt/adb");
+        let args: Vec<_> = command.get_args().collect();
+        assert_eq!(args, ["-s", "emulator-5554", "forward", "--list"]);
+    }
+}
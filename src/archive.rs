@@ -0,0 +1,203 @@
+//! Single-file HTML export with inlined resources
+
+use base64::Engine;
+use regex::{Captures, Regex};
+use reqwest::Url;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::LazyLock;
+
+/// CSS `url(...)` reference, quoted or not
+static CSS_URL: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"url\(\s*(?:"([^"]*)"|'([^']*)'|([^)\s]*))\s*\)"#).expect("valid CSS url regex")
+});
+
+/// Function serializing `this` document with resources inlined
+///
+/// Called with `(dataUris, stylesheets)`, both keyed by absolute URL.
+/// Scripts are dropped because the DOM is already rendered.
+pub(crate) const INLINE_FN: &str = r#"function(dataUris, stylesheets) {
+  const resolve = (value) => {
+    try { return new URL(value, this.baseURI).href; } catch (e) { return value; }
+  };
+  const inlineCss = (css) => css.replace(
+    /url\(\s*(?:"([^"]*)"|'([^']*)'|([^)\s]*))\s*\)/g,
+    (match, a, b, c) => {
+      const uri = dataUris[resolve(a ?? b ?? c)];
+      return uri ? `url("${uri}")` : match;
+    });
+  const inlineAttr = (el, name) => {
+    const uri = dataUris[resolve(el.getAttribute(name))];
+    if (uri) el.setAttribute(name, uri);
+    return !!uri;
+  };
+
+  const root = this.documentElement.cloneNode(true);
+  for (const el of root.querySelectorAll("script")) el.remove();
+  for (const el of root.querySelectorAll("style")) el.textContent = inlineCss(el.textContent);
+  for (const el of root.querySelectorAll("[style]")) {
+    el.setAttribute("style", inlineCss(el.getAttribute("style")));
+  }
+  for (const link of root.querySelectorAll('link[rel~="stylesheet"][href]')) {
+    const css = stylesheets[resolve(link.getAttribute("href"))];
+    if (css === undefined) continue;
+    const style = this.createElement("style");
+    if (link.media) style.media = link.media;
+    style.textContent = css;
+    link.replaceWith(style);
+  }
+  for (const img of root.querySelectorAll("img[src]")) {
+    if (inlineAttr(img, "src")) img.removeAttribute("srcset");
+  }
+  for (const el of root.querySelectorAll('link[rel~="icon"][href]')) inlineAttr(el, "href");
+  for (const el of root.querySelectorAll("video[poster]")) inlineAttr(el, "poster");
+  return "<!DOCTYPE html>\n" + root.outerHTML;
+}"#;
+
+/// Resource bodies collected for inlining, keyed by absolute URL
+#[derive(Debug, Default)]
+pub(crate) struct InlineResources {
+    pub(crate) data_uris: HashMap<String, String>,
+    pub(crate) stylesheets: HashMap<String, String>,
+}
+
+impl InlineResources {
+    /// Add a `Page.getResourceContent` result for a resource of the given type
+    pub(crate) fn add(&mut self, url: &str, resource_type: &str, mime_type: &str, content: &Value) {
+        let text = content["content"].as_str().unwrap_or_default();
+        let base64_encoded = content["base64Encoded"].as_bool().unwrap_or(false);
+        let engine = &base64::engine::general_purpose::STANDARD;
+
+        match resource_type {
+            "Stylesheet" => {
+                let css = if base64_encoded {
+                    let bytes = engine.decode(text).unwrap_or_default();
+                    String::from_utf8_lossy(&bytes).into_owned()
+                } else {
+                    text.to_string()
+                };
+                self.stylesheets.insert(url.to_string(), css);
+            }
+            "Image" | "Font" => {
+                let data = if base64_encoded {
+                    text.to_string()
+                } else {
+                    engine.encode(text)
+                };
+                self.data_uris.insert(
+                    url.to_string(),
+                    format!("data:{};base64,{}", mime_type, data),
+                );
+            }
+            _ => {}
+        }
+    }
+
+    /// Rewrite `url(...)` references in every stylesheet to data URIs
+    ///
+    /// Must run after all images and fonts were added, since references are
+    /// resolved against each stylesheet's own URL.
+    pub(crate) fn inline_stylesheets(&mut self) {
+        for (url, css) in self.stylesheets.iter_mut() {
+            *css = inline_css_urls(css, url, &self.data_uris);
+        }
+    }
+}
+
+/// Replace CSS `url(...)` references resolved against `base` with known data URIs
+pub(crate) fn inline_css_urls(
+    css: &str,
+    base: &str,
+    data_uris: &HashMap<String, String>,
+) -> String {
+    let Ok(base) = Url::parse(base) else {
+        return css.to_string();
+    };
+    CSS_URL
+        .replace_all(css, |caps: &Captures| {
+            let reference = caps
+                .get(1)
+                .or_else(|| caps.get(2))
+                .or_else(|| caps.get(3))
+                .map_or("", |m| m.as_str());
+            base.join(reference)
+                .ok()
+                .and_then(|url| data_uris.get(url.as_str()))
+                .map_or_else(|| caps[0].to_string(), |uri| format!("url(\"{}\")", uri))
+        })
+        .into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_inline_css_urls_resolves_against_stylesheet() {
+        let data_uris = HashMap::from([(
+            "https://cdn.example/fonts/a.woff2".to_string(),
+            "data:font/woff2;base64,AAAA".to_string(),
+        )]);
+        let css =
+            r#"@font-face { src: url('../fonts/a.woff2') } .x { background: url(missing.png) }"#;
+        let inlined = inline_css_urls(css, "https://cdn.example/css/site.css", &data_uris);
+        assert_eq!(
+            inlined,
+            r#"@font-face { src: url("data:font/woff2;base64,AAAA") } .x { background: url(missing.png) }"#
+        );
+    }
+
+    #[test]
+    fn test_inline_css_urls_keeps_data_uris() {
+        let css = r#"a { background: url("data:image/png;base64,xyz") }"#;
+        assert_eq!(
+            inline_css_urls(css, "https://site.example/", &HashMap::new()),
+            css
+        );
+    }
+
+    #[test]
+    fn test_add_resources() {
+        let mut resources = InlineResources::default();
+        resources.add(
+            "https://site.example/logo.png",
+            "Image",
+            "image/png",
+            &json!({"content": "iVBO", "base64Encoded": true}),
+        );
+        resources.add(
+            "https://site.example/icon.svg",
+            "Image",
+            "image/svg+xml",
+            &json!({"content": "<svg/>", "base64Encoded": false}),
+        );
+        resources.add(
+            "https://site.example/site.css",
+            "Stylesheet",
+            "text/css",
+            &json!({"content": "h1 { background: url(logo.png) }", "base64Encoded": false}),
+        );
+        resources.add(
+            "https://site.example/app.js",
+            "Script",
+            "text/javascript",
+            &json!({"content": "alert(1)"}),
+        );
+        resources.inline_stylesheets();
+
+        assert_eq!(
+            resources.data_uris["https://site.example/logo.png"],
+            "data:image/png;base64,iVBO"
+        );
+        assert_eq!(
+            resources.data_uris["https://site.example/icon.svg"],
+            "data:image/svg+xml;base64,PHN2Zy8+"
+        );
+        assert_eq!(
+            resources.stylesheets["https://site.example/site.css"],
+            r#"h1 { background: url("data:image/png;base64,iVBO") }"#
+        );
+        assert_eq!(resources.data_uris.len(), 2);
+    }
+}
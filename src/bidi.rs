@@ -0,0 +1,548 @@
+//! Experimental WebDriver BiDi backend, for driving Firefox
+
+use crate::driver::PageDriver;
+use crate::page::{decode_screenshot, exception_error, ELEMENT_EXISTS_FN};
+use crate::process::BrowserProcess;
+use crate::{CdpConnection, Error, LaunchFailure, Result, Timeouts};
+use futures::future::BoxFuture;
+use serde_json::{json, Value};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+use tokio::io::AsyncBufReadExt;
+
+/// Options for [`BidiBrowser::launch`]
+#[derive(Debug, Clone)]
+pub struct BidiLaunchOptions {
+    /// Firefox executable; falls back to `FIREFOX_BIN`, then `firefox` on the `PATH`
+    pub executable_path: Option<PathBuf>,
+    /// Run without a visible window
+    pub headless: bool,
+    /// Extra command-line arguments
+    pub args: Vec<String>,
+    /// Launch, command and wait timeouts
+    pub timeouts: Timeouts,
+}
+
+impl Default for BidiLaunchOptions {
+    fn default() -> Self {
+        Self {
+            executable_path: None,
+            headless: true,
+            args: Vec::new(),
+            timeouts: Timeouts::default(),
+        }
+    }
+}
+
+/// Browser driven over WebDriver BiDi, such as Firefox
+///
+/// Experimental: only the operations of [`PageDriver`] are covered so far.
+///
+/// ```no_run
+/// # async fn run() -> chrome_cdp::Result<()> {
+/// use chrome_cdp::{BidiBrowser, BidiLaunchOptions, PageDriver};
+///
+/// let browser = BidiBrowser::launch(BidiLaunchOptions::default()).await?;
+/// let page = browser.new_page().await?;
+/// page.goto("https://example.com").await?;
+/// page.wait_for_selector("h1").await?;
+/// let png = page.screenshot().await?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct BidiBrowser {
+    connection: CdpConnection,
+    timeouts: Timeouts,
+    process: Option<BrowserProcess>,
+    profile: Option<PathBuf>,
+}
+
+impl BidiBrowser {
+    /// Launch Firefox with its WebDriver BiDi server enabled
+    pub async fn launch(options: BidiLaunchOptions) -> Result<Self> {
+        let executable = options
+            .executable_path
+            .or_else(|| std::env::var_os("FIREFOX_BIN").map(PathBuf::from))
+            .unwrap_or_else(|| PathBuf::from("firefox"));
+        let profile = std::env::temp_dir().join(format!("firefox-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&profile)?;
+
+        let mut cmd = Command::new(&executable);
+        // Port 0 lets the OS assign a random port
+        cmd.arg("--remote-debugging-port=0");
+        cmd.arg("--no-remote");
+        cmd.arg("--profile").arg(&profile);
+        if options.headless {
+            cmd.arg("--headless");
+        }
+        cmd.args(&options.args);
+        cmd.stdout(Stdio::null());
+        cmd.stderr(Stdio::piped());
+
+        let mut child = BrowserProcess::spawn(&mut cmd).inspect_err(|_| {
+            let _ = std::fs::remove_dir_all(&profile);
+        })?;
+        let stderr = child
+            .child()
+            .stderr
+            .take()
+            .ok_or_else(|| Error::Browser("Firefox's stderr was not captured".to_string()))?;
+        let mut lines =
+            tokio::io::BufReader::new(tokio::process::ChildStderr::from_std(stderr)?).lines();
+
+        let launch_timeout = options.timeouts.launch_timeout();
+        let mut seen = Vec::new();
+        let endpoint = tokio::time::timeout(launch_timeout, async {
+            while let Ok(Some(line)) = lines.next_line().await {
+                if let Some(endpoint) = bidi_endpoint(&line) {
+                    return Some(endpoint);
+                }
+                seen.push(line);
+            }
+            None
+        })
+        .await;
+        let Ok(Some(endpoint)) = endpoint else {
+            let exit_status = child.try_reap();
+            if exit_status.is_none() {
+                let _ = child.kill_tree();
+            }
+            // The profile is kept for inspection, as the error points at it
+            let tail = seen.len().saturating_sub(100);
+            return Err(Error::LaunchFailed(Box::new(LaunchFailure {
+                executable,
+                user_data_dir: profile,
+                os: format!("{} {}", std::env::consts::OS, std::env::consts::ARCH),
+                stderr_tail: seen.split_off(tail),
+                exit_status: exit_status.map(|status| status.to_string()),
+                timeout: launch_timeout,
+            })));
+        };
+        // Keep draining stderr so Firefox never blocks on a full pipe
+        tokio::spawn(async move { while let Ok(Some(_)) = lines.next_line().await {} });
+
+        match Self::connect_with_timeouts(&endpoint, options.timeouts).await {
+            Ok(mut browser) => {
+                browser.process = Some(child);
+                browser.profile = Some(profile);
+                Ok(browser)
+            }
+            Err(e) => {
+                let _ = child.kill_tree();
+                let _ = std::fs::remove_dir_all(&profile);
+                Err(e)
+            }
+        }
+    }
+
+    /// Attach to a running WebDriver BiDi server, e.g. `ws://127.0.0.1:9222/session`
+    ///
+    /// A bare `ws://host:port` endpoint gets the `/session` path appended.
+    pub async fn connect(ws_url: &str) -> Result<Self> {
+        Self::connect_with_timeouts(ws_url, Timeouts::default()).await
+    }
+
+    async fn connect_with_timeouts(ws_url: &str, timeouts: Timeouts) -> Result<Self> {
+        let ws_url = session_url(ws_url);
+        let connection = CdpConnection::connect(&ws_url).await?;
+        connection.set_command_timeout(Some(timeouts.command_timeout()));
+        connection
+            .send_command("session.new", json!({ "capabilities": {} }))
+            .await
+            .map_err(|e| Error::Browser(format!("Failed to start BiDi session: {}", e)))?;
+        Ok(Self {
+            connection,
+            timeouts,
+            process: None,
+            profile: None,
+        })
+    }
+
+    /// Underlying connection, for BiDi commands not wrapped here
+    pub fn connection(&self) -> &CdpConnection {
+        &self.connection
+    }
+
+    /// Open a new tab
+    pub async fn new_page(&self) -> Result<BidiPage> {
+        let created = self
+            .connection
+            .send_command("browsingContext.create", json!({ "type": "tab" }))
+            .await
+            .map_err(|e| Error::Browser(format!("Failed to create tab: {}", e)))?;
+        let context = created["context"]
+            .as_str()
+            .ok_or_else(|| Error::Browser("browsingContext.create returned no context".into()))?;
+        Ok(BidiPage {
+            connection: self.connection.clone(),
+            context: context.to_string(),
+            timeouts: self.timeouts,
+        })
+    }
+
+    /// Shut the browser down, killing a launched process that does not exit in time
+    pub async fn close(mut self) -> Result<()> {
+        let closed = tokio::time::timeout(
+            self.timeouts.close_timeout(),
+            self.connection.send_command("browser.close", json!({})),
+        )
+        .await;
+        if let Some(mut process) = self.process.take() {
+            let deadline = Instant::now() + self.timeouts.close_timeout();
+            while !process.has_exited() && Instant::now() < deadline {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+            }
+            process.kill_tree()?;
+            return Ok(());
+        }
+        match closed {
+            // The browser may drop the connection before answering
+            Ok(Ok(_)) | Ok(Err(Error::WebSocket(_))) => Ok(()),
+            Ok(Err(e)) => Err(Error::Browser(format!("Failed to close browser: {}", e))),
+            Err(_) => Err(Error::Timeout("Browser did not close in time".to_string())),
+        }
+    }
+}
+
+impl Drop for BidiBrowser {
+    fn drop(&mut self) {
+        if let Some(mut process) = self.process.take() {
+            let _ = process.kill_tree();
+        }
+        if let Some(profile) = self.profile.take() {
+            // Best effort: a failure only leaves the directory behind
+            let _ = std::fs::remove_dir_all(profile);
+        }
+    }
+}
+
+/// Tab of a [`BidiBrowser`], identified by its browsing context
+pub struct BidiPage {
+    connection: CdpConnection,
+    context: String,
+    timeouts: Timeouts,
+}
+
+impl BidiPage {
+    /// Browsing context id of the tab
+    pub fn context(&self) -> &str {
+        &self.context
+    }
+
+    /// Start navigating to `url`
+    pub async fn goto(&self, url: &str) -> Result<()> {
+        self.connection
+            .send_command(
+                "browsingContext.navigate",
+                json!({ "context": self.context, "url": url, "wait": "none" }),
+            )
+            .await
+            .map_err(|e| Error::Browser(format!("Failed to navigate to '{}': {}", url, e)))?;
+        Ok(())
+    }
+
+    /// Evaluate an expression, awaiting promises, and return its value as JSON
+    ///
+    /// Values without a JSON form, such as DOM nodes or functions, become `null`.
+    pub async fn evaluate(&self, expression: &str) -> Result<Value> {
+        let result = self
+            .connection
+            .send_command(
+                "script.evaluate",
+                json!({
+                    "expression": expression,
+                    "target": { "context": self.context },
+                    "awaitPromise": true,
+                    "resultOwnership": "none"
+                }),
+            )
+            .await?;
+        script_result(&result)
+    }
+
+    /// Call a function with `this` unset and the given JSON arguments
+    pub async fn call_function(&self, function_declaration: &str, args: &[Value]) -> Result<Value> {
+        let arguments: Vec<Value> = args.iter().map(local_value).collect();
+        let result = self
+            .connection
+            .send_command(
+                "script.callFunction",
+                json!({
+                    "functionDeclaration": function_declaration,
+                    "arguments": arguments,
+                    "target": { "context": self.context },
+                    "awaitPromise": true,
+                    "resultOwnership": "none"
+                }),
+            )
+            .await?;
+        script_result(&result)
+    }
+
+    /// Wait until an element matching the selector exists, bounded by the wait timeout
+    pub async fn wait_for_selector(&self, selector: &str) -> Result<()> {
+        // `this` is undefined in BiDi calls, so the document is passed explicitly
+        let exists = format!(
+            "function(selector) {{ return ({}).call(document, selector); }}",
+            ELEMENT_EXISTS_FN
+        );
+        let timeout = self.timeouts.wait_timeout();
+        let start = Instant::now();
+        loop {
+            let found = self.call_function(&exists, &[json!(selector)]).await?;
+            if found.as_bool().unwrap_or(false) {
+                return Ok(());
+            }
+            if start.elapsed() >= timeout {
+                return Err(Error::Timeout(format!(
+                    "No element matched '{}' within {:?}",
+                    selector, timeout
+                )));
+            }
+            tokio::time::sleep(Duration::from_millis(500)).await;
+        }
+    }
+
+    /// Capture a PNG screenshot of the viewport
+    pub async fn screenshot(&self) -> Result<Vec<u8>> {
+        let result = self
+            .connection
+            .send_command(
+                "browsingContext.captureScreenshot",
+                json!({ "context": self.context }),
+            )
+            .await
+            .map_err(|e| Error::Browser(format!("Failed to capture screenshot: {}", e)))?;
+        decode_screenshot(&result)
+    }
+
+    /// Close the tab
+    pub async fn close(&self) -> Result<()> {
+        self.connection
+            .send_command("browsingContext.close", json!({ "context": self.context }))
+            .await
+            .map_err(|e| Error::Browser(format!("Failed to close tab: {}", e)))?;
+        Ok(())
+    }
+}
+
+impl PageDriver for BidiPage {
+    fn goto<'a>(&'a self, url: &'a str) -> BoxFuture<'a, Result<()>> {
+        Box::pin(BidiPage::goto(self, url))
+    }
+
+    fn wait_for_selector<'a>(&'a self, selector: &'a str) -> BoxFuture<'a, Result<()>> {
+        Box::pin(BidiPage::wait_for_selector(self, selector))
+    }
+
+    fn evaluate<'a>(&'a self, expression: &'a str) -> BoxFuture<'a, Result<Value>> {
+        Box::pin(BidiPage::evaluate(self, expression))
+    }
+
+    fn screenshot(&self) -> BoxFuture<'_, Result<Vec<u8>>> {
+        Box::pin(BidiPage::screenshot(self))
+    }
+
+    fn close(&self) -> BoxFuture<'_, Result<()>> {
+        Box::pin(BidiPage::close(self))
+    }
+}
+
+/// Endpoint announced by Firefox's `WebDriver BiDi listening on ws://...` line
+fn bidi_endpoint(line: &str) -> Option<String> {
+    let (_, url) = line.split_once("WebDriver BiDi listening on ")?;
+    Some(url.trim().to_string())
+}
+
+/// Session endpoint for a BiDi server URL
+fn session_url(ws_url: &str) -> String {
+    let ws_url = ws_url.trim_end_matches('/');
+    if ws_url.ends_with("/session") {
+        ws_url.to_string()
+    } else {
+        format!("{}/session", ws_url)
+    }
+}
+
+/// Value of a `script.evaluate` or `script.callFunction` result, or its exception
+fn script_result(result: &Value) -> Result<Value> {
+    if result["type"] == "exception" {
+        return Err(exception_error(result).unwrap_or_else(|| {
+            Error::Browser("JavaScript execution error: unknown error".to_string())
+        }));
+    }
+    Ok(remote_value(&result["result"]))
+}
+
+/// JSON form of a BiDi `script.RemoteValue`
+fn remote_value(value: &Value) -> Value {
+    let items = || value["value"].as_array().into_iter().flatten();
+    match value["type"].as_str().unwrap_or_default() {
+        "string" | "boolean" => value["value"].clone(),
+        // NaN, -0 and the infinities are sent as strings and have no JSON form
+        "number" if value["value"].is_number() => value["value"].clone(),
+        "number" if value["value"] == "-0" => json!(0),
+        "bigint" | "date" => value["value"].clone(),
+        "array" | "set" => Value::Array(items().map(remote_value).collect()),
+        "object" | "map" => Value::Object(
+            items()
+                .filter_map(|entry| {
+                    let key = match &entry[0] {
+                        Value::String(key) => key.clone(),
+                        key => remote_value(key).as_str()?.to_string(),
+                    };
+                    Some((key, remote_value(&entry[1])))
+                })
+                .collect(),
+        ),
+        _ => Value::Null,
+    }
+}
+
+/// BiDi `script.LocalValue` for a JSON argument
+fn local_value(value: &Value) -> Value {
+    match value {
+        Value::Null => json!({ "type": "null" }),
+        Value::Bool(b) => json!({ "type": "boolean", "value": b }),
+        Value::Number(n) => json!({ "type": "number", "value": n }),
+        Value::String(s) => json!({ "type": "string", "value": s }),
+        Value::Array(items) => {
+            json!({ "type": "array", "value": items.iter().map(local_value).collect::<Vec<_>>() })
+        }
+        Value::Object(fields) => json!({
+            "type": "object",
+            "value": fields
+                .iter()
+                .map(|(key, value)| json!([key, local_value(value)]))
+                .collect::<Vec<_>>()
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connection::tests::{mock_server, server_recv, server_send};
+
+    #[test]
+    fn test_endpoints() {
+        assert_eq!(
+            bidi_endpoint("WebDriver BiDi listening on ws://127.0.0.1:41234"),
+            Some("ws://127.0.0.1:41234".to_string())
+        );
+        assert_eq!(bidi_endpoint("Read port: 41234"), None);
+        assert_eq!(
+            session_url("ws://127.0.0.1:9222"),
+            "ws://127.0.0.1:9222/session"
+        );
+        assert_eq!(
+            session_url("ws://127.0.0.1:9222/session"),
+            "ws://127.0.0.1:9222/session"
+        );
+    }
+
+    #[test]
+    fn test_remote_value_to_json() {
+        let value = json!({"type": "object", "value": [
+            ["title", {"type": "string", "value": "Example"}],
+            ["links", {"type": "array", "value": [{"type": "number", "value": 1}, {"type": "number", "value": "NaN"}]}],
+            ["ok", {"type": "boolean", "value": true}],
+            ["missing", {"type": "undefined"}],
+            ["body", {"type": "node", "sharedId": "n1"}]
+        ]});
+        assert_eq!(
+            remote_value(&value),
+            json!({"title": "Example", "links": [1, null], "ok": true, "missing": null, "body": null})
+        );
+        assert_eq!(
+            local_value(&json!({"a": [1, "x"]})),
+            json!({"type": "object", "value": [["a", {"type": "array", "value": [
+                {"type": "number", "value": 1}, {"type": "string", "value": "x"}
+            ]}]]})
+        );
+    }
+
+    #[tokio::test]
+    async fn test_session_pages_and_errors() {
+        let url = mock_server(|mut ws| async move {
+            loop {
+                let request = server_recv(&mut ws).await;
+                let reply = match request["method"].as_str().unwrap_or_default() {
+                    "session.new" => json!({"type": "success", "result": {"sessionId": "S"}}),
+                    "browsingContext.create" => {
+                        json!({"type": "success", "result": {"context": "CTX"}})
+                    }
+                    "script.evaluate" if request["params"]["target"]["context"] == "CTX" => {
+                        json!({"type": "success", "result": {
+                            "type": "success",
+                            "result": {"type": "number", "value": 42}
+                        }})
+                    }
+                    "script.callFunction" => json!({"type": "success", "result": {
+                        "type": "exception",
+                        "exceptionDetails": {"text": "Error: boom", "lineNumber": 0, "columnNumber": 9}
+                    }}),
+                    _ => json!({
+                        "type": "error",
+                        "error": "no such frame",
+                        "message": "Browsing context not found"
+                    }),
+                };
+                let mut reply = reply;
+                reply["id"] = request["id"].clone();
+                server_send(&mut ws, reply).await;
+            }
+        })
+        .await;
+        let browser = BidiBrowser::connect(&url).await.unwrap();
+        let page = browser.new_page().await.unwrap();
+        assert_eq!(page.context(), "CTX");
+        assert_eq!(page.evaluate("6 * 7").await.unwrap(), 42);
+
+        let thrown = page.call_function("() => { throw 1 }", &[]).await;
+        assert!(thrown.unwrap_err().to_string().contains("Error: boom"));
+        let missing = page.screenshot().await.unwrap_err().to_string();
+        assert!(missing.contains("no such frame: Browsing context not found"));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_launch_connects_to_announced_endpoint() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let url = mock_server(|mut ws| async move {
+            let request = server_recv(&mut ws).await;
+            assert_eq!(request["method"], "session.new");
+            server_send(
+                &mut ws,
+                json!({"type": "success", "id": request["id"], "result": {}}),
+            )
+            .await;
+        })
+        .await;
+        let endpoint = url.trim_end_matches("/devtools/page/mock");
+        let firefox = std::env::temp_dir().join(format!("fake-firefox-{}", uuid::Uuid::new_v4()));
+        std::fs::write(
+            &firefox,
+            format!(
+                "#!/bin/sh\necho 'WebDriver BiDi listening on {}' >&2\nexec sleep 30\n",
+                endpoint
+            ),
+        )
+        .unwrap();
+        std::fs::set_permissions(&firefox, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let browser = BidiBrowser::launch(BidiLaunchOptions {
+            executable_path: Some(firefox.clone()),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let profile = browser.profile.clone().unwrap();
+        assert!(profile.is_dir());
+        drop(browser);
+        assert!(!profile.exists());
+        std::fs::remove_file(firefox).unwrap();
+    }
+}
@@ -1,20 +1,332 @@
 //! Chrome browser process management
 
-use crate::{Error, Result};
-use serde_json::Value;
-use std::path::PathBuf;
-use std::process::{Child, Command, Stdio};
+use crate::android::AdbForward;
+use crate::container::{container_args, shared_memory_size, ResourceTuning, SINGLE_PROCESS_ARGS};
+use crate::context::page_ws_url;
+use crate::logging::OutputTap;
+use crate::page::decode_screenshot;
+use crate::process::BrowserProcess;
+use crate::{
+    discover_executables, AndroidOptions, BrowserConnection, BrowserContext, CdpConnection,
+    CdpPage, Channel, ChromeLogging, ChromeOutput, ContextOptions, DevToolsHttpClient, Domain,
+    Error, HostLimiter, LaunchFailure, OutputStream, PageOptions, PagePool, PagePoolOptions,
+    ProtocolCompat, ProxyConfig, ProxyCredentials, Result, Timeouts, VersionInfo,
+};
+use futures::{FutureExt, StreamExt};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
-use tokio::sync::Mutex;
+use tokio::sync::{mpsc, oneshot, watch, Mutex, OnceCell};
+use tokio::task::JoinHandle;
 use tokio::time::sleep;
 
+/// How Chrome runs without a visible window
+///
+/// New headless is the regular browser without a window; old headless is a
+/// separate, lighter implementation that differs in font rendering, extension
+/// support and PDF output, and is only shipped by older Chrome versions and
+/// `chrome-headless-shell`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HeadlessMode {
+    /// Show a browser window
+    False,
+    /// Old headless implementation (`--headless=old`)
+    Old,
+    /// New headless mode (`--headless=new`)
+    #[default]
+    New,
+}
+
+impl HeadlessMode {
+    /// Command-line flag selecting the mode, if any
+    pub(crate) fn arg(self) -> Option<&'static str> {
+        match self {
+            HeadlessMode::False => None,
+            HeadlessMode::Old => Some("--headless=old"),
+            HeadlessMode::New => Some("--headless=new"),
+        }
+    }
+}
+
+impl From<bool> for HeadlessMode {
+    /// `true` selects [`HeadlessMode::New`]
+    fn from(headless: bool) -> Self {
+        if headless {
+            HeadlessMode::New
+        } else {
+            HeadlessMode::False
+        }
+    }
+}
+
+/// Default window size and device scale of a launched browser
+///
+/// Passed to Chrome as `--window-size` and `--force-device-scale-factor`,
+/// which apply in every headless mode as well. A window's outer size
+/// includes the browser's own UI, so new pages additionally get a matching
+/// `Emulation.setDeviceMetricsOverride` to make their viewport exactly this
+/// size, and screenshots predictable.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Viewport {
+    /// Width in CSS pixels
+    pub width: u32,
+    /// Height in CSS pixels
+    pub height: u32,
+    /// Device pixels per CSS pixel
+    pub device_scale_factor: f64,
+}
+
+impl Viewport {
+    /// Viewport of the given size at a device scale factor of 1
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            device_scale_factor: 1.0,
+        }
+    }
+
+    /// Render at `scale` device pixels per CSS pixel, e.g. 2 for HiDPI screenshots
+    pub fn with_scale(mut self, scale: f64) -> Self {
+        self.device_scale_factor = scale;
+        self
+    }
+
+    /// Command-line arguments sizing the window
+    pub(crate) fn args(&self) -> Vec<String> {
+        vec![
+            format!("--window-size={},{}", self.width, self.height),
+            format!("--force-device-scale-factor={}", self.device_scale_factor),
+        ]
+    }
+}
+
+/// Options for [`CdpBrowser::launch_with_options`]
+#[derive(Debug, Clone)]
+pub struct LaunchOptions {
+    /// Chrome executable; falls back to `CHROME_BIN`, then [`discover_executables`](crate::discover_executables)
+    pub executable_path: Option<PathBuf>,
+    /// Release channel whose installed executable is launched, unless `executable_path` is set
+    pub channel: Option<Channel>,
+    /// Extra command-line arguments
+    pub args: Vec<String>,
+    /// Whether and how to run without a visible window (default: [`HeadlessMode::New`])
+    pub headless: HeadlessMode,
+    /// Print the launch command and Chrome's stderr
+    pub debug: bool,
+    /// Fixed DevTools port, e.g. one opened in a firewall; a random free port if `None`
+    pub remote_debugging_port: Option<u16>,
+    /// Launch and connect timeouts, also inherited by pages
+    pub timeouts: Timeouts,
+    /// Keep the temporary profile directory after the browser is dropped
+    pub keep_user_data_dir: bool,
+    /// Keep Chrome's log in a managed directory instead of the temporary profile
+    pub logging: Option<ChromeLogging>,
+    /// Proxy for all traffic; its credentials are answered by every page of the browser
+    pub proxy: Option<ProxyConfig>,
+    /// Accept invalid TLS certificates, e.g. self-signed ones on internal sites
+    pub ignore_certificate_errors: bool,
+    /// Limit renderers, V8 heap and site isolation to the cgroup's memory and CPU limits
+    pub tune_to_resources: bool,
+    /// Environment changes for the Chrome process, applied in order: `Some`
+    /// sets a variable, `None` removes it; everything else is inherited
+    pub env: Vec<(String, Option<String>)>,
+    /// Receives each line Chrome prints on stdout and stderr, tagged with its process id
+    ///
+    /// Lines are still written to [`CdpBrowser::log_path`].
+    pub output: Option<mpsc::UnboundedSender<ChromeOutput>>,
+    /// Window size and device scale, also applied to the viewport of new pages
+    pub viewport: Option<Viewport>,
+    /// Open DevTools next to every tab with `--auto-open-devtools-for-tabs`
+    ///
+    /// DevTools need a window, so this launches Chrome headful whatever
+    /// `headless` says. See [`CdpPage::devtools_frontend_url`] to inspect a
+    /// headless page instead.
+    pub devtools: bool,
+}
+
+impl Default for LaunchOptions {
+    fn default() -> Self {
+        Self {
+            executable_path: None,
+            channel: None,
+            args: Vec::new(),
+            headless: HeadlessMode::New,
+            debug: false,
+            remote_debugging_port: None,
+            timeouts: Timeouts::default(),
+            keep_user_data_dir: false,
+            logging: None,
+            proxy: None,
+            ignore_certificate_errors: false,
+            tune_to_resources: false,
+            env: Vec::new(),
+            output: None,
+            viewport: None,
+            devtools: false,
+        }
+    }
+}
+
+impl LaunchOptions {
+    /// Defaults for Docker and Kubernetes
+    ///
+    /// Disables the sandbox and the GPU, and keeps shared memory out of
+    /// `/dev/shm` when it is smaller than 512 MiB, as with Docker's default
+    /// of 64 MiB. Launch flags are tuned to the container's memory and CPU
+    /// limits, see [`ResourceTuning`]. See
+    /// [`running_in_container`](crate::running_in_container) to apply this
+    /// preset only where needed.
+    pub fn container_defaults() -> Self {
+        Self {
+            args: container_args(shared_memory_size()),
+            tune_to_resources: true,
+            ..Default::default()
+        }
+    }
+
+    /// Run the browser and its renderers in a single process
+    ///
+    /// Saves memory under tight container limits, but one crashing page
+    /// takes the whole browser down.
+    pub fn with_single_process(mut self) -> Self {
+        self.args
+            .extend(SINGLE_PROCESS_ARGS.iter().map(|arg| arg.to_string()));
+        self
+    }
+
+    /// Set an environment variable for Chrome, e.g. `DISPLAY` for Xvfb or `TZ`
+    pub fn env(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.env.push((key.into(), Some(value.into())));
+        self
+    }
+
+    /// Keep an environment variable of this process away from Chrome
+    pub fn env_remove(mut self, key: impl Into<String>) -> Self {
+        self.env.push((key.into(), None));
+        self
+    }
+
+    /// Give Chrome `timeout` to report its DevTools port (default: 30 seconds)
+    ///
+    /// Raise it on slow CI runners; lower it to fail fast locally. When it
+    /// runs out, or Chrome exits before reporting the port, launching fails
+    /// with [`Error::LaunchFailed`].
+    pub fn with_launch_timeout(mut self, timeout: Duration) -> Self {
+        self.timeouts.launch = Some(timeout);
+        self
+    }
+}
+
+/// Target known to the browser, as reported by `Target.getTargets`
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TargetInfo {
+    /// Target id, usable in DevTools WebSocket URLs
+    pub target_id: String,
+    /// Target type, e.g. `page`, `iframe`, `service_worker`, `background_page`
+    #[serde(rename = "type")]
+    pub target_type: String,
+    /// Current URL
+    #[serde(default)]
+    pub url: String,
+    /// Current title
+    #[serde(default)]
+    pub title: String,
+    /// Whether a client is attached to the target
+    #[serde(default)]
+    pub attached: bool,
+    /// Browser context the target belongs to
+    #[serde(default)]
+    pub browser_context_id: Option<String>,
+}
+
+impl TargetInfo {
+    /// Whether the target is a tab
+    pub fn is_page(&self) -> bool {
+        self.target_type == "page"
+    }
+}
+
+/// Number of stderr lines kept in exit reports
+const STDERR_TAIL_LINES: usize = 20;
+
+/// Number of stderr lines included in launch errors
+const LAUNCH_ERROR_TAIL_LINES: usize = 100;
+
+/// How a launched browser process ended
+#[derive(Debug, Clone)]
+pub struct BrowserExit {
+    /// Exit status of the Chrome process
+    pub status: ExitStatus,
+    /// Last lines Chrome wrote to stderr
+    pub stderr_tail: Vec<String>,
+    /// Whether the exit was requested through [`CdpBrowser::close`]
+    pub expected: bool,
+}
+
+impl BrowserExit {
+    /// Error describing the exit, for reporting unexpected ones
+    pub fn error(&self) -> Error {
+        Error::BrowserExited {
+            status: self.status.to_string(),
+            stderr: self.stderr_tail.join("\n"),
+        }
+    }
+}
+
+type SharedProcess = Arc<std::sync::Mutex<Option<BrowserProcess>>>;
+
 /// Chrome browser process manager
 pub struct CdpBrowser {
-    process: Option<Child>,
-    port: u16,
+    process: SharedProcess,
+    exit: watch::Sender<Option<BrowserExit>>,
+    monitor: Option<JoinHandle<()>>,
+    stderr_path: Option<PathBuf>,
+    http: DevToolsHttpClient,
+    ws_url: String,
+    connection: OnceCell<BrowserConnection>,
+    timeouts: Timeouts,
+    proxy_credentials: Option<ProxyCredentials>,
+    resource_tuning: Option<ResourceTuning>,
+    viewport: Option<Viewport>,
+    /// Port forwarding to an Android device, removed when the browser is dropped
+    adb_forward: Option<AdbForward>,
+    /// Fields drop after `Drop::drop` stopped Chrome, so the profile is no longer in use
+    profile: Option<TempProfile>,
+}
+
+/// Temporary Chrome profile directory, removed on drop unless kept
+struct TempProfile {
+    path: PathBuf,
+    keep: bool,
 }
 
+impl Drop for TempProfile {
+    fn drop(&mut self) {
+        if !self.keep {
+            // Best effort: a failure only leaves the directory behind
+            let _ = std::fs::remove_dir_all(&self.path);
+        }
+    }
+}
+
+/// Delay between attempts to reach the DevTools HTTP endpoint
+const CONNECT_RETRY_DELAY: Duration = Duration::from_millis(500);
+
+/// Time the DevTools endpoint has to answer a health check
+const HEALTH_CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Time each page has to be captured by [`CdpBrowser::screenshot_all`]
+const PAGE_SCREENSHOT_TIMEOUT: Duration = Duration::from_secs(10);
+
 impl CdpBrowser {
     /// Launch Chrome/Chromium with CDP enabled
     pub async fn launch(
@@ -23,9 +335,74 @@ impl CdpBrowser {
         headless: bool,
         debug: bool,
     ) -> Result<Self> {
+        Self::launch_with_timeouts(executable_path, args, headless, debug, Timeouts::default())
+            .await
+    }
+
+    /// Launch Chrome/Chromium with CDP enabled, using the given timeouts
+    pub async fn launch_with_timeouts(
+        executable_path: Option<PathBuf>,
+        args: Vec<String>,
+        headless: bool,
+        debug: bool,
+        timeouts: Timeouts,
+    ) -> Result<Self> {
+        Self::launch_with_options(LaunchOptions {
+            executable_path,
+            args,
+            headless: headless.into(),
+            debug,
+            timeouts,
+            ..Default::default()
+        })
+        .await
+    }
+
+    /// Launch Chrome/Chromium with CDP enabled, using the given options
+    ///
+    /// On Unix, Chrome leads its own process group, so dropping or closing
+    /// the browser also kills its renderer, GPU and crashpad processes.
+    /// Signals sent to this program's group, such as Ctrl-C in a terminal,
+    /// then no longer reach Chrome directly; drop the browser on shutdown.
+    pub async fn launch_with_options(options: LaunchOptions) -> Result<Self> {
+        let LaunchOptions {
+            executable_path,
+            channel,
+            args,
+            headless,
+            debug,
+            remote_debugging_port,
+            timeouts,
+            keep_user_data_dir,
+            logging,
+            proxy,
+            ignore_certificate_errors,
+            tune_to_resources,
+            env,
+            output,
+            viewport,
+            devtools,
+        } = options;
+        let launch_timeout = timeouts.launch_timeout();
+        let executable_path = match (executable_path, channel) {
+            (None, Some(channel)) => Some(channel.executable().ok_or_else(|| {
+                Error::Browser(format!(
+                    "No installed Chrome found for channel {:?}",
+                    channel
+                ))
+            })?),
+            (path, _) => path,
+        };
         let chrome_path = executable_path
             .or_else(|| std::env::var("CHROME_BIN").ok().map(PathBuf::from))
+            .or_else(|| {
+                discover_executables()
+                    .into_iter()
+                    .next()
+                    .map(|executable| executable.path)
+            })
             .unwrap_or_else(|| {
+                // Nothing was found; the launch error will name the expected path
                 #[cfg(target_os = "windows")]
                 {
                     PathBuf::from("C:\\Program Files\\Google\\Chrome\\Application\\chrome.exe")
@@ -48,151 +425,494 @@ impl CdpBrowser {
         let unique_id = uuid::Uuid::new_v4();
         let temp_dir = std::env::temp_dir().join(format!("chrome-{}", unique_id));
         std::fs::create_dir_all(&temp_dir)?;
+        let mut profile = TempProfile {
+            path: temp_dir.clone(),
+            keep: keep_user_data_dir,
+        };
+
+        if let Some(port) = remote_debugging_port {
+            ensure_port_free(port)?;
+        }
 
         let mut cmd = Command::new(&chrome_path);
-        cmd.arg("--remote-debugging-port=0"); // Let OS assign a random port
+        for (key, value) in &env {
+            match value {
+                Some(value) => cmd.env(key, value),
+                None => cmd.env_remove(key),
+            };
+        }
+        // Port 0 lets the OS assign a random port
+        cmd.arg(format!(
+            "--remote-debugging-port={}",
+            remote_debugging_port.unwrap_or(0)
+        ));
         cmd.arg(format!("--user-data-dir={}", temp_dir.display()));
         cmd.arg("--password-store=basic"); // Prevent keychain prompts
         cmd.arg("--no-first-run"); // Skip first run wizards
 
-        if headless {
-            cmd.arg("--headless");
+        if devtools {
+            cmd.arg("--auto-open-devtools-for-tabs");
+        } else if let Some(flag) = headless.arg() {
+            cmd.arg(flag);
+        }
+
+        if let Some(logging) = &logging {
+            cmd.args(logging.args());
+        }
+
+        if let Some(proxy) = &proxy {
+            cmd.args(proxy.args());
+        }
+
+        if ignore_certificate_errors {
+            cmd.arg("--ignore-certificate-errors");
+        }
+
+        if let Some(viewport) = &viewport {
+            cmd.args(viewport.args());
+        }
+
+        // Before the extra args, so they can still override the tuning
+        let resource_tuning = tune_to_resources.then(ResourceTuning::detect);
+        if let Some(tuning) = &resource_tuning {
+            if debug {
+                eprintln!("Resource tuning: {:?}", tuning);
+            }
+            cmd.args(tuning.args());
         }
 
         for arg in args {
             cmd.arg(&arg);
         }
 
-        // Capture stderr to read the assigned port
-        let stderr_file = temp_dir.join("chrome_stderr.log");
-        let stderr_handle = std::fs::File::create(&stderr_file)?;
+        // Pipe stderr to read the assigned port; it is copied to a file as Chrome's log
+        let stderr_file = match &logging {
+            Some(logging) => logging.prepare()?,
+            None => temp_dir.join("chrome_stderr.log"),
+        };
+        let log = std::fs::File::create(&stderr_file)?;
 
-        cmd.stdout(Stdio::null());
-        cmd.stderr(Stdio::from(stderr_handle));
+        cmd.stdout(if output.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        });
+        cmd.stderr(Stdio::piped());
 
         if debug {
             eprintln!("Launching Chrome: {:?}", cmd);
         }
 
-        let process = Arc::new(std::sync::Mutex::new(cmd.spawn()?));
-
-        // Read the port from stderr
-        let port: Arc<std::sync::Mutex<Option<u16>>> = Arc::new(std::sync::Mutex::new(None));
-        let port_clone = port.clone();
-        let stderr_path = stderr_file.clone();
-
-        // Spawn a thread to read stderr and look for the port
-        tokio::spawn(async move {
-            let start = std::time::Instant::now();
-            // Try for up to 30 seconds (CI environments may be slower)
-            while start.elapsed().as_secs() < 30 {
-                if let Ok(content) = std::fs::read_to_string(&stderr_path) {
-                    for line in content.lines() {
-                        if debug {
-                            eprintln!("CHROME STDERR: {}", line);
-                        }
-                        if line.contains("DevTools listening on") {
-                            if let Some(port_str) = line.split("127.0.0.1:").nth(1) {
-                                if let Some(port_num) = port_str.split('/').next() {
-                                    if let Ok(p) = port_num.parse::<u16>() {
-                                        if let Ok(mut guard) = port_clone.lock() {
-                                            *guard = Some(p);
-                                        }
-                                        return;
-                                    }
-                                }
-                            }
-                        }
-                    }
+        // Renderers, GPU and crashpad helpers are killed along with the browser
+        let mut child = BrowserProcess::spawn(&mut cmd)?;
+        let tap = output.map(|sender| OutputTap {
+            pid: child.id(),
+            sender,
+        });
+        if let (Some(stdout), Some(tap)) = (child.child().stdout.take(), &tap) {
+            tokio::spawn(forward_stdout(
+                tokio::process::ChildStdout::from_std(stdout)?,
+                tap.clone(),
+            ));
+        }
+        let stderr = child
+            .child()
+            .stderr
+            .take()
+            .ok_or_else(|| Error::Browser("Chrome's stderr was not captured".to_string()))?;
+        let (listening_tx, listening_rx) = oneshot::channel();
+        tokio::spawn(forward_stderr(
+            tokio::process::ChildStderr::from_std(stderr)?,
+            log,
+            debug,
+            tap,
+            listening_tx,
+        ));
+
+        // The sender is dropped at end of stream, i.e. when Chrome exits early
+        let discovered_port = match tokio::time::timeout(launch_timeout, listening_rx).await {
+            Ok(Ok(port)) => port,
+            outcome => {
+                let exited = match outcome {
+                    Ok(_) => wait_for_exit(&mut child, HEALTH_CHECK_TIMEOUT).await,
+                    Err(_) => None,
+                };
+                if exited.is_none() {
+                    // Do not leave a browser that never came up behind
+                    let _ = child.kill_tree();
                 }
-                std::thread::sleep(Duration::from_millis(100));
+                // The error points at the profile, so it must outlive the launch
+                profile.keep = true;
+                return Err(Error::LaunchFailed(Box::new(LaunchFailure {
+                    executable: chrome_path,
+                    user_data_dir: temp_dir,
+                    os: format!(
+                        "{} {} ({})",
+                        std::env::consts::OS,
+                        std::env::consts::ARCH,
+                        std::env::consts::FAMILY
+                    ),
+                    stderr_tail: stderr_tail(&stderr_file, LAUNCH_ERROR_TAIL_LINES),
+                    exit_status: exited.map(|status| status.to_string()),
+                    timeout: launch_timeout,
+                })));
             }
-        });
+        };
 
-        let stderr_path_for_error = stderr_file.clone();
-        let process_for_error = process.clone();
-        // Wait for the port to be discovered
-        let discovered_port = tokio::task::spawn_blocking(move || {
-            for _ in 0..300 {
-                let port_val = port.lock().map_or(None, |guard| *guard);
-                if let Some(p) = port_val {
-                    return Ok(p);
-                }
-                std::thread::sleep(Duration::from_millis(100));
+        let retries = connect_retries(timeouts.connect_timeout());
+        let http = DevToolsHttpClient::new(LOCALHOST, discovered_port);
+        let ws_url = match Self::get_ws_url_with_retry(&http, retries, CONNECT_RETRY_DELAY).await {
+            Ok(ws_url) => ws_url,
+            Err(e) => {
+                let _ = child.kill_tree();
+                return Err(e);
             }
+        };
 
-            // Build detailed error message
-            let chrome_stderr =
-                std::fs::read_to_string(&stderr_path_for_error).unwrap_or_else(|_| "(unreadable)".to_string());
+        // Hand the process over to the browser and its exit monitor
+        let process: SharedProcess = Arc::new(std::sync::Mutex::new(Some(child)));
+        let (exit, _) = watch::channel(None);
+        let monitor = tokio::spawn(monitor_exit(
+            process.clone(),
+            exit.clone(),
+            stderr_file.clone(),
+        ));
 
-            let os_info = format!(
-                "{} {} ({})",
-                std::env::consts::OS,
-                std::env::consts::ARCH,
-                std::env::consts::FAMILY
-            );
+        Ok(Self {
+            process,
+            exit,
+            monitor: Some(monitor),
+            stderr_path: Some(stderr_file),
+            http,
+            ws_url,
+            connection: OnceCell::new(),
+            timeouts,
+            proxy_credentials: proxy.and_then(|proxy| proxy.credentials),
+            resource_tuning,
+            viewport,
+            adb_forward: None,
+            profile: Some(profile),
+        })
+    }
 
-            let mut err_msg = format!(
-                "=== Chrome Browser Launch Failure ===\n\
-                 OS: {}\n\
-                 Chrome Executable: {:?}\n\
-                 User Data Dir: {:?}\n\
-                 === Chrome stderr ===\n{}\n\
-                 === End of stderr ===",
-                os_info, chrome_path, temp_dir, chrome_stderr
-            );
+    /// Attach to an already-running Chrome started with `--remote-debugging-port`
+    ///
+    /// No process is spawned, and the browser is left running when this handle is dropped.
+    pub async fn connect(host: &str, port: u16) -> Result<Self> {
+        let http = DevToolsHttpClient::new(host, port);
+        let ws_url = http.ws_url().await?;
+        Ok(Self {
+            process: Arc::new(std::sync::Mutex::new(None)),
+            exit: watch::channel(None).0,
+            monitor: None,
+            stderr_path: None,
+            http,
+            ws_url,
+            connection: OnceCell::new(),
+            timeouts: Timeouts::default(),
+            proxy_credentials: None,
+            resource_tuning: None,
+            viewport: None,
+            adb_forward: None,
+            profile: None,
+        })
+    }
 
-            // Check process status
-            if let Ok(Some(status)) = process_for_error.lock().unwrap().try_wait() {
-                err_msg = format!("{}\n\nChrome process exited early with status: {}", err_msg, status);
-            } else {
-                err_msg = format!(
-                    "{}\n\nChrome process is still running but debugging port was not found after 30 seconds.\n\n\
-                     Troubleshooting:\n\
-                     - If running in CI, ensure Chrome/Chromium is installed\n\
-                     - Try setting CHROME_BIN environment variable\n\
-                     - For Linux CI, add --no-sandbox flag",
-                    err_msg
-                );
+    /// Attach to an already-running Chrome using its browser WebSocket debugger URL
+    pub async fn connect_ws(ws_url: &str) -> Result<Self> {
+        Ok(Self {
+            process: Arc::new(std::sync::Mutex::new(None)),
+            exit: watch::channel(None).0,
+            monitor: None,
+            stderr_path: None,
+            http: ws_endpoint_http(ws_url)?,
+            ws_url: ws_url.to_string(),
+            connection: OnceCell::new(),
+            timeouts: Timeouts::default(),
+            proxy_credentials: None,
+            resource_tuning: None,
+            viewport: None,
+            adb_forward: None,
+            profile: None,
+        })
+    }
+
+    /// Attach to Chrome on an Android device over adb
+    ///
+    /// Forwards a local port to the device's DevTools socket with
+    /// `adb forward` and connects through it. USB debugging must be enabled,
+    /// and Chrome must be running on the device. The forward is removed when
+    /// the browser is dropped; the browser itself is left running.
+    ///
+    /// ```no_run
+    /// # async fn run() -> chrome_cdp::Result<()> {
+    /// use chrome_cdp::{android_devices, AndroidOptions, CdpBrowser};
+    ///
+    /// let device = android_devices().await?.remove(0);
+    /// let browser = CdpBrowser::connect_android(AndroidOptions::device(device.serial)).await?;
+    /// let page = browser.new_page_connected(Some("https://example.com")).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn connect_android(options: AndroidOptions) -> Result<Self> {
+        let forward = AdbForward::open(&options).await?;
+        let mut browser = Self::connect(LOCALHOST, forward.port()).await?;
+        browser.adb_forward = Some(forward);
+        Ok(browser)
+    }
+
+    /// Temporary profile directory of a launched browser
+    ///
+    /// It is deleted when the browser is dropped unless
+    /// [`LaunchOptions::keep_user_data_dir`] was set.
+    pub fn user_data_dir(&self) -> Option<&std::path::Path> {
+        self.profile.as_ref().map(|profile| profile.path.as_path())
+    }
+
+    /// Process id of a launched browser, while it is running
+    pub fn pid(&self) -> Option<u32> {
+        self.process
+            .lock()
+            .ok()?
+            .as_ref()
+            .map(|process| process.id())
+    }
+
+    /// File receiving Chrome's stderr and log, for browsers this crate launched
+    ///
+    /// Lives in [`LaunchOptions::logging`]'s directory when set, otherwise in the
+    /// temporary profile.
+    pub fn log_path(&self) -> Option<&Path> {
+        self.stderr_path.as_deref()
+    }
+
+    /// Launch flags chosen for the cgroup's limits, if [`LaunchOptions::tune_to_resources`] was set
+    pub fn resource_tuning(&self) -> Option<&ResourceTuning> {
+        self.resource_tuning.as_ref()
+    }
+
+    /// Viewport applied to new pages, from [`LaunchOptions::viewport`]
+    pub fn viewport(&self) -> Option<Viewport> {
+        self.viewport
+    }
+
+    /// Timeouts applied to this browser and used as the fallback for its pages
+    pub fn timeouts(&self) -> Timeouts {
+        self.timeouts
+    }
+
+    /// Override timeouts for this browser; unset fields keep their current value
+    pub fn set_timeouts(&mut self, timeouts: Timeouts) {
+        self.timeouts = timeouts.or(self.timeouts);
+    }
+
+    /// Browser-level WebSocket debugger URL (`/devtools/browser/...`)
+    pub fn ws_url(&self) -> &str {
+        &self.ws_url
+    }
+
+    /// Browser-level CDP connection, established on first use
+    pub async fn connection(&self) -> Result<&BrowserConnection> {
+        self.connection
+            .get_or_try_init(|| BrowserConnection::connect(&self.ws_url))
+            .await
+    }
+
+    /// Shut the browser down gracefully
+    ///
+    /// Asks Chrome to exit via `Browser.close` so it can flush its profile and
+    /// stop its child processes, then sends SIGTERM, and only kills the process
+    /// if it is still running after that. Each step waits up to the close
+    /// timeout. An attached browser is closed via `Browser.close` as well.
+    pub async fn close(&self) -> Result<()> {
+        let timeout = self.timeouts.close_timeout();
+        let process = self.process.lock().ok().and_then(|mut p| p.take());
+
+        let requested = async {
+            let connection = self.connection().await?;
+            connection.send_command("Browser.close", json!({})).await
+        };
+        let requested = tokio::time::timeout(timeout, requested).await;
+
+        let Some(mut process) = process else {
+            return match requested {
+                // The browser may drop the connection before answering
+                Ok(Ok(_)) | Ok(Err(Error::WebSocket(_))) => Ok(()),
+                Ok(Err(e)) => Err(Error::Browser(format!("Failed to close browser: {}", e))),
+                Err(_) => Err(Error::Timeout(format!(
+                    "Browser did not close within {:?}",
+                    timeout
+                ))),
+            };
+        };
+
+        let status = match wait_for_exit(&mut process, timeout).await {
+            Some(status) => status,
+            None => {
+                process.terminate();
+                match wait_for_exit(&mut process, timeout).await {
+                    Some(status) => status,
+                    None => process.kill_tree()?,
+                }
             }
+        };
+        let stderr_tail = self
+            .stderr_path
+            .as_deref()
+            .map(|path| stderr_tail(path, STDERR_TAIL_LINES))
+            .unwrap_or_default();
+        self.exit.send_replace(Some(BrowserExit {
+            status,
+            stderr_tail,
+            expected: true,
+        }));
+        Ok(())
+    }
 
-            Err(Error::Browser(err_msg))
-        })
-        .await
-        .map_err(|e| Error::Browser(format!("Task failed: {}", e)))??;
+    /// Wait until the launched Chrome process exits
+    ///
+    /// Check [`BrowserExit::expected`] to tell a crash or OOM kill from a
+    /// requested [`CdpBrowser::close`]. Fails for attached browsers, whose
+    /// process is not owned by this handle.
+    pub async fn wait(&self) -> Result<BrowserExit> {
+        if self.monitor.is_none() {
+            return Err(Error::Browser(
+                "Browser was not launched by this handle".to_string(),
+            ));
+        }
+        let mut exit = self.exit.subscribe();
+        let exit = exit
+            .wait_for(Option::is_some)
+            .await
+            .map_err(|_| Error::Browser("Browser handle was dropped".to_string()))?;
+        Ok(exit.clone().expect("waited for an exit"))
+    }
 
-        let _ws_url =
-            Self::get_ws_url_with_retry(discovered_port, 10, Duration::from_millis(500)).await?;
+    /// Whether the launched Chrome process has exited
+    ///
+    /// Always `false` for attached browsers, whose process is not owned by
+    /// this handle; use [`health_check`](Self::health_check) for those.
+    pub fn has_exited(&self) -> bool {
+        if self.exit.borrow().is_some() {
+            return true;
+        }
+        self.process
+            .lock()
+            .ok()
+            .and_then(|mut process| Some(process.as_mut()?.has_exited()))
+            .unwrap_or(false)
+    }
 
-        // Unwrap the Arc<Mutex<>> to get the process
-        let process = match Arc::try_unwrap(process) {
-            Ok(mutex) => mutex.into_inner().unwrap(),
-            Err(_) => {
-                return Err(Error::Browser(
-                    "Failed to acquire process ownership".to_string(),
+    /// Check that the process is running and the DevTools endpoint answers
+    pub async fn health_check(&self) -> Result<()> {
+        if let Some(exit) = self.exit.borrow().as_ref() {
+            return Err(exit.error());
+        }
+        if self.has_exited() {
+            return Err(Error::Browser("Browser process has exited".to_string()));
+        }
+        tokio::time::timeout(HEALTH_CHECK_TIMEOUT, self.http.version())
+            .await
+            .map_err(|_| {
+                Error::Timeout(format!(
+                    "DevTools endpoint {}:{} did not answer within {:?}",
+                    self.http.host(),
+                    self.http.port(),
+                    HEALTH_CHECK_TIMEOUT
                 ))
+            })??;
+        Ok(())
+    }
+
+    /// Call `callback` once the launched Chrome process exits
+    ///
+    /// The callback is not called if this handle is dropped first, since
+    /// dropping kills the browser deliberately.
+    pub fn on_exit<F>(&self, callback: F)
+    where
+        F: FnOnce(&BrowserExit) + Send + 'static,
+    {
+        let mut exit = self.exit.subscribe();
+        tokio::spawn(async move {
+            if let Ok(exit) = exit.wait_for(Option::is_some).await {
+                if let Some(exit) = exit.as_ref() {
+                    callback(exit);
+                }
             }
+        });
+    }
+
+    /// Create a new isolated browser context
+    pub async fn new_context(&self) -> Result<BrowserContext> {
+        self.new_context_with_options(ContextOptions::default())
+            .await
+    }
+
+    /// Create a new isolated browser context whose pages all get `options`
+    ///
+    /// The proxy and permissions are set on the context itself; the user
+    /// agent, locale, headers and offline mode are applied to each page as
+    /// the context opens or attaches it.
+    pub async fn new_context_with_options(
+        &self,
+        options: ContextOptions,
+    ) -> Result<BrowserContext> {
+        let connection = self.connection().await?.clone();
+        let proxy_credentials = match &options.proxy {
+            Some(proxy) => proxy.credentials.clone(),
+            None => self.proxy_credentials.clone(),
         };
+        Ok(
+            BrowserContext::create(connection, &self.ws_url, self.timeouts, options)
+                .await?
+                .with_proxy_credentials(proxy_credentials)
+                .with_viewport(self.viewport),
+        )
+    }
 
-        // Verify WebSocket URL is accessible (discard the result)
-        Self::get_ws_url_with_retry(discovered_port, 10, Duration::from_millis(500)).await?;
+    /// Accept or reject invalid TLS certificates in every context of the browser
+    ///
+    /// Unlike [`LaunchOptions::ignore_certificate_errors`], this also works for
+    /// browsers attached with [`connect`](Self::connect).
+    pub async fn set_ignore_certificate_errors(&self, ignore: bool) -> Result<()> {
+        self.connection()
+            .await?
+            .send_command(
+                "Security.setIgnoreCertificateErrors",
+                json!({ "ignore": ignore }),
+            )
+            .await
+            .map_err(|e| {
+                Error::Browser(format!("Failed to set certificate error handling: {}", e))
+            })?;
+        Ok(())
+    }
 
-        Ok(Self {
-            process: Some(process),
-            port: discovered_port,
-        })
+    /// Host of the DevTools HTTP endpoint
+    pub fn host(&self) -> &str {
+        self.http.host()
+    }
+
+    /// Port of the DevTools HTTP endpoint
+    pub fn port(&self) -> u16 {
+        self.http.port()
+    }
+
+    /// Client for the DevTools HTTP endpoint, e.g. for `/json/list`
+    pub fn http(&self) -> &DevToolsHttpClient {
+        &self.http
     }
 
     /// Get WebSocket debugger URL from Chrome with retry logic
     async fn get_ws_url_with_retry(
-        port: u16,
+        http: &DevToolsHttpClient,
         max_retries: u32,
         retry_delay: Duration,
     ) -> Result<String> {
         let mut last_error = None;
 
         for attempt in 0..max_retries {
-            match Self::get_ws_url(port).await {
+            match http.ws_url().await {
                 Ok(url) => return Ok(url),
                 Err(e) => {
                     last_error = Some(e);
@@ -208,176 +928,1454 @@ impl CdpBrowser {
         }))
     }
 
-    /// Get WebSocket debugger URL from Chrome
-    async fn get_ws_url(port: u16) -> Result<String> {
-        let url = format!("http://127.0.0.1:{}/json/version", port);
-        let client = reqwest::Client::new();
+    /// Detect the browser version and check it against the pinned protocol version
+    ///
+    /// Apply the result to connections with [`crate::CdpConnection::set_protocol_compat`].
+    pub async fn protocol_compat(&self) -> Result<ProtocolCompat> {
+        let info = self.http.version().await?;
+        let compat = ProtocolCompat::from_version_info(&info);
+        compat.check()?;
+        Ok(compat)
+    }
+
+    /// Get the browser's product, revision, protocol and engine versions
+    ///
+    /// Useful to gate workarounds on [`VersionInfo::major`] at runtime.
+    pub async fn version_info(&self) -> Result<VersionInfo> {
+        let result = self
+            .connection()
+            .await?
+            .send_command("Browser.getVersion", json!({}))
+            .await
+            .map_err(|e| Error::Browser(format!("Failed to get browser version: {}", e)))?;
+        Ok(serde_json::from_value(result)?)
+    }
+
+    /// Get the protocol schema served at `/json/protocol`
+    ///
+    /// Lists every domain with its commands, events and types, e.g. to
+    /// complete method names in the `repl` feature's prompt.
+    pub async fn protocol_schema(&self) -> Result<Value> {
+        self.http.protocol().await
+    }
+
+    /// Connect to every open page (tab), including the initial `about:blank` one
+    ///
+    /// Pages of all browser contexts are returned; use
+    /// [`BrowserContext::pages`] for the pages of a single context.
+    pub async fn pages(&self) -> Result<Vec<CdpPage>> {
+        let mut pages = Vec::new();
+        for target in self.targets().await? {
+            if target.is_page() {
+                pages.push(self.attach_page(&target.target_id).await?);
+            }
+        }
+        Ok(pages)
+    }
+
+    /// Connect to an existing page target, e.g. a tab opened by the page itself
+    ///
+    /// Target ids come from [`targets`](Self::targets) or `Target.targetCreated`
+    /// events. The page inherits the browser's timeouts.
+    pub async fn attach_page(&self, target_id: &str) -> Result<CdpPage> {
+        let page = CdpPage::new_with_timeouts(&page_ws_url(&self.ws_url, target_id), self.timeouts)
+            .await?;
+        self.prepare_page(&page).await?;
+        Ok(page)
+    }
+
+    /// Capture every open page of every context concurrently, keyed by target id
+    ///
+    /// Each page is captured over its own short-lived connection without
+    /// enabling any domain or applying overrides, so the pages are left as
+    /// they were. A page that cannot be captured, e.g. because it closed
+    /// meanwhile or its renderer hangs, maps to its error instead of failing
+    /// the whole call; each page gets 10 seconds.
+    pub async fn screenshot_all(&self) -> Result<HashMap<String, Result<Vec<u8>>>> {
+        self.screenshot_all_within(PAGE_SCREENSHOT_TIMEOUT).await
+    }
+
+    /// [`screenshot_all`](Self::screenshot_all), giving each page `per_page`
+    async fn screenshot_all_within(
+        &self,
+        per_page: Duration,
+    ) -> Result<HashMap<String, Result<Vec<u8>>>> {
+        let captures = self
+            .targets()
+            .await?
+            .into_iter()
+            .filter(TargetInfo::is_page)
+            .map(|target| async move {
+                let image = async {
+                    let connection = self.attach_target(&target.target_id).await?;
+                    let result = connection
+                        .send_command("Page.captureScreenshot", json!({ "format": "png" }))
+                        .await
+                        .map_err(|e| {
+                            Error::Browser(format!("Failed to capture screenshot: {}", e))
+                        })?;
+                    decode_screenshot(&result)
+                };
+                let image = tokio::time::timeout(per_page, image)
+                    .await
+                    .unwrap_or_else(|_| {
+                        Err(Error::Timeout(format!(
+                            "Capturing page {} took longer than {:?}",
+                            target.target_id, per_page
+                        )))
+                    });
+                (target.target_id, image)
+            });
+        Ok(futures::future::join_all(captures)
+            .await
+            .into_iter()
+            .collect())
+    }
 
-        let response = client
-            .get(&url)
-            .send()
+    /// Open a raw connection to any target, e.g. a service worker or extension page
+    pub async fn attach_target(&self, target_id: &str) -> Result<CdpConnection> {
+        let ws_url = page_ws_url(&self.ws_url, target_id);
+        CdpConnection::connect_with_timeouts(&ws_url, self.timeouts).await
+    }
+
+    /// List every target of the browser: pages, workers, extensions and others
+    pub async fn targets(&self) -> Result<Vec<TargetInfo>> {
+        let result = self
+            .connection()
+            .await?
+            .send_command("Target.getTargets", json!({}))
             .await
-            .map_err(|e| Error::Http(format!("Failed to connect to {}: {}", url, e)))?;
+            .map_err(|e| Error::Browser(format!("Failed to list targets: {}", e)))?;
+        parse_targets(result)
+    }
+
+    /// Create a new page and connect to it, navigating to `url` if given
+    ///
+    /// The page inherits the browser's timeouts. If connecting or navigation
+    /// fails the tab is closed before the error is returned.
+    pub async fn new_page_connected(&self, url: Option<&str>) -> Result<CdpPage> {
+        self.open_page(|ws_url| async move {
+            let page = CdpPage::new_with_timeouts(&ws_url, self.timeouts).await?;
+            self.prepare_page(&page).await?;
+            if let Some(url) = url {
+                page.goto(url).await?;
+            }
+            Ok(page)
+        })
+        .await
+    }
+
+    /// Open an `about:blank` page ready for [`CdpPage::set_content`]
+    ///
+    /// The Page, Runtime and DOM domains are enabled and no navigation is
+    /// awaited, so DOM tests that never touch the network start quickly.
+    pub async fn blank_page(&self) -> Result<CdpPage> {
+        self.open_page(|ws_url| async move {
+            let page = CdpPage::new_with_domains(
+                &ws_url,
+                self.timeouts,
+                &[Domain::Page, Domain::Runtime, Domain::Dom],
+            )
+            .await?;
+            self.prepare_page(&page).await?;
+            Ok(page)
+        })
+        .await
+    }
+
+    /// Create a page target and connect to it with `setup`, closing the tab if that fails
+    pub(crate) async fn open_page<F, Fut>(&self, setup: F) -> Result<CdpPage>
+    where
+        F: FnOnce(String) -> Fut,
+        Fut: Future<Output = Result<CdpPage>>,
+    {
+        let ws_url = self.new_page().await?;
+        let outcome = setup(ws_url.clone()).await;
+        if outcome.is_err() {
+            self.discard_target(&ws_url).await;
+        }
+        outcome
+    }
+
+    /// Close the target behind a page WebSocket URL, ignoring failures
+    async fn discard_target(&self, ws_url: &str) {
+        let target_id = ws_url.rsplit('/').next().unwrap_or_default();
+        match self.connection().await {
+            Ok(connection) => {
+                let _ = connection
+                    .send_command("Target.closeTarget", json!({ "targetId": target_id }))
+                    .await;
+            }
+            // Pages from the /json/new fallback have no browser connection
+            Err(_) => {
+                let _ = self.http.close(target_id).await;
+            }
+        }
+    }
+
+    /// Apply browser-wide page settings, such as proxy credentials
+    pub(crate) async fn prepare_page(&self, page: &CdpPage) -> Result<()> {
+        // Only an established connection: pages opened through /json/new get none
+        if let Some(connection) = self.connection.get() {
+            page.set_browser_connection(connection.connection().clone());
+        }
+        if self.proxy_credentials.is_some() {
+            page.set_proxy_credentials(self.proxy_credentials.clone())
+                .await?;
+        }
+        if let Some(viewport) = self.viewport {
+            page.set_viewport(
+                viewport.width,
+                viewport.height,
+                viewport.device_scale_factor,
+                false,
+            )
+            .await?;
+        }
+        Ok(())
+    }
+
+    /// Create a new page and return its WebSocket URL
+    ///
+    /// The page is created with `Target.createTarget` over the browser
+    /// connection. The legacy `/json/new` HTTP endpoint, which some builds and
+    /// hosted browsers disable, is only tried when the browser answers that
+    /// the method is unavailable or not allowed; timeouts and lost
+    /// connections are returned as they are.
+    ///
+    /// A page connected from the URL with [`CdpPage::new`] gets none of the
+    /// browser's page settings, such as proxy credentials; use
+    /// [`new_page_connected`](Self::new_page_connected) for those.
+    pub async fn new_page(&self) -> Result<String> {
+        let target_id = match self.create_target().await {
+            Ok(target_id) => target_id,
+            Err(Error::Protocol(e)) if e.is_method_unavailable() => {
+                return self.new_page_http().await.map_err(|fallback| {
+                    Error::Browser(format!(
+                        "Failed to create new page: {} (/json/new fallback: {})",
+                        Error::Protocol(e),
+                        fallback
+                    ))
+                })
+            }
+            Err(e) => return Err(e.context("Failed to create new page")),
+        };
+        Ok(page_ws_url(&self.ws_url, &target_id))
+    }
+
+    /// Create an `about:blank` page target, returning its id
+    async fn create_target(&self) -> Result<String> {
+        let result = self
+            .connection()
+            .await?
+            .send_command("Target.createTarget", json!({ "url": "about:blank" }))
+            .await?;
+        result["targetId"]
+            .as_str()
+            .map(String::from)
+            .ok_or_else(|| {
+                Error::Browser(format!(
+                    "Target.createTarget returned no targetId: {}",
+                    result
+                ))
+            })
+    }
+
+    /// Create a page through the `/json/new` HTTP endpoint
+    async fn new_page_http(&self) -> Result<String> {
+        let target = self.http.new_page(None).await?;
+        target.web_socket_debugger_url.ok_or_else(|| {
+            Error::Browser(format!(
+                "Could not find webSocketDebuggerUrl for new page {}",
+                target.id
+            ))
+        })
+    }
+}
+
+impl Drop for CdpBrowser {
+    fn drop(&mut self) {
+        if let Some(monitor) = self.monitor.take() {
+            monitor.abort();
+        }
+        let process = self.process.lock().ok().and_then(|mut p| p.take());
+        if let Some(mut process) = process {
+            // Reaps the process so its profile files are no longer in use
+            let _ = process.kill_tree();
+        }
+    }
+}
+
+/// Wait until the process exits, returning its status if it did within `timeout`
+///
+/// Helpers that outlive the browser are killed before it is reaped.
+async fn wait_for_exit(process: &mut BrowserProcess, timeout: Duration) -> Option<ExitStatus> {
+    let start = Instant::now();
+    loop {
+        if let Some(status) = process.try_reap() {
+            return Some(status);
+        }
+        if start.elapsed() >= timeout {
+            return None;
+        }
+        sleep(Duration::from_millis(50)).await;
+    }
+}
+
+/// Report the process exit once it happens without `close` taking the process first
+async fn monitor_exit(
+    process: SharedProcess,
+    exit: watch::Sender<Option<BrowserExit>>,
+    stderr_path: PathBuf,
+) {
+    loop {
+        sleep(Duration::from_millis(250)).await;
+        let status = {
+            let Ok(mut process) = process.lock() else {
+                return;
+            };
+            let Some(child) = process.as_mut() else {
+                // Taken by `close` or `Drop`, which handle the exit themselves
+                return;
+            };
+            // Renderers of a crashed browser may linger; they are killed too
+            match child.try_reap() {
+                Some(status) => {
+                    process.take();
+                    status
+                }
+                None => continue,
+            }
+        };
+        exit.send_replace(Some(BrowserExit {
+            status,
+            stderr_tail: stderr_tail(&stderr_path, STDERR_TAIL_LINES),
+            expected: false,
+        }));
+        return;
+    }
+}
+
+/// Copy Chrome's stderr into its log file, reporting the DevTools port once announced
+async fn forward_stderr(
+    stderr: tokio::process::ChildStderr,
+    mut log: std::fs::File,
+    debug: bool,
+    tap: Option<OutputTap>,
+    listening: oneshot::Sender<u16>,
+) {
+    use std::io::Write;
+    use tokio::io::AsyncBufReadExt;
+
+    let mut listening = Some(listening);
+    let mut lines = tokio::io::BufReader::new(stderr).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        if debug {
+            eprintln!("CHROME STDERR: {}", line);
+        }
+        // Best effort: the log only feeds diagnostics
+        let _ = writeln!(log, "{}", line);
+        if let Some(tap) = &tap {
+            tap.send(OutputStream::Stderr, &line);
+        }
+        if let Some(port) = listening_port(&line) {
+            if let Some(listening) = listening.take() {
+                let _ = listening.send(port);
+            }
+        }
+    }
+}
+
+/// Pass Chrome's stdout on to the output receiver
+async fn forward_stdout(stdout: tokio::process::ChildStdout, tap: OutputTap) {
+    use tokio::io::AsyncBufReadExt;
+
+    let mut lines = tokio::io::BufReader::new(stdout).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        tap.send(OutputStream::Stdout, &line);
+    }
+}
+
+/// Port announced by Chrome's `DevTools listening on ws://...` line
+fn listening_port(line: &str) -> Option<u16> {
+    let (_, url) = line.split_once("DevTools listening on ")?;
+    reqwest::Url::parse(url.trim()).ok()?.port()
+}
+
+/// Last `lines` non-empty lines of a log file
+fn stderr_tail(path: &Path, lines: usize) -> Vec<String> {
+    let content = std::fs::read_to_string(path).unwrap_or_default();
+    let mut tail: Vec<String> = content
+        .lines()
+        .rev()
+        .filter(|line| !line.trim().is_empty())
+        .take(lines)
+        .map(String::from)
+        .collect();
+    tail.reverse();
+    tail
+}
+
+/// Host used for locally launched browsers
+const LOCALHOST: &str = "127.0.0.1";
+
+/// HTTP client for the DevTools endpoint serving a WebSocket URL
+///
+/// `wss://` endpoints, e.g. behind a TLS-terminating proxy, are reached over `https://`.
+pub(crate) fn ws_endpoint_http(ws_url: &str) -> Result<DevToolsHttpClient> {
+    let url = reqwest::Url::parse(ws_url)
+        .map_err(|e| Error::Browser(format!("Invalid WebSocket URL '{}': {}", ws_url, e)))?;
+
+    if url.scheme() != "ws" && url.scheme() != "wss" {
+        return Err(Error::Browser(format!(
+            "Invalid WebSocket URL '{}': expected ws:// or wss://",
+            ws_url
+        )));
+    }
+
+    let host = url
+        .host_str()
+        .ok_or_else(|| Error::Browser(format!("WebSocket URL '{}' has no host", ws_url)))?;
+    let port = url
+        .port_or_known_default()
+        .ok_or_else(|| Error::Browser(format!("WebSocket URL '{}' has no port", ws_url)))?;
+
+    Ok(DevToolsHttpClient::new(host, port).https(url.scheme() == "wss"))
+}
+
+/// Fail early with a clear message when a pinned debugging port is taken
+///
+/// Chrome itself only logs a failed bind and keeps running without DevTools.
+fn ensure_port_free(port: u16) -> Result<()> {
+    std::net::TcpListener::bind((LOCALHOST, port))
+        .map(drop)
+        .map_err(|e| {
+            Error::Browser(format!(
+                "Remote debugging port {} is not available: {}",
+                port, e
+            ))
+        })
+}
+
+/// Parse a `Target.getTargets` result
+fn parse_targets(mut result: Value) -> Result<Vec<TargetInfo>> {
+    Ok(serde_json::from_value(result["targetInfos"].take())?)
+}
+
+/// Number of endpoint attempts that fit into the connect timeout
+fn connect_retries(timeout: Duration) -> u32 {
+    (timeout.as_millis() / CONNECT_RETRY_DELAY.as_millis()).max(1) as u32
+}
+
+/// Browser state for managing lifecycle
+pub struct BrowserState {
+    pub browser: Option<Arc<CdpBrowser>>,
+    pub last_used: Instant,
+}
+
+/// Idle time after which [`BrowserManager`] closes its browser by default
+const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+/// When the browser becomes idle, if there is one and a timeout is set
+fn idle_deadline(state: &BrowserState, idle_timeout: Option<Duration>) -> Option<Instant> {
+    state.browser.as_ref()?;
+    Some(state.last_used + idle_timeout?)
+}
+
+/// Close the browser once it has been idle for the configured timeout
+///
+/// The deadline is recomputed whenever the timeout changes or a browser is
+/// launched. The task ends once every manager sharing the sender is dropped
+/// or the returned handle is aborted.
+fn spawn_idle_monitor(
+    state: Arc<Mutex<BrowserState>>,
+    mut idle_timeout: watch::Receiver<Option<Duration>>,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        loop {
+            let timeout = *idle_timeout.borrow_and_update();
+            let deadline = idle_deadline(&*state.lock().await, timeout);
+            let expired = match deadline {
+                Some(deadline) => tokio::select! {
+                    _ = tokio::time::sleep_until(deadline.into()) => true,
+                    changed = idle_timeout.changed() => {
+                        if changed.is_err() {
+                            break;
+                        }
+                        false
+                    }
+                },
+                None => {
+                    if idle_timeout.changed().await.is_err() {
+                        break;
+                    }
+                    false
+                }
+            };
+            if expired {
+                let mut s = state.lock().await;
+                // The browser may have been used while waiting for the lock
+                if idle_deadline(&s, timeout).is_some_and(|deadline| deadline <= Instant::now()) {
+                    s.browser = None; // Drops Arc<CdpBrowser>, which triggers process kill
+                }
+            }
+        }
+    })
+}
+
+/// When [`BrowserManager`] adds sandbox-disabling flags for CI machines
+///
+/// The flags are `--disable-gpu`, `--no-sandbox` and `--disable-setuid-sandbox`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CiFlags {
+    /// Add them when the `CI` environment variable is set
+    #[default]
+    Auto,
+    /// Always add them
+    Always,
+    /// Never add them
+    Never,
+}
+
+impl CiFlags {
+    /// Whether the flags are added in the current environment
+    fn applies(self) -> bool {
+        match self {
+            CiFlags::Auto => std::env::var("CI").is_ok(),
+            CiFlags::Always => true,
+            CiFlags::Never => false,
+        }
+    }
+}
+
+/// Builder for [`BrowserManager`], from [`BrowserManager::builder`]
+///
+/// ```no_run
+/// # async fn run() -> chrome_cdp::Result<()> {
+/// use chrome_cdp::{BrowserManager, CiFlags};
+/// use std::time::Duration;
+///
+/// let manager = BrowserManager::builder()
+///     .arg("--lang=en-US")
+///     .idle_timeout(Some(Duration::from_secs(60)))
+///     .pool_size(4)
+///     .ci_flags(CiFlags::Never)
+///     .build();
+/// let browser = manager.get_browser().await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct BrowserManagerBuilder {
+    launch: LaunchOptions,
+    idle_timeout: Option<Duration>,
+    pool_size: Option<usize>,
+    page_options: PageOptions,
+    ci_flags: CiFlags,
+    host_limit: Option<usize>,
+    relaunch_on_crash: bool,
+}
+
+impl Default for BrowserManagerBuilder {
+    fn default() -> Self {
+        Self {
+            launch: LaunchOptions::default(),
+            idle_timeout: Some(DEFAULT_IDLE_TIMEOUT),
+            pool_size: None,
+            page_options: PageOptions::default(),
+            ci_flags: CiFlags::default(),
+            host_limit: None,
+            relaunch_on_crash: false,
+        }
+    }
+}
+
+impl BrowserManagerBuilder {
+    /// Chrome executable to launch (default: `CHROME_BIN` or discovered)
+    pub fn executable(mut self, path: impl Into<PathBuf>) -> Self {
+        self.launch.executable_path = Some(path.into());
+        self
+    }
+
+    /// Run without a window, e.g. `true` or [`HeadlessMode::Old`] (default: [`HeadlessMode::New`])
+    pub fn headless(mut self, headless: impl Into<HeadlessMode>) -> Self {
+        self.launch.headless = headless.into();
+        self
+    }
+
+    /// Print Chrome's output and manager diagnostics (default: `false`)
+    pub fn debug(mut self, debug: bool) -> Self {
+        self.launch.debug = debug;
+        self
+    }
+
+    /// Add a Chrome command-line argument
+    pub fn arg(mut self, arg: impl Into<String>) -> Self {
+        self.launch.args.push(arg.into());
+        self
+    }
+
+    /// Add Chrome command-line arguments
+    pub fn args<I, S>(mut self, args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.launch.args.extend(args.into_iter().map(Into::into));
+        self
+    }
+
+    /// Launch browsers on a fixed DevTools port instead of a random one
+    pub fn remote_debugging_port(mut self, port: u16) -> Self {
+        self.launch.remote_debugging_port = Some(port);
+        self
+    }
+
+    /// Timeouts for launched browsers and, as a fallback, their pages
+    pub fn timeouts(mut self, timeouts: Timeouts) -> Self {
+        self.launch.timeouts = timeouts;
+        self
+    }
+
+    /// Give launched browsers `timeout` to start (default: 30 seconds)
+    pub fn launch_timeout(mut self, timeout: Duration) -> Self {
+        self.launch.timeouts.launch = Some(timeout);
+        self
+    }
+
+    /// Close the browser after it has not been requested for `timeout`
+    /// (default: five minutes; `None` keeps it open)
+    pub fn idle_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.idle_timeout = timeout;
+        self
+    }
+
+    /// Keep at most `size` pages opened by the manager at a time
+    ///
+    /// Tabs are then kept in a [`PagePool`] and reused: between leases they
+    /// stay open, frozen, so later leases skip creating a tab, but may still
+    /// show the previous lease's document. Further page requests wait until a
+    /// page is released. Unlimited by default, with every lease opening and
+    /// closing a tab of its own.
+    pub fn pool_size(mut self, size: usize) -> Self {
+        self.pool_size = Some(size.max(1));
+        self
+    }
+
+    /// Settings for pages the manager opens
+    pub fn page_options(mut self, options: PageOptions) -> Self {
+        self.page_options = options;
+        self
+    }
+
+    /// When to add sandbox-disabling flags for CI machines (default: [`CiFlags::Auto`])
+    pub fn ci_flags(mut self, ci_flags: CiFlags) -> Self {
+        self.ci_flags = ci_flags;
+        self
+    }
+
+    /// Allow at most `per_host` simultaneous navigations to each host
+    pub fn host_limit(mut self, per_host: usize) -> Self {
+        self.host_limit = Some(per_host);
+        self
+    }
+
+    /// Route the browser's traffic through a proxy, answering its credentials on every page
+    pub fn proxy(mut self, proxy: ProxyConfig) -> Self {
+        self.launch.proxy = Some(proxy);
+        self
+    }
+
+    /// Launch browsers that accept invalid TLS certificates, e.g. self-signed ones
+    pub fn ignore_certificate_errors(mut self, ignore: bool) -> Self {
+        self.launch.ignore_certificate_errors = ignore;
+        self
+    }
+
+    /// Set an environment variable for launched browsers, see [`LaunchOptions::env`]
+    pub fn env(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.launch.env.push((key.into(), Some(value.into())));
+        self
+    }
+
+    /// Keep an environment variable away from launched browsers, see [`LaunchOptions::env_remove`]
+    pub fn env_remove(mut self, key: impl Into<String>) -> Self {
+        self.launch.env.push((key.into(), None));
+        self
+    }
+
+    /// Stream the output of every launched browser, tagged with its process id
+    pub fn output(mut self, sender: mpsc::UnboundedSender<ChromeOutput>) -> Self {
+        self.launch.output = Some(sender);
+        self
+    }
+
+    /// Window size and device scale of launched browsers and their new pages
+    pub fn viewport(mut self, viewport: Viewport) -> Self {
+        self.launch.viewport = Some(viewport);
+        self
+    }
+
+    /// Launch browsers headful with DevTools open for every tab, see [`LaunchOptions::devtools`]
+    pub fn devtools(mut self, enabled: bool) -> Self {
+        self.launch.devtools = enabled;
+        self
+    }
+
+    /// Tune launch flags to the cgroup's memory and CPU limits, see [`ResourceTuning`]
+    pub fn tune_to_resources(mut self, enabled: bool) -> Self {
+        self.launch.tune_to_resources = enabled;
+        self
+    }
+
+    /// Retry page work once on a relaunched browser when the browser died during it
+    ///
+    /// Applies to [`BrowserManager::with_page_retrying`] and [`BrowserManager::map_pages`]:
+    /// if the closure fails and the browser no longer passes its health check,
+    /// a fresh browser is launched and the closure runs again on a new page.
+    /// Off by default, since the closure may have had side effects already.
+    pub fn relaunch_on_crash(mut self, enabled: bool) -> Self {
+        self.relaunch_on_crash = enabled;
+        self
+    }
+
+    /// Create the manager; must be called within a Tokio runtime
+    pub fn build(self) -> BrowserManager {
+        let state = Arc::new(Mutex::new(BrowserState {
+            browser: None,
+            last_used: Instant::now(),
+        }));
+
+        let (idle_timeout, idle_rx) = watch::channel(self.idle_timeout);
+        let idle_monitor = spawn_idle_monitor(state.clone(), idle_rx);
+
+        BrowserManager {
+            launch: self.launch,
+            state,
+            idle_timeout: Arc::new(idle_timeout),
+            idle_monitor: Arc::new(idle_monitor),
+            shut_down: Arc::new(AtomicBool::new(false)),
+            host_limiter: self
+                .host_limit
+                .map(|per_host| Arc::new(HostLimiter::new(per_host))),
+            pool_size: self.pool_size,
+            page_pool: Arc::new(std::sync::Mutex::new(None)),
+            page_options: self.page_options,
+            ci_flags: self.ci_flags,
+            relaunch_on_crash: self.relaunch_on_crash,
+        }
+    }
+}
+
+/// Manager for browser instances with auto-cleanup
+#[derive(Clone)]
+pub struct BrowserManager {
+    /// Options every browser is launched with, before the manager's own flags
+    launch: LaunchOptions,
+    state: Arc<Mutex<BrowserState>>,
+    idle_timeout: Arc<watch::Sender<Option<Duration>>>,
+    idle_monitor: Arc<JoinHandle<()>>,
+    shut_down: Arc<AtomicBool>,
+    host_limiter: Option<Arc<HostLimiter>>,
+    /// Most pages open at once, if limited
+    pool_size: Option<usize>,
+    /// Warm tabs of the current browser, if the pool size is limited
+    page_pool: Arc<std::sync::Mutex<Option<PagePool>>>,
+    page_options: PageOptions,
+    ci_flags: CiFlags,
+    relaunch_on_crash: bool,
+}
+
+impl BrowserManager {
+    /// Start configuring a manager
+    pub fn builder() -> BrowserManagerBuilder {
+        BrowserManagerBuilder::default()
+    }
+
+    /// Create a new browser manager
+    #[deprecated(note = "use `BrowserManager::builder()` instead")]
+    pub fn new(
+        browser_path: Option<PathBuf>,
+        headless: bool,
+        debug: bool,
+        chrome_args: Vec<String>,
+    ) -> Self {
+        let mut builder = Self::builder()
+            .headless(headless)
+            .debug(debug)
+            .args(chrome_args);
+        builder.launch.executable_path = browser_path;
+        builder.build()
+    }
+
+    /// Allow at most `per_host` simultaneous navigations to each host
+    ///
+    /// Applies to pages opened by [`map_pages`](Self::map_pages); share
+    /// [`host_limiter`](Self::host_limiter) with other pages to include them.
+    #[deprecated(note = "use `BrowserManagerBuilder::host_limit` instead")]
+    pub fn with_host_limit(mut self, per_host: usize) -> Self {
+        self.host_limiter = Some(Arc::new(HostLimiter::new(per_host)));
+        self
+    }
+
+    /// Navigation limiter shared by the manager's pages, if a limit is set
+    pub fn host_limiter(&self) -> Option<Arc<HostLimiter>> {
+        self.host_limiter.clone()
+    }
+
+    /// Close the browser after it has not been requested for `timeout`
+    ///
+    /// `None` keeps it open until the manager is dropped. The default is five
+    /// minutes. Browsers still held by callers stay alive until released.
+    #[deprecated(note = "use `BrowserManagerBuilder::idle_timeout` instead")]
+    pub fn with_idle_timeout(self, timeout: Option<Duration>) -> Self {
+        self.idle_timeout.send_replace(timeout);
+        self
+    }
+
+    /// Idle time after which the browser is closed, if any
+    pub fn idle_timeout(&self) -> Option<Duration> {
+        *self.idle_timeout.borrow()
+    }
+
+    /// Launch browsers on a fixed DevTools port instead of a random one
+    #[deprecated(note = "use `BrowserManagerBuilder::remote_debugging_port` instead")]
+    pub fn with_remote_debugging_port(mut self, port: u16) -> Self {
+        self.launch.remote_debugging_port = Some(port);
+        self
+    }
+
+    /// Set the timeouts used for launched browsers and, as a fallback, their pages
+    #[deprecated(note = "use `BrowserManagerBuilder::timeouts` instead")]
+    pub fn with_timeouts(mut self, timeouts: Timeouts) -> Self {
+        self.launch.timeouts = timeouts;
+        self
+    }
+
+    /// Get or create a browser instance
+    ///
+    /// A cached browser whose process died or whose DevTools endpoint stopped
+    /// answering is replaced by a freshly launched one.
+    pub async fn get_browser(&self) -> Result<Arc<CdpBrowser>> {
+        let cached = {
+            let mut s = self.state.lock().await;
+            self.check_running()?;
+            s.last_used = Instant::now();
+            s.browser.clone()
+        };
+
+        // Checked without the lock so a slow endpoint does not stall other callers
+        if let Some(browser) = &cached {
+            match browser.health_check().await {
+                Ok(()) => return Ok(Arc::clone(browser)),
+                Err(e) => {
+                    if self.launch.debug {
+                        eprintln!("Relaunching unhealthy browser: {}", e);
+                    }
+                }
+            }
+        }
+
+        let mut s = self.state.lock().await;
+        self.check_running()?;
+        if let Some(browser) = &s.browser {
+            // Another caller launched a replacement in the meantime
+            if !cached.is_some_and(|cached| Arc::ptr_eq(&cached, browser)) {
+                return Ok(Arc::clone(browser));
+            }
+            s.browser = None;
+        }
+
+        let mut args = vec!["--disable-blink-features=AutomationControlled".to_string()];
+
+        // In CI environments, add sandbox-disabling flags unless disabled
+        if self.ci_flags.applies() {
+            args.push("--disable-gpu".to_string());
+            args.push("--no-sandbox".to_string());
+            args.push("--disable-setuid-sandbox".to_string());
+        }
+
+        // Custom Chrome args come last so they can override the defaults
+        let mut options = self.launch.clone();
+        args.append(&mut options.args);
+        options.args = args;
+
+        let browser = Arc::new(CdpBrowser::launch_with_options(options).await?);
+        s.browser = Some(Arc::clone(&browser));
+        // Wake the idle monitor so it starts counting for the new browser
+        self.idle_timeout.send_modify(|_| {});
+
+        Ok(browser)
+    }
+
+    /// Fail if [`shutdown`](Self::shutdown) was called
+    fn check_running(&self) -> Result<()> {
+        if self.shut_down.load(Ordering::SeqCst) {
+            return Err(Error::Browser(
+                "Browser manager has been shut down".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Stop the idle monitor and close the managed browser
+    ///
+    /// Affects every clone of the manager: later calls to
+    /// [`get_browser`](Self::get_browser) fail. The browser is closed
+    /// gracefully even if callers still hold it. Calling this again does nothing.
+    pub async fn shutdown(&self) -> Result<()> {
+        let browser = {
+            let mut s = self.state.lock().await;
+            self.shut_down.store(true, Ordering::SeqCst);
+            s.browser.take()
+        };
+        self.idle_monitor.abort();
+        match browser {
+            Some(browser) => browser.close().await,
+            None => Ok(()),
+        }
+    }
+
+    /// Open each URL in its own page and run `f` on it, at most `concurrency` pages at a time
+    ///
+    /// Results are returned in the order of `urls`. Every page is closed
+    /// afterwards, including when `f` fails, panics, or the returned future is
+    /// dropped; a panic in `f` is reported as an error for that URL only.
+    /// URLs whose page died with the browser are retried once when
+    /// [`relaunch_on_crash`](BrowserManagerBuilder::relaunch_on_crash) is set.
+    pub async fn map_pages<I, S, F, Fut, T>(
+        &self,
+        urls: I,
+        concurrency: usize,
+        f: F,
+    ) -> Result<Vec<Result<T>>>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+        F: Fn(CdpPage) -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let browser = self.get_browser().await?;
+        let f = &f;
+        let jobs = urls.into_iter().map(|url| {
+            let mut browser = browser.clone();
+            async move {
+                let mut retried = false;
+                loop {
+                    let result = self
+                        .lease_page(&browser, |page| async {
+                            page.goto(url.as_ref()).await?;
+                            f(page).await
+                        })
+                        .await;
+                    match result {
+                        Err(e) if !retried && self.crashed(&browser, &e).await => {
+                            retried = true;
+                            browser = self.get_browser().await?;
+                        }
+                        result => return result,
+                    }
+                }
+            }
+        });
+        Ok(run_bounded(jobs, concurrency).await)
+    }
+
+    /// Open a page, run `f` on it and close the page afterwards
+    ///
+    /// The page is closed when `f` fails or panics too, and also when the
+    /// returned future is dropped, so leases never leave tabs behind. Panics
+    /// in `f` are resumed once the page is closed. With a
+    /// [`pool_size`](BrowserManagerBuilder::pool_size), the tab is returned
+    /// to the pool instead. See
+    /// [`with_page_retrying`](Self::with_page_retrying) to run `f` again
+    /// when the browser dies.
+    ///
+    /// ```no_run
+    /// # async fn run(manager: chrome_cdp::BrowserManager) -> chrome_cdp::Result<()> {
+    /// let title = manager
+    ///     .with_page(|page| async move {
+    ///         page.goto("https://example.com").await?;
+    ///         page.evaluate("document.title").await
+    ///     })
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn with_page<F, Fut, T>(&self, f: F) -> Result<T>
+    where
+        F: FnOnce(CdpPage) -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let browser = self.get_browser().await?;
+        self.lease_page(&browser, f).await
+    }
+
+    /// Like [`with_page`](Self::with_page), but with
+    /// [`relaunch_on_crash`](BrowserManagerBuilder::relaunch_on_crash) `f`
+    /// runs a second time on a relaunched browser if the browser died while it ran
+    pub async fn with_page_retrying<F, Fut, T>(&self, mut f: F) -> Result<T>
+    where
+        F: FnMut(CdpPage) -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let mut browser = self.get_browser().await?;
+        let mut retried = false;
+        loop {
+            match self.lease_page(&browser, &mut f).await {
+                Err(e) if !retried && self.crashed(&browser, &e).await => {
+                    retried = true;
+                    browser = self.get_browser().await?;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Lend a page of `browser` to `f`, closing it or returning it to the pool afterwards
+    async fn lease_page<F, Fut, T>(&self, browser: &Arc<CdpBrowser>, f: F) -> Result<T>
+    where
+        F: FnOnce(CdpPage) -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let pooled = match self.page_pool(browser) {
+            Some(pool) => Some(pool.checkout().await?),
+            None => None,
+        };
+        // `f` owns its page, so a pooled tab is lent through a connection of its own
+        let page = match &pooled {
+            Some(pooled) => self.connect_page(browser, pooled.ws_url()).await?,
+            None => {
+                browser
+                    .open_page(|ws_url| async move { self.connect_page(browser, &ws_url).await })
+                    .await?
+            }
+        };
+        let closer = PageCloser(pooled.is_none().then(|| page.connection().clone()));
+        let outcome = AssertUnwindSafe(f(page)).catch_unwind().await;
+        closer.close().await;
+        if let Some(pooled) = pooled {
+            pooled.release().await;
+        }
+        outcome.unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+    }
+
+    /// Whether page work failed because the browser died, and should be retried
+    async fn crashed(&self, browser: &CdpBrowser, error: &Error) -> bool {
+        if !self.relaunch_on_crash || browser.health_check().await.is_ok() {
+            return false;
+        }
+        if self.launch.debug {
+            eprintln!("Retrying page work on a relaunched browser: {}", error);
+        }
+        true
+    }
+
+    /// Pool of warm tabs on `browser`, if the pool size is limited
+    fn page_pool(&self, browser: &Arc<CdpBrowser>) -> Option<PagePool> {
+        let size = self.pool_size?;
+        let mut pool = self.page_pool.lock().ok()?;
+        match pool.as_ref() {
+            Some(current) if Arc::ptr_eq(current.browser(), browser) => Some(current.clone()),
+            // A relaunched browser starts with a pool of its own
+            _ => {
+                let fresh = PagePool::new(
+                    browser.clone(),
+                    PagePoolOptions {
+                        size,
+                        ..Default::default()
+                    },
+                );
+                *pool = Some(fresh.clone());
+                Some(fresh)
+            }
+        }
+    }
+
+    /// Connect to a page target with the manager's page options
+    async fn connect_page(&self, browser: &CdpBrowser, ws_url: &str) -> Result<CdpPage> {
+        let page = CdpPage::new_with_domains(
+            ws_url,
+            self.page_options.timeouts.or(browser.timeouts()),
+            &self.page_options.domains,
+        )
+        .await?;
+        browser.prepare_page(&page).await?;
+        page.set_host_limiter(self.host_limiter());
+        Ok(page)
+    }
+}
+
+/// Closes a page when dropped unless it was closed explicitly
+struct PageCloser(Option<CdpConnection>);
+
+impl PageCloser {
+    async fn close(mut self) {
+        if let Some(connection) = self.0.take() {
+            // The page may already have been closed by the caller
+            let _ = connection.send_command("Page.close", json!({})).await;
+        }
+    }
+}
+
+impl Drop for PageCloser {
+    fn drop(&mut self) {
+        if let Some(connection) = self.0.take() {
+            if let Ok(handle) = tokio::runtime::Handle::try_current() {
+                handle.spawn(async move {
+                    let _ = connection.send_command("Page.close", json!({})).await;
+                });
+            }
+        }
+    }
+}
+
+/// Run futures with bounded concurrency, keeping input order and isolating panics
+async fn run_bounded<I, Fut, T>(jobs: I, concurrency: usize) -> Vec<Result<T>>
+where
+    I: IntoIterator<Item = Fut>,
+    Fut: Future<Output = Result<T>>,
+{
+    futures::stream::iter(jobs.into_iter().map(|job| {
+        AssertUnwindSafe(job).catch_unwind().map(|outcome| {
+            outcome.unwrap_or_else(|panic| {
+                let message = panic
+                    .downcast_ref::<&str>()
+                    .map(|s| s.to_string())
+                    .or_else(|| panic.downcast_ref::<String>().cloned())
+                    .unwrap_or_else(|| "unknown panic".to_string());
+                Err(Error::Browser(format!("Page task panicked: {}", message)))
+            })
+        })
+    }))
+    .buffered(concurrency.max(1))
+    .collect()
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connection::tests::{mock_server, server_recv, server_send};
+    use std::sync::atomic::{AtomicUsize, Ordering};
 
-        let status = response.status();
-        let body = response
-            .text()
-            .await
-            .map_err(|e| Error::Http(format!("Failed to read response from {}: {}", url, e)))?;
+    #[tokio::test]
+    async fn test_run_bounded_keeps_order_and_limits_concurrency() {
+        let running = AtomicUsize::new(0);
+        let peak = AtomicUsize::new(0);
+        let jobs = (0..8u64).map(|i| {
+            let (running, peak) = (&running, &peak);
+            async move {
+                let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                peak.fetch_max(now, Ordering::SeqCst);
+                sleep(Duration::from_millis(10 * (8 - i))).await;
+                running.fetch_sub(1, Ordering::SeqCst);
+                Ok(i)
+            }
+        });
 
-        if !status.is_success() {
-            return Err(Error::Browser(format!(
-                "Chrome debugger returned error status {} ({}). Response: {}",
-                status, url, body
-            )));
-        }
+        let results = run_bounded(jobs, 3).await;
+        let values: Vec<u64> = results.into_iter().map(|r| r.unwrap()).collect();
+        assert_eq!(values, (0..8).collect::<Vec<_>>());
+        assert!(peak.load(Ordering::SeqCst) <= 3);
+    }
 
-        let value: Value = serde_json::from_str(&body)?;
+    #[tokio::test]
+    async fn test_run_bounded_isolates_panics() {
+        let jobs = (0..3).map(|i| async move {
+            if i == 1 {
+                panic!("boom");
+            }
+            Ok(i)
+        });
 
-        value["webSocketDebuggerUrl"]
-            .as_str()
-            .map(String::from)
-            .ok_or_else(|| {
-                Error::Browser(format!(
-                    "Chrome debugger response does not contain webSocketDebuggerUrl. Response: {}",
-                    body
-                ))
-            })
+        let results = run_bounded(jobs, 2).await;
+        assert_eq!(results[0].as_ref().unwrap(), &0);
+        assert!(matches!(&results[1], Err(Error::Browser(msg)) if msg.contains("boom")));
+        assert_eq!(results[2].as_ref().unwrap(), &2);
     }
 
-    /// Create a new page and return its WebSocket URL
-    pub async fn new_page(&self) -> Result<String> {
-        let url = format!("http://127.0.0.1:{}/json/new", self.port);
-        let client = reqwest::Client::new();
-
-        let response =
-            client.put(&url).send().await.map_err(|e| {
-                Error::Http(format!("Failed to create new page via {}: {}", url, e))
-            })?;
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_launch_reports_missing_channel() {
+        // Canary has no Linux build, so it is never discovered there
+        let result = CdpBrowser::launch_with_options(LaunchOptions {
+            channel: Some(Channel::Canary),
+            ..Default::default()
+        })
+        .await;
+        assert!(matches!(result, Err(Error::Browser(msg)) if msg.contains("Canary")));
+    }
 
-        let status = response.status();
-        let body = response
-            .text()
-            .await
-            .map_err(|e| Error::Http(format!("Failed to read response from {}: {}", url, e)))?;
+    #[cfg(unix)]
+    fn fake_chrome(script: &str) -> PathBuf {
+        use std::os::unix::fs::PermissionsExt;
 
-        if !status.is_success() {
-            return Err(Error::Browser(format!(
-                "Chrome debugger returned error {} when creating new page ({}). Response: {}",
-                status, url, body
-            )));
-        }
+        let path = std::env::temp_dir().join(format!("fake-chrome-{}", uuid::Uuid::new_v4()));
+        std::fs::write(&path, format!("#!/bin/sh\n{}\n", script)).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        path
+    }
 
-        let value: Value = serde_json::from_str(&body).map_err(|e| {
-            Error::Browser(format!(
-                "Failed to parse JSON response from {}: {}. Response body: {}",
-                url, e, body
-            ))
-        })?;
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_launch_fails_fast_when_chrome_exits() {
+        let chrome = fake_chrome("echo 'Missing X server or $DISPLAY' >&2; exit 1");
+        let started = std::time::Instant::now();
+        let result = CdpBrowser::launch_with_options(LaunchOptions {
+            executable_path: Some(chrome.clone()),
+            ..Default::default()
+        })
+        .await;
+        assert!(started.elapsed() < Duration::from_secs(10));
+        let Err(Error::LaunchFailed(failure)) = result else {
+            panic!("expected a launch failure");
+        };
+        // Kept so the error can point at it
+        assert!(failure.user_data_dir.is_dir());
+        std::fs::remove_dir_all(&failure.user_data_dir).unwrap();
+        assert_eq!(failure.executable, chrome);
+        assert_eq!(failure.stderr_tail, ["Missing X server or $DISPLAY"]);
+        assert!(failure.exit_status.unwrap().contains('1'));
+        std::fs::remove_file(chrome).unwrap();
+    }
 
-        value["webSocketDebuggerUrl"]
-            .as_str()
-            .map(String::from)
-            .ok_or_else(|| {
-                Error::Browser(format!(
-                    "Could not find webSocketDebuggerUrl in response. Response: {}",
-                    body
-                ))
-            })
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_launch_timeout_is_configurable() {
+        let chrome = fake_chrome("echo 'still starting' >&2; exec sleep 30");
+        let started = std::time::Instant::now();
+        let result = CdpBrowser::launch_with_options(
+            LaunchOptions {
+                executable_path: Some(chrome.clone()),
+                ..Default::default()
+            }
+            .with_launch_timeout(Duration::from_millis(300)),
+        )
+        .await;
+        assert!(started.elapsed() < Duration::from_secs(5));
+        let Err(Error::LaunchFailed(failure)) = result else {
+            panic!("expected a launch failure");
+        };
+        let _ = std::fs::remove_dir_all(&failure.user_data_dir);
+        assert_eq!(failure.timeout, Duration::from_millis(300));
+        assert_eq!(failure.exit_status, None);
+        assert!(failure.to_string().contains("not found after 300ms"));
+        std::fs::remove_file(chrome).unwrap();
     }
-}
 
-impl Drop for CdpBrowser {
-    fn drop(&mut self) {
-        if let Some(mut process) = self.process.take() {
-            let _ = process.kill();
-        }
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_launch_applies_env() {
+        let chrome = fake_chrome("echo \"TZ=$TZ LANG=${LANG-unset}\" >&2; exit 1");
+        let result = CdpBrowser::launch_with_options(
+            LaunchOptions {
+                executable_path: Some(chrome.clone()),
+                ..Default::default()
+            }
+            .env("LANG", "C")
+            .env("TZ", "Asia/Tokyo")
+            .env_remove("LANG"),
+        )
+        .await;
+        let Err(Error::LaunchFailed(failure)) = result else {
+            panic!("expected a launch failure");
+        };
+        let _ = std::fs::remove_dir_all(&failure.user_data_dir);
+        assert_eq!(failure.stderr_tail, ["TZ=Asia/Tokyo LANG=unset"]);
+        std::fs::remove_file(chrome).unwrap();
     }
-}
 
-/// Browser state for managing lifecycle
-pub struct BrowserState {
-    pub browser: Option<Arc<CdpBrowser>>,
-    pub last_used: Instant,
-}
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_launch_sizes_window() {
+        let chrome = fake_chrome(
+            "for arg; do case \"$arg\" in --window-size=*|--force-device-scale-factor=*) echo \"$arg\" >&2;; esac; done; exit 1",
+        );
+        let result = CdpBrowser::launch_with_options(LaunchOptions {
+            executable_path: Some(chrome.clone()),
+            viewport: Some(Viewport::new(1280, 720).with_scale(2.0)),
+            ..Default::default()
+        })
+        .await;
+        let Err(Error::LaunchFailed(failure)) = result else {
+            panic!("expected a launch failure");
+        };
+        let _ = std::fs::remove_dir_all(&failure.user_data_dir);
+        assert_eq!(
+            failure.stderr_tail,
+            ["--window-size=1280,720", "--force-device-scale-factor=2"]
+        );
+        std::fs::remove_file(chrome).unwrap();
+    }
 
-/// Manager for browser instances with auto-cleanup
-#[derive(Clone)]
-pub struct BrowserManager {
-    browser_path: Option<PathBuf>,
-    headless: bool,
-    debug: bool,
-    chrome_args: Vec<String>,
-    state: Arc<Mutex<BrowserState>>,
-}
+    #[tokio::test]
+    async fn test_screenshot_all_captures_each_page() {
+        use crate::connection::tests::fake_chrome_with;
+        use base64::Engine;
+
+        let chrome = fake_chrome_with(|target, request| match (request["method"].as_str(), target) {
+            (Some("Target.getTargets"), _) => Some(json!({"result": {"targetInfos": [
+                {"targetId": "P1", "type": "page", "url": "https://a.example/", "title": "", "attached": false},
+                {"targetId": "P2", "type": "page", "url": "https://b.example/", "title": "", "attached": false},
+                {"targetId": "P3", "type": "page", "url": "https://c.example/", "title": "", "attached": false},
+                {"targetId": "W1", "type": "service_worker", "url": "https://a.example/sw.js", "title": "", "attached": false}
+            ]}})),
+            (_, Some("P1")) => Some(json!({"result": {
+                "data": base64::engine::general_purpose::STANDARD.encode(b"png-1")
+            }})),
+            // A hung renderer never answers
+            (_, Some("P3")) => None,
+            _ => Some(json!({"error": {"code": -32000, "message": "Target closed"}})),
+        })
+        .await;
 
-impl BrowserManager {
-    /// Create a new browser manager
-    pub fn new(
-        browser_path: Option<PathBuf>,
-        headless: bool,
-        debug: bool,
-        chrome_args: Vec<String>,
-    ) -> Self {
-        let state = Arc::new(Mutex::new(BrowserState {
-            browser: None,
-            last_used: Instant::now(),
-        }));
+        let browser = CdpBrowser::connect_ws(&chrome.ws_url()).await.unwrap();
+        let images = browser
+            .screenshot_all_within(Duration::from_millis(200))
+            .await
+            .unwrap();
+        assert_eq!(images.len(), 3);
+        assert_eq!(images["P1"].as_ref().unwrap(), b"png-1");
+        assert!(images["P2"].is_err());
+        assert!(matches!(images["P3"], Err(Error::Timeout(_))));
+    }
 
-        // Spawn the inactivity monitor task
-        let state_clone = state.clone();
-        tokio::spawn(async move {
+    #[tokio::test]
+    async fn test_new_pages_get_default_viewport() {
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        let url = mock_server(|mut ws| async move {
             loop {
-                sleep(Duration::from_secs(60)).await;
-                let mut s = state_clone.lock().await;
-                if s.browser.is_some() && s.last_used.elapsed() > Duration::from_secs(5 * 60) {
-                    s.browser = None; // Drops Arc<CdpBrowser>, which triggers process kill
+                let request = server_recv(&mut ws).await;
+                server_send(&mut ws, json!({"id": request["id"], "result": {}})).await;
+                if request["method"] == "Emulation.setDeviceMetricsOverride" {
+                    let _ = tx.send(request["params"].clone());
+                    break;
                 }
             }
-        });
+        })
+        .await;
+        let page = CdpPage::new_with_domains(&url, Timeouts::default(), &[])
+            .await
+            .unwrap();
+        let mut browser = CdpBrowser::connect_ws("ws://127.0.0.1:9/devtools/browser/x")
+            .await
+            .unwrap();
+        browser.viewport = Some(Viewport::new(800, 600).with_scale(1.5));
 
-        Self {
-            browser_path,
-            headless,
-            debug,
-            chrome_args,
-            state,
-        }
+        browser.prepare_page(&page).await.unwrap();
+        assert_eq!(
+            rx.await.unwrap(),
+            json!({"width": 800, "height": 600, "deviceScaleFactor": 1.5, "mobile": false})
+        );
     }
 
-    /// Get or create a browser instance
-    pub async fn get_browser(&self) -> Result<Arc<CdpBrowser>> {
-        let mut s = self.state.lock().await;
-        s.last_used = Instant::now();
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_launch_streams_output() {
+        let chrome = fake_chrome("echo 'to stdout'; echo 'GPU process crashed' >&2; exit 1");
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let result = CdpBrowser::launch_with_options(LaunchOptions {
+            executable_path: Some(chrome.clone()),
+            output: Some(tx),
+            ..Default::default()
+        })
+        .await;
+        assert!(result.is_err());
 
-        if let Some(browser) = &s.browser {
-            return Ok(Arc::clone(browser));
+        let mut lines = Vec::new();
+        while let Some(output) = rx.recv().await {
+            assert!(output.pid > 0);
+            lines.push((output.stream, output.line));
         }
+        lines.sort_by_key(|(stream, _)| *stream == OutputStream::Stderr);
+        assert_eq!(
+            lines,
+            [
+                (OutputStream::Stdout, "to stdout".to_string()),
+                (OutputStream::Stderr, "GPU process crashed".to_string())
+            ]
+        );
+        std::fs::remove_file(chrome).unwrap();
+    }
 
-        let mut args = vec!["--disable-blink-features=AutomationControlled".to_string()];
+    #[test]
+    fn test_container_defaults() {
+        let options = LaunchOptions::container_defaults().with_single_process();
+        assert_eq!(options.args[0], "--no-sandbox");
+        assert!(options
+            .args
+            .ends_with(&["--single-process".to_string(), "--no-zygote".to_string()]));
+        assert_eq!(options.headless, HeadlessMode::New);
+        assert!(options.tune_to_resources);
+        assert!(!LaunchOptions::default().tune_to_resources);
+    }
 
-        // In CI environments, automatically add sandbox-disabling flags
-        if std::env::var("CI").is_ok() {
-            args.push("--disable-gpu".to_string());
-            args.push("--no-sandbox".to_string());
-            args.push("--disable-setuid-sandbox".to_string());
-        }
+    #[test]
+    fn test_headless_mode_args() {
+        assert_eq!(HeadlessMode::default(), HeadlessMode::from(true));
+        assert_eq!(HeadlessMode::from(false).arg(), None);
+        assert_eq!(HeadlessMode::Old.arg(), Some("--headless=old"));
+        assert_eq!(HeadlessMode::New.arg(), Some("--headless=new"));
+    }
 
-        // Add custom Chrome args
-        args.extend(self.chrome_args.clone());
+    #[test]
+    fn test_ensure_port_free_rejects_taken_port() {
+        let listener = std::net::TcpListener::bind((LOCALHOST, 0)).unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let err = ensure_port_free(port).unwrap_err();
+        assert!(err.to_string().contains(&port.to_string()));
+        drop(listener);
+        assert!(ensure_port_free(port).is_ok());
+    }
 
-        let browser = Arc::new(
-            CdpBrowser::launch(self.browser_path.clone(), args, self.headless, self.debug).await?,
-        );
-        s.browser = Some(Arc::clone(&browser));
+    #[test]
+    fn test_temp_profile_removed_unless_kept() {
+        let removed = std::env::temp_dir().join(format!("chrome-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(removed.join("Default")).unwrap();
+        drop(TempProfile {
+            path: removed.clone(),
+            keep: false,
+        });
+        assert!(!removed.exists());
 
-        Ok(browser)
+        let kept = std::env::temp_dir().join(format!("chrome-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&kept).unwrap();
+        drop(TempProfile {
+            path: kept.clone(),
+            keep: true,
+        });
+        assert!(kept.exists());
+        std::fs::remove_dir_all(&kept).unwrap();
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    #[test]
+    fn test_parse_targets() {
+        let targets = parse_targets(json!({"targetInfos": [
+            {"targetId": "P1", "type": "page", "url": "about:blank", "title": "",
+             "attached": true, "canAccessOpener": false},
+            {"targetId": "W1", "type": "service_worker", "url": "https://a.test/sw.js"},
+            {"targetId": "P2", "type": "page", "browserContextId": "CTX"}
+        ]}))
+        .unwrap();
+        let pages: Vec<_> = targets
+            .iter()
+            .filter(|t| t.is_page())
+            .map(|t| t.target_id.as_str())
+            .collect();
+        assert_eq!(pages, ["P1", "P2"]);
+        assert!(targets[0].attached);
+        assert_eq!(targets[1].target_type, "service_worker");
+        assert_eq!(targets[1].url, "https://a.test/sw.js");
+        assert_eq!(targets[2].browser_context_id.as_deref(), Some("CTX"));
+        assert!(parse_targets(json!({})).is_err());
+    }
+
+    #[test]
+    fn test_connect_retries() {
+        assert_eq!(connect_retries(Duration::from_secs(5)), 10);
+        assert_eq!(connect_retries(Duration::from_millis(100)), 1);
+    }
 
     #[test]
     fn test_chrome_path_linux() {
@@ -425,6 +2423,34 @@ mod tests {
         assert_eq!(url, "http://127.0.0.1:9222/json/new");
     }
 
+    #[test]
+    fn test_ws_endpoint_http() {
+        let http = ws_endpoint_http("ws://192.168.1.10:9222/devtools/browser/abc-123").unwrap();
+        assert_eq!(http.host(), "192.168.1.10");
+        assert_eq!(http.port(), 9222);
+        assert_eq!(http.scheme(), "http");
+    }
+
+    #[test]
+    fn test_ws_endpoint_http_default_port() {
+        let http = ws_endpoint_http("wss://chrome.example.com/devtools/browser").unwrap();
+        assert_eq!(http.host(), "chrome.example.com");
+        assert_eq!(http.port(), 443);
+        // A TLS WebSocket endpoint serves its /json documents over TLS too
+        assert_eq!(http.scheme(), "https");
+    }
+
+    #[test]
+    fn test_ws_endpoint_http_rejects_http() {
+        let result = ws_endpoint_http("http://127.0.0.1:9222/json/version");
+        assert!(matches!(result, Err(Error::Browser(_))));
+    }
+
+    #[test]
+    fn test_ws_endpoint_http_rejects_garbage() {
+        assert!(ws_endpoint_http("not a url").is_err());
+    }
+
     #[test]
     fn test_websocket_url_extraction_from_valid_json() {
         let json_body = r#"{"webSocketDebuggerUrl":"ws://localhost:9222/devtools/page/ABC123","browser":"Chrome"}"#;
@@ -474,30 +2500,36 @@ mod tests {
     }
 
     #[tokio::test]
+    #[allow(deprecated)]
     async fn test_browser_manager_creation() {
         let manager = BrowserManager::new(None, true, false, vec![]);
-        assert!(manager.headless);
-        assert!(!manager.debug);
-        assert!(manager.chrome_args.is_empty());
-        assert!(manager.browser_path.is_none());
+        assert_eq!(manager.launch.headless, HeadlessMode::New);
+        assert!(!manager.launch.debug);
+        assert!(manager.launch.args.is_empty());
+        assert!(manager.launch.executable_path.is_none());
     }
 
     #[tokio::test]
+    #[allow(deprecated)]
     async fn test_browser_manager_with_custom_args() {
         let custom_args = vec!["--disable-gpu".to_string(), "--no-sandbox".to_string()];
         let manager = BrowserManager::new(None, false, true, custom_args);
-        assert!(!manager.headless);
-        assert!(manager.debug);
-        assert_eq!(manager.chrome_args.len(), 2);
-        assert_eq!(manager.chrome_args[0], "--disable-gpu");
-        assert_eq!(manager.chrome_args[1], "--no-sandbox");
+        assert_eq!(manager.launch.headless, HeadlessMode::False);
+        assert!(manager.launch.debug);
+        assert_eq!(manager.launch.args.len(), 2);
+        assert_eq!(manager.launch.args[0], "--disable-gpu");
+        assert_eq!(manager.launch.args[1], "--no-sandbox");
     }
 
     #[tokio::test]
+    #[allow(deprecated)]
     async fn test_browser_manager_with_path() {
         let path = PathBuf::from("/custom/chrome");
         let manager = BrowserManager::new(Some(path), true, false, vec![]);
-        assert_eq!(manager.browser_path, Some(PathBuf::from("/custom/chrome")));
+        assert_eq!(
+            manager.launch.executable_path,
+            Some(PathBuf::from("/custom/chrome"))
+        );
     }
 
     #[test]
@@ -519,6 +2551,207 @@ mod tests {
         assert!(state.last_used.elapsed() < Duration::from_secs(5 * 60));
     }
 
+    #[test]
+    fn test_idle_deadline() {
+        let state = BrowserState {
+            browser: None,
+            last_used: Instant::now(),
+        };
+        assert_eq!(idle_deadline(&state, Some(Duration::from_secs(1))), None);
+    }
+
+    #[tokio::test]
+    async fn test_host_limit_is_shared() {
+        let manager = BrowserManager::builder().build();
+        assert!(manager.host_limiter().is_none());
+        let manager = BrowserManager::builder().host_limit(4).build();
+        let limiter = manager.host_limiter().unwrap();
+        assert_eq!(limiter.per_host(), 4);
+        assert!(Arc::ptr_eq(
+            &limiter,
+            &manager.clone().host_limiter().unwrap()
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_idle_timeout_is_configurable() {
+        let manager = BrowserManager::builder().build();
+        assert_eq!(manager.idle_timeout(), Some(DEFAULT_IDLE_TIMEOUT));
+        let manager = BrowserManager::builder().idle_timeout(None).build();
+        assert_eq!(manager.clone().idle_timeout(), None);
+    }
+
+    #[tokio::test]
+    async fn test_builder_configures_manager() {
+        let manager = BrowserManager::builder()
+            .executable("/custom/chrome")
+            .headless(HeadlessMode::Old)
+            .args(["--a", "--b"])
+            .arg("--c")
+            .idle_timeout(None)
+            .launch_timeout(Duration::from_secs(90))
+            .pool_size(2)
+            .ci_flags(CiFlags::Never)
+            .host_limit(3)
+            .proxy(ProxyConfig::new("http://proxy:3128"))
+            .relaunch_on_crash(true)
+            .ignore_certificate_errors(true)
+            .tune_to_resources(true)
+            .env("TZ", "UTC")
+            .env_remove("DISPLAY")
+            .output(mpsc::unbounded_channel().0)
+            .viewport(Viewport::new(1280, 720).with_scale(2.0))
+            .devtools(true)
+            .page_options(PageOptions {
+                domains: vec![],
+                ..Default::default()
+            })
+            .build();
+        let launch = &manager.launch;
+        assert_eq!(
+            launch.executable_path,
+            Some(PathBuf::from("/custom/chrome"))
+        );
+        assert_eq!(launch.headless, HeadlessMode::Old);
+        assert_eq!(launch.args, ["--a", "--b", "--c"]);
+        assert_eq!(manager.idle_timeout(), None);
+        assert_eq!(launch.timeouts.launch_timeout(), Duration::from_secs(90));
+        assert_eq!(manager.host_limiter().unwrap().per_host(), 3);
+        assert!(manager.page_options.domains.is_empty());
+        assert_eq!(launch.proxy.as_ref().unwrap().server, "http://proxy:3128");
+        assert!(manager.relaunch_on_crash);
+        assert!(launch.ignore_certificate_errors);
+        assert!(launch.tune_to_resources);
+        assert!(launch.output.is_some());
+        assert_eq!(launch.viewport.unwrap().device_scale_factor, 2.0);
+        assert!(launch.devtools);
+        assert_eq!(
+            launch.env,
+            [
+                ("TZ".to_string(), Some("UTC".to_string())),
+                ("DISPLAY".to_string(), None)
+            ]
+        );
+        assert!(!BrowserManager::builder().build().relaunch_on_crash);
+
+        assert_eq!(manager.pool_size, Some(2));
+
+        let defaults = BrowserManager::builder().build();
+        assert_eq!(defaults.launch.headless, HeadlessMode::New);
+        assert!(defaults.pool_size.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_failed_page_setup_closes_the_tab() {
+        use crate::connection::tests::fake_chrome_with;
+
+        let chrome = fake_chrome_with(|target, request| {
+            Some(match (target, request["method"].as_str()) {
+                (None, Some("Target.createTarget")) => json!({"result": {"targetId": "T1"}}),
+                (Some(_), Some("Page.enable")) => {
+                    json!({"error": {"code": -32000, "message": "Page.enable failed"}})
+                }
+                _ => json!({"result": {}}),
+            })
+        })
+        .await;
+        let browser = CdpBrowser::connect("127.0.0.1", chrome.addr.port())
+            .await
+            .unwrap();
+
+        assert!(browser.new_page_connected(None).await.is_err());
+        let closed: Vec<_> = chrome
+            .commands
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|command| command["method"] == "Target.closeTarget")
+            .map(|command| command["params"]["targetId"].clone())
+            .collect();
+        assert_eq!(closed, vec![json!("T1")]);
+    }
+
+    #[tokio::test]
+    async fn test_get_browser_reuses_healthy_browser() {
+        use crate::connection::tests::fake_chrome;
+
+        let (endpoint, _commands) = fake_chrome().await;
+        let browser = Arc::new(
+            CdpBrowser::connect(&endpoint.host, endpoint.port)
+                .await
+                .unwrap(),
+        );
+        let manager = BrowserManager::builder().build();
+        manager.state.lock().await.browser = Some(Arc::clone(&browser));
+
+        for _ in 0..2 {
+            let cached = manager.get_browser().await.unwrap();
+            assert!(Arc::ptr_eq(&cached, &browser));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_pooled_leases_reuse_tabs() {
+        use crate::connection::tests::{created, fake_chrome};
+
+        let (endpoint, commands) = fake_chrome().await;
+        let browser = Arc::new(
+            CdpBrowser::connect(&endpoint.host, endpoint.port)
+                .await
+                .unwrap(),
+        );
+        let closed = |commands: &std::sync::Mutex<Vec<Value>>| {
+            commands
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|command| command["method"] == "Page.close")
+                .count()
+        };
+
+        let pooled = BrowserManager::builder().pool_size(1).build();
+        for _ in 0..2 {
+            pooled
+                .lease_page(&browser, |page| async move {
+                    page.evaluate("1").await.map(|_| ())
+                })
+                .await
+                .unwrap();
+        }
+        assert_eq!(created(&commands), 1);
+        assert_eq!(closed(&commands), 0);
+
+        let unpooled = BrowserManager::builder().build();
+        unpooled
+            .lease_page(&browser, |_| async { Ok(()) })
+            .await
+            .unwrap();
+        assert_eq!(created(&commands), 2);
+        assert_eq!(closed(&commands), 1);
+    }
+
+    #[test]
+    fn test_ci_flags_policy() {
+        assert!(CiFlags::Always.applies());
+        assert!(!CiFlags::Never.applies());
+        assert_eq!(CiFlags::Auto.applies(), std::env::var("CI").is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_stops_manager() {
+        let manager = BrowserManager::builder().build();
+        let clone = manager.clone();
+        manager.shutdown().await.unwrap();
+        manager.shutdown().await.unwrap();
+
+        tokio::task::yield_now().await;
+        assert!(manager.idle_monitor.is_finished());
+        match clone.get_browser().await {
+            Err(e) => assert!(e.to_string().contains("shut down")),
+            Ok(_) => panic!("get_browser succeeded after shutdown"),
+        }
+    }
+
     #[tokio::test]
     async fn test_retry_delay_duration() {
         let delay = Duration::from_millis(500);
@@ -558,6 +2791,88 @@ mod tests {
         assert!(last_error.is_some());
     }
 
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_wait_for_exit_and_terminate() {
+        let mut process = BrowserProcess::spawn(Command::new("sleep").arg("30")).unwrap();
+        assert!(wait_for_exit(&mut process, Duration::from_millis(50))
+            .await
+            .is_none());
+        process.terminate();
+        assert!(wait_for_exit(&mut process, Duration::from_secs(5))
+            .await
+            .is_some());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_monitor_reports_unexpected_exit() {
+        let log = std::env::temp_dir().join(format!("chrome-stderr-{}.log", uuid::Uuid::new_v4()));
+        std::fs::write(&log, "starting\n\nFATAL: out of memory\n").unwrap();
+        let child = BrowserProcess::spawn(Command::new("sh").args(["-c", "exit 3"])).unwrap();
+        let process: SharedProcess = Arc::new(std::sync::Mutex::new(Some(child)));
+        let (exit, mut rx) = watch::channel(None);
+
+        monitor_exit(process.clone(), exit, log.clone()).await;
+        let report = rx.borrow_and_update().clone().unwrap();
+        assert_eq!(report.status.code(), Some(3));
+        assert!(!report.expected);
+        assert_eq!(report.stderr_tail, ["starting", "FATAL: out of memory"]);
+        assert!(report.error().to_string().contains("out of memory"));
+        assert!(process.lock().unwrap().is_none());
+        std::fs::remove_file(&log).unwrap();
+    }
+
+    #[test]
+    fn test_listening_port() {
+        assert_eq!(
+            listening_port("\nDevTools listening on ws://127.0.0.1:40123/devtools/browser/3f1c"),
+            Some(40123)
+        );
+        assert_eq!(
+            listening_port("DevTools listening on ws://[::1]:9222/devtools/browser/3f1c"),
+            Some(9222)
+        );
+        assert_eq!(listening_port("[WARNING] DevTools listening soon"), None);
+    }
+
+    #[tokio::test]
+    async fn test_forward_stderr_copies_log_and_reports_port() {
+        let log = std::env::temp_dir().join(format!("chrome-stderr-{}.log", uuid::Uuid::new_v4()));
+        let mut child = Command::new("sh")
+            .args([
+                "-c",
+                "echo starting >&2; echo 'DevTools listening on ws://127.0.0.1:40123/devtools/browser/x' >&2; echo ready >&2",
+            ])
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let stderr = tokio::process::ChildStderr::from_std(child.stderr.take().unwrap()).unwrap();
+        let (tx, rx) = oneshot::channel();
+
+        forward_stderr(
+            stderr,
+            std::fs::File::create(&log).unwrap(),
+            false,
+            None,
+            tx,
+        )
+        .await;
+        assert_eq!(rx.await.unwrap(), 40123);
+        assert_eq!(stderr_tail(&log, 10).len(), 3);
+        child.wait().unwrap();
+        std::fs::remove_file(&log).unwrap();
+    }
+
+    #[test]
+    fn test_stderr_tail_keeps_last_lines() {
+        let log = std::env::temp_dir().join(format!("chrome-stderr-{}.log", uuid::Uuid::new_v4()));
+        std::fs::write(&log, "a\nb\nc\n").unwrap();
+        assert_eq!(stderr_tail(&log, 2), ["b", "c"]);
+        std::fs::remove_file(&log).unwrap();
+        assert!(stderr_tail(&log, 2).is_empty());
+    }
+
     #[tokio::test]
     async fn test_drop_impl_kills_process() {
         // Verify Drop trait behavior is correctly defined
@@ -565,6 +2880,185 @@ mod tests {
         let _ = std::mem::needs_drop::<CdpBrowser>();
     }
 
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_launch_failure_kills_helper_processes() {
+        let pid_file = std::env::temp_dir().join(format!("helper-{}.pid", uuid::Uuid::new_v4()));
+        let chrome = fake_chrome(&format!(
+            "sleep 30 & echo $! > {}; exec sleep 30",
+            pid_file.display()
+        ));
+        let result = CdpBrowser::launch_with_options(
+            LaunchOptions {
+                executable_path: Some(chrome.clone()),
+                ..Default::default()
+            }
+            .with_launch_timeout(Duration::from_millis(300)),
+        )
+        .await;
+        let Err(Error::LaunchFailed(failure)) = result else {
+            panic!("expected a launch failure");
+        };
+        let _ = std::fs::remove_dir_all(&failure.user_data_dir);
+
+        let helper = std::fs::read_to_string(&pid_file).unwrap();
+        // Gone, or a zombie waiting for init to reap it
+        let alive = || {
+            std::fs::read_to_string(format!("/proc/{}/stat", helper.trim()))
+                .is_ok_and(|stat| !stat.contains(") Z "))
+        };
+        let started = Instant::now();
+        while alive() && started.elapsed() < Duration::from_secs(5) {
+            sleep(Duration::from_millis(50)).await;
+        }
+        assert!(!alive());
+        std::fs::remove_file(chrome).unwrap();
+        std::fs::remove_file(pid_file).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_health_check_probes_endpoint() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind((LOCALHOST, 0)).await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = vec![0; 1024];
+            let _ = socket.read(&mut buf).await.unwrap();
+            let body = r#"{"Browser":"Chrome/120.0.0.0"}"#;
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            socket.write_all(response.as_bytes()).await.unwrap();
+        });
+
+        let ws_url = format!("ws://{}:{}/devtools/browser/test", LOCALHOST, port);
+        let browser = CdpBrowser::connect_ws(&ws_url).await.unwrap();
+        assert!(!browser.has_exited());
+        browser.health_check().await.unwrap();
+        server.await.unwrap();
+        // Nothing listens any more
+        assert!(browser.health_check().await.is_err());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_connect_android_forwards_and_cleans_up() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind((LOCALHOST, 0)).await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = vec![0; 1024];
+            let _ = socket.read(&mut buf).await.unwrap();
+            let body = format!(
+                r#"{{"webSocketDebuggerUrl":"ws://localhost:{}/devtools/browser"}}"#,
+                port
+            );
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            socket.write_all(response.as_bytes()).await.unwrap();
+        });
+
+        let log = std::env::temp_dir().join(format!("fake-adb-{}.log", uuid::Uuid::new_v4()));
+        let adb = fake_chrome(&format!(
+            "echo \"$@\" >> {}\ncase \"$4\" in tcp:0) echo {} ;; esac",
+            log.display(),
+            port
+        ));
+        let browser = CdpBrowser::connect_android(AndroidOptions {
+            serial: Some("emulator-5554".to_string()),
+            adb_path: Some(adb.clone()),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        assert_eq!(browser.port(), port);
+        assert_eq!(
+            browser.ws_url,
+            format!("ws://localhost:{}/devtools/browser", port)
+        );
+        drop(browser);
+
+        let calls = std::fs::read_to_string(&log).unwrap();
+        assert_eq!(
+            calls.lines().collect::<Vec<_>>(),
+            [
+                "-s emulator-5554 forward tcp:0 localabstract:chrome_devtools_remote".to_string(),
+                format!("-s emulator-5554 forward --remove tcp:{}", port),
+            ]
+        );
+        std::fs::remove_file(adb).unwrap();
+        std::fs::remove_file(log).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_new_page_creates_target_over_cdp() {
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        let url = mock_server(|mut ws| async move {
+            let request = server_recv(&mut ws).await;
+            server_send(
+                &mut ws,
+                json!({"id": request["id"], "result": {"targetId": "T1"}}),
+            )
+            .await;
+            let _ = tx.send(request);
+        })
+        .await;
+        let url = url.replace("/devtools/page/", "/devtools/browser/");
+        let browser = CdpBrowser::connect_ws(&url).await.unwrap();
+
+        let ws_url = browser.new_page().await.unwrap();
+        assert!(ws_url.ends_with("/devtools/page/T1"));
+        let request = rx.await.unwrap();
+        assert_eq!(request["method"], "Target.createTarget");
+        assert_eq!(request["params"]["url"], "about:blank");
+    }
+
+    /// Browser answering `Target.createTarget` with `reply` and serving `/json/new`,
+    /// returning the outcome of `new_page` and whether `/json/new` was used
+    async fn new_page_with_create_target_reply(reply: Value) -> (Result<String>, bool) {
+        use crate::connection::tests::fake_chrome_with;
+
+        let chrome = fake_chrome_with(move |_, _| Some(reply.clone())).await;
+        let browser = CdpBrowser::connect_ws(&chrome.ws_url()).await.unwrap();
+
+        let result = browser.new_page().await;
+        let used_http = chrome
+            .http_requests
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|line| line.starts_with("PUT /json/new"));
+        (result, used_http)
+    }
+
+    #[tokio::test]
+    async fn test_new_page_falls_back_to_json_new() {
+        // Creating targets over CDP is refused
+        let (result, used_http) = new_page_with_create_target_reply(
+            json!({"error": {"code": -32000, "message": "Not allowed"}}),
+        )
+        .await;
+        assert!(result.unwrap().ends_with("/devtools/page/HTTP"));
+        assert!(used_http);
+    }
+
+    #[tokio::test]
+    async fn test_new_page_keeps_other_errors() {
+        // A dry-run style reply without a target id
+        let (result, used_http) = new_page_with_create_target_reply(json!({"result": {}})).await;
+        assert!(result.is_err());
+        assert!(!used_http);
+    }
+
     #[tokio::test]
     async fn test_new_page_error_on_invalid_response() {
         let body = r#"{"invalid":"response"}"#;
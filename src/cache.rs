@@ -0,0 +1,277 @@
+//! On-disk response cache serving intercepted requests
+
+use crate::{RequestInfo, RequestMatcher, Result};
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Response headers that no longer describe the body once it was decoded
+const DROPPED_HEADERS: [&str; 3] = ["content-encoding", "content-length", "transfer-encoding"];
+
+/// Cache of `GET` responses on disk, for iterating on scrapers without refetching
+///
+/// Entries are keyed by URL plus the values of the [`vary`](Self::vary)
+/// request headers and are served while younger than [`ttl`](Self::ttl),
+/// regardless of the site's own caching headers. Only successful responses
+/// are stored.
+///
+/// ```
+/// use chrome_cdp::{RequestMatcher, ResponseCache};
+/// use std::time::Duration;
+///
+/// let cache = ResponseCache {
+///     matcher: RequestMatcher::new().url_glob("https://slow.example/*"),
+///     vary: vec!["accept-language".to_string()],
+///     ..ResponseCache::new(".cache/responses", Duration::from_secs(3600))
+/// };
+/// ```
+#[derive(Debug, Clone)]
+pub struct ResponseCache {
+    /// Directory holding one file per entry, created on first store
+    pub dir: PathBuf,
+    /// How long an entry is served after it was stored
+    pub ttl: Duration,
+    /// Request headers whose values are part of the key
+    pub vary: Vec<String>,
+    /// Requests eligible for caching (default: all)
+    pub matcher: RequestMatcher,
+}
+
+/// Response stored in the cache
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct CachedResponse {
+    pub(crate) url: String,
+    pub(crate) status: u16,
+    pub(crate) headers: Vec<(String, String)>,
+    /// Body, base64-encoded
+    pub(crate) body: String,
+    /// Seconds since the Unix epoch
+    pub(crate) stored_at: u64,
+}
+
+impl CachedResponse {
+    /// `Fetch.fulfillRequest` params answering a paused request with this response
+    pub(crate) fn fulfill_params(&self, request_id: &Value) -> Value {
+        let headers: Vec<Value> = self
+            .headers
+            .iter()
+            .map(|(name, value)| json!({ "name": name, "value": value }))
+            .collect();
+        json!({
+            "requestId": request_id,
+            "responseCode": self.status,
+            "responseHeaders": headers,
+            "body": self.body,
+        })
+    }
+}
+
+impl ResponseCache {
+    /// Cache in `dir` serving entries for `ttl`
+    pub fn new(dir: impl Into<PathBuf>, ttl: Duration) -> Self {
+        Self {
+            dir: dir.into(),
+            ttl,
+            vary: Vec::new(),
+            matcher: RequestMatcher::new(),
+        }
+    }
+
+    /// Whether the request may be served from or stored in the cache
+    pub(crate) fn accepts(&self, request: &RequestInfo) -> bool {
+        request.method.eq_ignore_ascii_case("GET") && self.matcher.matches(request)
+    }
+
+    /// Stable file name for the request
+    fn key(&self, request: &RequestInfo) -> String {
+        let mut key = request.url.clone();
+        for name in &self.vary {
+            let value = request
+                .headers
+                .get(&name.to_ascii_lowercase())
+                .map_or("", String::as_str);
+            key.push('\n');
+            key.push_str(value);
+        }
+        format!("{:016x}.json", fnv1a(key.as_bytes()))
+    }
+
+    /// Fresh entry for the request, if any
+    pub(crate) async fn lookup(&self, request: &RequestInfo) -> Option<CachedResponse> {
+        let data = tokio::fs::read(self.dir.join(self.key(request)))
+            .await
+            .ok()?;
+        let entry: CachedResponse = serde_json::from_slice(&data).ok()?;
+        let age = now_secs().saturating_sub(entry.stored_at);
+        // Hash collisions are told apart by the URL
+        (entry.url == request.url && age < self.ttl.as_secs()).then_some(entry)
+    }
+
+    /// Store a response paused at the `Response` stage
+    ///
+    /// `body` is a `Fetch.getResponseBody` result. Unsuccessful responses are skipped.
+    pub(crate) async fn store(
+        &self,
+        request: &RequestInfo,
+        paused: &Value,
+        body: &Value,
+    ) -> Result<()> {
+        let status = paused["responseStatusCode"].as_u64().unwrap_or_default() as u16;
+        if !(200..300).contains(&status) {
+            return Ok(());
+        }
+        let headers = paused["responseHeaders"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|header| {
+                let name = header["name"].as_str()?;
+                let value = header["value"].as_str().unwrap_or_default();
+                (!DROPPED_HEADERS.contains(&name.to_ascii_lowercase().as_str()))
+                    .then(|| (name.to_string(), value.to_string()))
+            })
+            .collect();
+        let text = body["body"].as_str().unwrap_or_default();
+        let body = if body["base64Encoded"].as_bool().unwrap_or(false) {
+            text.to_string()
+        } else {
+            base64::engine::general_purpose::STANDARD.encode(text)
+        };
+
+        let entry = CachedResponse {
+            url: request.url.clone(),
+            status,
+            headers,
+            body,
+            stored_at: now_secs(),
+        };
+        tokio::fs::create_dir_all(&self.dir).await?;
+        tokio::fs::write(
+            self.dir.join(self.key(request)),
+            serde_json::to_vec(&entry)?,
+        )
+        .await?;
+        Ok(())
+    }
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs())
+}
+
+/// 64-bit FNV-1a, stable across builds unlike `DefaultHasher`
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn request(url: &str, language: &str) -> RequestInfo {
+        RequestInfo {
+            url: url.to_string(),
+            method: "GET".to_string(),
+            headers: HashMap::from([("accept-language".to_string(), language.to_string())]),
+            ..Default::default()
+        }
+    }
+
+    fn temp_cache(ttl: Duration) -> ResponseCache {
+        let dir = std::env::temp_dir().join(format!("chrome-cdp-cache-{}", uuid::Uuid::new_v4()));
+        ResponseCache {
+            vary: vec!["Accept-Language".to_string()],
+            ..ResponseCache::new(dir, ttl)
+        }
+    }
+
+    fn paused_response(status: u64) -> Value {
+        json!({
+            "requestId": "interception-1",
+            "responseStatusCode": status,
+            "responseHeaders": [
+                {"name": "Content-Type", "value": "text/html"},
+                {"name": "Content-Encoding", "value": "gzip"}
+            ]
+        })
+    }
+
+    #[test]
+    fn test_key_includes_vary_headers() {
+        let cache = temp_cache(Duration::from_secs(60));
+        let en = request("https://a.test/", "en");
+        assert_eq!(cache.key(&en), cache.key(&request("https://a.test/", "en")));
+        assert_ne!(cache.key(&en), cache.key(&request("https://a.test/", "de")));
+        assert_ne!(cache.key(&en), cache.key(&request("https://b.test/", "en")));
+    }
+
+    #[test]
+    fn test_only_get_requests_are_accepted() {
+        let cache = temp_cache(Duration::from_secs(60));
+        let mut post = request("https://a.test/", "en");
+        post.method = "POST".to_string();
+        assert!(cache.accepts(&request("https://a.test/", "en")));
+        assert!(!cache.accepts(&post));
+    }
+
+    #[tokio::test]
+    async fn test_store_and_lookup() {
+        let cache = temp_cache(Duration::from_secs(60));
+        let en = request("https://a.test/", "en");
+        assert!(cache.lookup(&en).await.is_none());
+
+        cache
+            .store(
+                &en,
+                &paused_response(200),
+                &json!({"body": "<p>hi</p>", "base64Encoded": false}),
+            )
+            .await
+            .unwrap();
+        let entry = cache.lookup(&en).await.unwrap();
+        assert_eq!(entry.status, 200);
+        assert_eq!(
+            entry.headers,
+            [("Content-Type".to_string(), "text/html".to_string())]
+        );
+        assert_eq!(entry.body, "PHA+aGk8L3A+");
+        assert!(cache
+            .lookup(&request("https://a.test/", "de"))
+            .await
+            .is_none());
+
+        let params = entry.fulfill_params(&json!("interception-2"));
+        assert_eq!(params["responseCode"], 200);
+        assert_eq!(params["responseHeaders"][0]["name"], "Content-Type");
+
+        std::fs::remove_dir_all(&cache.dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_expired_and_failed_responses_are_not_served() {
+        let cache = temp_cache(Duration::ZERO);
+        let en = request("https://a.test/", "en");
+        let body = json!({"body": "", "base64Encoded": false});
+
+        cache
+            .store(&en, &paused_response(404), &body)
+            .await
+            .unwrap();
+        assert!(!cache.dir.exists());
+
+        cache
+            .store(&en, &paused_response(200), &body)
+            .await
+            .unwrap();
+        assert!(cache.lookup(&en).await.is_none());
+
+        std::fs::remove_dir_all(&cache.dir).unwrap();
+    }
+}
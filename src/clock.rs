@@ -0,0 +1,186 @@
+//! Deterministic time control for pages
+
+/// Installs (or reconfigures) a controllable clock in the page
+///
+/// Called with `(startMs, rate)`: `startMs` is the epoch time the clock shows
+/// now (`null` keeps the current fake time) and `rate` is how fast it runs
+/// relative to real time (`0` freezes it). `Date`, `performance.now`, and
+/// `setTimeout`/`setInterval` follow the fake clock; `window.__cdpClock.tick(ms)`
+/// advances it manually and fires due timers.
+const CLOCK_FN: &str = r#"function(startMs, rate) {
+  if (window.__cdpClock) {
+    window.__cdpClock.configure(startMs, rate);
+    return;
+  }
+
+  const NativeDate = Date;
+  const nativeNow = performance.now.bind(performance);
+  const nativeSetTimeout = setTimeout;
+  const nativeClearTimeout = clearTimeout;
+
+  const clock = {
+    base: NativeDate.now(),
+    perfBase: nativeNow(),
+    realBase: nativeNow(),
+    rate: 1,
+    offset: 0,
+    timers: new Map(),
+    nextId: 1,
+    pump: null,
+  };
+
+  const elapsed = () => (nativeNow() - clock.realBase) * clock.rate + clock.offset;
+  const now = () => clock.base + elapsed();
+
+  function schedule() {
+    if (clock.pump !== null) {
+      nativeClearTimeout(clock.pump);
+      clock.pump = null;
+    }
+    if (clock.rate <= 0 || clock.timers.size === 0) return;
+    let due = Infinity;
+    for (const timer of clock.timers.values()) due = Math.min(due, timer.due);
+    const delay = Math.max(0, (due - elapsed()) / clock.rate);
+    clock.pump = nativeSetTimeout(() => { clock.pump = null; run(elapsed()); }, delay);
+  }
+
+  function run(until) {
+    for (;;) {
+      let next = null;
+      for (const [id, timer] of clock.timers) {
+        if (timer.due <= until && (next === null || timer.due < next[1].due)) next = [id, timer];
+      }
+      if (next === null) break;
+      const [id, timer] = next;
+      if (timer.interval === null) {
+        clock.timers.delete(id);
+      } else {
+        timer.due += Math.max(1, timer.interval);
+      }
+      try { timer.callback(...timer.args); } catch (e) { console.error(e); }
+    }
+    schedule();
+  }
+
+  function addTimer(callback, delay, args, repeat) {
+    if (typeof callback !== "function") callback = new Function(String(callback));
+    const id = clock.nextId++;
+    delay = Math.max(0, Number(delay) || 0);
+    clock.timers.set(id, {
+      callback, args, due: elapsed() + delay, interval: repeat ? delay : null,
+    });
+    schedule();
+    return id;
+  }
+
+  function clearTimer(id) {
+    clock.timers.delete(id);
+    schedule();
+  }
+
+  function FakeDate(...args) {
+    if (!new.target) return new NativeDate(now()).toString();
+    return args.length ? new NativeDate(...args) : new NativeDate(now());
+  }
+  FakeDate.prototype = NativeDate.prototype;
+  FakeDate.now = () => Math.floor(now());
+  FakeDate.parse = NativeDate.parse;
+  FakeDate.UTC = NativeDate.UTC;
+
+  window.Date = FakeDate;
+  performance.now = () => clock.perfBase + elapsed();
+  window.setTimeout = (callback, delay, ...args) => addTimer(callback, delay, args, false);
+  window.setInterval = (callback, delay, ...args) => addTimer(callback, delay, args, true);
+  window.clearTimeout = clearTimer;
+  window.clearInterval = clearTimer;
+
+  window.__cdpClock = {
+    configure(startMs, rate) {
+      const current = now();
+      const shift = elapsed();
+      for (const timer of clock.timers.values()) timer.due -= shift;
+      clock.realBase = nativeNow();
+      clock.offset = 0;
+      clock.perfBase += current - clock.base;
+      clock.base = startMs === null ? current : startMs;
+      clock.rate = rate;
+      schedule();
+    },
+    tick(ms) {
+      clock.offset += ms;
+      run(elapsed());
+    },
+    now,
+  };
+  window.__cdpClock.configure(startMs, rate);
+}"#;
+
+/// Expression installing the fake clock with the given start time and rate
+pub(crate) fn clock_script(start_ms: Option<f64>, rate: f64) -> String {
+    let start = start_ms.map_or_else(|| "null".to_string(), |ms| ms.to_string());
+    format!("({})({}, {})", CLOCK_FN, start, rate)
+}
+
+/// Expression advancing the fake clock by the given number of milliseconds
+pub(crate) fn tick_script(ms: f64) -> String {
+    format!(
+        "window.__cdpClock ? (window.__cdpClock.tick({}), true) : false",
+        ms
+    )
+}
+
+/// Policy for `Emulation.setVirtualTimePolicy`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VirtualTimePolicy {
+    /// Virtual time advances as long as there is pending work
+    Advance,
+    /// Virtual time is paused
+    Pause,
+    /// Virtual time pauses while network fetches are pending
+    PauseIfNetworkFetchesPending,
+}
+
+impl VirtualTimePolicy {
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            Self::Advance => "advance",
+            Self::Pause => "pause",
+            Self::PauseIfNetworkFetchesPending => "pauseIfNetworkFetchesPending",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clock_script_frozen_at_start() {
+        let script = clock_script(Some(1_700_000_000_000.0), 0.0);
+        assert!(script.starts_with("(function(startMs, rate)"));
+        assert!(script.ends_with(")(1700000000000, 0)"));
+    }
+
+    #[test]
+    fn test_clock_script_keeps_current_time() {
+        assert!(clock_script(None, 10.0).ends_with(")(null, 10)"));
+    }
+
+    #[test]
+    fn test_tick_script() {
+        assert_eq!(
+            tick_script(1500.0),
+            "window.__cdpClock ? (window.__cdpClock.tick(1500), true) : false"
+        );
+    }
+
+    #[test]
+    fn test_virtual_time_policy_names() {
+        assert_eq!(VirtualTimePolicy::Advance.as_str(), "advance");
+        assert_eq!(VirtualTimePolicy::Pause.as_str(), "pause");
+        assert_eq!(
+            VirtualTimePolicy::PauseIfNetworkFetchesPending.as_str(),
+            "pauseIfNetworkFetchesPending"
+        );
+    }
+}
@@ -1,121 +1,1393 @@
 //! CDP WebSocket connection handling
 
-use crate::{Error, Result};
+use crate::dry_run::DryRun;
+use crate::middleware::{Command, Middleware, Next};
+use crate::{protocol::ProtocolCompat, CdpError, Error, Result, Timeouts};
 use futures::{SinkExt, StreamExt};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
 use serde_json::{json, Value};
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
-use tokio::sync::{mpsc, oneshot, Mutex};
-use tokio_tungstenite::{connect_async, tungstenite::Message};
+use std::time::{Duration, Instant};
+use tokio::sync::{broadcast, mpsc, oneshot, watch, Mutex};
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::error::{CapacityError, Error as WsError};
+use tokio_tungstenite::tungstenite::handshake::client::Request;
+use tokio_tungstenite::tungstenite::http::{HeaderName, HeaderValue};
+use tokio_tungstenite::tungstenite::protocol::WebSocketConfig;
+use tokio_tungstenite::{connect_async_tls_with_config, tungstenite::Message, Connector};
 
 type Responder = oneshot::Sender<Result<Value>>;
+type PendingMap = Arc<std::sync::Mutex<HashMap<u32, Waiter>>>;
+type FailureState = Arc<std::sync::Mutex<Option<Failure>>>;
+type MiddlewareChain = Arc<std::sync::RwLock<Vec<Arc<dyn Middleware>>>>;
+type SharedEventFilter = Arc<std::sync::RwLock<Option<EventFilter>>>;
+type SharedTimeout = Arc<std::sync::RwLock<Option<Duration>>>;
+
+type SharedDryRun = Arc<std::sync::RwLock<Option<Arc<DryRun>>>>;
+
+/// Number of events buffered per subscriber before it starts lagging
+const EVENT_CHANNEL_CAPACITY: usize = 1024;
+
+/// Why a connection stopped accepting commands
+#[derive(Debug, Clone)]
+enum Failure {
+    Crashed,
+    Detached(String),
+}
+
+impl Failure {
+    /// Inspect an event for a target crash or detach
+    fn from_event(event: &CdpEvent) -> Option<Self> {
+        // Events of flattened child sessions do not concern this connection's own target
+        if event.session_id.is_some() {
+            return None;
+        }
+        match event.method.as_str() {
+            "Inspector.targetCrashed" => Some(Self::Crashed),
+            "Inspector.detached" => Some(Self::Detached(
+                event.params["reason"]
+                    .as_str()
+                    .unwrap_or("unknown")
+                    .to_string(),
+            )),
+            _ => None,
+        }
+    }
+
+    fn error(&self) -> Error {
+        match self {
+            Self::Crashed => Error::TargetCrashed,
+            Self::Detached(reason) => Error::TargetDetached(reason.clone()),
+        }
+    }
+}
+
+/// CDP event pushed by the browser
+#[derive(Debug, Clone)]
+pub struct CdpEvent {
+    /// Event method, e.g. `Target.targetCreated`
+    pub method: String,
+    /// Event parameters
+    pub params: Value,
+    /// Session the event belongs to, for flattened target sessions
+    pub session_id: Option<String>,
+}
+
+/// Incoming message, deserialized in a single pass
+///
+/// Payloads are moved out of the message into responders and events rather
+/// than cloned from an intermediate document.
+#[derive(Debug, Deserialize)]
+struct Incoming {
+    id: Option<u64>,
+    result: Option<Value>,
+    error: Option<Value>,
+    /// Error message next to the error name, in WebDriver BiDi responses
+    message: Option<String>,
+    method: Option<String>,
+    #[serde(default)]
+    params: Value,
+    #[serde(rename = "sessionId")]
+    session_id: Option<String>,
+}
+
+/// Method and id of an incoming message, read without building its payload
+#[derive(Deserialize)]
+struct Header<'a> {
+    id: Option<u64>,
+    #[serde(borrow)]
+    method: Option<std::borrow::Cow<'a, str>>,
+}
+
+/// Allowlist of event methods; `Domain.*` entries allow a whole domain
+#[derive(Debug, Clone)]
+struct EventFilter {
+    methods: Vec<String>,
+}
+
+impl EventFilter {
+    fn new(methods: Vec<String>) -> Self {
+        Self { methods }
+    }
+
+    fn allows(&self, method: &str) -> bool {
+        // Crash and detach events drive the connection state and are never dropped
+        if method == "Inspector.targetCrashed" || method == "Inspector.detached" {
+            return true;
+        }
+        self.methods
+            .iter()
+            .any(|allowed| match allowed.strip_suffix('*') {
+                Some(prefix) => method.starts_with(prefix),
+                None => allowed == method,
+            })
+    }
+
+    /// Whether a raw message is an event the filter drops
+    fn drops(&self, text: &str) -> bool {
+        match serde_json::from_str::<Header>(text) {
+            Ok(Header {
+                id: None,
+                method: Some(method),
+            }) => !self.allows(&method),
+            _ => false,
+        }
+    }
+}
+
+/// Where an incoming message has to be delivered
+#[derive(Debug)]
+enum Dispatch {
+    Response { id: u32, result: Result<Value> },
+    Event(CdpEvent),
+}
+
+impl Incoming {
+    fn dispatch(self) -> Option<Dispatch> {
+        if let Some(id) = self.id {
+            let result = match self.error {
+                Some(Value::String(name)) => Err(Error::Protocol(CdpError {
+                    code: (-1).into(),
+                    message: format!("{}: {}", name, self.message.unwrap_or_default()),
+                    data: None,
+                })),
+                Some(error) => Err(Error::Protocol(CdpError::from_response(&error))),
+                None => Ok(self.result.unwrap_or_default()),
+            };
+            return Some(Dispatch::Response {
+                id: id as u32,
+                result,
+            });
+        }
+        Some(Dispatch::Event(CdpEvent {
+            method: self.method?,
+            params: self.params,
+            session_id: self.session_id,
+        }))
+    }
+}
+
+/// Opt-in ring buffers of recent events, keyed by event method
+#[derive(Default)]
+struct EventBuffers {
+    buffers: HashMap<String, (usize, VecDeque<CdpEvent>)>,
+    /// Replay buffers keyed by domain, with events numbered in arrival order
+    domains: HashMap<String, (usize, VecDeque<(u64, CdpEvent)>)>,
+    next_seq: u64,
+}
+
+impl EventBuffers {
+    /// Start retaining up to `capacity` events of the given method
+    fn enable(&mut self, method: &str, capacity: usize) {
+        let (cap, events) = self
+            .buffers
+            .entry(method.to_string())
+            .or_insert_with(|| (capacity, VecDeque::with_capacity(capacity)));
+        *cap = capacity;
+        while events.len() > capacity {
+            events.pop_front();
+        }
+    }
+
+    /// Stop retaining events of the given method
+    fn disable(&mut self, method: &str) {
+        self.buffers.remove(method);
+    }
+
+    /// Start retaining up to `capacity` events of every method in the domain
+    fn enable_domain(&mut self, domain: &str, capacity: usize) {
+        let (cap, events) = self
+            .domains
+            .entry(domain.to_string())
+            .or_insert_with(|| (capacity, VecDeque::with_capacity(capacity)));
+        *cap = capacity;
+        while events.len() > capacity {
+            events.pop_front();
+        }
+    }
+
+    /// Stop retaining events of the domain
+    fn disable_domain(&mut self, domain: &str) {
+        self.domains.remove(domain);
+    }
+
+    /// Events retained for replay across all domains, oldest first
+    fn replay(&self) -> VecDeque<CdpEvent> {
+        let mut events: Vec<&(u64, CdpEvent)> = self
+            .domains
+            .values()
+            .flat_map(|(_, events)| events.iter())
+            .collect();
+        events.sort_by_key(|(seq, _)| *seq);
+        events.into_iter().map(|(_, event)| event.clone()).collect()
+    }
+
+    /// Record an event if its method or domain is buffered
+    fn push(&mut self, event: &CdpEvent) {
+        let domain = event.method.split('.').next().unwrap_or_default();
+        if let Some((capacity, events)) = self.domains.get_mut(domain) {
+            if *capacity > 0 {
+                if events.len() == *capacity {
+                    events.pop_front();
+                }
+                events.push_back((self.next_seq, event.clone()));
+                self.next_seq += 1;
+            }
+        }
+        if let Some((capacity, events)) = self.buffers.get_mut(&event.method) {
+            if *capacity == 0 {
+                return;
+            }
+            if events.len() == *capacity {
+                events.pop_front();
+            }
+            events.push_back(event.clone());
+        }
+    }
+
+    /// Remove and return the oldest buffered event of the method matching the predicate
+    fn take_first(&mut self, method: &str, predicate: impl Fn(&Value) -> bool) -> Option<CdpEvent> {
+        let (_, events) = self.buffers.get_mut(method)?;
+        let index = events.iter().position(|e| predicate(&e.params))?;
+        events.remove(index)
+    }
+
+    /// Buffered events of the given method, oldest first
+    fn get(&self, method: &str) -> Vec<CdpEvent> {
+        self.buffers
+            .get(method)
+            .map(|(_, events)| events.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+}
+
+/// Stream of events received on a connection
+pub struct EventStream {
+    rx: broadcast::Receiver<CdpEvent>,
+    /// Events from before the subscription, delivered first
+    replay: VecDeque<CdpEvent>,
+}
+
+impl EventStream {
+    /// Receive the next event, or `None` once the connection is closed
+    ///
+    /// Events dropped because this subscriber fell behind are skipped.
+    pub async fn recv(&mut self) -> Option<CdpEvent> {
+        if let Some(event) = self.replay.pop_front() {
+            return Some(event);
+        }
+        loop {
+            match self.rx.recv().await {
+                Ok(event) => return Some(event),
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    }
+
+    /// Receive an already delivered event without waiting
+    pub fn try_recv(&mut self) -> Option<CdpEvent> {
+        if let Some(event) = self.replay.pop_front() {
+            return Some(event);
+        }
+        loop {
+            match self.rx.try_recv() {
+                Ok(event) => return Some(event),
+                Err(broadcast::error::TryRecvError::Lagged(_)) => continue,
+                Err(_) => return None,
+            }
+        }
+    }
+}
+
+/// Options for establishing a CDP WebSocket connection
+#[derive(Debug, Clone, Default)]
+pub struct ConnectOptions {
+    /// Accept invalid TLS certificates and hostnames on `wss://` endpoints
+    pub accept_invalid_certs: bool,
+    /// Additional PEM-encoded root certificates to trust on `wss://` endpoints
+    pub root_certificates: Vec<Vec<u8>>,
+    /// Maximum size of an incoming message (default: 64 MiB)
+    ///
+    /// Full-page screenshots and `Page.captureSnapshot` responses can be large.
+    pub max_message_size: Option<usize>,
+    /// Maximum size of a single incoming frame (default: 16 MiB)
+    pub max_frame_size: Option<usize>,
+    /// Extra headers sent with the WebSocket handshake, e.g. `Authorization`
+    pub headers: Vec<(String, String)>,
+    /// Extra query parameters appended to the WebSocket URL, e.g. `token`
+    pub query: Vec<(String, String)>,
+    /// Event methods to deliver, e.g. `Page.loadEventFired` or `Network.*`
+    ///
+    /// Other events are dropped before their parameters are parsed. `None`
+    /// (the default) delivers every event. See [`CdpConnection::set_event_allowlist`].
+    pub event_allowlist: Option<Vec<String>>,
+}
+
+impl ConnectOptions {
+    /// Build the WebSocket configuration, keeping library defaults for unset limits
+    fn websocket_config(&self) -> WebSocketConfig {
+        let mut config = WebSocketConfig::default();
+        if let Some(size) = self.max_message_size {
+            config = config.max_message_size(Some(size));
+        }
+        if let Some(size) = self.max_frame_size {
+            config = config.max_frame_size(Some(size));
+        }
+        config
+    }
+
+    /// Build the handshake request, adding the configured query parameters and headers
+    fn handshake_request<R: IntoClientRequest>(&self, request: R) -> Result<Request> {
+        let mut request = request
+            .into_client_request()
+            .map_err(|e| Error::WebSocket(format!("Invalid WebSocket request: {}", e)))?;
+
+        if !self.query.is_empty() {
+            let mut url = reqwest::Url::parse(&request.uri().to_string())
+                .map_err(|e| Error::WebSocket(format!("Invalid WebSocket URL: {}", e)))?;
+            url.query_pairs_mut().extend_pairs(&self.query);
+            *request.uri_mut() = url
+                .as_str()
+                .parse()
+                .map_err(|e| Error::WebSocket(format!("Invalid WebSocket URL: {}", e)))?;
+        }
+
+        for (name, value) in &self.headers {
+            let name = HeaderName::from_bytes(name.as_bytes())
+                .map_err(|e| Error::WebSocket(format!("Invalid header name '{}': {}", name, e)))?;
+            let value = HeaderValue::from_str(value).map_err(|e| {
+                Error::WebSocket(format!("Invalid value for header '{}': {}", name, e))
+            })?;
+            request.headers_mut().append(name, value);
+        }
+        Ok(request)
+    }
+
+    /// Build the TLS connector for the given URL scheme
+    fn connector(&self, ws_url: &str) -> Result<Option<Connector>> {
+        let scheme = ws_url.split("://").next().unwrap_or_default();
+        match scheme {
+            "ws" => Ok(Some(Connector::Plain)),
+            "wss" => {
+                let mut builder = native_tls::TlsConnector::builder();
+                for pem in &self.root_certificates {
+                    let cert = native_tls::Certificate::from_pem(pem).map_err(|e| {
+                        Error::WebSocket(format!("Invalid root certificate: {}", e))
+                    })?;
+                    builder.add_root_certificate(cert);
+                }
+                if self.accept_invalid_certs {
+                    builder.danger_accept_invalid_certs(true);
+                    builder.danger_accept_invalid_hostnames(true);
+                }
+                let connector = builder.build().map_err(|e| {
+                    Error::WebSocket(format!("Failed to build TLS connector: {}", e))
+                })?;
+                Ok(Some(Connector::NativeTls(connector)))
+            }
+            _ => Err(Error::WebSocket(format!(
+                "Unsupported WebSocket URL '{}': expected ws:// or wss://",
+                ws_url
+            ))),
+        }
+    }
+}
+
+/// Lifecycle state of a connection
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionState {
+    /// Commands can be sent
+    Connected,
+    /// The target crashed or was detached; commands fail from now on, see
+    /// [`CdpConnection::is_failed`]
+    Failed,
+    /// The socket is closed; commands fail from now on
+    Closed,
+}
+
+/// Snapshot of a connection's activity counters
+///
+/// Counters are cumulative since the connection was opened.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ConnectionMetrics {
+    /// Commands still waiting for a response
+    pub pending_commands: usize,
+    /// Commands written to the WebSocket
+    pub commands_sent: u64,
+    /// Command responses received
+    pub responses_received: u64,
+    /// Events received
+    pub events_received: u64,
+    /// Bytes of message payload written
+    pub bytes_sent: u64,
+    /// Bytes of message payload received
+    pub bytes_received: u64,
+}
+
+/// Command sent on a connection and still waiting for its response
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PendingCommand {
+    /// Message id of the command
+    pub id: u32,
+    /// CDP method, e.g. `Page.navigate`
+    pub method: String,
+    /// Time since the command was queued
+    pub elapsed: Duration,
+}
+
+/// Caller waiting for the response to a command
+struct Waiter {
+    responder: Responder,
+    method: String,
+    queued_at: Instant,
+}
+
+/// Activity counters updated by the reader and writer tasks
+#[derive(Default)]
+struct Counters {
+    commands_sent: AtomicU64,
+    responses_received: AtomicU64,
+    events_received: AtomicU64,
+    bytes_sent: AtomicU64,
+    bytes_received: AtomicU64,
+}
+
+impl Counters {
+    fn add(counter: &AtomicU64, n: u64) {
+        counter.fetch_add(n, Ordering::Relaxed);
+    }
+}
 
 /// CDP connection managing WebSocket communication
+///
+/// Cloning is cheap; clones share the same WebSocket.
+#[derive(Clone)]
 pub struct CdpConnection {
-    command_tx: mpsc::UnboundedSender<(u32, String, Value, Responder)>,
+    command_tx: mpsc::UnboundedSender<(u32, Command)>,
     next_id: Arc<Mutex<u32>>,
+    pending: PendingMap,
+    events_tx: broadcast::Sender<CdpEvent>,
+    event_buffers: Arc<std::sync::Mutex<EventBuffers>>,
+    compat: Arc<std::sync::RwLock<ProtocolCompat>>,
+    counters: Arc<Counters>,
+    failure: FailureState,
+    middleware: MiddlewareChain,
+    event_filter: SharedEventFilter,
+    command_timeout: SharedTimeout,
+    dry_run: SharedDryRun,
+    state: watch::Sender<ConnectionState>,
 }
 
 impl CdpConnection {
     /// Connect to Chrome DevTools Protocol via WebSocket
     pub async fn connect(ws_url: &str) -> Result<Self> {
-        let (ws_stream, _) = connect_async(ws_url)
+        Self::connect_with_options(ws_url, ConnectOptions::default()).await
+    }
+
+    /// Connect within the connect timeout and apply the command timeout
+    pub(crate) async fn connect_with_timeouts(ws_url: &str, timeouts: Timeouts) -> Result<Self> {
+        let connect_timeout = timeouts.connect_timeout();
+        let connection = tokio::time::timeout(connect_timeout, Self::connect(ws_url))
+            .await
+            .map_err(|_| {
+                Error::Timeout(format!(
+                    "Connecting to {} took longer than {:?}",
+                    ws_url, connect_timeout
+                ))
+            })??;
+        connection.set_command_timeout(Some(timeouts.command_timeout()));
+        Ok(connection)
+    }
+
+    /// Connect to a local or remote (`ws://` or `wss://`) CDP endpoint with custom options
+    pub async fn connect_with_options(ws_url: &str, options: ConnectOptions) -> Result<Self> {
+        Self::connect_with_request(ws_url, options).await
+    }
+
+    /// Connect using a prepared handshake request
+    ///
+    /// Use this when a hosted browser service needs headers or query parameters
+    /// beyond those in [`ConnectOptions`]; the options' headers and query are
+    /// still applied on top of the request.
+    pub async fn connect_with_request<R: IntoClientRequest>(
+        request: R,
+        options: ConnectOptions,
+    ) -> Result<Self> {
+        let request = options.handshake_request(request)?;
+        let ws_url = request.uri().to_string();
+        let connector = options.connector(&ws_url)?;
+        let config = options.websocket_config();
+        let (ws_stream, _) = connect_async_tls_with_config(request, Some(config), false, connector)
             .await
             .map_err(|e| Error::WebSocket(format!("Failed to connect to {}: {}", ws_url, e)))?;
 
         let (mut write, mut read) = ws_stream.split();
 
-        let (command_tx, mut command_rx) =
-            mpsc::unbounded_channel::<(u32, String, Value, Responder)>();
-        let pending: Arc<Mutex<HashMap<u32, Responder>>> = Arc::new(Mutex::new(HashMap::new()));
+        let (command_tx, mut command_rx) = mpsc::unbounded_channel::<(u32, Command)>();
+        let pending: PendingMap = Arc::new(std::sync::Mutex::new(HashMap::new()));
+        let (events_tx, _) = broadcast::channel(EVENT_CHANNEL_CAPACITY);
+        let event_buffers = Arc::new(std::sync::Mutex::new(EventBuffers::default()));
+        let counters = Arc::new(Counters::default());
+        let failure: FailureState = Arc::new(std::sync::Mutex::new(None));
+        let event_filter: SharedEventFilter = Arc::new(std::sync::RwLock::new(
+            options.event_allowlist.clone().map(EventFilter::new),
+        ));
+
+        let (state, _) = watch::channel(ConnectionState::Connected);
 
         // Task for sending commands
-        let pending_clone = pending.clone();
+        let writer_counters = counters.clone();
+        let writer_state = state.clone();
         tokio::spawn(async move {
-            while let Some((id, method, params, responder)) = command_rx.recv().await {
-                let msg = json!({
-                    "id": id,
-                    "method": method,
-                    "params": params
-                });
+            while let Some(first) = command_rx.recv().await {
+                // Write every queued command before flushing so batches are pipelined
+                let mut batch = vec![first];
+                while let Ok(next) = command_rx.try_recv() {
+                    batch.push(next);
+                }
 
-                pending_clone.lock().await.insert(id, responder);
+                let mut result = Ok(());
+                for (id, command) in batch {
+                    let mut msg = json!({
+                        "id": id,
+                        "method": command.method,
+                        "params": command.params
+                    });
+                    if let Some(session_id) = command.session_id {
+                        msg["sessionId"] = json!(session_id);
+                    }
 
-                if let Err(e) = write.send(Message::Text(msg.to_string().into())).await {
+                    let text = msg.to_string();
+                    let len = text.len() as u64;
+                    result = write.feed(Message::Text(text.into())).await;
+                    if result.is_err() {
+                        break;
+                    }
+                    Counters::add(&writer_counters.commands_sent, 1);
+                    Counters::add(&writer_counters.bytes_sent, len);
+                }
+
+                if let Err(e) = result.and(write.flush().await) {
                     eprintln!("Failed to send CDP command: {}", e);
+                    writer_state.send_replace(ConnectionState::Closed);
                     break;
                 }
             }
         });
 
-        // Task for receiving responses
+        // Task for receiving responses and events
+        let pending_clone = pending.clone();
+        let events_tx_clone = events_tx.clone();
+        let event_buffers_clone = event_buffers.clone();
+        let reader_counters = counters.clone();
+        let reader_failure = failure.clone();
+        let reader_filter = event_filter.clone();
+        let reader_state = state.clone();
         tokio::spawn(async move {
             while let Some(msg) = read.next().await {
                 match msg {
                     Ok(Message::Text(text)) => {
-                        if let Ok(v) = serde_json::from_str::<Value>(&text) {
-                            // Handle response
-                            if let Some(id) = v["id"].as_u64() {
-                                let id = id as u32;
-                                let responder = pending.lock().await.remove(&id);
-                                if let Some(responder) = responder {
-                                    if let Some(error) = v.get("error") {
-                                        let message =
-                                            error["message"].as_str().unwrap_or("unknown");
-                                        let _ = responder.send(Err(Error::Cdp(format!(
-                                            "CDP error for command {}: {} - {}",
-                                            id,
-                                            error["code"].as_i64().unwrap_or(-1),
-                                            message
-                                        ))));
-                                    } else if let Some(result) = v.get("result") {
-                                        let _ = responder.send(Ok(result.clone()));
+                        Counters::add(&reader_counters.bytes_received, text.len() as u64);
+                        let dropped = reader_filter
+                            .read()
+                            .ok()
+                            .and_then(|filter| filter.as_ref().map(|f| f.drops(&text)))
+                            .unwrap_or(false);
+                        if dropped {
+                            continue;
+                        }
+                        match parse_message(&text).and_then(Incoming::dispatch) {
+                            Some(Dispatch::Response { id, result }) => {
+                                Counters::add(&reader_counters.responses_received, 1);
+                                let waiter =
+                                    pending_clone.lock().ok().and_then(|mut p| p.remove(&id));
+                                if let Some(waiter) = waiter {
+                                    let _ = waiter.responder.send(result);
+                                }
+                            }
+                            Some(Dispatch::Event(event)) => {
+                                Counters::add(&reader_counters.events_received, 1);
+                                if let Some(f) = Failure::from_event(&event) {
+                                    // A crashed or detached target never answers, so
+                                    // fail waiting commands and refuse new ones
+                                    if let Ok(mut pending) = pending_clone.lock() {
+                                        if let Ok(mut failure) = reader_failure.lock() {
+                                            *failure = Some(f.clone());
+                                        }
+                                        reader_state.send_if_modified(|state| {
+                                            let connected = *state == ConnectionState::Connected;
+                                            if connected {
+                                                *state = ConnectionState::Failed;
+                                            }
+                                            connected
+                                        });
+                                        for (_, waiter) in pending.drain() {
+                                            let _ = waiter.responder.send(Err(f.error()));
+                                        }
                                     }
                                 }
+                                // Broadcasting under the lock keeps replaying
+                                // subscriptions free of gaps and duplicates
+                                let mut buffers = event_buffers_clone.lock().ok();
+                                if let Some(buffers) = buffers.as_mut() {
+                                    buffers.push(&event);
+                                }
+                                // No subscribers is not an error
+                                let _ = events_tx_clone.send(event);
+                                drop(buffers);
+                            }
+                            None => {}
+                        }
+                    }
+                    Ok(Message::Close(_)) => break,
+                    Err(WsError::Capacity(CapacityError::MessageTooLong { size, max_size })) => {
+                        // The oversized frame cannot be attributed to a command, and the
+                        // stream is unusable afterwards, so fail every pending command
+                        fail_pending(&pending_clone, || Error::MessageTooLarge {
+                            size,
+                            max: max_size,
+                        });
+                        break;
+                    }
+                    Err(e) => {
+                        eprintln!("WebSocket error: {}", e);
+                        break;
+                    }
+                    _ => {}
+                }
+            }
+
+            reader_state.send_replace(ConnectionState::Closed);
+            fail_pending(&pending_clone, || {
+                Error::WebSocket("Connection closed before response was received".to_string())
+            });
+        });
+
+        Ok(Self {
+            command_tx,
+            next_id: Arc::new(Mutex::new(1)),
+            pending,
+            events_tx,
+            event_buffers,
+            compat: Arc::new(std::sync::RwLock::new(ProtocolCompat::default())),
+            counters,
+            failure,
+            middleware: Arc::new(std::sync::RwLock::new(Vec::new())),
+            event_filter,
+            command_timeout: Arc::new(std::sync::RwLock::new(None)),
+            dry_run: Arc::new(std::sync::RwLock::new(None)),
+            state,
+        })
+    }
+
+    /// Deliver only events whose method is in the allowlist, or every event with `None`
+    ///
+    /// Entries are exact methods or `Domain.*`. Dropped events are discarded
+    /// before their parameters are parsed, so they also never reach buffers
+    /// or [`CdpConnection::wait_for_event`].
+    pub fn set_event_allowlist(&self, methods: Option<Vec<String>>) {
+        if let Ok(mut filter) = self.event_filter.write() {
+            *filter = methods.map(EventFilter::new);
+        }
+    }
+
+    /// Fail commands whose response takes longer than `timeout`, or wait forever with `None`
+    ///
+    /// The timeout is shared by all clones of the connection and applies to
+    /// each attempt a middleware such as [`Retry`](crate::Retry) makes.
+    pub fn set_command_timeout(&self, timeout: Option<Duration>) {
+        if let Ok(mut current) = self.command_timeout.write() {
+            *current = timeout;
+        }
+    }
+
+    /// Report commands instead of sending those that change state, or send all with `None`
+    ///
+    /// The check runs after the middleware chain, so reported commands carry
+    /// the session ids and parameters middleware gave them. The mode is shared
+    /// by all clones of the connection.
+    pub fn set_dry_run(&self, dry_run: Option<DryRun>) {
+        if let Ok(mut current) = self.dry_run.write() {
+            *current = dry_run.map(Arc::new);
+        }
+    }
+
+    /// Dry-run mode, if enabled
+    fn dry_run(&self) -> Option<Arc<DryRun>> {
+        self.dry_run.read().ok().and_then(|dry_run| dry_run.clone())
+    }
+
+    /// Append a middleware to the chain every command passes through
+    ///
+    /// The chain is shared by all clones of the connection.
+    pub fn add_middleware<M: Middleware + 'static>(&self, middleware: M) {
+        if let Ok(mut chain) = self.middleware.write() {
+            chain.push(Arc::new(middleware));
+        }
+    }
+
+    /// Append a middleware that stays in the chain until the returned guard is dropped
+    pub(crate) fn add_scoped_middleware<M: Middleware + 'static>(
+        &self,
+        middleware: M,
+    ) -> MiddlewareGuard {
+        let middleware: Arc<dyn Middleware> = Arc::new(middleware);
+        if let Ok(mut chain) = self.middleware.write() {
+            chain.push(middleware.clone());
+        }
+        MiddlewareGuard {
+            chain: Arc::downgrade(&self.middleware),
+            middleware,
+        }
+    }
+
+    /// Snapshot of the middleware chain
+    pub(crate) fn middleware_chain(&self) -> Vec<Arc<dyn Middleware>> {
+        self.middleware
+            .read()
+            .map(|chain| chain.clone())
+            .unwrap_or_default()
+    }
+
+    /// Current state of the connection
+    pub fn state(&self) -> ConnectionState {
+        *self.state.borrow()
+    }
+
+    /// Receiver notified whenever the connection state changes
+    ///
+    /// Use `wait_for` on it to gate work on readiness or to react to a closed socket.
+    pub fn state_changes(&self) -> watch::Receiver<ConnectionState> {
+        self.state.subscribe()
+    }
+
+    /// Whether the target crashed or was detached, making the connection unusable
+    pub fn is_failed(&self) -> bool {
+        self.failure.lock().map(|f| f.is_some()).unwrap_or(false)
+    }
+
+    /// Snapshot of the connection's activity counters
+    ///
+    /// A `pending_commands` count that keeps growing indicates a backed-up
+    /// browser or responders that are never resolved.
+    pub fn metrics(&self) -> ConnectionMetrics {
+        let load = |counter: &AtomicU64| counter.load(Ordering::Relaxed);
+        ConnectionMetrics {
+            pending_commands: self.pending_count(),
+            commands_sent: load(&self.counters.commands_sent),
+            responses_received: load(&self.counters.responses_received),
+            events_received: load(&self.counters.events_received),
+            bytes_sent: load(&self.counters.bytes_sent),
+            bytes_received: load(&self.counters.bytes_received),
+        }
+    }
+
+    /// Retain the most recent `capacity` events of the given method
+    ///
+    /// Events fired between enabling a domain and subscribing are otherwise lost;
+    /// buffered events can be inspected with [`CdpConnection::buffered_events`].
+    pub fn buffer_events(&self, method: &str, capacity: usize) {
+        if let Ok(mut buffers) = self.event_buffers.lock() {
+            buffers.enable(method, capacity);
+        }
+    }
+
+    /// Stop retaining events of the given method and drop the buffered ones
+    pub fn unbuffer_events(&self, method: &str) {
+        if let Ok(mut buffers) = self.event_buffers.lock() {
+            buffers.disable(method);
+        }
+    }
+
+    /// Buffered events of the given method, oldest first
+    pub fn buffered_events(&self, method: &str) -> Vec<CdpEvent> {
+        self.event_buffers
+            .lock()
+            .map(|buffers| buffers.get(method))
+            .unwrap_or_default()
+    }
+
+    /// Wait for an event of the given method whose params satisfy the predicate
+    ///
+    /// If the method is buffered (see [`CdpConnection::buffer_events`]), a matching
+    /// event that arrived before this call is consumed and returned immediately.
+    pub async fn wait_for_event<F>(
+        &self,
+        method: &str,
+        predicate: F,
+        timeout: Duration,
+    ) -> Result<Value>
+    where
+        F: Fn(&Value) -> bool,
+    {
+        // Subscribe before checking the buffer so no event can slip in between
+        let mut events = self.subscribe();
+
+        let buffered = self
+            .event_buffers
+            .lock()
+            .ok()
+            .and_then(|mut buffers| buffers.take_first(method, &predicate));
+        if let Some(event) = buffered {
+            return Ok(event.params);
+        }
+
+        let wait = async {
+            while let Some(event) = events.recv().await {
+                if event.method == method && predicate(&event.params) {
+                    // The reader buffers events before broadcasting them; consume that copy too
+                    if let Ok(mut buffers) = self.event_buffers.lock() {
+                        buffers.take_first(method, |params| params == &event.params);
+                    }
+                    return Ok(event.params);
+                }
+            }
+            Err(Error::WebSocket(format!(
+                "Connection closed while waiting for {}",
+                method
+            )))
+        };
+
+        tokio::time::timeout(timeout, wait).await.map_err(|_| {
+            Error::Timeout(format!(
+                "Event {} not received within {:?}",
+                method, timeout
+            ))
+        })?
+    }
+
+    /// Translate renamed or moved methods for the given browser version from now on
+    pub fn set_protocol_compat(&self, compat: ProtocolCompat) {
+        if let Ok(mut guard) = self.compat.write() {
+            *guard = compat;
+        }
+    }
+
+    /// Subscribe to events received on this connection from now on
+    pub fn subscribe(&self) -> EventStream {
+        EventStream {
+            rx: self.events_tx.subscribe(),
+            replay: VecDeque::new(),
+        }
+    }
+
+    /// Retain the most recent `capacity` events of a domain, e.g. `Network`
+    ///
+    /// Subscribers created with [`CdpConnection::subscribe_with_replay`]
+    /// receive these events first, so a recorder started slightly after an
+    /// action still sees its beginning.
+    pub fn buffer_domain(&self, domain: &str, capacity: usize) {
+        if let Ok(mut buffers) = self.event_buffers.lock() {
+            buffers.enable_domain(domain, capacity);
+        }
+    }
+
+    /// Stop retaining events of a domain and drop the retained ones
+    pub fn unbuffer_domain(&self, domain: &str) {
+        if let Ok(mut buffers) = self.event_buffers.lock() {
+            buffers.disable_domain(domain);
+        }
+    }
+
+    /// Subscribe to events, starting with those retained by [`CdpConnection::buffer_domain`]
+    ///
+    /// Retained events are delivered in arrival order, followed without gaps
+    /// or duplicates by events received from now on.
+    pub fn subscribe_with_replay(&self) -> EventStream {
+        match self.event_buffers.lock() {
+            Ok(buffers) => EventStream {
+                replay: buffers.replay(),
+                rx: self.events_tx.subscribe(),
+            },
+            Err(_) => self.subscribe(),
+        }
+    }
+
+    /// Send a CDP command and wait for response
+    ///
+    /// This method is cancellation safe: if the returned future is dropped
+    /// (e.g. by `tokio::time::timeout`), its pending entry is removed and a
+    /// late response is discarded. A command that was already written is
+    /// still executed by the browser.
+    pub async fn send_command(&self, method: &str, params: Value) -> Result<Value> {
+        let chain = self.middleware_chain();
+        Next::new(&chain, self)
+            .run(Command::new(method, params))
+            .await
+    }
+
+    /// Send a command, bypassing the middleware chain
+    pub(crate) async fn send_unchained(&self, command: Command) -> Result<Value> {
+        if let Some(dry_run) = self.dry_run() {
+            if !dry_run.check(&command) {
+                return Ok(json!({}));
+            }
+        }
+        let id = {
+            let mut next_id = self.next_id.lock().await;
+            let id = *next_id;
+            *next_id += 1;
+            id
+        };
+
+        let method = command.method.clone();
+        let (rx, _guard) = self.enqueue(id, command)?;
+        self.await_response(&method, rx).await
+    }
+
+    /// Wait for a response, bounded by the command timeout
+    async fn await_response(
+        &self,
+        method: &str,
+        rx: oneshot::Receiver<Result<Value>>,
+    ) -> Result<Value> {
+        let timeout = self.command_timeout.read().ok().and_then(|t| *t);
+        let response = match timeout {
+            Some(timeout) => tokio::time::timeout(timeout, rx).await.map_err(|_| {
+                Error::Timeout(format!("{} did not complete within {:?}", method, timeout))
+            })?,
+            None => rx.await,
+        };
+        response.map_err(|_| Error::Cdp("Response channel closed".to_string()))?
+    }
+
+    /// Send a CDP command and deserialize its result into `T`
+    ///
+    /// The result is moved into `T` without an intermediate copy, which keeps
+    /// large payloads such as HTML dumps cheap.
+    pub async fn send_command_as<T: DeserializeOwned>(
+        &self,
+        method: &str,
+        params: Value,
+    ) -> Result<T> {
+        Ok(serde_json::from_value(
+            self.send_command(method, params).await?,
+        )?)
+    }
+
+    /// Send several commands back-to-back, then wait for all responses
+    ///
+    /// All commands are written before any response is awaited, which saves a
+    /// round trip per command on remote connections. Results are returned in
+    /// the same order as the commands. With middleware installed, each command
+    /// passes through the chain concurrently instead, as it does in dry-run mode.
+    pub async fn send_commands(&self, commands: &[(&str, Value)]) -> Vec<Result<Value>> {
+        if !self.middleware_chain().is_empty() || self.dry_run().is_some() {
+            return futures::future::join_all(
+                commands
+                    .iter()
+                    .map(|(method, params)| self.send_command(method, params.clone())),
+            )
+            .await;
+        }
+
+        let receivers: Vec<_> = {
+            // Hold the id lock so the batch is queued contiguously and in order
+            let mut next_id = self.next_id.lock().await;
+            commands
+                .iter()
+                .map(|(method, params)| {
+                    let id = *next_id;
+                    *next_id += 1;
+                    self.enqueue(id, Command::new(method, params.clone()))
+                })
+                .collect()
+        };
+
+        let mut results = Vec::with_capacity(receivers.len());
+        for ((method, _), rx) in commands.iter().zip(receivers) {
+            results.push(match rx {
+                Ok((rx, _guard)) => self.await_response(method, rx).await,
+                Err(e) => Err(e),
+            });
+        }
+        results
+    }
+
+    /// Register a responder and queue a command for the writer task
+    ///
+    /// The responder is registered before the command can be written, and the
+    /// returned guard removes it again when the caller stops waiting.
+    fn enqueue(
+        &self,
+        id: u32,
+        mut command: Command,
+    ) -> Result<(oneshot::Receiver<Result<Value>>, PendingGuard)> {
+        if let Ok(compat) = self.compat.read() {
+            let params = std::mem::take(&mut command.params);
+            (command.method, command.params) = compat.translate(&command.method, params);
+        }
+
+        let (tx, rx) = oneshot::channel();
+        if let Ok(mut pending) = self.pending.lock() {
+            // Checked under the pending lock so a concurrent failure cannot miss this entry
+            if let Some(failure) = self.failure.lock().ok().and_then(|f| f.clone()) {
+                return Err(failure.error());
+            }
+            pending.insert(
+                id,
+                Waiter {
+                    responder: tx,
+                    method: command.method.clone(),
+                    queued_at: Instant::now(),
+                },
+            );
+        }
+        let guard = PendingGuard {
+            id,
+            pending: self.pending.clone(),
+        };
+
+        self.command_tx
+            .send((id, command))
+            .map_err(|_| Error::Cdp("Failed to send command to channel".to_string()))?;
+        Ok((rx, guard))
+    }
+
+    /// Number of commands waiting for a response
+    pub fn pending_count(&self) -> usize {
+        self.pending.lock().map(|p| p.len()).unwrap_or(0)
+    }
+
+    /// Commands waiting for a response, oldest first
+    ///
+    /// A command that has been pending for long points at what a stuck run
+    /// is waiting on, e.g. a `Runtime.evaluate` blocked by a busy renderer.
+    pub fn pending_commands(&self) -> Vec<PendingCommand> {
+        let Ok(pending) = self.pending.lock() else {
+            return Vec::new();
+        };
+        let mut commands: Vec<PendingCommand> = pending
+            .iter()
+            .map(|(id, waiter)| PendingCommand {
+                id: *id,
+                method: waiter.method.clone(),
+                elapsed: waiter.queued_at.elapsed(),
+            })
+            .collect();
+        commands.sort_by_key(|command| command.id);
+        commands
+    }
+}
+
+/// Removes a middleware from its connection's chain when dropped
+pub(crate) struct MiddlewareGuard {
+    chain: std::sync::Weak<std::sync::RwLock<Vec<Arc<dyn Middleware>>>>,
+    middleware: Arc<dyn Middleware>,
+}
+
+impl Drop for MiddlewareGuard {
+    fn drop(&mut self) {
+        let Some(chain) = self.chain.upgrade() else {
+            return;
+        };
+        if let Ok(mut middleware) = chain.write() {
+            middleware.retain(|m| !Arc::ptr_eq(m, &self.middleware));
+        };
+    }
+}
+
+/// Removes a pending entry when its caller stops waiting
+struct PendingGuard {
+    id: u32,
+    pending: PendingMap,
+}
+
+impl Drop for PendingGuard {
+    fn drop(&mut self) {
+        if let Ok(mut pending) = self.pending.lock() {
+            pending.remove(&self.id);
+        }
+    }
+}
+
+/// Fail every pending command with the given error
+fn fail_pending(pending: &std::sync::Mutex<HashMap<u32, Waiter>>, error: impl Fn() -> Error) {
+    if let Ok(mut pending) = pending.lock() {
+        for (_, waiter) in pending.drain() {
+            let _ = waiter.responder.send(Err(error()));
+        }
+    }
+}
+
+/// Parse an incoming WebSocket text message
+#[cfg(not(feature = "simd-json"))]
+fn parse_message(text: &str) -> Option<Incoming> {
+    serde_json::from_str(text).ok()
+}
+
+/// Parse an incoming WebSocket text message
+#[cfg(feature = "simd-json")]
+fn parse_message(text: &str) -> Option<Incoming> {
+    // simd-json parses in place, so it needs its own mutable copy of the buffer
+    let mut bytes = text.as_bytes().to_vec();
+    simd_json::serde::from_slice(&mut bytes).ok()
+}
+
+/// Browser-level CDP connection (`/devtools/browser/...`)
+///
+/// Unlike page connections, this endpoint accepts `Target.*` and `Browser.*`
+/// commands and receives browser-scope events such as `Target.targetCreated`.
+#[derive(Clone)]
+pub struct BrowserConnection {
+    connection: CdpConnection,
+}
+
+impl BrowserConnection {
+    /// Connect to a browser WebSocket debugger URL
+    pub async fn connect(ws_url: &str) -> Result<Self> {
+        Self::connect_with_options(ws_url, ConnectOptions::default()).await
+    }
+
+    /// Connect to a browser WebSocket debugger URL with custom options
+    pub async fn connect_with_options(ws_url: &str, options: ConnectOptions) -> Result<Self> {
+        if !is_browser_endpoint(ws_url) {
+            return Err(Error::WebSocket(format!(
+                "'{}' is not a browser endpoint (expected /devtools/browser/...)",
+                ws_url
+            )));
+        }
+        let connection = CdpConnection::connect_with_options(ws_url, options).await?;
+        Ok(Self { connection })
+    }
+
+    /// Send a browser-level CDP command and wait for response
+    pub async fn send_command(&self, method: &str, params: Value) -> Result<Value> {
+        self.connection.send_command(method, params).await
+    }
+
+    /// Subscribe to browser-scope events
+    pub fn subscribe(&self) -> EventStream {
+        self.connection.subscribe()
+    }
+
+    /// Underlying CDP connection
+    pub fn connection(&self) -> &CdpConnection {
+        &self.connection
+    }
+}
+
+/// Whether a WebSocket URL points at the browser target rather than a page
+fn is_browser_endpoint(ws_url: &str) -> bool {
+    ws_url.contains("/devtools/browser")
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::future::Future;
+    use std::sync::Arc;
+    use tokio::net::{TcpListener, TcpStream};
+    use tokio::sync::Mutex;
+    use tokio_tungstenite::WebSocketStream;
+
+    /// Serve a single WebSocket connection with the given handler and return its URL
+    pub(crate) async fn mock_server<F, Fut>(handler: F) -> String
+    where
+        F: FnOnce(WebSocketStream<TcpStream>) -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send,
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let ws = tokio_tungstenite::accept_async(stream).await.unwrap();
+            handler(ws).await;
+        });
+        format!("ws://{}/devtools/page/mock", addr)
+    }
+
+    /// Send a JSON message from the mock server
+    pub(crate) async fn server_send(ws: &mut WebSocketStream<TcpStream>, value: Value) {
+        ws.send(Message::Text(value.to_string().into()))
+            .await
+            .unwrap();
+    }
+
+    /// Receive the next JSON command on the mock server
+    pub(crate) async fn server_recv(ws: &mut WebSocketStream<TcpStream>) -> Value {
+        loop {
+            if let Message::Text(text) = ws.next().await.unwrap().unwrap() {
+                return serde_json::from_str(&text).unwrap();
+            }
+        }
+    }
+
+    /// Fake remote Chrome, from [`fake_chrome_with`]
+    pub(crate) struct FakeChrome {
+        /// Address of its DevTools endpoint
+        pub(crate) addr: std::net::SocketAddr,
+        /// Commands received over all WebSockets, in order
+        pub(crate) commands: Arc<std::sync::Mutex<Vec<Value>>>,
+        /// Request lines of the HTTP requests answered, e.g. `PUT /json/new HTTP/1.1`
+        pub(crate) http_requests: Arc<std::sync::Mutex<Vec<String>>>,
+    }
+
+    impl FakeChrome {
+        /// Browser WebSocket URL
+        pub(crate) fn ws_url(&self) -> String {
+            format!("ws://{}/devtools/browser/B", self.addr)
+        }
+    }
+
+    /// Fake remote Chrome serving `/json/version`, `/json/new` and a WebSocket per target
+    ///
+    /// Each command is answered with the `result` or `error` object `reply`
+    /// returns for it, given the id of the page the socket belongs to, or
+    /// `None` for the browser socket; a `None` reply leaves it unanswered.
+    /// Like Chrome, `Page.close` drops the page's socket and reports
+    /// `Target.targetDestroyed` to browser sockets, and requests naming the
+    /// host rather than an IP address are refused.
+    pub(crate) async fn fake_chrome_with<F>(reply: F) -> FakeChrome
+    where
+        F: Fn(Option<&str>, &Value) -> Option<Value> + Send + Sync + 'static,
+    {
+        use tokio::io::AsyncWriteExt;
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let chrome = FakeChrome {
+            addr,
+            commands: Arc::new(std::sync::Mutex::new(Vec::new())),
+            http_requests: Arc::new(std::sync::Mutex::new(Vec::new())),
+        };
+        let reply = Arc::new(reply);
+        // Ids of closed targets, reported to browser connections
+        let (destroyed, _) = broadcast::channel::<String>(16);
+        let commands = chrome.commands.clone();
+        let http_requests = chrome.http_requests.clone();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let (commands, http_requests) = (commands.clone(), http_requests.clone());
+                let (reply, destroyed) = (reply.clone(), destroyed.clone());
+                tokio::spawn(async move {
+                    let mut head = vec![0; 1024];
+                    let n = stream.peek(&mut head).await.unwrap();
+                    let head = String::from_utf8_lossy(&head[..n]).into_owned();
+                    let respond = |status: &str, body: &str| {
+                        format!(
+                            "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                            status,
+                            body.len(),
+                            body
+                        )
+                    };
+                    // Stricter than Chrome, which also accepts localhost, so
+                    // tests can use a name that resolves everywhere
+                    if !host_is_ip(&head) {
+                        let body =
+                            "Host header is specified and is not an IP address or localhost.";
+                        let response = respond("500 Internal Server Error", body);
+                        stream.write_all(response.as_bytes()).await.unwrap();
+                        return;
+                    }
+                    let request_line = head.lines().next().unwrap_or_default().to_string();
+                    let body = if request_line.starts_with("GET /json/version") {
+                        Some(format!(
+                            r#"{{"webSocketDebuggerUrl":"ws://{}/devtools/browser/B"}}"#,
+                            addr
+                        ))
+                    } else if request_line.starts_with("PUT /json/new") {
+                        Some(format!(
+                            r#"{{"id":"HTTP","type":"page","webSocketDebuggerUrl":"ws://{}/devtools/page/HTTP"}}"#,
+                            addr
+                        ))
+                    } else {
+                        None
+                    };
+                    if let Some(body) = body {
+                        http_requests.lock().unwrap().push(request_line);
+                        let response = respond("200 OK", &body);
+                        stream.write_all(response.as_bytes()).await.unwrap();
+                        return;
+                    }
+
+                    let target_id = request_line
+                        .split_whitespace()
+                        .nth(1)
+                        .and_then(|path| path.strip_prefix("/devtools/page/"))
+                        .map(String::from);
+                    let mut closed = destroyed.subscribe();
+                    let mut ws = tokio_tungstenite::accept_async(stream).await.unwrap();
+                    loop {
+                        let text = tokio::select! {
+                            Some(Ok(Message::Text(text))) = ws.next() => text,
+                            Ok(closed) = closed.recv(), if target_id.is_none() => {
+                                let event = json!({
+                                    "method": "Target.targetDestroyed",
+                                    "params": {"targetId": closed}
+                                });
+                                let _ = ws.send(Message::Text(event.to_string().into())).await;
+                                continue;
                             }
-                            // Ignore events for now
+                            else => break,
+                        };
+                        let request: Value = serde_json::from_str(&text).unwrap();
+                        commands.lock().unwrap().push(request.clone());
+                        let Some(mut response) = reply(target_id.as_deref(), &request) else {
+                            continue;
+                        };
+                        response["id"] = request["id"].clone();
+                        let _ = ws.send(Message::Text(response.to_string().into())).await;
+                        // Chrome drops the connection of a closed target
+                        if request["method"] == "Page.close" {
+                            let _ = destroyed.send(target_id.clone().unwrap_or_default());
+                            break;
                         }
                     }
-                    Ok(Message::Close(_)) => break,
-                    Err(e) => {
-                        eprintln!("WebSocket error: {}", e);
-                        break;
-                    }
-                    _ => {}
-                }
+                });
             }
         });
+        chrome
+    }
 
-        Ok(Self {
-            command_tx,
-            next_id: Arc::new(Mutex::new(1)),
+    /// Fake remote Chrome creating targets `T1`, `T2`, ... and answering other
+    /// commands with an empty result, returning its endpoint and the commands
+    /// it received
+    pub(crate) async fn fake_chrome() -> (crate::BrowserEndpoint, Arc<std::sync::Mutex<Vec<Value>>>)
+    {
+        let targets = std::sync::atomic::AtomicUsize::new(0);
+        let chrome = fake_chrome_with(move |_, request| {
+            let result = if request["method"] == "Target.createTarget" {
+                let n = targets.fetch_add(1, std::sync::atomic::Ordering::SeqCst) + 1;
+                json!({"targetId": format!("T{}", n)})
+            } else {
+                json!({})
+            };
+            Some(json!({ "result": result }))
         })
+        .await;
+        let endpoint = crate::BrowserEndpoint::new("127.0.0.1", chrome.addr.port());
+        (endpoint, chrome.commands)
     }
 
-    /// Send a CDP command and wait for response
-    pub async fn send_command(&self, method: &str, params: Value) -> Result<Value> {
-        let id = {
-            let mut next_id = self.next_id.lock().await;
-            let id = *next_id;
-            *next_id += 1;
-            id
-        };
-
-        let (tx, rx) = oneshot::channel();
-        self.command_tx
-            .send((id, method.to_string(), params, tx))
-            .map_err(|_| Error::Cdp("Failed to send command to channel".to_string()))?;
-
-        rx.await
-            .map_err(|_| Error::Cdp("Response channel closed".to_string()))?
+    /// Whether the Host header of a request head is an IP address
+    fn host_is_ip(head: &str) -> bool {
+        head.lines()
+            .find_map(|line| line.strip_prefix("host: ").or(line.strip_prefix("Host: ")))
+            .and_then(|host| {
+                let host = host.trim();
+                host.parse::<std::net::SocketAddr>()
+                    .map(|address| address.ip())
+                    .or_else(|_| host.parse::<std::net::IpAddr>())
+                    .ok()
+            })
+            .is_some()
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::sync::Arc;
-    use tokio::sync::Mutex;
+    /// Number of `Target.createTarget` commands received
+    pub(crate) fn created(commands: &std::sync::Mutex<Vec<Value>>) -> usize {
+        commands
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|command| command["method"] == "Target.createTarget")
+            .count()
+    }
 
     // Test helper to verify command ID increment
     struct TestConnection {
@@ -224,6 +1496,843 @@ mod tests {
             .is_err());
     }
 
+    #[test]
+    fn test_connector_for_plain_scheme() {
+        let connector = ConnectOptions::default()
+            .connector("ws://127.0.0.1:9222/devtools/browser/abc")
+            .unwrap();
+        assert!(matches!(connector, Some(Connector::Plain)));
+    }
+
+    #[test]
+    fn test_connector_for_tls_scheme() {
+        let options = ConnectOptions {
+            accept_invalid_certs: true,
+            ..Default::default()
+        };
+        let connector = options
+            .connector("wss://chrome.example.com/devtools/browser/abc")
+            .unwrap();
+        assert!(matches!(connector, Some(Connector::NativeTls(_))));
+    }
+
+    #[test]
+    fn test_connector_rejects_unknown_scheme() {
+        let result = ConnectOptions::default().connector("http://127.0.0.1:9222/json");
+        assert!(matches!(result, Err(Error::WebSocket(_))));
+    }
+
+    #[test]
+    fn test_connector_rejects_invalid_root_certificate() {
+        let options = ConnectOptions {
+            root_certificates: vec![b"not a certificate".to_vec()],
+            ..Default::default()
+        };
+        let result = options.connector("wss://chrome.example.com/devtools/browser/abc");
+        assert!(matches!(result, Err(Error::WebSocket(_))));
+    }
+
+    #[test]
+    fn test_websocket_config_defaults() {
+        let config = ConnectOptions::default().websocket_config();
+        let defaults = WebSocketConfig::default();
+        assert_eq!(config.max_message_size, defaults.max_message_size);
+        assert_eq!(config.max_frame_size, defaults.max_frame_size);
+    }
+
+    #[test]
+    fn test_websocket_config_custom_limits() {
+        let options = ConnectOptions {
+            max_message_size: Some(256 << 20),
+            max_frame_size: Some(128 << 20),
+            ..Default::default()
+        };
+        let config = options.websocket_config();
+        assert_eq!(config.max_message_size, Some(256 << 20));
+        assert_eq!(config.max_frame_size, Some(128 << 20));
+    }
+
+    #[test]
+    fn test_handshake_request_adds_query_and_headers() {
+        let options = ConnectOptions {
+            headers: vec![("Authorization".to_string(), "Bearer abc".to_string())],
+            query: vec![("token".to_string(), "s3cr3t".to_string())],
+            ..Default::default()
+        };
+        let request = options
+            .handshake_request("wss://cloud.example.com/devtools/browser/x?launch=1")
+            .unwrap();
+        assert_eq!(
+            request.uri().to_string(),
+            "wss://cloud.example.com/devtools/browser/x?launch=1&token=s3cr3t"
+        );
+        assert_eq!(request.headers()["authorization"], "Bearer abc");
+    }
+
+    #[test]
+    fn test_handshake_request_rejects_invalid_header() {
+        let options = ConnectOptions {
+            headers: vec![("bad header".to_string(), "x".to_string())],
+            ..Default::default()
+        };
+        assert!(matches!(
+            options.handshake_request("ws://127.0.0.1:9222/devtools/page/x"),
+            Err(Error::WebSocket(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_fail_pending_drains_responders() {
+        let pending: std::sync::Mutex<HashMap<u32, Waiter>> = std::sync::Mutex::new(HashMap::new());
+        let (tx, rx) = oneshot::channel();
+        pending.lock().unwrap().insert(
+            1,
+            Waiter {
+                responder: tx,
+                method: "Page.navigate".to_string(),
+                queued_at: Instant::now(),
+            },
+        );
+
+        fail_pending(&pending, || Error::MessageTooLarge { size: 2, max: 1 });
+
+        assert!(pending.lock().unwrap().is_empty());
+        assert!(matches!(
+            rx.await.unwrap(),
+            Err(Error::MessageTooLarge { size: 2, max: 1 })
+        ));
+    }
+
+    #[test]
+    fn test_dispatch_event() {
+        let incoming = parse_message(
+            r#"{"method":"Target.targetCreated","params":{"targetInfo":{"targetId":"T1"}},"sessionId":"S1"}"#,
+        )
+        .unwrap();
+        let Some(Dispatch::Event(event)) = incoming.dispatch() else {
+            panic!("expected event");
+        };
+        assert_eq!(event.method, "Target.targetCreated");
+        assert_eq!(event.params["targetInfo"]["targetId"], "T1");
+        assert_eq!(event.session_id.as_deref(), Some("S1"));
+    }
+
+    #[test]
+    fn test_dispatch_response() {
+        let incoming = parse_message(r#"{"id":3,"result":{"frameId":"F"}}"#).unwrap();
+        let Some(Dispatch::Response { id, result }) = incoming.dispatch() else {
+            panic!("expected response");
+        };
+        assert_eq!(id, 3);
+        assert_eq!(result.unwrap()["frameId"], "F");
+    }
+
+    #[test]
+    fn test_dispatch_error_response() {
+        let incoming =
+            parse_message(r#"{"id":4,"error":{"code":-32000,"message":"Target closed"}}"#).unwrap();
+        let Some(Dispatch::Response {
+            result: Err(Error::Protocol(error)),
+            ..
+        }) = incoming.dispatch()
+        else {
+            panic!("expected error response");
+        };
+        assert!(error.is_target_closed());
+    }
+
+    #[test]
+    fn test_dispatch_ignores_unknown_messages() {
+        assert!(parse_message(r#"{"foo":1}"#).unwrap().dispatch().is_none());
+    }
+
+    fn test_event(method: &str, n: i64) -> CdpEvent {
+        CdpEvent {
+            method: method.to_string(),
+            params: json!({ "n": n }),
+            session_id: None,
+        }
+    }
+
+    #[test]
+    fn test_event_buffers_ignore_unbuffered_methods() {
+        let mut buffers = EventBuffers::default();
+        buffers.push(&test_event("Page.loadEventFired", 1));
+        assert!(buffers.get("Page.loadEventFired").is_empty());
+    }
+
+    #[test]
+    fn test_event_buffers_ring_capacity() {
+        let mut buffers = EventBuffers::default();
+        buffers.enable("Page.loadEventFired", 2);
+        for n in 0..3 {
+            buffers.push(&test_event("Page.loadEventFired", n));
+        }
+        let events = buffers.get("Page.loadEventFired");
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].params["n"], 1);
+        assert_eq!(events[1].params["n"], 2);
+    }
+
+    #[test]
+    fn test_event_buffers_shrink_and_disable() {
+        let mut buffers = EventBuffers::default();
+        buffers.enable("Network.requestWillBeSent", 3);
+        for n in 0..3 {
+            buffers.push(&test_event("Network.requestWillBeSent", n));
+        }
+        buffers.enable("Network.requestWillBeSent", 1);
+        assert_eq!(buffers.get("Network.requestWillBeSent")[0].params["n"], 2);
+
+        buffers.disable("Network.requestWillBeSent");
+        buffers.push(&test_event("Network.requestWillBeSent", 4));
+        assert!(buffers.get("Network.requestWillBeSent").is_empty());
+    }
+
+    #[test]
+    fn test_event_buffers_replay_domains_in_arrival_order() {
+        let mut buffers = EventBuffers::default();
+        buffers.enable_domain("Network", 2);
+        buffers.enable_domain("Page", 4);
+        buffers.push(&test_event("Network.requestWillBeSent", 0));
+        buffers.push(&test_event("Page.frameNavigated", 1));
+        buffers.push(&test_event("Runtime.consoleAPICalled", 2));
+        buffers.push(&test_event("Network.responseReceived", 3));
+        buffers.push(&test_event("Network.loadingFinished", 4));
+
+        let replay: Vec<_> = buffers
+            .replay()
+            .into_iter()
+            .map(|e| e.params["n"].clone())
+            .collect();
+        assert_eq!(replay, [1, 3, 4]);
+
+        buffers.disable_domain("Network");
+        assert_eq!(buffers.replay().len(), 1);
+    }
+
+    #[test]
+    fn test_event_buffers_take_first_matching() {
+        let mut buffers = EventBuffers::default();
+        buffers.enable("Page.frameNavigated", 4);
+        for n in 0..3 {
+            buffers.push(&test_event("Page.frameNavigated", n));
+        }
+        let event = buffers
+            .take_first("Page.frameNavigated", |p| p["n"].as_i64() == Some(1))
+            .unwrap();
+        assert_eq!(event.params["n"], 1);
+        assert_eq!(buffers.get("Page.frameNavigated").len(), 2);
+        assert!(buffers
+            .take_first("Page.frameNavigated", |p| p["n"].as_i64() == Some(1))
+            .is_none());
+        assert!(buffers.take_first("Page.unknown", |_| true).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_send_command_roundtrip() {
+        let url = mock_server(|mut ws| async move {
+            let cmd = server_recv(&mut ws).await;
+            assert_eq!(cmd["method"], "Browser.getVersion");
+            server_send(
+                &mut ws,
+                json!({"id": cmd["id"], "result": {"product": "Mock/1.0"}}),
+            )
+            .await;
+            server_recv(&mut ws).await;
+        })
+        .await;
+
+        let conn = CdpConnection::connect(&url).await.unwrap();
+        let result = conn
+            .send_command("Browser.getVersion", json!({}))
+            .await
+            .unwrap();
+        assert_eq!(result["product"], "Mock/1.0");
+    }
+
+    #[tokio::test]
+    async fn test_send_command_as_deserializes_result() {
+        #[derive(Deserialize)]
+        struct Version {
+            product: String,
+        }
+
+        let url = mock_server(|mut ws| async move {
+            let cmd = server_recv(&mut ws).await;
+            server_send(
+                &mut ws,
+                json!({"id": cmd["id"], "result": {"product": "Mock/1.0", "revision": "x"}}),
+            )
+            .await;
+            server_recv(&mut ws).await;
+        })
+        .await;
+
+        let conn = CdpConnection::connect(&url).await.unwrap();
+        let version: Version = conn
+            .send_command_as("Browser.getVersion", json!({}))
+            .await
+            .unwrap();
+        assert_eq!(version.product, "Mock/1.0");
+    }
+
+    #[tokio::test]
+    async fn test_error_response_is_typed() {
+        let url = mock_server(|mut ws| async move {
+            let cmd = server_recv(&mut ws).await;
+            server_send(
+                &mut ws,
+                json!({
+                    "id": cmd["id"],
+                    "error": {"code": -32601, "message": "'Foo.bar' wasn't found"}
+                }),
+            )
+            .await;
+            server_recv(&mut ws).await;
+        })
+        .await;
+
+        let conn = CdpConnection::connect(&url).await.unwrap();
+        match conn.send_command("Foo.bar", json!({})).await {
+            Err(Error::Protocol(error)) => {
+                assert_eq!(error.code, crate::CdpErrorCode::MethodNotFound);
+                assert_eq!(error.message, "'Foo.bar' wasn't found");
+            }
+            other => panic!("expected protocol error, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_state_changes_to_closed() {
+        let url = mock_server(|mut ws| async move {
+            server_recv(&mut ws).await;
+            ws.close(None).await.unwrap();
+        })
+        .await;
+
+        let conn = CdpConnection::connect(&url).await.unwrap();
+        assert_eq!(conn.state(), ConnectionState::Connected);
+        let mut changes = conn.state_changes();
+
+        assert!(conn.send_command("Page.enable", json!({})).await.is_err());
+        changes
+            .wait_for(|state| *state == ConnectionState::Closed)
+            .await
+            .unwrap();
+        assert_eq!(conn.state(), ConnectionState::Closed);
+    }
+
+    #[tokio::test]
+    async fn test_target_crash_fails_pending_and_new_commands() {
+        let url = mock_server(|mut ws| async move {
+            server_recv(&mut ws).await;
+            server_send(
+                &mut ws,
+                json!({"method": "Inspector.targetCrashed", "params": {}}),
+            )
+            .await;
+            server_recv(&mut ws).await;
+        })
+        .await;
+
+        let conn = CdpConnection::connect(&url).await.unwrap();
+        assert!(matches!(
+            conn.send_command("Runtime.evaluate", json!({})).await,
+            Err(Error::TargetCrashed)
+        ));
+        assert!(conn.is_failed());
+        assert_eq!(conn.state(), ConnectionState::Failed);
+        assert!(matches!(
+            conn.send_command("Page.reload", json!({})).await,
+            Err(Error::TargetCrashed)
+        ));
+        assert_eq!(conn.pending_count(), 0);
+    }
+
+    #[test]
+    fn test_failure_from_event() {
+        let detached = CdpEvent {
+            method: "Inspector.detached".to_string(),
+            params: json!({"reason": "replaced_with_devtools"}),
+            session_id: None,
+        };
+        assert!(matches!(
+            Failure::from_event(&detached).unwrap().error(),
+            Error::TargetDetached(reason) if reason == "replaced_with_devtools"
+        ));
+
+        let child_crash = CdpEvent {
+            method: "Inspector.targetCrashed".to_string(),
+            params: json!({}),
+            session_id: Some("S1".to_string()),
+        };
+        assert!(Failure::from_event(&child_crash).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_metrics_count_traffic() {
+        let url = mock_server(|mut ws| async move {
+            let cmd = server_recv(&mut ws).await;
+            server_send(
+                &mut ws,
+                json!({"method": "Page.loadEventFired", "params": {}}),
+            )
+            .await;
+            server_send(&mut ws, json!({"id": cmd["id"], "result": {}})).await;
+            server_recv(&mut ws).await;
+        })
+        .await;
+
+        let conn = CdpConnection::connect(&url).await.unwrap();
+        assert_eq!(conn.metrics(), ConnectionMetrics::default());
+
+        conn.send_command("Page.enable", json!({})).await.unwrap();
+        let metrics = conn.metrics();
+        assert_eq!(metrics.pending_commands, 0);
+        assert_eq!(metrics.commands_sent, 1);
+        assert_eq!(metrics.responses_received, 1);
+        assert_eq!(metrics.events_received, 1);
+        assert!(metrics.bytes_sent > 0);
+        assert!(metrics.bytes_received > metrics.bytes_sent);
+    }
+
+    #[tokio::test]
+    #[allow(clippy::result_large_err)] // signature imposed by accept_hdr_async
+    async fn test_connect_sends_handshake_headers() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (seen_tx, seen_rx) = oneshot::channel();
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let callback = |request: &Request, response| {
+                let _ = seen_tx.send((
+                    request.uri().to_string(),
+                    request.headers().get("x-api-key").cloned(),
+                ));
+                Ok(response)
+            };
+            let _ws = tokio_tungstenite::accept_hdr_async(stream, callback)
+                .await
+                .unwrap();
+        });
+
+        let options = ConnectOptions {
+            headers: vec![("X-Api-Key".to_string(), "k1".to_string())],
+            query: vec![("token".to_string(), "t1".to_string())],
+            ..Default::default()
+        };
+        let ws_url = format!("ws://{}/devtools/page/mock", addr);
+        CdpConnection::connect_with_request(ws_url.as_str(), options)
+            .await
+            .unwrap();
+
+        let (uri, api_key) = seen_rx.await.unwrap();
+        assert_eq!(uri, "/devtools/page/mock?token=t1");
+        assert_eq!(api_key.unwrap(), "k1");
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_command_removes_pending_entry() {
+        let url = mock_server(|mut ws| async move {
+            // Never answer the command
+            let cmd = server_recv(&mut ws).await;
+            assert_eq!(cmd["method"], "Runtime.evaluate");
+            server_recv(&mut ws).await;
+        })
+        .await;
+
+        let conn = CdpConnection::connect(&url).await.unwrap();
+        let result = tokio::time::timeout(
+            Duration::from_millis(50),
+            conn.send_command("Runtime.evaluate", json!({})),
+        )
+        .await;
+        assert!(result.is_err());
+        assert_eq!(conn.pending_count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_pending_commands_lists_in_flight_commands() {
+        let url = mock_server(|mut ws| async move {
+            // Hold both commands without answering
+            server_recv(&mut ws).await;
+            server_recv(&mut ws).await;
+            server_recv(&mut ws).await;
+        })
+        .await;
+
+        let conn = CdpConnection::connect(&url).await.unwrap();
+        let navigate = tokio::spawn({
+            let conn = conn.clone();
+            async move { conn.send_command("Page.navigate", json!({})).await }
+        });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        let evaluate = tokio::spawn({
+            let conn = conn.clone();
+            async move { conn.send_command("Runtime.evaluate", json!({})).await }
+        });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+
+        let pending = conn.pending_commands();
+        let methods: Vec<&str> = pending.iter().map(|c| c.method.as_str()).collect();
+        assert_eq!(methods, ["Page.navigate", "Runtime.evaluate"]);
+        assert!(pending[0].id < pending[1].id);
+        assert!(pending[0].elapsed > pending[1].elapsed);
+
+        navigate.abort();
+        evaluate.abort();
+        let _ = navigate.await;
+        let _ = evaluate.await;
+        assert!(conn.pending_commands().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_command_timeout() {
+        let url = mock_server(|mut ws| async move {
+            // Never answer the command
+            server_recv(&mut ws).await;
+            server_recv(&mut ws).await;
+        })
+        .await;
+
+        let conn = CdpConnection::connect(&url).await.unwrap();
+        conn.set_command_timeout(Some(Duration::from_millis(50)));
+        let err = conn
+            .send_command("Runtime.evaluate", json!({}))
+            .await
+            .unwrap_err();
+        match err {
+            Error::Timeout(message) => assert!(message.starts_with("Runtime.evaluate")),
+            other => panic!("expected timeout, got {:?}", other),
+        }
+        assert_eq!(conn.pending_count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_dry_run_only_sends_read_only_commands() {
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        let url = mock_server(|mut ws| async move {
+            let mut sent = Vec::new();
+            loop {
+                let request = server_recv(&mut ws).await;
+                server_send(
+                    &mut ws,
+                    json!({"id": request["id"], "result": {"ok": true}}),
+                )
+                .await;
+                sent.push(request["method"].clone());
+                if request["method"] == "Page.navigate" {
+                    let _ = tx.send(sent);
+                    break;
+                }
+            }
+        })
+        .await;
+        let reports = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let conn = CdpConnection::connect(&url).await.unwrap();
+        conn.set_dry_run(Some(DryRun::with_reporter({
+            let reports = reports.clone();
+            move |command| reports.lock().unwrap().push(command.clone())
+        })));
+
+        let document = conn
+            .send_command("DOM.getDocument", json!({}))
+            .await
+            .unwrap();
+        assert_eq!(document["ok"], true);
+        let skipped = conn
+            .send_commands(&[
+                ("Input.insertText", json!({"text": "a"})),
+                ("Network.setCookie", json!({"name": "sid", "value": "x"})),
+            ])
+            .await;
+        assert!(skipped.iter().all(|r| r.as_ref().unwrap() == &json!({})));
+        {
+            let reports = reports.lock().unwrap();
+            let dispatched: Vec<_> = reports
+                .iter()
+                .map(|r| (r.method.as_str(), r.dispatched))
+                .collect();
+            assert_eq!(
+                dispatched,
+                [
+                    ("DOM.getDocument", true),
+                    ("Input.insertText", false),
+                    ("Network.setCookie", false),
+                ]
+            );
+        }
+
+        // Leaving dry-run mode sends everything again
+        conn.set_dry_run(None);
+        conn.send_command("Page.navigate", json!({"url": "about:blank"}))
+            .await
+            .unwrap();
+        assert_eq!(rx.await.unwrap(), ["DOM.getDocument", "Page.navigate"]);
+        assert_eq!(reports.lock().unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_send_commands_preserves_order() {
+        let url = mock_server(|mut ws| async move {
+            // Read the whole batch before answering, in reverse order
+            let mut commands = Vec::new();
+            for _ in 0..3 {
+                commands.push(server_recv(&mut ws).await);
+            }
+            let methods: Vec<_> = commands.iter().map(|c| c["method"].clone()).collect();
+            assert_eq!(methods, vec!["DOM.a", "DOM.b", "DOM.c"]);
+            for cmd in commands.iter().rev() {
+                server_send(
+                    &mut ws,
+                    json!({"id": cmd["id"], "result": {"method": cmd["method"]}}),
+                )
+                .await;
+            }
+            server_recv(&mut ws).await;
+        })
+        .await;
+
+        let conn = CdpConnection::connect(&url).await.unwrap();
+        let results = conn
+            .send_commands(&[
+                ("DOM.a", json!({})),
+                ("DOM.b", json!({})),
+                ("DOM.c", json!({})),
+            ])
+            .await;
+        let methods: Vec<_> = results
+            .into_iter()
+            .map(|r| r.unwrap()["method"].as_str().unwrap().to_string())
+            .collect();
+        assert_eq!(methods, vec!["DOM.a", "DOM.b", "DOM.c"]);
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_event_matches_predicate() {
+        let url = mock_server(|mut ws| async move {
+            let cmd = server_recv(&mut ws).await;
+            server_send(
+                &mut ws,
+                json!({"method": "Page.frameNavigated", "params": {"n": 1}}),
+            )
+            .await;
+            server_send(
+                &mut ws,
+                json!({"method": "Page.frameNavigated", "params": {"n": 2}}),
+            )
+            .await;
+            server_send(&mut ws, json!({"id": cmd["id"], "result": {}})).await;
+            server_recv(&mut ws).await;
+        })
+        .await;
+
+        let conn = CdpConnection::connect(&url).await.unwrap();
+        // The waiter subscribes on first poll, before the trigger command is sent
+        let (params, triggered) = tokio::join!(
+            conn.wait_for_event(
+                "Page.frameNavigated",
+                |p| p["n"] == 2,
+                Duration::from_secs(5)
+            ),
+            conn.send_command("Test.trigger", json!({}))
+        );
+        assert!(triggered.is_ok());
+        assert_eq!(params.unwrap()["n"], 2);
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_event_uses_buffer() {
+        let url = mock_server(|mut ws| async move {
+            server_send(
+                &mut ws,
+                json!({"method": "Page.loadEventFired", "params": {"timestamp": 1.0}}),
+            )
+            .await;
+            server_recv(&mut ws).await;
+        })
+        .await;
+
+        let conn = CdpConnection::connect(&url).await.unwrap();
+        conn.buffer_events("Page.loadEventFired", 8);
+        // Give the event time to arrive before anyone waits for it
+        for _ in 0..50 {
+            if !conn.buffered_events("Page.loadEventFired").is_empty() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        let params = conn
+            .wait_for_event("Page.loadEventFired", |_| true, Duration::from_millis(100))
+            .await
+            .unwrap();
+        assert_eq!(params["timestamp"], 1.0);
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_event_consumes_live_events() {
+        let url = mock_server(|mut ws| async move {
+            for timestamp in [1.0, 2.0] {
+                let cmd = server_recv(&mut ws).await;
+                server_send(
+                    &mut ws,
+                    json!({"method": "Page.loadEventFired", "params": {"timestamp": timestamp}}),
+                )
+                .await;
+                server_send(&mut ws, json!({"id": cmd["id"], "result": {}})).await;
+            }
+            server_recv(&mut ws).await;
+        })
+        .await;
+
+        let conn = CdpConnection::connect(&url).await.unwrap();
+        conn.buffer_events("Page.loadEventFired", 8);
+        for expected in [1.0, 2.0] {
+            let (params, triggered) = tokio::join!(
+                conn.wait_for_event("Page.loadEventFired", |_| true, Duration::from_secs(5)),
+                conn.send_command("Page.navigate", json!({}))
+            );
+            assert!(triggered.is_ok());
+            assert_eq!(params.unwrap()["timestamp"], expected);
+        }
+        assert!(conn.buffered_events("Page.loadEventFired").is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_with_replay_sees_earlier_events() {
+        let url = mock_server(|mut ws| async move {
+            let request = server_recv(&mut ws).await;
+            for n in 0..2 {
+                server_send(
+                    &mut ws,
+                    json!({"method": "Network.requestWillBeSent", "params": {"n": n}}),
+                )
+                .await;
+            }
+            server_send(&mut ws, json!({"id": request["id"], "result": {}})).await;
+            let request = server_recv(&mut ws).await;
+            server_send(
+                &mut ws,
+                json!({"method": "Network.loadingFinished", "params": {"n": 2}}),
+            )
+            .await;
+            server_send(&mut ws, json!({"id": request["id"], "result": {}})).await;
+        })
+        .await;
+
+        let conn = CdpConnection::connect(&url).await.unwrap();
+        conn.buffer_domain("Network", 8);
+        conn.send_command("Page.navigate", json!({})).await.unwrap();
+
+        let mut late = conn.subscribe_with_replay();
+        conn.send_command("Runtime.evaluate", json!({}))
+            .await
+            .unwrap();
+        for n in 0..3 {
+            let event = late.recv().await.unwrap();
+            assert_eq!(event.params["n"], n);
+        }
+        assert!(late.try_recv().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_event_timeout() {
+        let url = mock_server(|mut ws| async move {
+            server_recv(&mut ws).await;
+        })
+        .await;
+
+        let conn = CdpConnection::connect(&url).await.unwrap();
+        let result = conn
+            .wait_for_event("Page.loadEventFired", |_| true, Duration::from_millis(50))
+            .await;
+        assert!(matches!(result, Err(Error::Timeout(_))));
+    }
+
+    #[tokio::test]
+    async fn test_event_stream_skips_lagged_events() {
+        let (tx, rx) = broadcast::channel(2);
+        let mut stream = EventStream {
+            rx,
+            replay: VecDeque::new(),
+        };
+        for i in 0..4 {
+            tx.send(CdpEvent {
+                method: format!("Test.event{}", i),
+                params: Value::Null,
+                session_id: None,
+            })
+            .unwrap();
+        }
+        assert_eq!(stream.recv().await.unwrap().method, "Test.event2");
+        assert_eq!(stream.recv().await.unwrap().method, "Test.event3");
+        drop(tx);
+        assert!(stream.recv().await.is_none());
+    }
+
+    #[test]
+    fn test_event_stream_try_recv_does_not_wait() {
+        let (tx, rx) = broadcast::channel(4);
+        let mut stream = EventStream {
+            rx,
+            replay: VecDeque::new(),
+        };
+        assert!(stream.try_recv().is_none());
+        tx.send(CdpEvent {
+            method: "Test.event".to_string(),
+            params: Value::Null,
+            session_id: None,
+        })
+        .unwrap();
+        assert_eq!(stream.try_recv().unwrap().method, "Test.event");
+        assert!(stream.try_recv().is_none());
+    }
+
+    #[test]
+    fn test_parse_message() {
+        let incoming = parse_message(r#"{"id":3,"result":{"data":"\u00e9","n":1.5}}"#).unwrap();
+        assert_eq!(incoming.id, Some(3));
+        let result = incoming.result.unwrap();
+        assert_eq!(result["data"], "é");
+        assert_eq!(result["n"], 1.5);
+        assert!(parse_message("not json").is_none());
+    }
+
+    #[test]
+    fn test_event_filter_allows() {
+        let filter = EventFilter::new(vec![
+            "Page.loadEventFired".to_string(),
+            "Target.*".to_string(),
+        ]);
+        assert!(filter.allows("Page.loadEventFired"));
+        assert!(!filter.allows("Page.frameNavigated"));
+        assert!(filter.allows("Target.targetCreated"));
+        assert!(!filter.allows("Network.requestWillBeSent"));
+        assert!(filter.allows("Inspector.targetCrashed"));
+    }
+
+    #[test]
+    fn test_event_filter_drops_only_unlisted_events() {
+        let filter = EventFilter::new(vec!["Page.*".to_string()]);
+        assert!(filter.drops(r#"{"method":"Network.dataReceived","params":{"requestId":"1"}}"#));
+        assert!(!filter.drops(r#"{"method":"Page.loadEventFired","params":{}}"#));
+        assert!(!filter.drops(r#"{"id":1,"result":{}}"#));
+        // Escaped method names are decoded before matching
+        assert!(!filter.drops(r#"{"method":"P\u0061ge.x","params":{}}"#));
+    }
+
+    #[test]
+    fn test_is_browser_endpoint() {
+        assert!(is_browser_endpoint(
+            "ws://127.0.0.1:9222/devtools/browser/abc"
+        ));
+        assert!(!is_browser_endpoint(
+            "ws://127.0.0.1:9222/devtools/page/abc"
+        ));
+    }
+
     #[tokio::test]
     async fn test_oneshot_channel_closure() {
         let (tx, rx) = oneshot::channel::<Result<Value>>();
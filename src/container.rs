@@ -0,0 +1,299 @@
+//! Launch settings for Docker and Kubernetes
+
+use std::path::Path;
+
+/// Smallest `/dev/shm` Chrome is trusted with; Docker's default is only 64 MiB
+pub(crate) const MIN_SHARED_MEMORY: u64 = 512 * 1024 * 1024;
+
+/// Whether this process appears to run inside a container
+///
+/// Checks for Docker's `/.dockerenv`, the Kubernetes service environment and
+/// container cgroups of the init process.
+pub fn running_in_container() -> bool {
+    Path::new("/.dockerenv").exists()
+        || Path::new("/run/.containerenv").exists()
+        || std::env::var_os("KUBERNETES_SERVICE_HOST").is_some()
+        || std::fs::read_to_string("/proc/1/cgroup")
+            .is_ok_and(|cgroup| is_container_cgroup(&cgroup))
+}
+
+/// Whether a `/proc/<pid>/cgroup` file names a container runtime
+fn is_container_cgroup(cgroup: &str) -> bool {
+    ["docker", "kubepods", "containerd", "libpod"]
+        .iter()
+        .any(|runtime| cgroup.contains(runtime))
+}
+
+/// Size of `/dev/shm` in bytes, if it is mounted
+#[cfg(unix)]
+pub fn shared_memory_size() -> Option<u64> {
+    let path = std::ffi::CString::new("/dev/shm").ok()?;
+    // SAFETY: `stat` is a plain-old-data out parameter and `path` is a valid
+    // NUL-terminated string for the duration of the call
+    let stat = unsafe {
+        let mut stat: libc::statvfs = std::mem::zeroed();
+        if libc::statvfs(path.as_ptr(), &mut stat) != 0 {
+            return None;
+        }
+        stat
+    };
+    Some(stat.f_blocks as u64 * stat.f_frsize as u64)
+}
+
+/// Size of `/dev/shm` in bytes, if it is mounted
+#[cfg(not(unix))]
+pub fn shared_memory_size() -> Option<u64> {
+    None
+}
+
+/// Flags for running Chrome in a container with `shared_memory` bytes of `/dev/shm`
+pub(crate) fn container_args(shared_memory: Option<u64>) -> Vec<String> {
+    let mut args = vec![
+        // Containers usually lack the user namespaces the sandbox needs
+        "--no-sandbox".to_string(),
+        "--disable-setuid-sandbox".to_string(),
+        "--disable-gpu".to_string(),
+    ];
+    // A small /dev/shm makes renderers crash; fall back to /tmp
+    if shared_memory.is_none_or(|size| size < MIN_SHARED_MEMORY) {
+        args.push("--disable-dev-shm-usage".to_string());
+    }
+    args
+}
+
+/// Flags running the browser and renderers in one process
+pub(crate) const SINGLE_PROCESS_ARGS: [&str; 2] = ["--single-process", "--no-zygote"];
+
+/// Memory budgeted for each renderer process when capping their number
+const RENDERER_MEMORY: u64 = 256 * 1024 * 1024;
+
+/// Memory limit below which site isolation is turned off to save renderers
+const SITE_ISOLATION_MIN_MEMORY: u64 = 1024 * 1024 * 1024;
+
+/// Bounds of the V8 old-space size given to each renderer, in MiB
+const MIN_OLD_SPACE_MIB: u64 = 128;
+const MAX_OLD_SPACE_MIB: u64 = 4096;
+
+/// cgroup limits at or above this many bytes mean "unlimited" (cgroup v1 reports ~2^63)
+const UNLIMITED_MEMORY: u64 = 1 << 60;
+
+/// Memory and CPU limits of the cgroup this process runs in
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ResourceLimits {
+    /// Memory limit in bytes, `None` if unlimited
+    pub memory: Option<u64>,
+    /// CPU quota in cores, e.g. `1.5`, `None` if unlimited
+    pub cpus: Option<f64>,
+}
+
+impl ResourceLimits {
+    /// Read the limits from cgroup v2, falling back to cgroup v1
+    ///
+    /// Both are `None` outside Linux or when no limit is set.
+    pub fn detect() -> Self {
+        let read = |path: &str| std::fs::read_to_string(path).ok();
+        let v2_dir = read("/proc/self/cgroup")
+            .and_then(|cgroup| {
+                let path = cgroup_v2_path(&cgroup)?;
+                Some(format!("/sys/fs/cgroup{}", path.trim_end_matches('/')))
+            })
+            .unwrap_or_else(|| "/sys/fs/cgroup".to_string());
+        let v2 = |file: &str| {
+            read(&format!("{}/{}", v2_dir, file))
+                .or_else(|| read(&format!("/sys/fs/cgroup/{}", file)))
+        };
+
+        let memory = match v2("memory.max") {
+            Some(max) => parse_memory_max(&max),
+            None => read("/sys/fs/cgroup/memory/memory.limit_in_bytes")
+                .and_then(|limit| parse_memory_max(&limit)),
+        };
+        let cpus = match v2("cpu.max") {
+            Some(max) => parse_cpu_max(&max),
+            None => match (
+                read("/sys/fs/cgroup/cpu/cpu.cfs_quota_us"),
+                read("/sys/fs/cgroup/cpu/cpu.cfs_period_us"),
+            ) {
+                (Some(quota), Some(period)) => parse_cfs_quota(&quota, &period),
+                _ => None,
+            },
+        };
+        Self { memory, cpus }
+    }
+}
+
+/// Path of the cgroup v2 hierarchy in a `/proc/self/cgroup` file, e.g. `/user.slice`
+fn cgroup_v2_path(cgroup: &str) -> Option<&str> {
+    cgroup.lines().find_map(|line| line.strip_prefix("0::"))
+}
+
+/// Bytes in a `memory.max` or `memory.limit_in_bytes` file, `None` if unlimited
+fn parse_memory_max(max: &str) -> Option<u64> {
+    max.trim()
+        .parse::<u64>()
+        .ok()
+        .filter(|bytes| *bytes < UNLIMITED_MEMORY)
+}
+
+/// Cores in a cgroup v2 `cpu.max` file such as `150000 100000`, `None` for `max`
+fn parse_cpu_max(max: &str) -> Option<f64> {
+    let mut fields = max.split_whitespace();
+    let quota: f64 = fields.next()?.parse().ok()?;
+    let period: f64 = fields.next().unwrap_or("100000").parse().ok()?;
+    (quota > 0.0 && period > 0.0).then(|| quota / period)
+}
+
+/// Cores from cgroup v1 `cpu.cfs_quota_us` and `cpu.cfs_period_us`, `None` for a quota of -1
+fn parse_cfs_quota(quota: &str, period: &str) -> Option<f64> {
+    let quota: f64 = quota.trim().parse().ok()?;
+    let period: f64 = period.trim().parse().ok()?;
+    (quota > 0.0 && period > 0.0).then(|| quota / period)
+}
+
+/// Launch flags chosen for the resources the browser may use
+///
+/// Applied by [`LaunchOptions::tune_to_resources`](crate::LaunchOptions::tune_to_resources)
+/// and reported by [`CdpBrowser::resource_tuning`](crate::CdpBrowser::resource_tuning),
+/// so it is visible why a browser runs with fewer renderers than usual.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResourceTuning {
+    /// Limits the tuning was derived from
+    pub limits: ResourceLimits,
+    /// Cap on renderer processes, passed as `--renderer-process-limit`
+    pub renderer_process_limit: Option<usize>,
+    /// V8 old-space size per renderer in MiB, passed as `--js-flags=--max-old-space-size`
+    pub max_old_space_mib: Option<u64>,
+    /// Whether site isolation stays on; it is turned off below 1 GiB of memory
+    pub site_isolation: bool,
+}
+
+impl ResourceTuning {
+    /// Tuning for the limits of the current cgroup
+    pub fn detect() -> Self {
+        Self::for_limits(ResourceLimits::detect())
+    }
+
+    /// Tuning for the given limits
+    ///
+    /// Renderers are capped at one per 256 MiB and two per core, and each
+    /// gets half of its share of memory as V8 heap. Without limits, nothing
+    /// changes.
+    pub fn for_limits(limits: ResourceLimits) -> Self {
+        let by_memory = limits
+            .memory
+            .map(|memory| (memory / RENDERER_MEMORY).max(1) as usize);
+        let by_cpus = limits
+            .cpus
+            .map(|cpus| ((cpus * 2.0).ceil() as usize).max(1));
+        let renderer_process_limit = match (by_memory, by_cpus) {
+            (Some(memory), Some(cpus)) => Some(memory.min(cpus)),
+            (memory, cpus) => memory.or(cpus),
+        };
+        let max_old_space_mib = limits.memory.map(|memory| {
+            let share = memory / renderer_process_limit.unwrap_or(1) as u64;
+            (share / 2 / (1024 * 1024)).clamp(MIN_OLD_SPACE_MIB, MAX_OLD_SPACE_MIB)
+        });
+        Self {
+            limits,
+            renderer_process_limit,
+            max_old_space_mib,
+            site_isolation: limits
+                .memory
+                .is_none_or(|memory| memory >= SITE_ISOLATION_MIN_MEMORY),
+        }
+    }
+
+    /// Command-line arguments applying the tuning
+    pub fn args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        if let Some(limit) = self.renderer_process_limit {
+            args.push(format!("--renderer-process-limit={}", limit));
+        }
+        if let Some(mib) = self.max_old_space_mib {
+            args.push(format!("--js-flags=--max-old-space-size={}", mib));
+        }
+        if !self.site_isolation {
+            args.push("--disable-site-isolation-trials".to_string());
+            args.push("--disable-features=IsolateOrigins,site-per-process".to_string());
+        }
+        args
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_container_args_depend_on_shared_memory() {
+        let small = container_args(Some(64 * 1024 * 1024));
+        assert!(small.contains(&"--no-sandbox".to_string()));
+        assert!(small.contains(&"--disable-dev-shm-usage".to_string()));
+        assert!(container_args(None).contains(&"--disable-dev-shm-usage".to_string()));
+
+        let large = container_args(Some(2 * MIN_SHARED_MEMORY));
+        assert!(!large.contains(&"--disable-dev-shm-usage".to_string()));
+    }
+
+    #[test]
+    fn test_is_container_cgroup() {
+        assert!(is_container_cgroup(
+            "0::/kubepods/burstable/pod1234/abcdef\n"
+        ));
+        assert!(is_container_cgroup("12:cpu:/docker/0123456789ab\n"));
+        assert!(!is_container_cgroup("0::/init.scope\n"));
+    }
+
+    #[test]
+    fn test_parse_cgroup_limits() {
+        assert_eq!(parse_memory_max("536870912\n"), Some(512 * 1024 * 1024));
+        assert_eq!(parse_memory_max("max\n"), None);
+        assert_eq!(parse_memory_max("9223372036854771712\n"), None);
+        assert_eq!(parse_cpu_max("150000 100000\n"), Some(1.5));
+        assert_eq!(parse_cpu_max("max 100000\n"), None);
+        assert_eq!(parse_cfs_quota("200000\n", "100000\n"), Some(2.0));
+        assert_eq!(parse_cfs_quota("-1\n", "100000\n"), None);
+        assert_eq!(
+            cgroup_v2_path("0::/system.slice/app.service\n"),
+            Some("/system.slice/app.service")
+        );
+    }
+
+    #[test]
+    fn test_resource_tuning() {
+        let unlimited = ResourceTuning::for_limits(ResourceLimits::default());
+        assert!(unlimited.site_isolation);
+        assert!(unlimited.args().is_empty());
+
+        let small = ResourceTuning::for_limits(ResourceLimits {
+            memory: Some(768 * 1024 * 1024),
+            cpus: Some(4.0),
+        });
+        assert_eq!(small.renderer_process_limit, Some(3));
+        assert_eq!(
+            small.args(),
+            [
+                "--renderer-process-limit=3",
+                "--js-flags=--max-old-space-size=128",
+                "--disable-site-isolation-trials",
+                "--disable-features=IsolateOrigins,site-per-process"
+            ]
+        );
+
+        let cpu_bound = ResourceTuning::for_limits(ResourceLimits {
+            memory: Some(8 * 1024 * 1024 * 1024),
+            cpus: Some(1.5),
+        });
+        assert_eq!(cpu_bound.renderer_process_limit, Some(3));
+        assert_eq!(cpu_bound.max_old_space_mib, Some(1365));
+        assert!(cpu_bound.site_isolation);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_shared_memory_size() {
+        if std::path::Path::new("/dev/shm").is_dir() {
+            assert!(shared_memory_size().is_some_and(|size| size > 0));
+        }
+    }
+}
@@ -0,0 +1,1032 @@
+//! Isolated browser contexts
+
+use crate::{
+    BrowserConnection, CdpEvent, CdpPage, Download, DownloadEvent, Error, EventStream,
+    NavigationPolicy, ProxyConfig, ProxyCredentials, Result, Timeouts, Viewport,
+};
+use serde_json::json;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Settings applied to a context and to every page opened in it
+///
+/// ```
+/// use chrome_cdp::{ContextOptions, ProxyConfig};
+///
+/// let options = ContextOptions {
+///     proxy: Some(ProxyConfig::new("http://proxy.example:3128")),
+///     user_agent: Some("ReportBot/1.0".into()),
+///     locale: Some("de-DE".into()),
+///     permissions: vec!["geolocation".into()],
+///     extra_headers: vec![("X-Job-Id".into(), "42".into())],
+///     ..Default::default()
+/// };
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ContextOptions {
+    /// Proxy for the context's traffic instead of the browser's; its credentials
+    /// replace the browser's too
+    pub proxy: Option<ProxyConfig>,
+    /// User agent string sent and reported by the pages
+    pub user_agent: Option<String>,
+    /// Locale such as `de-DE`, used for `Intl`, `navigator.language` and `Accept-Language`
+    pub locale: Option<String>,
+    /// Permissions granted to every origin, e.g. `geolocation` or `clipboardReadWrite`
+    pub permissions: Vec<String>,
+    /// Headers sent with every request
+    pub extra_headers: Vec<(String, String)>,
+    /// Start the pages with the network cut off
+    pub offline: bool,
+}
+
+/// Isolated browser context, similar to an incognito profile
+///
+/// Pages and events obtained through a context only ever see targets that
+/// belong to it, so several tenants can share one browser without leaking
+/// events across each other.
+///
+/// The context is a guard: dropping it disposes the context and closes all
+/// of its pages, even when a test panics. Call [`close`](Self::close) to
+/// dispose it explicitly and observe errors.
+pub struct BrowserContext {
+    id: String,
+    connection: BrowserConnection,
+    browser_ws_url: String,
+    timeouts: Timeouts,
+    navigation_policy: Mutex<Option<NavigationPolicy>>,
+    download_dir: Mutex<Option<PathBuf>>,
+    /// Whether the context was already disposed
+    closed: AtomicBool,
+    proxy_credentials: Option<ProxyCredentials>,
+    viewport: Option<Viewport>,
+    ignore_certificate_errors: AtomicBool,
+    options: ContextOptions,
+}
+
+impl BrowserContext {
+    /// Create a new context on the given browser connection
+    pub(crate) async fn create(
+        connection: BrowserConnection,
+        browser_ws_url: &str,
+        timeouts: Timeouts,
+        options: ContextOptions,
+    ) -> Result<Self> {
+        let mut params = json!({ "disposeOnDetach": true });
+        if let Some(proxy) = &options.proxy {
+            params["proxyServer"] = json!(proxy.server);
+            if !proxy.bypass_list.is_empty() {
+                params["proxyBypassList"] = json!(proxy.bypass_list.join(","));
+            }
+        }
+        let result = connection
+            .send_command("Target.createBrowserContext", params)
+            .await
+            .map_err(|e| Error::Browser(format!("Failed to create browser context: {}", e)))?;
+
+        let id = result["browserContextId"]
+            .as_str()
+            .ok_or_else(|| {
+                Error::Browser(format!(
+                    "Target.createBrowserContext returned no browserContextId: {}",
+                    result
+                ))
+            })?
+            .to_string();
+
+        // Built before any further setup so a failure disposes the context on drop
+        let context = Self {
+            id,
+            connection,
+            browser_ws_url: browser_ws_url.to_string(),
+            timeouts,
+            navigation_policy: Mutex::new(None),
+            download_dir: Mutex::new(None),
+            closed: AtomicBool::new(false),
+            proxy_credentials: None,
+            viewport: None,
+            ignore_certificate_errors: AtomicBool::new(false),
+            options,
+        };
+        if !context.options.permissions.is_empty() {
+            context
+                .connection
+                .send_command(
+                    "Browser.grantPermissions",
+                    json!({
+                        "permissions": context.options.permissions,
+                        "browserContextId": context.id
+                    }),
+                )
+                .await
+                .map_err(|e| Error::Browser(format!("Failed to grant permissions: {}", e)))?;
+        }
+        Ok(context)
+    }
+
+    /// Answer proxy authentication challenges on the context's pages
+    pub(crate) fn with_proxy_credentials(mut self, credentials: Option<ProxyCredentials>) -> Self {
+        self.proxy_credentials = credentials;
+        self
+    }
+
+    /// Give the context's pages this viewport
+    pub(crate) fn with_viewport(mut self, viewport: Option<Viewport>) -> Self {
+        self.viewport = viewport;
+        self
+    }
+
+    /// Browser context id
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Options the context was created with
+    pub fn options(&self) -> &ContextOptions {
+        &self.options
+    }
+
+    /// Open a new page in this context
+    pub async fn new_page(&self) -> Result<CdpPage> {
+        let result = self
+            .connection
+            .send_command(
+                "Target.createTarget",
+                json!({ "url": "about:blank", "browserContextId": self.id }),
+            )
+            .await
+            .map_err(|e| Error::Browser(format!("Failed to create page in context: {}", e)))?;
+
+        let target_id = result["targetId"].as_str().ok_or_else(|| {
+            Error::Browser(format!(
+                "Target.createTarget returned no targetId: {}",
+                result
+            ))
+        })?;
+
+        self.attach_page(target_id).await
+    }
+
+    /// Connect to every page that belongs to this context
+    pub async fn pages(&self) -> Result<Vec<CdpPage>> {
+        let mut pages = Vec::new();
+        for target_id in self.target_ids(Some("page")).await? {
+            pages.push(self.attach_page(&target_id).await?);
+        }
+        Ok(pages)
+    }
+
+    /// Apply a navigation policy to every page obtained from this context afterwards
+    ///
+    /// See [`CdpPage::set_navigation_policy`].
+    pub fn set_navigation_policy<F>(&self, policy: F)
+    where
+        F: Fn(&str) -> bool + Send + Sync + 'static,
+    {
+        if let Ok(mut current) = self.navigation_policy.lock() {
+            *current = Some(Arc::new(policy));
+        }
+    }
+
+    /// Stop applying a navigation policy to pages obtained afterwards
+    pub fn clear_navigation_policy(&self) {
+        if let Ok(mut current) = self.navigation_policy.lock() {
+            *current = None;
+        }
+    }
+
+    /// Accept invalid TLS certificates on every page obtained from this context afterwards
+    ///
+    /// See [`CdpPage::set_ignore_certificate_errors`].
+    pub fn set_ignore_certificate_errors(&self, ignore: bool) {
+        self.ignore_certificate_errors
+            .store(ignore, Ordering::SeqCst);
+    }
+
+    /// Save downloads started by this context's pages into `dir`, creating it if needed
+    ///
+    /// Each context keeps its own directory, so parallel jobs in separate
+    /// contexts never mix their files. Files are named after their download
+    /// guid, so downloads suggesting the same name never overwrite or rename
+    /// each other; the suggested name is reported alongside.
+    pub async fn set_download_dir(&self, dir: impl AsRef<Path>) -> Result<()> {
+        std::fs::create_dir_all(dir.as_ref())?;
+        // Chrome requires an absolute path
+        let dir = std::fs::canonicalize(dir.as_ref())?;
+        self.connection
+            .send_command(
+                "Browser.setDownloadBehavior",
+                json!({
+                    "behavior": "allowAndName",
+                    "browserContextId": self.id,
+                    "downloadPath": dir.to_string_lossy(),
+                    "eventsEnabled": true,
+                }),
+            )
+            .await
+            .map_err(|e| Error::Browser(format!("Failed to set download directory: {}", e)))?;
+        if let Ok(mut current) = self.download_dir.lock() {
+            *current = Some(dir);
+        }
+        Ok(())
+    }
+
+    /// Directory downloads of this context are saved to, if one was set
+    pub fn download_dir(&self) -> Option<PathBuf> {
+        self.download_dir.lock().ok().and_then(|dir| dir.clone())
+    }
+
+    /// Subscribe to the progress of downloads started by this context's pages
+    ///
+    /// Only downloads started by a page's main frame can be attributed to
+    /// the context; those of iframes are not reported.
+    pub async fn download_events(&self) -> Result<DownloadEvents> {
+        Ok(DownloadEvents {
+            events: self.subscribe().await?,
+            dir: self.download_dir(),
+            filenames: HashMap::new(),
+        })
+    }
+
+    /// Wait until a download started by this context's pages is complete
+    ///
+    /// Requires a directory set with [`set_download_dir`](Self::set_download_dir).
+    /// Start waiting before triggering the download, e.g. with `tokio::join!`.
+    pub async fn wait_for_download(&self, timeout: Duration) -> Result<Download> {
+        if self.download_dir().is_none() {
+            return Err(Error::Browser(
+                "No download directory set; call set_download_dir first".to_string(),
+            ));
+        }
+        let mut events = self.download_events().await?;
+        let wait = async {
+            let mut urls = HashMap::new();
+            while let Some(event) = events.recv().await {
+                match event {
+                    DownloadEvent::Started { guid, url, .. } => {
+                        urls.insert(guid, url);
+                    }
+                    DownloadEvent::Completed {
+                        guid, path: None, ..
+                    } => {
+                        return Err(Error::Browser(format!(
+                            "Download of {} completed at an unknown path",
+                            urls.remove(&guid).unwrap_or(guid)
+                        )));
+                    }
+                    DownloadEvent::Completed {
+                        guid,
+                        received_bytes,
+                        path: Some(path),
+                    } => {
+                        let size = std::fs::metadata(&path)
+                            .map(|metadata| metadata.len())
+                            .unwrap_or(received_bytes);
+                        return Ok(Download {
+                            url: urls.remove(&guid).unwrap_or_default(),
+                            suggested_filename: events.filenames.remove(&guid).unwrap_or_default(),
+                            path,
+                            size,
+                        });
+                    }
+                    DownloadEvent::Canceled { guid } => {
+                        return Err(Error::Browser(format!(
+                            "Download of {} was canceled",
+                            urls.remove(&guid).unwrap_or(guid)
+                        )));
+                    }
+                    _ => {}
+                }
+            }
+            Err(Error::WebSocket(
+                "Connection closed while waiting for a download".to_string(),
+            ))
+        };
+        tokio::time::timeout(timeout, wait)
+            .await
+            .map_err(|_| Error::Timeout(format!("No download finished within {:?}", timeout)))?
+    }
+
+    /// Connect to a page target, applying the context's timeouts, navigation policy and options
+    ///
+    /// Use it for pages the context opened on its own, e.g. popups reported
+    /// through [`subscribe`](Self::subscribe).
+    pub async fn attach_page(&self, target_id: &str) -> Result<CdpPage> {
+        let ws_url = page_ws_url(&self.browser_ws_url, target_id);
+        let page = CdpPage::new_with_timeouts(&ws_url, self.timeouts).await?;
+        page.set_browser_connection(self.connection.connection().clone());
+        let policy = self
+            .navigation_policy
+            .lock()
+            .ok()
+            .and_then(|policy| policy.clone());
+        if policy.is_some() {
+            page.set_shared_navigation_policy(policy).await?;
+        }
+        if self.proxy_credentials.is_some() {
+            page.set_proxy_credentials(self.proxy_credentials.clone())
+                .await?;
+        }
+        if self.ignore_certificate_errors.load(Ordering::SeqCst) {
+            page.set_ignore_certificate_errors(true).await?;
+        }
+        if let Some(viewport) = self.viewport {
+            page.set_viewport(
+                viewport.width,
+                viewport.height,
+                viewport.device_scale_factor,
+                false,
+            )
+            .await?;
+        }
+        self.apply_options(&page).await?;
+        Ok(page)
+    }
+
+    /// Apply the page-level part of the context's options
+    async fn apply_options(&self, page: &CdpPage) -> Result<()> {
+        let options = &self.options;
+        if options.user_agent.is_some() || options.locale.is_some() {
+            let user_agent = match &options.user_agent {
+                Some(user_agent) => user_agent.clone(),
+                None => page.browser_user_agent().await?,
+            };
+            page.set_user_agent(&user_agent, options.locale.as_deref())
+                .await?;
+        }
+        if let Some(locale) = &options.locale {
+            page.set_locale(locale).await?;
+        }
+        if !options.extra_headers.is_empty() {
+            page.set_extra_headers(&options.extra_headers).await?;
+        }
+        if options.offline {
+            page.set_network_throttling(Duration::ZERO, -1.0, -1.0, true)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Subscribe to events of targets that belong to this context
+    pub async fn subscribe(&self) -> Result<ContextEventStream> {
+        // Target lifecycle events are only sent once discovery is enabled
+        self.connection
+            .send_command("Target.setDiscoverTargets", json!({ "discover": true }))
+            .await?;
+
+        let events = self.connection.subscribe();
+        let targets = self.target_ids(None).await?.into_iter().collect();
+
+        Ok(ContextEventStream {
+            events,
+            scope: ContextScope {
+                context_id: self.id.clone(),
+                targets,
+                sessions: HashSet::new(),
+                downloads: HashSet::new(),
+            },
+            method: None,
+        })
+    }
+
+    /// Subscribe to a single event method of targets that belong to this context
+    pub async fn on(&self, method: &str) -> Result<ContextEventStream> {
+        let mut stream = self.subscribe().await?;
+        stream.method = Some(method.to_string());
+        Ok(stream)
+    }
+
+    /// Dispose the context, closing all of its pages
+    ///
+    /// Closing an already closed context does nothing.
+    pub async fn close(&self) -> Result<()> {
+        if self.closed.load(Ordering::SeqCst) {
+            return Ok(());
+        }
+        self.connection
+            .send_command(
+                "Target.disposeBrowserContext",
+                json!({ "browserContextId": self.id }),
+            )
+            .await
+            .map_err(|e| Error::Browser(format!("Failed to dispose browser context: {}", e)))?;
+        // Only now, so a failed dispose can be retried and is retried on drop
+        self.closed.store(true, Ordering::SeqCst);
+        Ok(())
+    }
+
+    /// Ids of targets in this context, optionally filtered by target type
+    async fn target_ids(&self, target_type: Option<&str>) -> Result<Vec<String>> {
+        let result = self
+            .connection
+            .send_command("Target.getTargets", json!({}))
+            .await?;
+
+        Ok(result["targetInfos"]
+            .as_array()
+            .map(|infos| {
+                infos
+                    .iter()
+                    .filter(|info| info["browserContextId"].as_str() == Some(self.id.as_str()))
+                    .filter(|info| target_type.is_none_or(|t| info["type"].as_str() == Some(t)))
+                    .filter_map(|info| info["targetId"].as_str().map(String::from))
+                    .collect()
+            })
+            .unwrap_or_default())
+    }
+}
+
+impl Drop for BrowserContext {
+    fn drop(&mut self) {
+        if self.closed.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        if let Ok(handle) = tokio::runtime::Handle::try_current() {
+            let connection = self.connection.clone();
+            let id = std::mem::take(&mut self.id);
+            handle.spawn(async move {
+                // The browser may already be gone
+                let _ = connection
+                    .send_command(
+                        "Target.disposeBrowserContext",
+                        json!({ "browserContextId": id }),
+                    )
+                    .await;
+            });
+        }
+    }
+}
+
+/// Stream of events scoped to one browser context
+pub struct ContextEventStream {
+    events: EventStream,
+    scope: ContextScope,
+    method: Option<String>,
+}
+
+impl ContextEventStream {
+    /// Receive the next event belonging to the context, or `None` once the connection is closed
+    pub async fn recv(&mut self) -> Option<CdpEvent> {
+        loop {
+            let event = self.events.recv().await?;
+            if !self.scope.accept(&event) {
+                continue;
+            }
+            if self.method.as_deref().is_some_and(|m| m != event.method) {
+                continue;
+            }
+            return Some(event);
+        }
+    }
+}
+
+/// Typed download events of a context, from [`BrowserContext::download_events`]
+pub struct DownloadEvents {
+    events: ContextEventStream,
+    dir: Option<PathBuf>,
+    /// Suggested file names of running downloads, by guid
+    filenames: HashMap<String, String>,
+}
+
+impl DownloadEvents {
+    /// Receive the next download event, or `None` once the connection is closed
+    ///
+    /// Completed downloads report the path the browser saved them to; older
+    /// Chrome versions omit it, in which case it is derived from the download
+    /// directory and the guid the file is named after.
+    pub async fn recv(&mut self) -> Option<DownloadEvent> {
+        loop {
+            let event = self.events.recv().await?;
+            let Some(mut download) = DownloadEvent::from_event(&event) else {
+                continue;
+            };
+            match &mut download {
+                DownloadEvent::Started {
+                    guid,
+                    suggested_filename,
+                    ..
+                } => {
+                    self.filenames
+                        .insert(guid.clone(), suggested_filename.clone());
+                }
+                DownloadEvent::Completed { guid, path, .. } => {
+                    if let (None, Some(dir)) = (&path, &self.dir) {
+                        *path = Some(dir.join(guid.as_str()));
+                    }
+                }
+                _ => {}
+            }
+            return Some(download);
+        }
+    }
+}
+
+/// Tracks which targets and sessions belong to a context
+struct ContextScope {
+    context_id: String,
+    targets: HashSet<String>,
+    sessions: HashSet<String>,
+    /// Downloads started by the context's main frames, by guid
+    downloads: HashSet<String>,
+}
+
+impl ContextScope {
+    /// Whether the event belongs to the context, updating tracked targets and sessions
+    fn accept(&mut self, event: &CdpEvent) -> bool {
+        let params = &event.params;
+
+        if let Some(info) = params.get("targetInfo") {
+            let target_id = info["targetId"].as_str().unwrap_or_default();
+            let belongs = info["browserContextId"].as_str() == Some(self.context_id.as_str());
+            if belongs {
+                self.targets.insert(target_id.to_string());
+                if let Some(session_id) = params["sessionId"].as_str() {
+                    self.sessions.insert(session_id.to_string());
+                }
+            } else {
+                self.targets.remove(target_id);
+            }
+            return belongs;
+        }
+
+        if let Some(session_id) = &event.session_id {
+            return self.sessions.contains(session_id);
+        }
+
+        if let Some(session_id) = params["sessionId"].as_str() {
+            let belongs = self.sessions.contains(session_id);
+            if event.method == "Target.detachedFromTarget" {
+                self.sessions.remove(session_id);
+            }
+            return belongs;
+        }
+
+        // Browser-wide download events are attributed by the frame that started them
+        match event.method.as_str() {
+            "Browser.downloadWillBegin" => {
+                // A page's main frame id is its target id
+                let frame_id = params["frameId"].as_str().unwrap_or_default();
+                let belongs = self.targets.contains(frame_id);
+                if belongs {
+                    self.downloads
+                        .insert(params["guid"].as_str().unwrap_or_default().to_string());
+                }
+                return belongs;
+            }
+            "Browser.downloadProgress" => {
+                let guid = params["guid"].as_str().unwrap_or_default();
+                let belongs = self.downloads.contains(guid);
+                if params["state"] != "inProgress" {
+                    self.downloads.remove(guid);
+                }
+                return belongs;
+            }
+            _ => {}
+        }
+
+        if let Some(target_id) = params["targetId"].as_str() {
+            let belongs = self.targets.contains(target_id);
+            if event.method == "Target.targetDestroyed" {
+                self.targets.remove(target_id);
+            }
+            return belongs;
+        }
+
+        // Events that cannot be attributed to a target are never shared across contexts
+        false
+    }
+}
+
+/// Build the page WebSocket URL of a target from the browser WebSocket URL
+pub(crate) fn page_ws_url(browser_ws_url: &str, target_id: &str) -> String {
+    match browser_ws_url.find("/devtools/browser") {
+        Some(index) => format!("{}/devtools/page/{}", &browser_ws_url[..index], target_id),
+        None => format!(
+            "{}/devtools/page/{}",
+            browser_ws_url.trim_end_matches('/'),
+            target_id
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connection::tests::{mock_server, server_recv, server_send};
+    use serde_json::Value;
+
+    /// Context backed by a mock browser that answers the creation command
+    async fn mock_context<F, Fut>(handler: F) -> BrowserContext
+    where
+        F: FnOnce(tokio_tungstenite::WebSocketStream<tokio::net::TcpStream>) -> Fut
+            + Send
+            + 'static,
+        Fut: std::future::Future<Output = ()> + Send,
+    {
+        let url = mock_server(|mut ws| async move {
+            let create = server_recv(&mut ws).await;
+            server_send(
+                &mut ws,
+                json!({"id": create["id"], "result": {"browserContextId": "CTX-A"}}),
+            )
+            .await;
+            handler(ws).await;
+        })
+        .await;
+        let url = url.replace("/devtools/page/", "/devtools/browser/");
+        let connection = BrowserConnection::connect(&url).await.unwrap();
+        BrowserContext::create(
+            connection,
+            &url,
+            Timeouts::default(),
+            ContextOptions::default(),
+        )
+        .await
+        .unwrap()
+    }
+
+    fn event(method: &str, params: Value, session_id: Option<&str>) -> CdpEvent {
+        CdpEvent {
+            method: method.to_string(),
+            params,
+            session_id: session_id.map(String::from),
+        }
+    }
+
+    fn scope() -> ContextScope {
+        ContextScope {
+            context_id: "CTX-A".to_string(),
+            targets: HashSet::new(),
+            sessions: HashSet::new(),
+            downloads: HashSet::new(),
+        }
+    }
+
+    #[test]
+    fn test_page_ws_url() {
+        assert_eq!(
+            page_ws_url("ws://127.0.0.1:9222/devtools/browser/abc", "T1"),
+            "ws://127.0.0.1:9222/devtools/page/T1"
+        );
+        assert_eq!(
+            page_ws_url("wss://cloud.example.com/devtools/browser/abc?token=x", "T1"),
+            "wss://cloud.example.com/devtools/page/T1"
+        );
+    }
+
+    #[test]
+    fn test_scope_accepts_own_target_created() {
+        let mut scope = scope();
+        let created = event(
+            "Target.targetCreated",
+            json!({"targetInfo": {"targetId": "T1", "browserContextId": "CTX-A"}}),
+            None,
+        );
+        assert!(scope.accept(&created));
+        assert!(scope.targets.contains("T1"));
+    }
+
+    #[test]
+    fn test_scope_rejects_other_context() {
+        let mut scope = scope();
+        let created = event(
+            "Target.targetCreated",
+            json!({"targetInfo": {"targetId": "T2", "browserContextId": "CTX-B"}}),
+            None,
+        );
+        assert!(!scope.accept(&created));
+        let destroyed = event("Target.targetDestroyed", json!({"targetId": "T2"}), None);
+        assert!(!scope.accept(&destroyed));
+    }
+
+    #[test]
+    fn test_scope_tracks_target_destroyed() {
+        let mut scope = scope();
+        scope.targets.insert("T1".to_string());
+        let destroyed = event("Target.targetDestroyed", json!({"targetId": "T1"}), None);
+        assert!(scope.accept(&destroyed));
+        assert!(!scope.targets.contains("T1"));
+    }
+
+    #[test]
+    fn test_scope_tracks_sessions() {
+        let mut scope = scope();
+        let attached = event(
+            "Target.attachedToTarget",
+            json!({
+                "sessionId": "S1",
+                "targetInfo": {"targetId": "T1", "browserContextId": "CTX-A"}
+            }),
+            None,
+        );
+        assert!(scope.accept(&attached));
+
+        let page_event = event("Page.loadEventFired", json!({}), Some("S1"));
+        assert!(scope.accept(&page_event));
+
+        let foreign_event = event("Page.loadEventFired", json!({}), Some("S9"));
+        assert!(!scope.accept(&foreign_event));
+
+        let detached = event(
+            "Target.detachedFromTarget",
+            json!({"sessionId": "S1"}),
+            None,
+        );
+        assert!(scope.accept(&detached));
+        assert!(!scope.accept(&page_event));
+    }
+
+    #[test]
+    fn test_scope_rejects_unattributable_events() {
+        let mut scope = scope();
+        let download = event("Browser.downloadWillBegin", json!({"guid": "g"}), None);
+        assert!(!scope.accept(&download));
+    }
+
+    #[tokio::test]
+    async fn test_set_download_dir_is_context_scoped() {
+        let dir = std::env::temp_dir().join(format!("cdp-downloads-{}", uuid::Uuid::new_v4()));
+        let context = mock_context(|mut ws| async move {
+            let cmd = server_recv(&mut ws).await;
+            assert_eq!(cmd["method"], "Browser.setDownloadBehavior");
+            assert_eq!(cmd["params"]["behavior"], "allowAndName");
+            assert_eq!(cmd["params"]["browserContextId"], "CTX-A");
+            assert!(Path::new(cmd["params"]["downloadPath"].as_str().unwrap()).is_absolute());
+            server_send(&mut ws, json!({"id": cmd["id"], "result": {}})).await;
+            server_recv(&mut ws).await;
+        })
+        .await;
+
+        assert!(context.download_dir().is_none());
+        context.set_download_dir(&dir).await.unwrap();
+        assert!(dir.is_dir());
+        assert_eq!(context.download_dir(), Some(dir.canonicalize().unwrap()));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_scope_attributes_downloads_by_main_frame() {
+        let mut scope = scope();
+        scope.targets.insert("T1".to_string());
+        let ours = event(
+            "Browser.downloadWillBegin",
+            json!({"guid": "g1", "frameId": "T1"}),
+            None,
+        );
+        let theirs = event(
+            "Browser.downloadWillBegin",
+            json!({"guid": "g2", "frameId": "T9"}),
+            None,
+        );
+        assert!(scope.accept(&ours));
+        assert!(!scope.accept(&theirs));
+
+        let progress = |guid: &str, state: &str| {
+            event(
+                "Browser.downloadProgress",
+                json!({"guid": guid, "state": state}),
+                None,
+            )
+        };
+        assert!(scope.accept(&progress("g1", "inProgress")));
+        assert!(!scope.accept(&progress("g2", "completed")));
+        assert!(scope.accept(&progress("g1", "completed")));
+        assert!(scope.downloads.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_download_resolves_saved_file() {
+        let dir = std::env::temp_dir().join(format!("cdp-downloads-{}", uuid::Uuid::new_v4()));
+        let context = mock_context(|mut ws| async move {
+            loop {
+                let cmd = server_recv(&mut ws).await;
+                let result = match cmd["method"].as_str() {
+                    Some("Target.getTargets") => json!({"targetInfos": [
+                        {"targetId": "T1", "type": "page", "browserContextId": "CTX-A"}
+                    ]}),
+                    _ => json!({}),
+                };
+                server_send(&mut ws, json!({"id": cmd["id"], "result": result})).await;
+                if cmd["method"] == "Target.getTargets" {
+                    break;
+                }
+            }
+            let events = [
+                ("Browser.downloadWillBegin", json!({"guid": "other", "frameId": "T9", "url": "https://b.example/x", "suggestedFilename": "x"})),
+                ("Browser.downloadWillBegin", json!({"guid": "g", "frameId": "T1", "url": "https://a.example/r.csv", "suggestedFilename": "r.csv"})),
+                ("Browser.downloadProgress", json!({"guid": "other", "state": "completed", "receivedBytes": 1})),
+                ("Browser.downloadProgress", json!({"guid": "g", "state": "inProgress", "receivedBytes": 2})),
+                ("Browser.downloadProgress", json!({"guid": "g", "state": "completed", "receivedBytes": 5})),
+            ];
+            for (method, params) in events {
+                server_send(&mut ws, json!({"method": method, "params": params})).await;
+            }
+            server_recv(&mut ws).await;
+        })
+        .await;
+        assert!(context
+            .wait_for_download(Duration::from_millis(10))
+            .await
+            .is_err());
+
+        context.set_download_dir(&dir).await.unwrap();
+        let saved = context.download_dir().unwrap().join("g");
+        std::fs::write(&saved, "a,b\n").unwrap();
+        let download = context
+            .wait_for_download(Duration::from_secs(5))
+            .await
+            .unwrap();
+        assert_eq!(
+            download,
+            Download {
+                url: "https://a.example/r.csv".to_string(),
+                suggested_filename: "r.csv".to_string(),
+                path: saved,
+                size: 4,
+            }
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_create_sets_proxy_and_permissions() {
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        let url = mock_server(|mut ws| async move {
+            let create = server_recv(&mut ws).await;
+            server_send(
+                &mut ws,
+                json!({"id": create["id"], "result": {"browserContextId": "CTX-A"}}),
+            )
+            .await;
+            let grant = server_recv(&mut ws).await;
+            server_send(&mut ws, json!({"id": grant["id"], "result": {}})).await;
+            let _ = tx.send((create, grant));
+            server_recv(&mut ws).await;
+        })
+        .await;
+        let url = url.replace("/devtools/page/", "/devtools/browser/");
+        let connection = BrowserConnection::connect(&url).await.unwrap();
+        let options = ContextOptions {
+            proxy: Some(
+                ProxyConfig::new("socks5://proxy:1080").bypass(["localhost", "*.internal"]),
+            ),
+            permissions: vec!["geolocation".to_string(), "notifications".to_string()],
+            ..Default::default()
+        };
+        let context = BrowserContext::create(connection, &url, Timeouts::default(), options)
+            .await
+            .unwrap();
+
+        let (create, grant) = rx.await.unwrap();
+        assert_eq!(create["params"]["proxyServer"], "socks5://proxy:1080");
+        assert_eq!(create["params"]["proxyBypassList"], "localhost,*.internal");
+        assert_eq!(grant["method"], "Browser.grantPermissions");
+        assert_eq!(grant["params"]["browserContextId"], "CTX-A");
+        assert_eq!(
+            grant["params"]["permissions"],
+            json!(["geolocation", "notifications"])
+        );
+        assert_eq!(context.options().permissions.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_failed_setup_disposes_context() {
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        let url = mock_server(|mut ws| async move {
+            let create = server_recv(&mut ws).await;
+            server_send(
+                &mut ws,
+                json!({"id": create["id"], "result": {"browserContextId": "CTX-A"}}),
+            )
+            .await;
+            let grant = server_recv(&mut ws).await;
+            server_send(
+                &mut ws,
+                json!({"id": grant["id"], "error": {"code": -32000, "message": "Unknown permission type"}}),
+            )
+            .await;
+            let _ = tx.send(server_recv(&mut ws).await);
+        })
+        .await;
+        let url = url.replace("/devtools/page/", "/devtools/browser/");
+        let connection = BrowserConnection::connect(&url).await.unwrap();
+        let options = ContextOptions {
+            permissions: vec!["bogus".to_string()],
+            ..Default::default()
+        };
+        let created = BrowserContext::create(connection, &url, Timeouts::default(), options).await;
+
+        assert!(created.is_err());
+        let dispose = tokio::time::timeout(std::time::Duration::from_secs(1), rx)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(dispose["method"], "Target.disposeBrowserContext");
+        assert_eq!(dispose["params"]["browserContextId"], "CTX-A");
+    }
+
+    #[tokio::test]
+    async fn test_options_apply_to_pages() {
+        let mut context = mock_context(|mut ws| async move {
+            server_recv(&mut ws).await;
+        })
+        .await;
+        context.options = ContextOptions {
+            locale: Some("de-DE".to_string()),
+            extra_headers: vec![("X-Job-Id".to_string(), "42".to_string())],
+            offline: true,
+            ..Default::default()
+        };
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let url = mock_server(|mut ws| async move {
+            loop {
+                let request = server_recv(&mut ws).await;
+                let result = match request["method"].as_str() {
+                    Some("Browser.getVersion") => json!({"userAgent": "Chrome/1"}),
+                    _ => json!({}),
+                };
+                let id = request["id"].clone();
+                let _ = tx.send(request);
+                server_send(&mut ws, json!({"id": id, "result": result})).await;
+            }
+        })
+        .await;
+        let page = CdpPage::new_with_domains(&url, Timeouts::default(), &[])
+            .await
+            .unwrap();
+
+        context.apply_options(&page).await.unwrap();
+        let mut sent = HashMap::new();
+        while let Ok(request) = rx.try_recv() {
+            sent.insert(
+                request["method"].as_str().unwrap().to_string(),
+                request["params"].clone(),
+            );
+        }
+        // Without a user agent of its own the browser's is kept, with the locale's language
+        assert_eq!(
+            sent["Emulation.setUserAgentOverride"],
+            json!({"userAgent": "Chrome/1", "acceptLanguage": "de-DE"})
+        );
+        assert_eq!(sent["Emulation.setLocaleOverride"]["locale"], "de-DE");
+        assert_eq!(
+            sent["Network.setExtraHTTPHeaders"]["headers"],
+            json!({"X-Job-Id": "42"})
+        );
+        assert_eq!(sent["Network.emulateNetworkConditions"]["offline"], true);
+    }
+
+    #[tokio::test]
+    async fn test_close_disposes_once() {
+        let context = mock_context(|mut ws| async move {
+            let cmd = server_recv(&mut ws).await;
+            assert_eq!(cmd["method"], "Target.disposeBrowserContext");
+            assert_eq!(cmd["params"]["browserContextId"], "CTX-A");
+            server_send(&mut ws, json!({"id": cmd["id"], "result": {}})).await;
+            server_recv(&mut ws).await;
+        })
+        .await;
+
+        context.close().await.unwrap();
+        context.close().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_close_retries_failed_dispose() {
+        let context = mock_context(|mut ws| async move {
+            let failed = server_recv(&mut ws).await;
+            server_send(
+                &mut ws,
+                json!({"id": failed["id"], "error": {"code": -32000, "message": "busy"}}),
+            )
+            .await;
+            let retried = server_recv(&mut ws).await;
+            server_send(&mut ws, json!({"id": retried["id"], "result": {}})).await;
+            server_recv(&mut ws).await;
+        })
+        .await;
+
+        assert!(context.close().await.is_err());
+        context.close().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_drop_disposes_context() {
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        let context = mock_context(|mut ws| async move {
+            let cmd = server_recv(&mut ws).await;
+            let _ = tx.send(cmd);
+        })
+        .await;
+
+        drop(context);
+        let cmd = tokio::time::timeout(std::time::Duration::from_secs(1), rx)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(cmd["method"], "Target.disposeBrowserContext");
+        assert_eq!(cmd["params"]["browserContextId"], "CTX-A");
+    }
+}
@@ -0,0 +1,301 @@
+//! Chrome's DevTools HTTP endpoint (`/json/*`)
+
+use crate::{Error, Result};
+use reqwest::Method;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::Value;
+
+/// Target as listed by `/json/list` and created by `/json/new`
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HttpTarget {
+    /// Target id, the same as in CDP's `Target` domain
+    pub id: String,
+    /// Target type, e.g. `page`, `iframe`, `service_worker`
+    #[serde(rename = "type")]
+    pub target_type: String,
+    /// Current title
+    #[serde(default)]
+    pub title: String,
+    /// Current URL
+    #[serde(default)]
+    pub url: String,
+    /// WebSocket URL to debug the target; Chrome omits it while DevTools is attached
+    pub web_socket_debugger_url: Option<String>,
+    /// Path of the DevTools frontend for the target
+    pub devtools_frontend_url: Option<String>,
+}
+
+/// Client for the `/json/*` endpoints of one browser
+///
+/// Cloning is cheap; clones share the same connection pool.
+///
+/// ```no_run
+/// # async fn run() -> chrome_cdp::Result<()> {
+/// use chrome_cdp::DevToolsHttpClient;
+///
+/// let http = DevToolsHttpClient::new("127.0.0.1", 9222);
+/// for target in http.list().await? {
+///     if target.url.starts_with("https://ads.") {
+///         http.close(&target.id).await?;
+///     }
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct DevToolsHttpClient {
+    client: reqwest::Client,
+    host: String,
+    port: u16,
+    https: bool,
+}
+
+impl DevToolsHttpClient {
+    /// Client for the endpoint at `host:port`
+    pub fn new(host: &str, port: u16) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            host: host.to_string(),
+            port,
+            https: false,
+        }
+    }
+
+    /// Reach the endpoint over HTTPS, e.g. behind a proxy serving DevTools on `wss://`
+    pub fn https(mut self, https: bool) -> Self {
+        self.https = https;
+        self
+    }
+
+    /// Scheme of the endpoint, `http` or `https`
+    pub fn scheme(&self) -> &'static str {
+        if self.https {
+            "https"
+        } else {
+            "http"
+        }
+    }
+
+    /// Host of the endpoint
+    pub fn host(&self) -> &str {
+        &self.host
+    }
+
+    /// Port of the endpoint
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    /// The `/json/version` document: browser, protocol version and `webSocketDebuggerUrl`
+    pub async fn version(&self) -> Result<Value> {
+        self.get_json("version").await
+    }
+
+    /// Browser-level WebSocket debugger URL from `/json/version`
+    pub async fn ws_url(&self) -> Result<String> {
+        let value = self.version().await?;
+        value["webSocketDebuggerUrl"]
+            .as_str()
+            .map(String::from)
+            .ok_or_else(|| {
+                Error::Browser(format!(
+                    "Chrome debugger response does not contain webSocketDebuggerUrl. Response: {}",
+                    value
+                ))
+            })
+    }
+
+    /// Protocol schema with every domain's commands, events and types
+    pub async fn protocol(&self) -> Result<Value> {
+        self.get_json("protocol").await
+    }
+
+    /// Pages, workers and other targets of the browser
+    pub async fn list(&self) -> Result<Vec<HttpTarget>> {
+        self.get_json("list").await
+    }
+
+    /// Open a tab at `url`, or `about:blank`
+    ///
+    /// The URL is percent-encoded so its own query and fragment reach Chrome intact.
+    pub async fn new_page(&self, url: Option<&str>) -> Result<HttpTarget> {
+        let path = match url {
+            Some(url) => format!("new?{}", percent_encode(url)),
+            None => "new".to_string(),
+        };
+        let body = self.request(Method::PUT, &path).await?;
+        serde_json::from_str(&body).map_err(|e| {
+            Error::Browser(format!(
+                "Failed to parse JSON response from {}: {}. Response body: {}",
+                self.url(&path),
+                e,
+                body
+            ))
+        })
+    }
+
+    /// Close a target
+    pub async fn close(&self, target_id: &str) -> Result<()> {
+        self.request(Method::GET, &format!("close/{}", target_id))
+            .await?;
+        Ok(())
+    }
+
+    /// Bring a tab to the front
+    pub async fn activate(&self, target_id: &str) -> Result<()> {
+        self.request(Method::GET, &format!("activate/{}", target_id))
+            .await?;
+        Ok(())
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!(
+            "{}://{}:{}/json/{}",
+            self.scheme(),
+            self.host,
+            self.port,
+            path
+        )
+    }
+
+    /// Get and parse a JSON document
+    async fn get_json<T: DeserializeOwned>(&self, path: &str) -> Result<T> {
+        let body = self.request(Method::GET, path).await?;
+        Ok(serde_json::from_str(&body)?)
+    }
+
+    /// Send a request, returning the body of a successful response
+    async fn request(&self, method: Method, path: &str) -> Result<String> {
+        let url = self.url(path);
+        let response = self
+            .client
+            .request(method, &url)
+            .send()
+            .await
+            .map_err(|e| Error::Http(format!("Failed to connect to {}: {}", url, e)))?;
+
+        let status = response.status();
+        let body = response
+            .text()
+            .await
+            .map_err(|e| Error::Http(format!("Failed to read response from {}: {}", url, e)))?;
+
+        if !status.is_success() {
+            return Err(Error::Browser(format!(
+                "Chrome debugger returned error status {} ({}). Response: {}",
+                status, url, body
+            )));
+        }
+        Ok(body)
+    }
+}
+
+/// Encode every byte except unreserved characters as `%XX`
+fn percent_encode(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    /// HTTP server answering each request with `respond(request_line)`, as (status, body)
+    async fn serve<F>(respond: F) -> u16
+    where
+        F: Fn(&str) -> (&'static str, String) + Send + Sync + 'static,
+    {
+        let listener = tokio::net::TcpListener::bind(("127.0.0.1", 0))
+            .await
+            .unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let respond = std::sync::Arc::new(respond);
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let respond = respond.clone();
+                tokio::spawn(async move {
+                    let mut buf = vec![0; 1024];
+                    let n = socket.read(&mut buf).await.unwrap();
+                    let head = String::from_utf8_lossy(&buf[..n]).to_string();
+                    let (status, body) = respond(head.lines().next().unwrap_or_default());
+                    let response = format!(
+                        "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                        status,
+                        body.len(),
+                        body
+                    );
+                    socket.write_all(response.as_bytes()).await.unwrap();
+                });
+            }
+        });
+        port
+    }
+
+    #[tokio::test]
+    async fn test_list_and_target_operations() {
+        let port = serve(|line| match line {
+            "GET /json/list HTTP/1.1" => (
+                "200 OK",
+                r#"[{"id": "T1", "type": "page", "title": "Example", "url": "https://example.com/",
+                     "webSocketDebuggerUrl": "ws://127.0.0.1/devtools/page/T1",
+                     "devtoolsFrontendUrl": "/devtools/inspector.html?ws=127.0.0.1/devtools/page/T1"},
+                    {"id": "W1", "type": "service_worker", "url": "https://example.com/sw.js"}]"#
+                    .to_string(),
+            ),
+            "PUT /json/new?https%3A%2F%2Fexample.com%2F%3Fq%3Da%20b%26c%3D1%23top HTTP/1.1" => (
+                "200 OK",
+                r#"{"id": "T2", "type": "page", "url": "https://example.com/"}"#.to_string(),
+            ),
+            "GET /json/activate/T1 HTTP/1.1" => ("200 OK", "Target activated".to_string()),
+            _ => ("404 Not Found", "No such target id".to_string()),
+        })
+        .await;
+        let http = DevToolsHttpClient::new("127.0.0.1", port);
+
+        let targets = http.list().await.unwrap();
+        assert_eq!(targets.len(), 2);
+        assert_eq!(targets[0].target_type, "page");
+        assert_eq!(
+            targets[0].web_socket_debugger_url.as_deref(),
+            Some("ws://127.0.0.1/devtools/page/T1")
+        );
+        assert!(targets[1].web_socket_debugger_url.is_none());
+
+        let created = http
+            .new_page(Some("https://example.com/?q=a b&c=1#top"))
+            .await
+            .unwrap();
+        assert_eq!(created.id, "T2");
+        http.activate("T1").await.unwrap();
+        let err = http.close("T9").await.unwrap_err();
+        assert!(err.to_string().contains("404"));
+    }
+
+    #[tokio::test]
+    async fn test_ws_url_from_version() {
+        let port = serve(|_| {
+            (
+                "200 OK",
+                r#"{"Browser": "Chrome/120", "webSocketDebuggerUrl": "ws://127.0.0.1:9222/devtools/browser/B"}"#
+                    .to_string(),
+            )
+        })
+        .await;
+        let http = DevToolsHttpClient::new("127.0.0.1", port);
+        assert_eq!(
+            http.ws_url().await.unwrap(),
+            "ws://127.0.0.1:9222/devtools/browser/B"
+        );
+    }
+}
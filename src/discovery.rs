@@ -0,0 +1,527 @@
+//! Discovery of Chromium-based browser executables installed on this machine
+
+use std::collections::HashSet;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+/// Chromium-based browser build an executable belongs to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BrowserFlavor {
+    /// Google Chrome stable
+    Chrome,
+    /// Google Chrome Beta
+    ChromeBeta,
+    /// Google Chrome Dev
+    ChromeDev,
+    /// Google Chrome Canary (Windows and macOS only)
+    ChromeCanary,
+    /// Chromium, including snap and flatpak packages
+    Chromium,
+    /// Microsoft Edge
+    Edge,
+    /// Brave
+    Brave,
+    /// `chrome-headless-shell`, the old headless mode as a standalone binary
+    ChromeHeadlessShell,
+}
+
+/// Chrome release channel to launch, see [`LaunchOptions::channel`](crate::LaunchOptions::channel)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Channel {
+    /// Google Chrome stable
+    Stable,
+    /// Google Chrome Beta
+    Beta,
+    /// Google Chrome Dev
+    Dev,
+    /// Google Chrome Canary (Windows and macOS only)
+    Canary,
+    /// `chrome-headless-shell` from Chrome for Testing, found on `PATH`
+    HeadlessShell,
+}
+
+impl Channel {
+    /// Flavor of the channel's executables
+    pub fn flavor(self) -> BrowserFlavor {
+        match self {
+            Channel::Stable => BrowserFlavor::Chrome,
+            Channel::Beta => BrowserFlavor::ChromeBeta,
+            Channel::Dev => BrowserFlavor::ChromeDev,
+            Channel::Canary => BrowserFlavor::ChromeCanary,
+            Channel::HeadlessShell => BrowserFlavor::ChromeHeadlessShell,
+        }
+    }
+
+    /// Installed executable of the channel, if any
+    pub fn executable(self) -> Option<PathBuf> {
+        discover_executables()
+            .into_iter()
+            .find(|executable| executable.flavor == self.flavor())
+            .map(|executable| executable.path)
+    }
+}
+
+/// Browser executable found by [`discover_executables`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BrowserExecutable {
+    /// Path to the executable
+    pub path: PathBuf,
+    /// Build the executable belongs to
+    pub flavor: BrowserFlavor,
+}
+
+/// Where to look for one flavor
+struct Candidate {
+    flavor: BrowserFlavor,
+    /// Executable names looked up on `PATH`
+    commands: &'static [&'static str],
+    /// Install locations; a leading `~` or `%VAR%` is expanded
+    paths: &'static [&'static str],
+    /// Executable registered under the Windows `App Paths` key
+    registry: Option<&'static str>,
+}
+
+/// Candidates in order of preference
+#[cfg(target_os = "linux")]
+const CANDIDATES: &[Candidate] = &[
+    Candidate {
+        flavor: BrowserFlavor::Chrome,
+        commands: &["google-chrome", "google-chrome-stable"],
+        paths: &[
+            "/opt/google/chrome/chrome",
+            "/var/lib/flatpak/exports/bin/com.google.Chrome",
+            "~/.local/share/flatpak/exports/bin/com.google.Chrome",
+        ],
+        registry: None,
+    },
+    Candidate {
+        flavor: BrowserFlavor::Chromium,
+        commands: &["chromium", "chromium-browser"],
+        paths: &[
+            "/snap/bin/chromium",
+            "/var/lib/flatpak/exports/bin/org.chromium.Chromium",
+            "~/.local/share/flatpak/exports/bin/org.chromium.Chromium",
+        ],
+        registry: None,
+    },
+    Candidate {
+        flavor: BrowserFlavor::ChromeBeta,
+        commands: &["google-chrome-beta"],
+        paths: &["/opt/google/chrome-beta/chrome"],
+        registry: None,
+    },
+    Candidate {
+        flavor: BrowserFlavor::ChromeDev,
+        commands: &["google-chrome-unstable"],
+        paths: &["/opt/google/chrome-unstable/chrome"],
+        registry: None,
+    },
+    Candidate {
+        flavor: BrowserFlavor::Edge,
+        commands: &["microsoft-edge", "microsoft-edge-stable"],
+        paths: &["/opt/microsoft/msedge/msedge"],
+        registry: None,
+    },
+    Candidate {
+        flavor: BrowserFlavor::Brave,
+        commands: &["brave-browser", "brave"],
+        paths: &[
+            "/opt/brave.com/brave/brave",
+            "/snap/bin/brave",
+            "/var/lib/flatpak/exports/bin/com.brave.Browser",
+        ],
+        registry: None,
+    },
+    Candidate {
+        flavor: BrowserFlavor::ChromeHeadlessShell,
+        commands: &["chrome-headless-shell"],
+        paths: &[],
+        registry: None,
+    },
+];
+
+/// Candidates in order of preference
+#[cfg(target_os = "macos")]
+const CANDIDATES: &[Candidate] = &[
+    Candidate {
+        flavor: BrowserFlavor::Chrome,
+        commands: &[],
+        paths: &[
+            "/Applications/Google Chrome.app/Contents/MacOS/Google Chrome",
+            "~/Applications/Google Chrome.app/Contents/MacOS/Google Chrome",
+        ],
+        registry: None,
+    },
+    Candidate {
+        flavor: BrowserFlavor::Chromium,
+        commands: &["chromium"],
+        paths: &[
+            "/Applications/Chromium.app/Contents/MacOS/Chromium",
+            "~/Applications/Chromium.app/Contents/MacOS/Chromium",
+        ],
+        registry: None,
+    },
+    Candidate {
+        flavor: BrowserFlavor::ChromeBeta,
+        commands: &[],
+        paths: &["/Applications/Google Chrome Beta.app/Contents/MacOS/Google Chrome Beta"],
+        registry: None,
+    },
+    Candidate {
+        flavor: BrowserFlavor::ChromeDev,
+        commands: &[],
+        paths: &["/Applications/Google Chrome Dev.app/Contents/MacOS/Google Chrome Dev"],
+        registry: None,
+    },
+    Candidate {
+        flavor: BrowserFlavor::ChromeCanary,
+        commands: &[],
+        paths: &[
+            "/Applications/Google Chrome Canary.app/Contents/MacOS/Google Chrome Canary",
+            "~/Applications/Google Chrome Canary.app/Contents/MacOS/Google Chrome Canary",
+        ],
+        registry: None,
+    },
+    Candidate {
+        flavor: BrowserFlavor::Edge,
+        commands: &[],
+        paths: &["/Applications/Microsoft Edge.app/Contents/MacOS/Microsoft Edge"],
+        registry: None,
+    },
+    Candidate {
+        flavor: BrowserFlavor::Brave,
+        commands: &[],
+        paths: &["/Applications/Brave Browser.app/Contents/MacOS/Brave Browser"],
+        registry: None,
+    },
+    Candidate {
+        flavor: BrowserFlavor::ChromeHeadlessShell,
+        commands: &["chrome-headless-shell"],
+        paths: &[],
+        registry: None,
+    },
+];
+
+/// Candidates in order of preference
+#[cfg(target_os = "windows")]
+const CANDIDATES: &[Candidate] = &[
+    Candidate {
+        flavor: BrowserFlavor::Chrome,
+        commands: &["chrome.exe"],
+        paths: &[
+            "%ProgramFiles%\\Google\\Chrome\\Application\\chrome.exe",
+            "%ProgramFiles(x86)%\\Google\\Chrome\\Application\\chrome.exe",
+            "%LOCALAPPDATA%\\Google\\Chrome\\Application\\chrome.exe",
+        ],
+        registry: Some("chrome.exe"),
+    },
+    Candidate {
+        flavor: BrowserFlavor::Chromium,
+        commands: &["chromium.exe"],
+        paths: &["%LOCALAPPDATA%\\Chromium\\Application\\chrome.exe"],
+        registry: None,
+    },
+    Candidate {
+        flavor: BrowserFlavor::ChromeBeta,
+        commands: &[],
+        paths: &[
+            "%ProgramFiles%\\Google\\Chrome Beta\\Application\\chrome.exe",
+            "%LOCALAPPDATA%\\Google\\Chrome Beta\\Application\\chrome.exe",
+        ],
+        registry: None,
+    },
+    Candidate {
+        flavor: BrowserFlavor::ChromeDev,
+        commands: &[],
+        paths: &[
+            "%ProgramFiles%\\Google\\Chrome Dev\\Application\\chrome.exe",
+            "%LOCALAPPDATA%\\Google\\Chrome Dev\\Application\\chrome.exe",
+        ],
+        registry: None,
+    },
+    Candidate {
+        flavor: BrowserFlavor::ChromeCanary,
+        commands: &[],
+        paths: &["%LOCALAPPDATA%\\Google\\Chrome SxS\\Application\\chrome.exe"],
+        registry: None,
+    },
+    Candidate {
+        flavor: BrowserFlavor::Edge,
+        commands: &["msedge.exe"],
+        paths: &[
+            "%ProgramFiles(x86)%\\Microsoft\\Edge\\Application\\msedge.exe",
+            "%ProgramFiles%\\Microsoft\\Edge\\Application\\msedge.exe",
+        ],
+        registry: Some("msedge.exe"),
+    },
+    Candidate {
+        flavor: BrowserFlavor::Brave,
+        commands: &["brave.exe"],
+        paths: &[
+            "%ProgramFiles%\\BraveSoftware\\Brave-Browser\\Application\\brave.exe",
+            "%LOCALAPPDATA%\\BraveSoftware\\Brave-Browser\\Application\\brave.exe",
+        ],
+        registry: Some("brave.exe"),
+    },
+    Candidate {
+        flavor: BrowserFlavor::ChromeHeadlessShell,
+        commands: &["chrome-headless-shell.exe"],
+        paths: &[],
+        registry: None,
+    },
+];
+
+/// Candidates in order of preference
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+const CANDIDATES: &[Candidate] = &[
+    Candidate {
+        flavor: BrowserFlavor::Chrome,
+        commands: &["chrome", "google-chrome"],
+        paths: &[],
+        registry: None,
+    },
+    Candidate {
+        flavor: BrowserFlavor::Chromium,
+        commands: &["chromium", "chromium-browser"],
+        paths: &[],
+        registry: None,
+    },
+];
+
+/// Find installed Chromium-based browsers, most preferred first
+///
+/// Probes `PATH`, the usual install locations of the current OS (including
+/// snap and flatpak exports on Linux), and the `App Paths` registry key on
+/// Windows. Chrome stable comes first, followed by Chromium, the other
+/// Chrome channels, Edge, Brave and `chrome-headless-shell`. Each executable
+/// is listed once even if it is reachable through several locations.
+pub fn discover_executables() -> Vec<BrowserExecutable> {
+    discover(
+        CANDIDATES,
+        |var| std::env::var_os(var),
+        |path| path.is_file(),
+        registry_path,
+    )
+}
+
+/// Ranked discovery over explicit candidates and probes
+fn discover(
+    candidates: &[Candidate],
+    env: impl Fn(&str) -> Option<OsString>,
+    is_file: impl Fn(&Path) -> bool,
+    registry: impl Fn(&str) -> Option<PathBuf>,
+) -> Vec<BrowserExecutable> {
+    let dirs: Vec<PathBuf> = env("PATH")
+        .map(|path| std::env::split_paths(&path).collect())
+        .unwrap_or_default();
+    let mut seen = HashSet::new();
+    let mut found = Vec::new();
+
+    for candidate in candidates {
+        let on_path = candidate
+            .commands
+            .iter()
+            .flat_map(|command| dirs.iter().map(move |dir| dir.join(command)));
+        let installed = candidate.paths.iter().filter_map(|path| expand(path, &env));
+        let registered = candidate.registry.and_then(&registry);
+
+        for path in on_path.chain(installed).chain(registered) {
+            if !is_file(&path) {
+                continue;
+            }
+            // Symlinks such as /usr/bin/chromium -> /snap/bin/chromium count once
+            let key = std::fs::canonicalize(&path).unwrap_or_else(|_| path.clone());
+            if seen.insert(key) {
+                found.push(BrowserExecutable {
+                    path,
+                    flavor: candidate.flavor,
+                });
+            }
+        }
+    }
+    found
+}
+
+/// Expand a leading `~` or `%VAR%`, or `None` if the variable is not set
+fn expand(path: &str, env: impl Fn(&str) -> Option<OsString>) -> Option<PathBuf> {
+    if let Some(rest) = path.strip_prefix("~/") {
+        let home = env("HOME").or_else(|| env("USERPROFILE"))?;
+        return Some(PathBuf::from(home).join(rest));
+    }
+    if let Some(rest) = path.strip_prefix('%') {
+        let (var, rest) = rest.split_once('%')?;
+        let value = env(var)?;
+        return Some(PathBuf::from(format!(
+            "{}{}",
+            value.to_string_lossy(),
+            rest
+        )));
+    }
+    Some(PathBuf::from(path))
+}
+
+/// Executable registered under `App Paths` for `exe`
+#[cfg(target_os = "windows")]
+fn registry_path(exe: &str) -> Option<PathBuf> {
+    const KEY: &str = "SOFTWARE\\Microsoft\\Windows\\CurrentVersion\\App Paths";
+    ["HKLM", "HKCU"].iter().find_map(|hive| {
+        let output = std::process::Command::new("reg")
+            .args(["query", &format!("{}\\{}\\{}", hive, KEY, exe), "/ve"])
+            .output()
+            .ok()?;
+        parse_reg_default(&String::from_utf8_lossy(&output.stdout))
+    })
+}
+
+/// Executable registered under `App Paths` for `exe`
+#[cfg(not(target_os = "windows"))]
+fn registry_path(_exe: &str) -> Option<PathBuf> {
+    None
+}
+
+/// Default value in `reg query ... /ve` output
+#[cfg_attr(not(target_os = "windows"), allow(dead_code))]
+fn parse_reg_default(output: &str) -> Option<PathBuf> {
+    output.lines().find_map(|line| {
+        let (_, value) = line.split_once("REG_SZ")?;
+        let value = value.trim().trim_matches('"');
+        (!value.is_empty()).then(|| PathBuf::from(value))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEST_CANDIDATES: &[Candidate] = &[
+        Candidate {
+            flavor: BrowserFlavor::Chrome,
+            commands: &["google-chrome"],
+            paths: &["/opt/google/chrome/chrome"],
+            registry: Some("chrome.exe"),
+        },
+        Candidate {
+            flavor: BrowserFlavor::Chromium,
+            commands: &["chromium"],
+            paths: &["/snap/bin/chromium"],
+            registry: None,
+        },
+        Candidate {
+            flavor: BrowserFlavor::Brave,
+            commands: &["brave"],
+            paths: &[],
+            registry: None,
+        },
+    ];
+
+    fn search_path(dirs: &[&str]) -> impl Fn(&str) -> Option<OsString> {
+        let path = std::env::join_paths(dirs).unwrap();
+        move |var| (var == "PATH").then(|| path.clone())
+    }
+
+    #[test]
+    fn test_discover_ranks_by_candidate_order() {
+        let existing = [
+            "/snap/bin/chromium",
+            "/usr/bin/brave",
+            "/opt/google/chrome/chrome",
+        ];
+        let found = discover(
+            TEST_CANDIDATES,
+            search_path(&["/usr/local/bin", "/usr/bin"]),
+            |path| existing.iter().any(|e| Path::new(e) == path),
+            |_| None,
+        );
+        let flavors: Vec<_> = found.iter().map(|e| e.flavor).collect();
+        assert_eq!(
+            flavors,
+            [
+                BrowserFlavor::Chrome,
+                BrowserFlavor::Chromium,
+                BrowserFlavor::Brave
+            ]
+        );
+        assert_eq!(found[2].path, PathBuf::from("/usr/bin/brave"));
+    }
+
+    #[test]
+    fn test_discover_prefers_path_and_lists_each_location_once() {
+        let found = discover(
+            TEST_CANDIDATES,
+            search_path(&["/usr/bin", "/usr/bin"]),
+            |path| path == Path::new("/usr/bin/chromium"),
+            |_| None,
+        );
+        assert_eq!(
+            found,
+            [BrowserExecutable {
+                path: PathBuf::from("/usr/bin/chromium"),
+                flavor: BrowserFlavor::Chromium,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_channels_map_to_distinct_flavors() {
+        let channels = [
+            Channel::Stable,
+            Channel::Beta,
+            Channel::Dev,
+            Channel::Canary,
+            Channel::HeadlessShell,
+        ];
+        let flavors: HashSet<_> = channels.iter().map(|c| c.flavor()).collect();
+        assert_eq!(flavors.len(), channels.len());
+        assert_eq!(Channel::Stable.flavor(), BrowserFlavor::Chrome);
+        assert!(CANDIDATES
+            .iter()
+            .any(|c| c.flavor == Channel::HeadlessShell.flavor()));
+    }
+
+    #[test]
+    fn test_discover_uses_registry() {
+        let registered = PathBuf::from("/registered/chrome.exe");
+        let found = discover(
+            TEST_CANDIDATES,
+            |_| None,
+            |path| path == registered,
+            |exe| (exe == "chrome.exe").then(|| registered.clone()),
+        );
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].flavor, BrowserFlavor::Chrome);
+    }
+
+    #[test]
+    fn test_expand() {
+        let env = |var: &str| match var {
+            "LOCALAPPDATA" => Some(OsString::from("C:\\Apps")),
+            "HOME" => Some(OsString::from("/home/user")),
+            _ => None,
+        };
+        assert_eq!(
+            expand("/usr/bin/chromium", env),
+            Some(PathBuf::from("/usr/bin/chromium"))
+        );
+        assert_eq!(expand("%PROGRAMFILES%\\chrome.exe", env), None);
+        assert_eq!(
+            expand("%LOCALAPPDATA%\\chrome.exe", env),
+            Some(PathBuf::from("C:\\Apps\\chrome.exe"))
+        );
+        assert_eq!(
+            expand("~/.local/bin/chrome", env),
+            Some(PathBuf::from("/home/user/.local/bin/chrome"))
+        );
+    }
+
+    #[test]
+    fn test_parse_reg_default() {
+        let output = "\r\nHKEY_LOCAL_MACHINE\\SOFTWARE\\Microsoft\\Windows\\CurrentVersion\\App Paths\\chrome.exe\r\n    (Default)    REG_SZ    C:\\Program Files\\Google\\Chrome\\Application\\chrome.exe\r\n";
+        assert_eq!(
+            parse_reg_default(output),
+            Some(PathBuf::from(
+                "C:\\Program Files\\Google\\Chrome\\Application\\chrome.exe"
+            ))
+        );
+        assert_eq!(parse_reg_default("ERROR: not found"), None);
+    }
+}
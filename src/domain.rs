@@ -0,0 +1,158 @@
+//! CDP domains a page subscribes to
+
+use crate::{connection::CdpConnection, Error, Result};
+use serde_json::json;
+use std::collections::HashSet;
+use std::sync::Mutex;
+
+/// Protocol domain whose events must be enabled before they are reported
+///
+/// Enabling a domain has a cost: `Runtime` in particular reports every
+/// console call and execution context and can be detected by page scripts.
+/// Pages enable [`Domain::DEFAULTS`] unless created with
+/// [`CdpPage::new_with_domains`](crate::CdpPage::new_with_domains); other
+/// domains are enabled on first use by the methods that need them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Domain {
+    /// Lifecycle and frame events
+    Page,
+    /// Execution contexts, console calls and exceptions
+    Runtime,
+    /// Requests, responses and WebSocket frames
+    Network,
+    /// Browser-side log entries
+    Log,
+    /// Document updates
+    Dom,
+}
+
+impl Domain {
+    /// Domains enabled by [`CdpPage::new`](crate::CdpPage::new)
+    pub const DEFAULTS: &'static [Domain] = &[Domain::Page, Domain::Runtime];
+
+    /// Protocol name of the domain, e.g. `Runtime`
+    pub fn name(self) -> &'static str {
+        match self {
+            Domain::Page => "Page",
+            Domain::Runtime => "Runtime",
+            Domain::Network => "Network",
+            Domain::Log => "Log",
+            Domain::Dom => "DOM",
+        }
+    }
+
+    /// Domain reporting the given event method, e.g. `Network.requestWillBeSent`
+    pub fn of_event(method: &str) -> Option<Domain> {
+        let (name, _) = method.split_once('.')?;
+        [
+            Domain::Page,
+            Domain::Runtime,
+            Domain::Network,
+            Domain::Log,
+            Domain::Dom,
+        ]
+        .into_iter()
+        .find(|domain| domain.name() == name)
+    }
+}
+
+/// Domains currently enabled on a connection
+pub(crate) struct Domains {
+    connection: CdpConnection,
+    enabled: Mutex<HashSet<Domain>>,
+}
+
+impl Domains {
+    pub(crate) fn new(connection: CdpConnection) -> Self {
+        Self {
+            connection,
+            enabled: Mutex::new(HashSet::new()),
+        }
+    }
+
+    fn is_enabled(&self, domain: Domain) -> bool {
+        self.enabled
+            .lock()
+            .map(|enabled| enabled.contains(&domain))
+            .unwrap_or(false)
+    }
+
+    /// Enable the domain unless it already is
+    pub(crate) async fn enable(&self, domain: Domain) -> Result<()> {
+        if self.is_enabled(domain) {
+            return Ok(());
+        }
+        self.connection
+            .send_command(&format!("{}.enable", domain.name()), json!({}))
+            .await
+            .map_err(|e| {
+                Error::Browser(format!("Failed to enable {} domain: {}", domain.name(), e))
+            })?;
+        if let Ok(mut enabled) = self.enabled.lock() {
+            enabled.insert(domain);
+        }
+        Ok(())
+    }
+
+    /// Disable the domain if it is enabled
+    pub(crate) async fn disable(&self, domain: Domain) -> Result<()> {
+        if !self.is_enabled(domain) {
+            return Ok(());
+        }
+        self.connection
+            .send_command(&format!("{}.disable", domain.name()), json!({}))
+            .await
+            .map_err(|e| {
+                Error::Browser(format!("Failed to disable {} domain: {}", domain.name(), e))
+            })?;
+        if let Ok(mut enabled) = self.enabled.lock() {
+            enabled.remove(&domain);
+        }
+        Ok(())
+    }
+
+    /// Enabled domains, in no particular order
+    pub(crate) fn enabled(&self) -> Vec<Domain> {
+        self.enabled
+            .lock()
+            .map(|enabled| enabled.iter().copied().collect())
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connection::tests::{mock_server, server_recv, server_send};
+
+    #[test]
+    fn test_of_event() {
+        assert_eq!(
+            Domain::of_event("Network.requestWillBeSent"),
+            Some(Domain::Network)
+        );
+        assert_eq!(Domain::of_event("DOM.documentUpdated"), Some(Domain::Dom));
+        assert_eq!(Domain::of_event("Fetch.requestPaused"), None);
+        assert_eq!(Domain::of_event("nodot"), None);
+    }
+
+    #[tokio::test]
+    async fn test_enable_is_sent_once() {
+        let url = mock_server(|mut ws| async move {
+            for expected in ["Network.enable", "Network.disable"] {
+                let request = server_recv(&mut ws).await;
+                assert_eq!(request["method"], expected);
+                server_send(&mut ws, json!({"id": request["id"], "result": {}})).await;
+            }
+        })
+        .await;
+        let domains = Domains::new(CdpConnection::connect(&url).await.unwrap());
+
+        domains.enable(Domain::Network).await.unwrap();
+        domains.enable(Domain::Network).await.unwrap();
+        assert_eq!(domains.enabled(), [Domain::Network]);
+        domains.disable(Domain::Network).await.unwrap();
+        domains.disable(Domain::Network).await.unwrap();
+        assert!(domains.enabled().is_empty());
+    }
+}
@@ -0,0 +1,60 @@
+//! Automation shared by the CDP and WebDriver BiDi backends
+
+use crate::{CdpPage, Result};
+use futures::future::BoxFuture;
+use serde_json::Value;
+
+/// Page operations every backend supports
+///
+/// Write automation against this trait to run it on Chrome through
+/// [`CdpPage`] and, with the experimental `bidi` feature, on Firefox through
+/// `BidiPage`. Backend-specific features stay on the concrete types.
+///
+/// ```
+/// use chrome_cdp::{PageDriver, Result};
+///
+/// async fn heading(page: &dyn PageDriver, url: &str) -> Result<String> {
+///     page.goto(url).await?;
+///     page.wait_for_selector("h1").await?;
+///     let text = page.evaluate("document.querySelector('h1').textContent").await?;
+///     Ok(text.as_str().unwrap_or_default().to_string())
+/// }
+/// ```
+pub trait PageDriver: Send + Sync {
+    /// Start navigating to `url`, without waiting for the page to load
+    fn goto<'a>(&'a self, url: &'a str) -> BoxFuture<'a, Result<()>>;
+
+    /// Wait until an element matching the selector exists, bounded by the wait timeout
+    fn wait_for_selector<'a>(&'a self, selector: &'a str) -> BoxFuture<'a, Result<()>>;
+
+    /// Evaluate an expression, awaiting promises, and return its value as JSON
+    fn evaluate<'a>(&'a self, expression: &'a str) -> BoxFuture<'a, Result<Value>>;
+
+    /// Capture a PNG screenshot of the viewport
+    fn screenshot(&self) -> BoxFuture<'_, Result<Vec<u8>>>;
+
+    /// Close the page
+    fn close(&self) -> BoxFuture<'_, Result<()>>;
+}
+
+impl PageDriver for CdpPage {
+    fn goto<'a>(&'a self, url: &'a str) -> BoxFuture<'a, Result<()>> {
+        Box::pin(CdpPage::goto(self, url))
+    }
+
+    fn wait_for_selector<'a>(&'a self, selector: &'a str) -> BoxFuture<'a, Result<()>> {
+        Box::pin(CdpPage::wait_for_selector(self, selector))
+    }
+
+    fn evaluate<'a>(&'a self, expression: &'a str) -> BoxFuture<'a, Result<Value>> {
+        Box::pin(CdpPage::evaluate(self, expression))
+    }
+
+    fn screenshot(&self) -> BoxFuture<'_, Result<Vec<u8>>> {
+        Box::pin(CdpPage::screenshot(self))
+    }
+
+    fn close(&self) -> BoxFuture<'_, Result<()>> {
+        Box::pin(CdpPage::close(self))
+    }
+}
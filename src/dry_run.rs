@@ -0,0 +1,202 @@
+//! Auditing commands without changing browser state
+
+use crate::middleware::Command;
+use serde_json::Value;
+use std::collections::HashSet;
+
+/// Placeholder replacing redacted parameter values
+const REDACTED: &str = "[redacted]";
+
+/// Parameter names whose values are redacted by default, matched case-insensitively as substrings
+const SENSITIVE_KEYS: &[&str] = &["password", "token", "secret", "authorization", "cookie"];
+
+/// Method verbs, e.g. `get` in `DOM.getDocument`, of commands that only read state
+const READ_ONLY_VERBS: &[&str] = &[
+    "get", "query", "describe", "resolve", "capture", "enable", "disable",
+];
+
+/// Commands releasing a request the browser already paused, which would hang if skipped
+const PAUSED_REQUEST_METHODS: &[&str] = &[
+    "Fetch.continueRequest",
+    "Fetch.continueResponse",
+    "Fetch.continueWithAuth",
+    "Fetch.failRequest",
+    "Fetch.fulfillRequest",
+];
+
+/// Command seen by a connection in dry-run mode
+#[derive(Debug, Clone, PartialEq)]
+pub struct DryRunCommand {
+    /// Method name, e.g. `Input.dispatchMouseEvent`
+    pub method: String,
+    /// Parameters with sensitive values replaced by `[redacted]`
+    pub params: Value,
+    /// Flattened target session the command was addressed to
+    pub session_id: Option<String>,
+    /// Whether the command was sent; state-changing commands are only reported
+    pub dispatched: bool,
+}
+
+/// Dry-run mode for [`CdpConnection::set_dry_run`](crate::CdpConnection::set_dry_run)
+///
+/// Read-only commands such as `DOM.getDocument` or `Page.captureScreenshot`
+/// still reach the browser so flows can look up what they act on, as do the
+/// `Fetch` commands resolving paused requests, so interception keeps working;
+/// everything else is reported and answered with an empty result. Code that needs the
+/// result of a skipped command, e.g. the value of `Runtime.evaluate`, sees
+/// `null` fields.
+///
+/// ```
+/// use chrome_cdp::DryRun;
+/// use std::sync::{Arc, Mutex};
+///
+/// let plan = Arc::new(Mutex::new(Vec::new()));
+/// let dry_run = DryRun::with_reporter({
+///     let plan = plan.clone();
+///     move |command| plan.lock().unwrap().push(command.clone())
+/// })
+/// .redact("text")
+/// .allow("Runtime.evaluate");
+/// ```
+pub struct DryRun {
+    report: Box<dyn Fn(&DryRunCommand) + Send + Sync>,
+    redacted: Vec<String>,
+    allowed: HashSet<String>,
+}
+
+impl DryRun {
+    /// Log commands as `tracing` events
+    #[cfg(feature = "tracing")]
+    pub fn new() -> Self {
+        Self::with_reporter(|command| {
+            tracing::info!(
+                method = %command.method,
+                params = %command.params,
+                session_id = command.session_id.as_deref().unwrap_or_default(),
+                dispatched = command.dispatched,
+                "dry-run CDP command"
+            );
+        })
+    }
+
+    /// Pass every command to `report`
+    pub fn with_reporter<F>(report: F) -> Self
+    where
+        F: Fn(&DryRunCommand) + Send + Sync + 'static,
+    {
+        Self {
+            report: Box::new(report),
+            redacted: SENSITIVE_KEYS.iter().map(|key| key.to_string()).collect(),
+            allowed: HashSet::new(),
+        }
+    }
+
+    /// Also redact parameters whose name contains `key`, e.g. `text` for typed input
+    pub fn redact(mut self, key: &str) -> Self {
+        self.redacted.push(key.to_lowercase());
+        self
+    }
+
+    /// Send `method` even though it may change state
+    pub fn allow(mut self, method: &str) -> Self {
+        self.allowed.insert(method.to_string());
+        self
+    }
+
+    /// Report a command, returning whether to send it
+    pub(crate) fn check(&self, command: &Command) -> bool {
+        let dispatched = self.allowed.contains(&command.method)
+            || is_read_only(&command.method)
+            || PAUSED_REQUEST_METHODS.contains(&command.method.as_str());
+        (self.report)(&DryRunCommand {
+            method: command.method.clone(),
+            params: self.redact_value(&command.params),
+            session_id: command.session_id.clone(),
+            dispatched,
+        });
+        dispatched
+    }
+
+    /// Copy of `value` with the values of sensitive keys replaced
+    fn redact_value(&self, value: &Value) -> Value {
+        match value {
+            Value::Object(map) => map
+                .iter()
+                .map(|(key, value)| {
+                    let key_lower = key.to_lowercase();
+                    let value = if self.redacted.iter().any(|k| key_lower.contains(k.as_str())) {
+                        Value::String(REDACTED.to_string())
+                    } else {
+                        self.redact_value(value)
+                    };
+                    (key.clone(), value)
+                })
+                .collect(),
+            Value::Array(items) => items.iter().map(|item| self.redact_value(item)).collect(),
+            other => other.clone(),
+        }
+    }
+}
+
+#[cfg(feature = "tracing")]
+impl Default for DryRun {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl std::fmt::Debug for DryRun {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DryRun")
+            .field("redacted", &self.redacted)
+            .field("allowed", &self.allowed)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Whether a method only reads browser state
+fn is_read_only(method: &str) -> bool {
+    let verb = method.rsplit('.').next().unwrap_or(method);
+    READ_ONLY_VERBS.iter().any(|prefix| {
+        verb.strip_prefix(prefix)
+            .is_some_and(|rest| rest.chars().next().is_none_or(char::is_uppercase))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_is_read_only() {
+        assert!(is_read_only("DOM.getDocument"));
+        assert!(is_read_only("Page.captureScreenshot"));
+        assert!(is_read_only("Network.enable"));
+        assert!(!is_read_only("Page.navigate"));
+        assert!(!is_read_only("Runtime.evaluate"));
+        assert!(!is_read_only("Input.dispatchKeyEvent"));
+        // A verb merely starting like a read-only one
+        assert!(!is_read_only("Storage.getaway"));
+    }
+
+    #[test]
+    fn test_redacts_nested_sensitive_values() {
+        let dry_run = DryRun::with_reporter(|_| {}).redact("text");
+        let params = json!({
+            "authChallengeResponse": {"response": "ProvideCredentials", "username": "u", "password": "p"},
+            "headers": {"Authorization": "Bearer x", "Accept": "*/*"},
+            "cookies": [{"name": "sid", "value": "1"}],
+            "text": "hunter2",
+        });
+        assert_eq!(
+            dry_run.redact_value(&params),
+            json!({
+                "authChallengeResponse": {"response": "ProvideCredentials", "username": "u", "password": REDACTED},
+                "headers": {"Authorization": REDACTED, "Accept": "*/*"},
+                "cookies": REDACTED,
+                "text": REDACTED,
+            })
+        );
+    }
+}
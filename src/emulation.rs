@@ -0,0 +1,115 @@
+//! Bookkeeping of emulation overrides active on a page
+
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+/// Kind of override that can be active on a page
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum Override {
+    DeviceMetrics,
+    UserAgent,
+    Geolocation,
+    Timezone,
+    Locale,
+    Media,
+    AutoDarkMode,
+    CpuThrottling,
+    NetworkConditions,
+}
+
+impl Override {
+    /// Command removing the override
+    fn clear_command(self) -> (&'static str, Value) {
+        match self {
+            Self::DeviceMetrics => ("Emulation.clearDeviceMetricsOverride", json!({})),
+            Self::UserAgent => ("Emulation.setUserAgentOverride", json!({ "userAgent": "" })),
+            Self::Geolocation => ("Emulation.clearGeolocationOverride", json!({})),
+            Self::Timezone => ("Emulation.setTimezoneOverride", json!({ "timezoneId": "" })),
+            Self::Locale => ("Emulation.setLocaleOverride", json!({})),
+            Self::Media => (
+                "Emulation.setEmulatedMedia",
+                json!({ "media": "", "features": [] }),
+            ),
+            Self::AutoDarkMode => ("Emulation.setAutoDarkModeOverride", json!({})),
+            Self::CpuThrottling => ("Emulation.setCPUThrottlingRate", json!({ "rate": 1 })),
+            Self::NetworkConditions => (
+                "Network.emulateNetworkConditions",
+                json!({
+                    "offline": false,
+                    "latency": 0,
+                    "downloadThroughput": -1,
+                    "uploadThroughput": -1
+                }),
+            ),
+        }
+    }
+}
+
+/// Overrides set on a page, with the parameters they were set with
+#[derive(Default)]
+pub(crate) struct Emulation {
+    active: Mutex<BTreeMap<Override, Value>>,
+}
+
+impl Emulation {
+    /// Remember that an override was set
+    pub(crate) fn record(&self, kind: Override, params: Value) {
+        self.active.lock().unwrap().insert(kind, params);
+    }
+
+    /// Forget an override that was cleared
+    pub(crate) fn forget(&self, kind: Override) {
+        self.active.lock().unwrap().remove(&kind);
+    }
+
+    /// Parameters an override was set with, if it is active
+    pub(crate) fn get(&self, kind: Override) -> Option<Value> {
+        self.active.lock().unwrap().get(&kind).cloned()
+    }
+
+    /// Forget every override, returning the commands that clear them
+    pub(crate) fn take_clear_commands(&self) -> Vec<(&'static str, Value)> {
+        std::mem::take(&mut *self.active.lock().unwrap())
+            .into_keys()
+            .map(Override::clear_command)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clear_commands_cover_recorded_overrides() {
+        let emulation = Emulation::default();
+        emulation.record(Override::Timezone, json!({ "timezoneId": "Asia/Tokyo" }));
+        emulation.record(Override::DeviceMetrics, json!({ "width": 800 }));
+        emulation.record(Override::CpuThrottling, json!({ "rate": 4 }));
+        emulation.forget(Override::CpuThrottling);
+
+        let commands = emulation.take_clear_commands();
+        let methods: Vec<_> = commands.iter().map(|(method, _)| *method).collect();
+        assert_eq!(
+            methods,
+            [
+                "Emulation.clearDeviceMetricsOverride",
+                "Emulation.setTimezoneOverride"
+            ]
+        );
+        assert_eq!(commands[1].1["timezoneId"], "");
+        assert!(emulation.take_clear_commands().is_empty());
+    }
+
+    #[test]
+    fn test_get_returns_recorded_params() {
+        let emulation = Emulation::default();
+        assert!(emulation.get(Override::DeviceMetrics).is_none());
+        emulation.record(Override::DeviceMetrics, json!({ "width": 800 }));
+        assert_eq!(
+            emulation.get(Override::DeviceMetrics).unwrap()["width"],
+            800
+        );
+    }
+}
@@ -0,0 +1,192 @@
+//! Self-test of the browser environment as seen by page scripts
+
+use serde::{Deserialize, Serialize};
+
+/// Expression collecting what fingerprinting scripts typically look at
+pub(crate) const ENVIRONMENT_EXPR: &str = r#"(() => {
+  const timezone = Intl.DateTimeFormat().resolvedOptions().timeZone;
+  const now = new Date();
+  const wall = (zone) => new Date(now.toLocaleString("en-US", { timeZone: zone }));
+  let webglVendor = null, webglRenderer = null;
+  try {
+    const gl = document.createElement("canvas").getContext("webgl");
+    const info = gl && gl.getExtension("WEBGL_debug_renderer_info");
+    if (info) {
+      webglVendor = gl.getParameter(info.UNMASKED_VENDOR_WEBGL);
+      webglRenderer = gl.getParameter(info.UNMASKED_RENDERER_WEBGL);
+    }
+  } catch (e) {}
+  return {
+    webdriver: navigator.webdriver === true,
+    plugins: navigator.plugins ? navigator.plugins.length : 0,
+    userAgent: navigator.userAgent,
+    brands: (navigator.userAgentData?.brands || []).map((b) => b.brand),
+    language: navigator.language,
+    languages: Array.from(navigator.languages || []),
+    locale: Intl.DateTimeFormat().resolvedOptions().locale,
+    timezone,
+    timezoneOffset: -now.getTimezoneOffset(),
+    intlTimezoneOffset: Math.round((wall(timezone) - wall("UTC")) / 60000),
+    webglVendor,
+    webglRenderer,
+    outerWidth: window.outerWidth,
+    outerHeight: window.outerHeight,
+  };
+})()"#;
+
+/// Renderers used when there is no GPU, a common headless giveaway
+const SOFTWARE_RENDERERS: [&str; 3] = ["SwiftShader", "llvmpipe", "Software"];
+
+/// Environment of a page as seen by its scripts, from [`CdpPage::environment_report`](crate::CdpPage::environment_report)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EnvironmentReport {
+    /// `navigator.webdriver`
+    pub webdriver: bool,
+    /// Number of entries in `navigator.plugins`
+    pub plugins: u32,
+    /// `navigator.userAgent`
+    pub user_agent: String,
+    /// Brands reported by `navigator.userAgentData`
+    pub brands: Vec<String>,
+    /// `navigator.language`
+    pub language: String,
+    /// `navigator.languages`
+    pub languages: Vec<String>,
+    /// Locale resolved by `Intl`
+    pub locale: String,
+    /// IANA timezone resolved by `Intl`
+    pub timezone: String,
+    /// UTC offset in minutes according to `Date`
+    pub timezone_offset: i32,
+    /// UTC offset in minutes of [`timezone`](Self::timezone) according to `Intl`
+    pub intl_timezone_offset: i32,
+    /// Unmasked WebGL vendor, if WebGL is available
+    pub webgl_vendor: Option<String>,
+    /// Unmasked WebGL renderer, if WebGL is available
+    pub webgl_renderer: Option<String>,
+    /// `window.outerWidth`
+    pub outer_width: u32,
+    /// `window.outerHeight`
+    pub outer_height: u32,
+}
+
+impl EnvironmentReport {
+    /// Findings that make the browser stand out as automated or inconsistent
+    pub fn issues(&self) -> Vec<String> {
+        let mut issues = Vec::new();
+        if self.webdriver {
+            issues.push("navigator.webdriver is true".to_string());
+        }
+        if self.plugins == 0 {
+            issues.push("navigator.plugins is empty".to_string());
+        }
+        if self.user_agent.contains("HeadlessChrome") {
+            issues.push("user agent contains HeadlessChrome".to_string());
+        }
+        if self.brands.iter().any(|brand| brand.contains("Headless")) {
+            issues.push("userAgentData brands mention Headless".to_string());
+        }
+        match &self.webgl_renderer {
+            None => issues.push("WebGL is unavailable".to_string()),
+            Some(renderer) if SOFTWARE_RENDERERS.iter().any(|r| renderer.contains(r)) => {
+                issues.push(format!("WebGL uses a software renderer ({})", renderer))
+            }
+            Some(_) => {}
+        }
+        if self.languages.first() != Some(&self.language) {
+            issues.push(format!(
+                "navigator.language {} is not first in navigator.languages {:?}",
+                self.language, self.languages
+            ));
+        }
+        if primary_subtag(&self.locale) != primary_subtag(&self.language) {
+            issues.push(format!(
+                "Intl locale {} does not match navigator.language {}",
+                self.locale, self.language
+            ));
+        }
+        if self.timezone_offset != self.intl_timezone_offset {
+            issues.push(format!(
+                "Date offset {} min does not match {} offset {} min",
+                self.timezone_offset, self.timezone, self.intl_timezone_offset
+            ));
+        }
+        if self.outer_width == 0 || self.outer_height == 0 {
+            issues.push("window has no outer size".to_string());
+        }
+        issues
+    }
+
+    /// Whether no issue was found
+    pub fn is_clean(&self) -> bool {
+        self.issues().is_empty()
+    }
+}
+
+/// Language part of a BCP 47 tag, e.g. `de` for `de-DE`
+fn primary_subtag(tag: &str) -> String {
+    tag.split(['-', '_'])
+        .next()
+        .unwrap_or_default()
+        .to_ascii_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn desktop() -> EnvironmentReport {
+        serde_json::from_value(json!({
+            "webdriver": false,
+            "plugins": 5,
+            "userAgent": "Mozilla/5.0 (X11; Linux x86_64) Chrome/120.0.0.0 Safari/537.36",
+            "brands": ["Chromium", "Google Chrome"],
+            "language": "de-DE",
+            "languages": ["de-DE", "de", "en"],
+            "locale": "de-DE",
+            "timezone": "Europe/Berlin",
+            "timezoneOffset": 60,
+            "intlTimezoneOffset": 60,
+            "webglVendor": "Intel",
+            "webglRenderer": "Mesa Intel(R) UHD Graphics 620",
+            "outerWidth": 1280,
+            "outerHeight": 800
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_consistent_desktop_is_clean() {
+        assert!(desktop().is_clean(), "{:?}", desktop().issues());
+    }
+
+    #[test]
+    fn test_headless_markers_are_reported() {
+        let report = EnvironmentReport {
+            webdriver: true,
+            plugins: 0,
+            user_agent: "Mozilla/5.0 HeadlessChrome/120.0.0.0".to_string(),
+            webgl_renderer: Some("Google SwiftShader".to_string()),
+            outer_width: 0,
+            ..desktop()
+        };
+        let issues = report.issues();
+        assert_eq!(issues.len(), 5, "{:?}", issues);
+        assert!(issues[3].contains("SwiftShader"));
+    }
+
+    #[test]
+    fn test_locale_and_timezone_mismatches_are_reported() {
+        let report = EnvironmentReport {
+            locale: "en-US".to_string(),
+            intl_timezone_offset: -300,
+            ..desktop()
+        };
+        let issues = report.issues();
+        assert_eq!(issues.len(), 2, "{:?}", issues);
+        assert!(issues[0].starts_with("Intl locale en-US"));
+        assert!(issues[1].contains("Europe/Berlin"));
+    }
+}
@@ -1,5 +1,11 @@
 //! Error types for chrome-cdp
 
+use crate::FailureArtifacts;
+use serde_json::Value;
+use std::fmt;
+use std::path::PathBuf;
+use std::time::Duration;
+
 /// Error type for CDP operations
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
@@ -11,6 +17,10 @@ pub enum Error {
     #[error("CDP error: {0}")]
     Cdp(String),
 
+    /// Error response returned by the browser for a command
+    #[error("CDP error: {0}")]
+    Protocol(CdpError),
+
     /// I/O errors
     #[error("I/O error: {0}")]
     Io(#[from] std::io::Error),
@@ -26,6 +36,274 @@ pub enum Error {
     /// WebSocket errors
     #[error("WebSocket error: {0}")]
     WebSocket(String),
+
+    /// Operation did not complete in time
+    #[error("Timeout: {0}")]
+    Timeout(String),
+
+    /// The target's renderer crashed; the connection no longer accepts commands
+    #[error("Target crashed")]
+    TargetCrashed,
+
+    /// The browser detached the debugger from the target
+    #[error("Target detached: {0}")]
+    TargetDetached(String),
+
+    /// The browser process exited after it launched
+    ///
+    /// Exits before the browser reports its endpoint are
+    /// [`Error::LaunchFailed`] instead.
+    #[error("Browser exited with {status}; last stderr lines:\n{stderr}")]
+    BrowserExited {
+        /// Exit status of the process
+        status: String,
+        /// Last lines the process wrote to stderr
+        stderr: String,
+    },
+
+    /// An operation failed; `source` is the error that caused it
+    ///
+    /// Keeps the typed cause, e.g. [`Error::TargetCrashed`], reachable
+    /// through [`std::error::Error::source`].
+    #[error("{context}: {source}")]
+    Context {
+        /// What was being done, e.g. "Failed to navigate to 'https://example.com'"
+        context: String,
+        /// Error that made it fail
+        #[source]
+        source: Box<Error>,
+    },
+
+    /// The browser exited or did not report its endpoint within the launch timeout
+    ///
+    /// [`LaunchFailure::exit_status`] tells the two apart.
+    #[error("Failed to launch browser:\n{0}")]
+    LaunchFailed(Box<LaunchFailure>),
+
+    /// A page operation failed while failure capture was enabled
+    ///
+    /// The artifacts describe the page at the time; `error` is what the
+    /// operation returned.
+    #[error("{error} ({artifacts})")]
+    WithArtifacts {
+        /// Error of the operation
+        #[source]
+        error: Box<Error>,
+        /// Screenshot, report and console output saved for the failure
+        artifacts: Box<FailureArtifacts>,
+    },
+
+    /// Incoming message exceeded the configured size limit
+    #[error("Message too large: {size} bytes exceeds the limit of {max} bytes")]
+    MessageTooLarge {
+        /// Size of the rejected message
+        size: usize,
+        /// Configured maximum size
+        max: usize,
+    },
+}
+
+impl Error {
+    /// Wrap the error with a description of the operation that failed
+    pub(crate) fn context(self, context: impl Into<String>) -> Self {
+        Self::Context {
+            context: context.into(),
+            source: Box::new(self),
+        }
+    }
+
+    /// Artifacts saved for the failure, if failure capture was enabled
+    pub fn artifacts(&self) -> Option<&FailureArtifacts> {
+        match self {
+            Self::WithArtifacts { artifacts, .. } => Some(artifacts),
+            _ => None,
+        }
+    }
+
+    /// The error that started it all, without context or failure artifacts
+    ///
+    /// Unwraps [`Error::Context`] and [`Error::WithArtifacts`], e.g. to
+    /// match a navigation that failed with [`Error::TargetCrashed`].
+    pub fn root(&self) -> &Error {
+        match self {
+            Self::Context { source, .. } => source.root(),
+            Self::WithArtifacts { error, .. } => error.root(),
+            _ => self,
+        }
+    }
+}
+
+/// Well-known JSON-RPC error codes used by CDP
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CdpErrorCode {
+    /// Invalid JSON was received (-32700)
+    ParseError,
+    /// The message is not a valid request (-32600)
+    InvalidRequest,
+    /// The method does not exist in this browser (-32601)
+    MethodNotFound,
+    /// Invalid method parameters (-32602)
+    InvalidParams,
+    /// Internal protocol error (-32603)
+    InternalError,
+    /// Generic command failure, e.g. a missing node or closed target (-32000)
+    ServerError,
+    /// Any other code
+    Other(i64),
+}
+
+impl From<i64> for CdpErrorCode {
+    fn from(code: i64) -> Self {
+        match code {
+            -32700 => Self::ParseError,
+            -32600 => Self::InvalidRequest,
+            -32601 => Self::MethodNotFound,
+            -32602 => Self::InvalidParams,
+            -32603 => Self::InternalError,
+            -32000 => Self::ServerError,
+            other => Self::Other(other),
+        }
+    }
+}
+
+impl CdpErrorCode {
+    /// Numeric JSON-RPC code
+    pub fn code(self) -> i64 {
+        match self {
+            Self::ParseError => -32700,
+            Self::InvalidRequest => -32600,
+            Self::MethodNotFound => -32601,
+            Self::InvalidParams => -32602,
+            Self::InternalError => -32603,
+            Self::ServerError => -32000,
+            Self::Other(code) => code,
+        }
+    }
+}
+
+/// Structured error response of a CDP command
+#[derive(Debug, Clone, PartialEq)]
+pub struct CdpError {
+    /// Error code
+    pub code: CdpErrorCode,
+    /// Human-readable message
+    pub message: String,
+    /// Additional details, if the browser sent any
+    pub data: Option<Value>,
+}
+
+impl CdpError {
+    /// Parse the `error` object of a CDP response
+    pub fn from_response(error: &Value) -> Self {
+        Self {
+            code: error["code"].as_i64().unwrap_or(-1).into(),
+            message: error["message"].as_str().unwrap_or("unknown").to_string(),
+            data: error.get("data").cloned(),
+        }
+    }
+
+    /// Whether the command failed because its target or session is gone
+    pub fn is_target_closed(&self) -> bool {
+        [
+            "Target closed",
+            "No target with given id",
+            "Session with given id not found",
+            "No session with given id",
+        ]
+        .iter()
+        .any(|m| self.message.contains(m))
+    }
+
+    /// Whether the browser does not offer the method, or refuses it to this client
+    ///
+    /// Hosted browsers and some embedders reject methods such as
+    /// `Target.createTarget` this way.
+    pub fn is_method_unavailable(&self) -> bool {
+        self.code == CdpErrorCode::MethodNotFound
+            || [
+                "Not allowed",
+                "not allowed",
+                "not supported",
+                "wasn't found",
+            ]
+            .iter()
+            .any(|m| self.message.contains(m))
+    }
+
+    /// Whether the command referred to a DOM node that no longer exists
+    pub fn is_node_not_found(&self) -> bool {
+        [
+            "Could not find node with given id",
+            "No node with given id found",
+            "Node with given id does not belong to the document",
+            "No node found for given backend id",
+        ]
+        .iter()
+        .any(|m| self.message.contains(m))
+    }
+}
+
+impl fmt::Display for CdpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", self.message, self.code.code())?;
+        match &self.data {
+            Some(Value::String(data)) => write!(f, ": {}", data),
+            Some(data) => write!(f, ": {}", data),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Diagnostics of a browser that failed to start
+#[derive(Debug, Clone, PartialEq)]
+pub struct LaunchFailure {
+    /// Executable that was launched
+    pub executable: PathBuf,
+    /// Profile directory of the launch, kept for inspection; remove it when done
+    pub user_data_dir: PathBuf,
+    /// Operating system, architecture and family, e.g. `linux x86_64 (unix)`
+    pub os: String,
+    /// Last lines Chrome wrote to stderr
+    pub stderr_tail: Vec<String>,
+    /// Exit status if the process exited before reporting its port
+    pub exit_status: Option<String>,
+    /// Launch timeout that applied
+    pub timeout: Duration,
+}
+
+impl fmt::Display for LaunchFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "=== Chrome Browser Launch Failure ===\n\
+             OS: {}\n\
+             Chrome Executable: {:?}\n\
+             User Data Dir: {:?}\n\
+             === Chrome stderr ===\n{}\n\
+             === End of stderr ===",
+            self.os,
+            self.executable,
+            self.user_data_dir,
+            self.stderr_tail.join("\n")
+        )?;
+        match &self.exit_status {
+            Some(status) => write!(
+                f,
+                "\n\nChrome process exited early with status: {}",
+                status
+            ),
+            None => write!(
+                f,
+                "\n\nChrome process is still running but debugging port was not found after {:?}.\n\n\
+                 Troubleshooting:\n\
+                 - If running in CI, ensure Chrome/Chromium is installed\n\
+                 - Try setting CHROME_BIN environment variable\n\
+                 - For Linux CI, add --no-sandbox flag\n\
+                 - On slow machines, raise the launch timeout",
+                self.timeout
+            ),
+        }
+    }
 }
 
 /// Result type for CDP operations
@@ -42,6 +320,39 @@ mod tests {
         assert_eq!(err.to_string(), "Browser error: test error");
     }
 
+    #[test]
+    fn test_launch_failure_message() {
+        let mut failure = LaunchFailure {
+            executable: PathBuf::from("/usr/bin/chromium"),
+            user_data_dir: PathBuf::from("/tmp/chrome-1"),
+            os: "linux x86_64 (unix)".to_string(),
+            stderr_tail: vec!["[ERROR] no display".to_string()],
+            exit_status: None,
+            timeout: Duration::from_secs(5),
+        };
+        let message = Error::LaunchFailed(Box::new(failure.clone())).to_string();
+        assert!(
+            message.starts_with("Failed to launch browser:\n=== Chrome Browser Launch Failure ===")
+        );
+        assert!(message.contains("[ERROR] no display"));
+        assert!(message.contains("not found after 5s"));
+
+        failure.exit_status = Some("exit status: 1".to_string());
+        assert!(failure
+            .to_string()
+            .contains("exited early with status: exit status: 1"));
+    }
+
+    #[test]
+    fn test_root_unwraps_context_and_artifacts() {
+        let err = Error::TargetCrashed
+            .context("Failed to navigate to 'https://example.com'")
+            .context("Failed to load page");
+        assert!(matches!(err.root(), Error::TargetCrashed));
+        let timeout = Error::Timeout("No element matched '#save'".to_string());
+        assert!(matches!(timeout.root(), Error::Timeout(_)));
+    }
+
     #[test]
     fn test_error_cdp_creation() {
         let err = Error::Cdp("protocol error".to_string());
@@ -60,6 +371,79 @@ mod tests {
         assert_eq!(err.to_string(), "WebSocket error: handshake failed");
     }
 
+    #[test]
+    fn test_error_timeout_creation() {
+        let err = Error::Timeout("event not received".to_string());
+        assert_eq!(err.to_string(), "Timeout: event not received");
+    }
+
+    #[test]
+    fn test_error_message_too_large_creation() {
+        let err = Error::MessageTooLarge { size: 100, max: 10 };
+        assert_eq!(
+            err.to_string(),
+            "Message too large: 100 bytes exceeds the limit of 10 bytes"
+        );
+    }
+
+    #[test]
+    fn test_cdp_error_code_roundtrip() {
+        for code in [-32700, -32600, -32601, -32602, -32603, -32000, -31999] {
+            assert_eq!(CdpErrorCode::from(code).code(), code);
+        }
+        assert_eq!(CdpErrorCode::from(-32601), CdpErrorCode::MethodNotFound);
+        assert_eq!(CdpErrorCode::from(7), CdpErrorCode::Other(7));
+    }
+
+    #[test]
+    fn test_cdp_error_from_response() {
+        let error = CdpError::from_response(&serde_json::json!({
+            "code": -32000,
+            "message": "Could not find node with given id",
+            "data": "nodeId 42"
+        }));
+        assert_eq!(error.code, CdpErrorCode::ServerError);
+        assert!(error.is_node_not_found());
+        assert!(!error.is_target_closed());
+        assert_eq!(
+            Error::Protocol(error).to_string(),
+            "CDP error: Could not find node with given id (-32000): nodeId 42"
+        );
+    }
+
+    #[test]
+    fn test_cdp_error_target_closed() {
+        let error = CdpError::from_response(&serde_json::json!({
+            "code": -32000,
+            "message": "Session with given id not found."
+        }));
+        assert!(error.is_target_closed());
+        assert_eq!(
+            error.to_string(),
+            "Session with given id not found. (-32000)"
+        );
+    }
+
+    #[test]
+    fn test_cdp_error_method_unavailable() {
+        let unavailable = |code: i64, message: &str| {
+            CdpError::from_response(&serde_json::json!({"code": code, "message": message}))
+                .is_method_unavailable()
+        };
+        assert!(unavailable(-32601, "'Target.createTarget' wasn't found"));
+        assert!(unavailable(-32000, "Not allowed"));
+        assert!(!unavailable(-32000, "Target closed"));
+    }
+
+    #[test]
+    fn test_error_target_failures() {
+        assert_eq!(Error::TargetCrashed.to_string(), "Target crashed");
+        assert_eq!(
+            Error::TargetDetached("target_closed".to_string()).to_string(),
+            "Target detached: target_closed"
+        );
+    }
+
     #[test]
     fn test_error_io_conversion() {
         let io_err = io::Error::new(io::ErrorKind::NotFound, "file not found");
@@ -0,0 +1,225 @@
+//! Page state saved when a high-level operation fails
+
+use crate::trace::console_line;
+use crate::{Error, EventStream};
+use std::collections::VecDeque;
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use tokio::task::JoinHandle;
+
+/// Where to save failure artifacts, for [`CdpPage::set_failure_capture`](crate::CdpPage::set_failure_capture)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FailureCapture {
+    /// Directory receiving a screenshot and a report per failure; created if missing
+    pub dir: PathBuf,
+    /// Number of most recent console lines kept for the report
+    pub console_lines: usize,
+}
+
+impl FailureCapture {
+    /// Save artifacts to `dir`, keeping the last 50 console lines
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            console_lines: 50,
+        }
+    }
+}
+
+/// State of a page when one of its operations failed, attached to [`Error::WithArtifacts`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct FailureArtifacts {
+    /// Operation that failed, e.g. `goto` or `wait_for_selector`
+    pub operation: String,
+    /// URL of the main frame at the time
+    pub url: Option<String>,
+    /// PNG of the viewport, unless capturing it failed too
+    pub screenshot: Option<PathBuf>,
+    /// Text report with the error, URL and recent console output
+    pub report: PathBuf,
+    /// Recent console messages and uncaught exceptions, oldest first
+    pub console: Vec<String>,
+}
+
+impl fmt::Display for FailureArtifacts {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "report: {}", self.report.display())?;
+        if let Some(screenshot) = &self.screenshot {
+            write!(f, ", screenshot: {}", screenshot.display())?;
+        }
+        Ok(())
+    }
+}
+
+/// Enabled failure capture of a page, with the console output seen since
+pub(crate) struct FailureRecorder {
+    capture: FailureCapture,
+    console: Arc<Mutex<VecDeque<String>>>,
+    task: JoinHandle<()>,
+}
+
+impl FailureRecorder {
+    /// Start keeping the page's recent console output
+    pub(crate) fn spawn(capture: FailureCapture, mut events: EventStream) -> Self {
+        let console = Arc::new(Mutex::new(VecDeque::new()));
+        let task = tokio::spawn({
+            let console = console.clone();
+            let capacity = capture.console_lines;
+            async move {
+                while let Some(event) = events.recv().await {
+                    let Some(line) = console_line(&event.method, &event.params) else {
+                        continue;
+                    };
+                    if let Ok(mut console) = console.lock() {
+                        if console.len() == capacity {
+                            console.pop_front();
+                        }
+                        if capacity > 0 {
+                            console.push_back(line);
+                        }
+                    }
+                }
+            }
+        });
+        Self {
+            capture,
+            console,
+            task,
+        }
+    }
+
+    /// Write the screenshot and report of a failed operation
+    pub(crate) async fn save(
+        &self,
+        operation: &str,
+        error: &Error,
+        url: Option<String>,
+        screenshot: Option<Vec<u8>>,
+    ) -> std::io::Result<FailureArtifacts> {
+        tokio::fs::create_dir_all(&self.capture.dir).await?;
+        let stem = format!("{}-{}", operation, uuid::Uuid::new_v4());
+        let screenshot = match screenshot {
+            Some(png) => {
+                let path = self.capture.dir.join(format!("{}.png", stem));
+                tokio::fs::write(&path, png).await?;
+                Some(path)
+            }
+            None => None,
+        };
+        let console: Vec<String> = self
+            .console
+            .lock()
+            .map(|console| console.iter().cloned().collect())
+            .unwrap_or_default();
+
+        let report = self.capture.dir.join(format!("{}.txt", stem));
+        tokio::fs::write(
+            &report,
+            render_report(operation, error, url.as_deref(), &console),
+        )
+        .await?;
+        Ok(FailureArtifacts {
+            operation: operation.to_string(),
+            url,
+            screenshot: screenshot.map(absolute),
+            report: absolute(report),
+            console,
+        })
+    }
+}
+
+impl Drop for FailureRecorder {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+fn render_report(operation: &str, error: &Error, url: Option<&str>, console: &[String]) -> String {
+    let mut report = format!(
+        "operation: {}\nerror: {}\nurl: {}\n\n--- console ---\n",
+        operation,
+        error,
+        url.unwrap_or("unknown")
+    );
+    for line in console {
+        report.push_str(line);
+        report.push('\n');
+    }
+    report
+}
+
+fn absolute(path: PathBuf) -> PathBuf {
+    std::path::absolute(&path).unwrap_or(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connection::tests::{mock_server, server_recv, server_send};
+    use crate::CdpConnection;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_save_keeps_recent_console_lines() {
+        let url = mock_server(|mut ws| async move {
+            // Emit console output once the recorder is subscribed
+            let enable = server_recv(&mut ws).await;
+            server_send(&mut ws, json!({"id": enable["id"], "result": {}})).await;
+            for text in ["first", "second", "third"] {
+                server_send(
+                    &mut ws,
+                    json!({"method": "Runtime.consoleAPICalled", "params": {
+                        "type": "log", "args": [{"type": "string", "value": text}]
+                    }}),
+                )
+                .await;
+            }
+            server_recv(&mut ws).await;
+        })
+        .await;
+        let connection = CdpConnection::connect(&url).await.unwrap();
+        let dir = std::env::temp_dir().join(format!("cdp-failures-{}", uuid::Uuid::new_v4()));
+        let recorder = FailureRecorder::spawn(
+            FailureCapture {
+                dir: dir.clone(),
+                console_lines: 2,
+            },
+            connection.subscribe(),
+        );
+        connection
+            .send_command("Runtime.enable", json!({}))
+            .await
+            .unwrap();
+        let received = async {
+            while recorder.console.lock().unwrap().back().map(String::as_str) != Some("[log] third")
+            {
+                tokio::task::yield_now().await;
+            }
+        };
+        tokio::time::timeout(std::time::Duration::from_secs(5), received)
+            .await
+            .unwrap();
+
+        let error = Error::Timeout("No element matched '#save'".to_string());
+        let artifacts = recorder
+            .save(
+                "wait_for_selector",
+                &error,
+                Some("https://app.example/".to_string()),
+                Some(b"png".to_vec()),
+            )
+            .await
+            .unwrap();
+        assert_eq!(artifacts.console, ["[log] second", "[log] third"]);
+        assert_eq!(
+            std::fs::read(artifacts.screenshot.unwrap()).unwrap(),
+            b"png"
+        );
+        let report = std::fs::read_to_string(&artifacts.report).unwrap();
+        assert!(report.contains("operation: wait_for_selector"));
+        assert!(report.contains("url: https://app.example/"));
+        assert!(report.ends_with("[log] second\n[log] third\n"));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,286 @@
+//! Download and cache of pinned Chrome for Testing builds
+
+use crate::{Error, Result};
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+
+/// Chrome for Testing version downloaded by default
+pub const CHROME_FOR_TESTING_VERSION: &str = "131.0.6778.85";
+
+/// Host serving Chrome for Testing archives
+const DOWNLOAD_BASE_URL: &str = "https://storage.googleapis.com/chrome-for-testing-public";
+
+/// Downloads a pinned Chrome for Testing build once and reuses it afterwards
+///
+/// Builds are unpacked into `cache_dir/<version>/`, so machines without
+/// Chrome installed launch the exact same browser on every run.
+///
+/// ```no_run
+/// # async fn run() -> chrome_cdp::Result<()> {
+/// use chrome_cdp::{BrowserFetcher, CdpBrowser, LaunchOptions};
+///
+/// let executable = BrowserFetcher::default().fetch().await?;
+/// let browser = CdpBrowser::launch_with_options(LaunchOptions {
+///     executable_path: Some(executable),
+///     ..Default::default()
+/// })
+/// .await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BrowserFetcher {
+    /// Directory holding unpacked builds (default: `chrome-cdp` in the user cache directory)
+    pub cache_dir: PathBuf,
+    /// Chrome for Testing version (default: [`CHROME_FOR_TESTING_VERSION`])
+    pub version: String,
+    /// Archive host, e.g. an internal mirror
+    pub base_url: String,
+}
+
+impl Default for BrowserFetcher {
+    fn default() -> Self {
+        Self {
+            cache_dir: default_cache_dir(),
+            version: CHROME_FOR_TESTING_VERSION.to_string(),
+            base_url: DOWNLOAD_BASE_URL.to_string(),
+        }
+    }
+}
+
+impl BrowserFetcher {
+    /// Chrome for Testing platform name of this machine, if builds exist for it
+    pub fn platform() -> Option<&'static str> {
+        match (std::env::consts::OS, std::env::consts::ARCH) {
+            ("linux", "x86_64") => Some("linux64"),
+            ("macos", "aarch64") => Some("mac-arm64"),
+            ("macos", "x86_64") => Some("mac-x64"),
+            ("windows", "x86_64") => Some("win64"),
+            ("windows", "x86") => Some("win32"),
+            _ => None,
+        }
+    }
+
+    /// URL of the archive for this machine
+    pub fn download_url(&self) -> Result<String> {
+        let platform = supported_platform()?;
+        Ok(format!(
+            "{}/{}/{}/chrome-{}.zip",
+            self.base_url.trim_end_matches('/'),
+            self.version,
+            platform,
+            platform
+        ))
+    }
+
+    /// Path of the executable once the build is unpacked
+    pub fn executable_path(&self) -> Result<PathBuf> {
+        let platform = supported_platform()?;
+        Ok(self
+            .cache_dir
+            .join(&self.version)
+            .join(executable_in_archive(platform)))
+    }
+
+    /// Return the cached executable, downloading the build first if needed
+    pub async fn fetch(&self) -> Result<PathBuf> {
+        let executable = self.executable_path()?;
+        if executable.is_file() {
+            return Ok(executable);
+        }
+
+        let url = self.download_url()?;
+        let response = reqwest::get(&url)
+            .await
+            .map_err(|e| Error::Http(format!("Failed to download {}: {}", url, e)))?;
+        let status = response.status();
+        if !status.is_success() {
+            return Err(Error::Http(format!(
+                "Downloading {} failed with status {}",
+                url, status
+            )));
+        }
+        let archive = response
+            .bytes()
+            .await
+            .map_err(|e| Error::Http(format!("Failed to read {}: {}", url, e)))?;
+
+        let target = self.cache_dir.join(&self.version);
+        tokio::task::spawn_blocking(move || unpack(&archive, &target))
+            .await
+            .map_err(|e| Error::Browser(format!("Task failed: {}", e)))??;
+
+        if !executable.is_file() {
+            return Err(Error::Browser(format!(
+                "Archive {} does not contain {}",
+                url,
+                executable.display()
+            )));
+        }
+        Ok(executable)
+    }
+}
+
+fn supported_platform() -> Result<&'static str> {
+    BrowserFetcher::platform().ok_or_else(|| {
+        Error::Browser(format!(
+            "Chrome for Testing has no builds for {} {}",
+            std::env::consts::OS,
+            std::env::consts::ARCH
+        ))
+    })
+}
+
+/// Executable path relative to the unpacked archive
+fn executable_in_archive(platform: &str) -> PathBuf {
+    let root = PathBuf::from(format!("chrome-{}", platform));
+    if platform.starts_with("mac") {
+        root.join("Google Chrome for Testing.app/Contents/MacOS/Google Chrome for Testing")
+    } else if platform.starts_with("win") {
+        root.join("chrome.exe")
+    } else {
+        root.join("chrome")
+    }
+}
+
+/// `$XDG_CACHE_HOME`, `%LOCALAPPDATA%` or `~/.cache`, falling back to the temp directory
+fn default_cache_dir() -> PathBuf {
+    let base = std::env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("LOCALAPPDATA").map(PathBuf::from))
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))
+        .unwrap_or_else(std::env::temp_dir);
+    base.join("chrome-cdp").join("chrome-for-testing")
+}
+
+/// Unpack a zip archive into `target`
+///
+/// Extraction happens in a sibling directory that is renamed into place, so
+/// concurrent runs never see a half-unpacked build.
+fn unpack(archive: &[u8], target: &Path) -> Result<()> {
+    let parent = target
+        .parent()
+        .ok_or_else(|| Error::Browser(format!("Invalid cache directory {}", target.display())))?;
+    std::fs::create_dir_all(parent)?;
+    let staging = parent.join(format!(".partial-{}", uuid::Uuid::new_v4()));
+
+    let extracted = zip::ZipArchive::new(Cursor::new(archive))
+        .and_then(|mut zip| zip.extract(&staging))
+        .map_err(|e| Error::Browser(format!("Failed to unpack Chrome archive: {}", e)));
+    if let Err(e) = extracted {
+        let _ = std::fs::remove_dir_all(&staging);
+        return Err(e);
+    }
+
+    if std::fs::rename(&staging, target).is_err() {
+        // Another run unpacked the same build first
+        let _ = std::fs::remove_dir_all(&staging);
+        if !target.is_dir() {
+            return Err(Error::Browser(format!(
+                "Failed to move Chrome build into {}",
+                target.display()
+            )));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use zip::write::SimpleFileOptions;
+
+    /// Zip containing a fake executable at the path expected for this machine
+    fn fake_archive() -> Vec<u8> {
+        let platform = BrowserFetcher::platform().unwrap();
+        let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        let name = executable_in_archive(platform);
+        zip.start_file(
+            name.to_string_lossy().replace('\\', "/"),
+            SimpleFileOptions::default().unix_permissions(0o755),
+        )
+        .unwrap();
+        zip.write_all(b"#!/bin/sh\n").unwrap();
+        zip.finish().unwrap().into_inner()
+    }
+
+    fn temp_fetcher(base_url: &str) -> BrowserFetcher {
+        BrowserFetcher {
+            cache_dir: std::env::temp_dir()
+                .join(format!("chrome-cdp-cft-{}", uuid::Uuid::new_v4())),
+            version: "120.0.0.0".to_string(),
+            base_url: base_url.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_executable_in_archive() {
+        assert_eq!(
+            executable_in_archive("linux64"),
+            PathBuf::from("chrome-linux64/chrome")
+        );
+        assert_eq!(
+            executable_in_archive("win64"),
+            PathBuf::from("chrome-win64/chrome.exe")
+        );
+        assert!(executable_in_archive("mac-arm64").ends_with("MacOS/Google Chrome for Testing"));
+    }
+
+    #[test]
+    fn test_download_url() {
+        let Some(platform) = BrowserFetcher::platform() else {
+            return;
+        };
+        let fetcher = temp_fetcher("https://mirror.example/cft/");
+        assert_eq!(
+            fetcher.download_url().unwrap(),
+            format!(
+                "https://mirror.example/cft/120.0.0.0/{}/chrome-{}.zip",
+                platform, platform
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn test_fetch_downloads_once() {
+        if BrowserFetcher::platform().is_none() {
+            return;
+        }
+        let archive = fake_archive();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(async move {
+            // Only one download is served; a second request would hang the test
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = vec![0; 4096];
+            let _ = socket.read(&mut buf).await.unwrap();
+            let head = format!(
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                archive.len()
+            );
+            socket.write_all(head.as_bytes()).await.unwrap();
+            socket.write_all(&archive).await.unwrap();
+        });
+
+        let fetcher = temp_fetcher(&format!("http://{}", addr));
+        let executable = fetcher.fetch().await.unwrap();
+        assert_eq!(executable, fetcher.executable_path().unwrap());
+        assert!(executable.is_file());
+        server.await.unwrap();
+
+        assert_eq!(fetcher.fetch().await.unwrap(), executable);
+        std::fs::remove_dir_all(&fetcher.cache_dir).unwrap();
+    }
+
+    #[test]
+    fn test_unpack_rejects_invalid_archive() {
+        let fetcher = temp_fetcher("http://unused");
+        let target = fetcher.cache_dir.join("broken");
+        assert!(unpack(b"not a zip", &target).is_err());
+        assert!(!target.exists());
+        assert_eq!(std::fs::read_dir(&fetcher.cache_dir).unwrap().count(), 0);
+        std::fs::remove_dir_all(&fetcher.cache_dir).unwrap();
+    }
+}
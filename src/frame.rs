@@ -0,0 +1,327 @@
+//! Frame tracking for pages with iframes
+
+use crate::{CdpEvent, Error, EventStream, Result};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+
+/// Last known state of a frame
+#[derive(Debug, Clone, Default, PartialEq)]
+struct FrameState {
+    parent_id: Option<String>,
+    url: String,
+    name: String,
+    loading: bool,
+}
+
+type FrameMap = HashMap<String, FrameState>;
+
+/// Frame of a page, identified by its CDP frame id
+///
+/// The parent, URL, and name are a snapshot taken when the handle was obtained.
+#[derive(Debug, Clone)]
+pub struct Frame {
+    id: String,
+    state: FrameState,
+    frames: watch::Receiver<FrameMap>,
+}
+
+impl Frame {
+    /// CDP frame id
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Id of the parent frame, or `None` for the main frame
+    pub fn parent_id(&self) -> Option<&str> {
+        self.state.parent_id.as_deref()
+    }
+
+    /// URL of the frame's document
+    pub fn url(&self) -> &str {
+        &self.state.url
+    }
+
+    /// Value of the frame's `name` attribute
+    pub fn name(&self) -> &str {
+        &self.state.name
+    }
+
+    /// Whether this is the page's main frame
+    pub fn is_main(&self) -> bool {
+        self.state.parent_id.is_none()
+    }
+
+    /// Wait until the frame has no load in progress
+    ///
+    /// Returns immediately when the frame is idle, so call it after the action
+    /// that starts the iframe's navigation, e.g. submitting a form inside it.
+    pub async fn wait_for_load(&self, timeout: Duration) -> Result<()> {
+        let mut frames = self.frames.clone();
+        let wait = frames.wait_for(|frames| frames.get(&self.id).is_none_or(|f| !f.loading));
+        let detached = match tokio::time::timeout(timeout, wait).await {
+            Ok(Ok(frames)) => !frames.contains_key(&self.id),
+            Ok(Err(_)) => true,
+            Err(_) => {
+                return Err(Error::Timeout(format!(
+                    "Frame {} did not finish loading within {:?}",
+                    self.id, timeout
+                )))
+            }
+        };
+        if detached {
+            return Err(Error::Browser(format!("Frame {} was detached", self.id)));
+        }
+        Ok(())
+    }
+}
+
+/// Keeps the frame map of a page up to date from `Page` domain events
+pub(crate) struct FrameTracker {
+    frames: watch::Sender<FrameMap>,
+    task: JoinHandle<()>,
+}
+
+impl FrameTracker {
+    /// Start tracking; subscribe before enabling the `Page` domain to see every frame
+    pub(crate) fn spawn(mut events: EventStream) -> Self {
+        let (frames, _) = watch::channel(FrameMap::new());
+        let tx = frames.clone();
+        let task = tokio::spawn(async move {
+            while let Some(event) = events.recv().await {
+                tx.send_if_modified(|frames| apply(frames, &event));
+            }
+        });
+        Self { frames, task }
+    }
+
+    /// Add frames from a `Page.getFrameTree` result that no event reported yet
+    pub(crate) fn seed(&self, tree: &Value) {
+        self.frames
+            .send_modify(|frames| seed(frames, &tree["frameTree"], None));
+    }
+
+    /// Handles for all known frames, main frame first
+    pub(crate) fn frames(&self) -> Vec<Frame> {
+        let mut frames: Vec<Frame> = self
+            .frames
+            .borrow()
+            .iter()
+            .map(|(id, state)| self.handle(id, state))
+            .collect();
+        frames.sort_by_key(|frame| !frame.is_main());
+        frames
+    }
+
+    /// Handle for the frame with the given id
+    pub(crate) fn frame(&self, id: &str) -> Option<Frame> {
+        self.frames
+            .borrow()
+            .get(id)
+            .map(|state| self.handle(id, state))
+    }
+
+    fn handle(&self, id: &str, state: &FrameState) -> Frame {
+        Frame {
+            id: id.to_string(),
+            state: state.clone(),
+            frames: self.frames.subscribe(),
+        }
+    }
+}
+
+impl Drop for FrameTracker {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// Insert a frame tree node and its children, keeping frames already tracked
+fn seed(frames: &mut FrameMap, node: &Value, parent_id: Option<&str>) {
+    let frame = &node["frame"];
+    let Some(id) = frame["id"].as_str() else {
+        return;
+    };
+    frames.entry(id.to_string()).or_insert_with(|| FrameState {
+        parent_id: parent_id.map(String::from),
+        url: frame["url"].as_str().unwrap_or_default().to_string(),
+        name: frame["name"].as_str().unwrap_or_default().to_string(),
+        loading: false,
+    });
+    for child in node["childFrames"].as_array().into_iter().flatten() {
+        seed(frames, child, Some(id));
+    }
+}
+
+/// Apply a page event to the frame map, returning whether anything changed
+fn apply(frames: &mut FrameMap, event: &CdpEvent) -> bool {
+    // Events of flattened child sessions describe frames of other targets
+    if event.session_id.is_some() {
+        return false;
+    }
+    let params = &event.params;
+    match event.method.as_str() {
+        "Page.frameAttached" => {
+            let Some(id) = params["frameId"].as_str() else {
+                return false;
+            };
+            let state = frames.entry(id.to_string()).or_default();
+            state.parent_id = params["parentFrameId"].as_str().map(String::from);
+            true
+        }
+        "Page.frameNavigated" => {
+            let frame = &params["frame"];
+            let Some(id) = frame["id"].as_str() else {
+                return false;
+            };
+            let state = frames.entry(id.to_string()).or_default();
+            state.parent_id = frame["parentId"].as_str().map(String::from);
+            state.url = frame["url"].as_str().unwrap_or_default().to_string();
+            state.name = frame["name"].as_str().unwrap_or_default().to_string();
+            true
+        }
+        "Page.frameStartedLoading" | "Page.frameStoppedLoading" => {
+            let Some(id) = params["frameId"].as_str() else {
+                return false;
+            };
+            let loading = event.method == "Page.frameStartedLoading";
+            let state = frames.entry(id.to_string()).or_default();
+            let changed = state.loading != loading;
+            state.loading = loading;
+            changed
+        }
+        "Page.frameDetached" => params["frameId"]
+            .as_str()
+            .is_some_and(|id| frames.remove(id).is_some()),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn event(method: &str, params: Value) -> CdpEvent {
+        CdpEvent {
+            method: method.to_string(),
+            params,
+            session_id: None,
+        }
+    }
+
+    fn tracked(frames: FrameMap) -> (watch::Sender<FrameMap>, Frame) {
+        let (tx, rx) = watch::channel(frames);
+        let frame = Frame {
+            id: "child".to_string(),
+            state: FrameState::default(),
+            frames: rx,
+        };
+        (tx, frame)
+    }
+
+    #[test]
+    fn test_apply_tracks_frame_lifecycle() {
+        let mut frames = FrameMap::new();
+        assert!(apply(
+            &mut frames,
+            &event(
+                "Page.frameAttached",
+                json!({"frameId": "child", "parentFrameId": "main"})
+            )
+        ));
+        assert!(apply(
+            &mut frames,
+            &event("Page.frameStartedLoading", json!({"frameId": "child"}))
+        ));
+        assert!(frames["child"].loading);
+        assert!(apply(
+            &mut frames,
+            &event(
+                "Page.frameNavigated",
+                json!({"frame": {"id": "child", "parentId": "main", "url": "https://pay.example/", "name": "pay"}})
+            )
+        ));
+        assert_eq!(frames["child"].url, "https://pay.example/");
+        assert_eq!(frames["child"].parent_id.as_deref(), Some("main"));
+        assert!(apply(
+            &mut frames,
+            &event("Page.frameStoppedLoading", json!({"frameId": "child"}))
+        ));
+        assert!(!frames["child"].loading);
+        assert!(apply(
+            &mut frames,
+            &event("Page.frameDetached", json!({"frameId": "child"}))
+        ));
+        assert!(frames.is_empty());
+    }
+
+    #[test]
+    fn test_apply_ignores_child_sessions() {
+        let mut frames = FrameMap::new();
+        let mut event = event("Page.frameStartedLoading", json!({"frameId": "x"}));
+        event.session_id = Some("S1".to_string());
+        assert!(!apply(&mut frames, &event));
+        assert!(frames.is_empty());
+    }
+
+    #[test]
+    fn test_seed_keeps_tracked_frames() {
+        let mut frames = FrameMap::new();
+        frames.insert(
+            "child".to_string(),
+            FrameState {
+                loading: true,
+                ..Default::default()
+            },
+        );
+        let tree = json!({
+            "frame": {"id": "main", "url": "https://shop.example/"},
+            "childFrames": [{"frame": {"id": "child", "url": "about:blank"}}]
+        });
+        seed(&mut frames, &tree, None);
+        assert_eq!(frames["main"].parent_id, None);
+        assert!(frames["child"].loading);
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_load_waits_for_stop() {
+        let loading = FrameState {
+            loading: true,
+            ..Default::default()
+        };
+        let (tx, frame) = tracked(FrameMap::from([("child".to_string(), loading)]));
+        let wait = tokio::spawn(async move { frame.wait_for_load(Duration::from_secs(1)).await });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        tx.send_modify(|frames| {
+            apply(
+                frames,
+                &event("Page.frameStoppedLoading", json!({"frameId": "child"})),
+            );
+        });
+        wait.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_load_timeout_and_detach() {
+        let loading = FrameState {
+            loading: true,
+            ..Default::default()
+        };
+        let (tx, frame) = tracked(FrameMap::from([("child".to_string(), loading)]));
+        let err = frame
+            .wait_for_load(Duration::from_millis(20))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::Timeout(_)));
+
+        tx.send_modify(|frames| frames.clear());
+        let err = frame
+            .wait_for_load(Duration::from_millis(20))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::Browser(_)));
+    }
+}
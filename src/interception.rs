@@ -0,0 +1,907 @@
+//! Fetch-domain request interception shared by page-level request policies
+
+use crate::cache::CachedResponse;
+use crate::transfer::{is_download, stream_download};
+use crate::{
+    CdpConnection, Download, Error, EventStream, ProxyCredentials, RequestInfo, RequestMatcher,
+    ResponseCache, Result,
+};
+use futures::future::BoxFuture;
+use serde_json::{json, Value};
+use std::collections::HashSet;
+use std::future::Future;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+
+/// Callback deciding whether a navigation to a URL is allowed
+pub type NavigationPolicy = Arc<dyn Fn(&str) -> bool + Send + Sync>;
+
+/// Async callback run before each navigation is sent, see [`CdpPage::before_navigation`](crate::CdpPage::before_navigation)
+pub type NavigationHook =
+    Arc<dyn Fn(NavigationRequest) -> BoxFuture<'static, NavigationAction> + Send + Sync>;
+
+/// Wrap an async closure as a [`NavigationHook`]
+pub(crate) fn navigation_hook<F, Fut>(hook: F) -> NavigationHook
+where
+    F: Fn(NavigationRequest) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = NavigationAction> + Send + 'static,
+{
+    Arc::new(move |request| Box::pin(hook(request)))
+}
+
+/// Document request about to be sent, as seen by a [`NavigationHook`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct NavigationRequest {
+    /// URL being navigated to
+    pub url: String,
+    /// HTTP method, e.g. `GET`, or `POST` for form submissions
+    pub method: String,
+    /// Request headers
+    pub headers: Vec<(String, String)>,
+    /// Frame navigating, if reported
+    pub frame_id: Option<String>,
+}
+
+impl NavigationRequest {
+    fn from_event(params: &Value) -> Self {
+        Self {
+            url: params["request"]["url"]
+                .as_str()
+                .unwrap_or_default()
+                .to_string(),
+            method: params["request"]["method"]
+                .as_str()
+                .unwrap_or("GET")
+                .to_string(),
+            headers: request_headers(params),
+            frame_id: params["frameId"].as_str().map(str::to_string),
+        }
+    }
+
+    /// Navigate to `url` instead, without the page noticing the change
+    pub fn with_url(mut self, url: impl Into<String>) -> Self {
+        self.url = url.into();
+        self
+    }
+
+    /// Set a header, overwriting any existing value
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        HeaderAction::Add(name.to_string(), value.to_string()).apply(&mut self.headers);
+        self
+    }
+
+    /// Write the URL and headers back into a `Fetch.requestPaused` event
+    fn apply(self, params: &mut Value) {
+        params["request"]["url"] = json!(self.url);
+        params["request"]["headers"] = Value::Object(
+            self.headers
+                .into_iter()
+                .map(|(name, value)| (name, Value::String(value)))
+                .collect(),
+        );
+    }
+}
+
+/// What a [`NavigationHook`] does with a navigation
+#[derive(Debug, Clone, PartialEq)]
+pub enum NavigationAction {
+    /// Send the request unchanged
+    Continue,
+    /// Send the request with the URL and headers of the given request
+    Rewrite(NavigationRequest),
+    /// Cancel the navigation; it fails with `net::ERR_BLOCKED_BY_CLIENT`
+    Block,
+}
+
+/// Change a [`HeaderRule`] makes to a request header
+#[derive(Debug, Clone, PartialEq)]
+pub enum HeaderAction {
+    /// Set the header, overwriting any existing value
+    Add(String, String),
+    /// Drop the header if present
+    Remove(String),
+    /// Change the value of the header, only if the request already has it
+    Replace(String, String),
+}
+
+impl HeaderAction {
+    /// Apply the change to a header list, comparing names case-insensitively
+    fn apply(&self, headers: &mut Vec<(String, String)>) {
+        match self {
+            Self::Add(name, value) => {
+                headers.retain(|(n, _)| !n.eq_ignore_ascii_case(name));
+                headers.push((name.clone(), value.clone()));
+            }
+            Self::Remove(name) => headers.retain(|(n, _)| !n.eq_ignore_ascii_case(name)),
+            Self::Replace(name, value) => {
+                for (n, v) in headers.iter_mut() {
+                    if n.eq_ignore_ascii_case(name) {
+                        *v = value.clone();
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Declarative header rewrite applied to requests matching a [`RequestMatcher`]
+///
+/// ```
+/// use chrome_cdp::{HeaderRule, RequestMatcher};
+///
+/// let rule = HeaderRule::add(
+///     RequestMatcher::new().url_glob("https://api.example.com/*"),
+///     "Authorization",
+///     "Bearer secret",
+/// );
+/// ```
+#[derive(Debug, Clone)]
+pub struct HeaderRule {
+    /// Requests the rule applies to
+    pub matcher: RequestMatcher,
+    /// Change made to matching requests
+    pub action: HeaderAction,
+}
+
+impl HeaderRule {
+    /// Set a header on matching requests
+    pub fn add(matcher: RequestMatcher, name: &str, value: &str) -> Self {
+        Self {
+            matcher,
+            action: HeaderAction::Add(name.to_string(), value.to_string()),
+        }
+    }
+
+    /// Strip a header from matching requests
+    pub fn remove(matcher: RequestMatcher, name: &str) -> Self {
+        Self {
+            matcher,
+            action: HeaderAction::Remove(name.to_string()),
+        }
+    }
+
+    /// Change the value of a header that matching requests already carry
+    pub fn replace(matcher: RequestMatcher, name: &str, value: &str) -> Self {
+        Self {
+            matcher,
+            action: HeaderAction::Replace(name.to_string(), value.to_string()),
+        }
+    }
+}
+
+/// What to do with a paused request
+#[derive(Debug, PartialEq)]
+enum Decision {
+    /// Let the request through unchanged
+    Continue,
+    /// Let the request through with the given headers
+    ContinueWithHeaders(Vec<(String, String)>),
+    /// Answer the request from the response cache
+    Fulfill(CachedResponse),
+    /// Fail the request with a `Network.ErrorReason`
+    Fail(&'static str),
+}
+
+/// Request policies installed on a page
+#[derive(Default)]
+struct Rules {
+    navigation_policy: Option<NavigationPolicy>,
+    navigation_hook: Option<NavigationHook>,
+    header_rules: Vec<HeaderRule>,
+    cache: Option<Arc<ResponseCache>>,
+    /// Local directory receiving downloads instead of the browser's host
+    download_dir: Option<PathBuf>,
+    /// Credentials answering proxy authentication challenges
+    proxy_credentials: Option<ProxyCredentials>,
+}
+
+impl Rules {
+    /// `Fetch.enable` patterns needed by the installed policies
+    fn patterns(&self) -> Vec<Value> {
+        let mut patterns = Vec::new();
+        if !self.header_rules.is_empty() || self.cache.is_some() || self.proxy_credentials.is_some()
+        {
+            // Any resource type may match, which covers documents too; auth
+            // challenges are only reported for requests that are intercepted
+            patterns.push(json!({ "urlPattern": "*", "requestStage": "Request" }));
+        } else if self.navigation_policy.is_some() || self.navigation_hook.is_some() {
+            patterns.push(json!({
+                "urlPattern": "*",
+                "resourceType": "Document",
+                "requestStage": "Request"
+            }));
+        }
+        if self.cache.is_some() {
+            // Responses that were not served from the cache are stored
+            patterns.push(json!({ "urlPattern": "*", "requestStage": "Response" }));
+        } else if self.download_dir.is_some() {
+            // Downloads start as navigations or as requests of unknown type
+            for resource_type in ["Document", "Other"] {
+                patterns.push(json!({
+                    "urlPattern": "*",
+                    "resourceType": resource_type,
+                    "requestStage": "Response"
+                }));
+            }
+        }
+        patterns
+    }
+
+    /// Hook to run before resolving a paused request, if it is a navigation
+    fn hook_for(&self, params: &Value) -> Option<NavigationHook> {
+        self.navigation_hook
+            .clone()
+            .filter(|_| params["resourceType"].as_str() == Some("Document"))
+    }
+
+    /// Decide what to do with a `Fetch.requestPaused` event
+    fn decide(&self, params: &Value) -> Decision {
+        if params["resourceType"].as_str() == Some("Document") {
+            if let Some(policy) = &self.navigation_policy {
+                let url = params["request"]["url"].as_str().unwrap_or_default();
+                if !policy(url) {
+                    return Decision::Fail("BlockedByClient");
+                }
+            }
+        }
+        self.rewrite_headers(params)
+    }
+
+    /// Apply the header rules matching the request, in the order they were added
+    fn rewrite_headers(&self, params: &Value) -> Decision {
+        if self.header_rules.is_empty() {
+            return Decision::Continue;
+        }
+        let request = RequestInfo::from_event(params);
+        let mut matching = self
+            .header_rules
+            .iter()
+            .filter(|rule| rule.matcher.matches(&request))
+            .peekable();
+        if matching.peek().is_none() {
+            return Decision::Continue;
+        }
+
+        let mut headers = request_headers(params);
+        for rule in matching {
+            rule.action.apply(&mut headers);
+        }
+        Decision::ContinueWithHeaders(headers)
+    }
+}
+
+/// Headers of the request in a `Fetch.requestPaused` event
+fn request_headers(params: &Value) -> Vec<(String, String)> {
+    params["request"]["headers"]
+        .as_object()
+        .map(|headers| {
+            headers
+                .iter()
+                .map(|(name, value)| (name.clone(), value.as_str().unwrap_or_default().to_string()))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Download saved into the download directory, or why saving it failed
+pub(crate) type DownloadOutcome = std::result::Result<Download, String>;
+
+/// Dispatcher resolving every paused request of a page against its rules
+///
+/// A single `Fetch.requestPaused` handler runs per page, so policies never
+/// race each other to continue the same request.
+pub(crate) struct Interceptor {
+    connection: CdpConnection,
+    rules: Arc<Mutex<Rules>>,
+    task: Mutex<Option<JoinHandle<()>>>,
+    downloads: broadcast::Sender<DownloadOutcome>,
+}
+
+impl Interceptor {
+    pub(crate) fn new(connection: CdpConnection) -> Self {
+        Self {
+            connection,
+            rules: Arc::new(Mutex::new(Rules::default())),
+            task: Mutex::new(None),
+            downloads: broadcast::channel(16).0,
+        }
+    }
+
+    /// Install or remove the navigation policy
+    pub(crate) async fn set_navigation_policy(
+        &self,
+        policy: Option<NavigationPolicy>,
+    ) -> Result<()> {
+        self.rules.lock().unwrap().navigation_policy = policy;
+        self.sync().await
+    }
+
+    /// Install or remove the hook run before each navigation
+    pub(crate) async fn set_navigation_hook(&self, hook: Option<NavigationHook>) -> Result<()> {
+        self.rules.lock().unwrap().navigation_hook = hook;
+        self.sync().await
+    }
+
+    /// Append a header rewrite rule
+    pub(crate) async fn add_header_rule(&self, rule: HeaderRule) -> Result<()> {
+        self.rules.lock().unwrap().header_rules.push(rule);
+        self.sync().await
+    }
+
+    /// Remove every header rewrite rule
+    pub(crate) async fn clear_header_rules(&self) -> Result<()> {
+        self.rules.lock().unwrap().header_rules.clear();
+        self.sync().await
+    }
+
+    /// Install or remove the response cache
+    pub(crate) async fn set_response_cache(&self, cache: Option<ResponseCache>) -> Result<()> {
+        self.rules.lock().unwrap().cache = cache.map(Arc::new);
+        self.sync().await
+    }
+
+    /// Save downloads into a local directory, or leave them to the browser
+    pub(crate) async fn set_download_dir(&self, dir: Option<PathBuf>) -> Result<()> {
+        self.rules.lock().unwrap().download_dir = dir;
+        self.sync().await
+    }
+
+    /// Answer proxy authentication challenges with `credentials`, or stop answering them
+    pub(crate) async fn set_proxy_credentials(
+        &self,
+        credentials: Option<ProxyCredentials>,
+    ) -> Result<()> {
+        self.rules.lock().unwrap().proxy_credentials = credentials;
+        self.sync().await
+    }
+
+    /// Downloads saved, or failed to save, from now on
+    pub(crate) fn downloads(&self) -> broadcast::Receiver<DownloadOutcome> {
+        self.downloads.subscribe()
+    }
+
+    /// Enable or disable the Fetch domain to match the installed rules
+    async fn sync(&self) -> Result<()> {
+        let (patterns, handle_auth) = {
+            let rules = self.rules.lock().unwrap();
+            (rules.patterns(), rules.proxy_credentials.is_some())
+        };
+        if patterns.is_empty() {
+            self.connection
+                .send_command("Fetch.disable", json!({}))
+                .await
+                .map_err(|e| Error::Browser(format!("Failed to disable interception: {}", e)))?;
+            return Ok(());
+        }
+
+        self.ensure_dispatcher();
+        self.connection
+            .send_command(
+                "Fetch.enable",
+                json!({ "patterns": patterns, "handleAuthRequests": handle_auth }),
+            )
+            .await
+            .map_err(|e| Error::Browser(format!("Failed to enable interception: {}", e)))?;
+        Ok(())
+    }
+
+    /// Start the paused-request handler unless it is already running
+    fn ensure_dispatcher(&self) {
+        let mut task = self.task.lock().unwrap();
+        if task.is_none() {
+            // Subscribe before Fetch.enable so no paused request is missed
+            let events = self.connection.subscribe();
+            *task = Some(tokio::spawn(dispatch(
+                self.connection.clone(),
+                self.rules.clone(),
+                events,
+                self.downloads.clone(),
+            )));
+        }
+    }
+}
+
+impl Drop for Interceptor {
+    fn drop(&mut self) {
+        if let Some(task) = self.task.lock().unwrap().take() {
+            task.abort();
+        }
+    }
+}
+
+/// Resolve paused requests until the connection closes
+async fn dispatch(
+    connection: CdpConnection,
+    rules: Arc<Mutex<Rules>>,
+    mut events: EventStream,
+    downloads: broadcast::Sender<DownloadOutcome>,
+) {
+    // Requests whose challenge was already answered, to give up on wrong credentials
+    let mut answered: HashSet<String> = HashSet::new();
+    while let Some(event) = events.recv().await {
+        if event.method == "Fetch.authRequired" {
+            let request_id = event.params["requestId"].as_str().unwrap_or_default();
+            let first_attempt = answered.insert(request_id.to_string());
+            if answered.len() > ANSWERED_CHALLENGES_LIMIT {
+                answered.clear();
+            }
+            let credentials = rules.lock().unwrap().proxy_credentials.clone();
+            let response = challenge_response(&event.params, credentials.as_ref(), first_attempt);
+            let _ = connection
+                .send_command(
+                    "Fetch.continueWithAuth",
+                    json!({
+                        "requestId": event.params["requestId"],
+                        "authChallengeResponse": response,
+                    }),
+                )
+                .await;
+            continue;
+        }
+        if event.method != "Fetch.requestPaused" {
+            continue;
+        }
+        if is_response_stage(&event.params) {
+            let request = RequestInfo::from_event(&event.params);
+            let cache = rules
+                .lock()
+                .unwrap()
+                .cache
+                .clone()
+                .filter(|cache| cache.accepts(&request));
+            let download_dir = rules.lock().unwrap().download_dir.clone();
+            if let Some(dir) = download_dir.filter(|_| is_download(&event.params)) {
+                // Streaming may take a while; keep resolving other requests
+                let connection = connection.clone();
+                let downloads = downloads.clone();
+                tokio::spawn(async move {
+                    let outcome = stream_download(&connection, &event.params, &dir).await;
+                    let _ = downloads.send(outcome.map_err(|e| e.to_string()));
+                });
+                continue;
+            }
+            if let Some(cache) = cache {
+                store_response(&connection, &cache, &request, &event.params).await;
+            }
+            let _ = connection
+                .send_command(
+                    "Fetch.continueRequest",
+                    json!({ "requestId": event.params["requestId"] }),
+                )
+                .await;
+            continue;
+        }
+
+        let hook = rules.lock().unwrap().hook_for(&event.params);
+        match hook {
+            Some(hook) => {
+                // Hooks may take a while, e.g. to refresh a token; keep resolving other requests
+                let connection = connection.clone();
+                let rules = rules.clone();
+                tokio::spawn(async move {
+                    let mut params = event.params;
+                    match hook(NavigationRequest::from_event(&params)).await {
+                        NavigationAction::Continue => {
+                            resolve_request(&connection, &rules, &params, false).await
+                        }
+                        NavigationAction::Rewrite(request) => {
+                            request.apply(&mut params);
+                            resolve_request(&connection, &rules, &params, true).await
+                        }
+                        NavigationAction::Block => {
+                            let _ = connection
+                                .send_command(
+                                    "Fetch.failRequest",
+                                    json!({
+                                        "requestId": params["requestId"],
+                                        "errorReason": "BlockedByClient"
+                                    }),
+                                )
+                                .await;
+                        }
+                    }
+                });
+            }
+            None => resolve_request(&connection, &rules, &event.params, false).await,
+        }
+    }
+}
+
+/// Resolve a request paused at the `Request` stage against the rules
+///
+/// A `rewritten` request carries a URL and headers changed by a navigation
+/// hook, which are sent even if no rule changes them further.
+async fn resolve_request(
+    connection: &CdpConnection,
+    rules: &Mutex<Rules>,
+    params: &Value,
+    rewritten: bool,
+) {
+    let request_id = params["requestId"].clone();
+    let request = RequestInfo::from_event(params);
+    let (mut decision, cache) = {
+        let rules = rules.lock().unwrap();
+        let cache = rules.cache.clone().filter(|cache| cache.accepts(&request));
+        (rules.decide(params), cache)
+    };
+    if let (false, Some(cache)) = (matches!(decision, Decision::Fail(_)), cache) {
+        if let Some(entry) = cache.lookup(&request).await {
+            decision = Decision::Fulfill(entry);
+        }
+    }
+    if rewritten && decision == Decision::Continue {
+        decision = Decision::ContinueWithHeaders(request_headers(params));
+    }
+    let (method, mut command) = match decision {
+        Decision::Continue => ("Fetch.continueRequest", json!({ "requestId": request_id })),
+        Decision::ContinueWithHeaders(headers) => {
+            let headers: Vec<Value> = headers
+                .into_iter()
+                .map(|(name, value)| json!({ "name": name, "value": value }))
+                .collect();
+            (
+                "Fetch.continueRequest",
+                json!({ "requestId": request_id, "headers": headers }),
+            )
+        }
+        Decision::Fulfill(entry) => ("Fetch.fulfillRequest", entry.fulfill_params(&request_id)),
+        Decision::Fail(reason) => (
+            "Fetch.failRequest",
+            json!({ "requestId": request_id, "errorReason": reason }),
+        ),
+    };
+    if rewritten && method == "Fetch.continueRequest" {
+        command["url"] = params["request"]["url"].clone();
+    }
+    // The request may already be gone, e.g. when the page navigated away
+    let _ = connection.send_command(method, command).await;
+}
+
+/// Answered challenges remembered before the set is reset
+const ANSWERED_CHALLENGES_LIMIT: usize = 1024;
+
+/// `Fetch.AuthChallengeResponse` for a `Fetch.authRequired` event
+///
+/// Only proxy challenges are answered, and only once per request: a second
+/// challenge means the credentials were rejected, so it is cancelled rather
+/// than retried forever. Server challenges get the browser's default handling.
+fn challenge_response(
+    params: &Value,
+    credentials: Option<&ProxyCredentials>,
+    first_attempt: bool,
+) -> Value {
+    let from_proxy = params["authChallenge"]["source"].as_str() == Some("Proxy");
+    match credentials {
+        Some(credentials) if from_proxy && first_attempt => json!({
+            "response": "ProvideCredentials",
+            "username": credentials.username,
+            "password": credentials.password,
+        }),
+        Some(_) if from_proxy => json!({ "response": "CancelAuth" }),
+        _ => json!({ "response": "Default" }),
+    }
+}
+
+/// Whether a `Fetch.requestPaused` event was paused at the `Response` stage
+fn is_response_stage(params: &Value) -> bool {
+    params.get("responseStatusCode").is_some() || params.get("responseErrorReason").is_some()
+}
+
+/// Save the body of a paused response into the cache
+///
+/// Failures only cost a cache miss next time, so they are ignored.
+async fn store_response(
+    connection: &CdpConnection,
+    cache: &ResponseCache,
+    request: &RequestInfo,
+    params: &Value,
+) {
+    if params.get("responseStatusCode").is_none() {
+        return;
+    }
+    let body = connection
+        .send_command(
+            "Fetch.getResponseBody",
+            json!({ "requestId": params["requestId"] }),
+        )
+        .await;
+    if let Ok(body) = body {
+        let _ = cache.store(request, params, &body).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connection::tests::{mock_server, server_recv, server_send};
+
+    fn paused(url: &str, resource_type: &str) -> Value {
+        json!({
+            "requestId": "interception-1",
+            "resourceType": resource_type,
+            "request": { "url": url, "method": "GET", "headers": {} }
+        })
+    }
+
+    fn same_domain() -> Rules {
+        Rules {
+            navigation_policy: Some(Arc::new(|url: &str| {
+                url.starts_with("https://example.com/") && !url.contains("/logout")
+            })),
+            ..Default::default()
+        }
+    }
+
+    fn api_auth() -> Rules {
+        Rules {
+            header_rules: vec![
+                HeaderRule::add(
+                    RequestMatcher::new().url_glob("https://api.example.com/*"),
+                    "Authorization",
+                    "Bearer token",
+                ),
+                HeaderRule::remove(RequestMatcher::new(), "X-Debug"),
+                HeaderRule::replace(RequestMatcher::new(), "user-agent", "Bot/1.0"),
+            ],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_no_rules_no_patterns() {
+        assert!(Rules::default().patterns().is_empty());
+    }
+
+    #[test]
+    fn test_navigation_policy_intercepts_documents_only() {
+        let patterns = same_domain().patterns();
+        assert_eq!(patterns.len(), 1);
+        assert_eq!(patterns[0]["resourceType"], "Document");
+    }
+
+    #[test]
+    fn test_navigation_policy_blocks_disallowed_documents() {
+        let rules = same_domain();
+        assert_eq!(
+            rules.decide(&paused("https://example.com/page", "Document")),
+            Decision::Continue
+        );
+        assert_eq!(
+            rules.decide(&paused("https://other.com/", "Document")),
+            Decision::Fail("BlockedByClient")
+        );
+        assert_eq!(
+            rules.decide(&paused("https://example.com/logout", "Document")),
+            Decision::Fail("BlockedByClient")
+        );
+    }
+
+    #[test]
+    fn test_navigation_policy_ignores_subresources() {
+        let rules = same_domain();
+        assert_eq!(
+            rules.decide(&paused("https://cdn.other.com/app.js", "Script")),
+            Decision::Continue
+        );
+    }
+
+    #[test]
+    fn test_cache_intercepts_both_stages() {
+        let rules = Rules {
+            cache: Some(Arc::new(ResponseCache::new(
+                "cache",
+                std::time::Duration::from_secs(60),
+            ))),
+            ..same_domain()
+        };
+        let patterns = rules.patterns();
+        assert_eq!(patterns.len(), 2);
+        assert!(patterns[0].get("resourceType").is_none());
+        assert_eq!(patterns[1]["requestStage"], "Response");
+    }
+
+    #[test]
+    fn test_download_capture_intercepts_document_responses() {
+        let rules = Rules {
+            download_dir: Some(PathBuf::from("downloads")),
+            ..Rules::default()
+        };
+        let patterns = rules.patterns();
+        assert_eq!(patterns.len(), 2);
+        assert_eq!(patterns[0]["resourceType"], "Document");
+        assert!(patterns.iter().all(|p| p["requestStage"] == "Response"));
+    }
+
+    #[test]
+    fn test_proxy_credentials_intercept_every_request() {
+        let rules = Rules {
+            proxy_credentials: Some(ProxyCredentials {
+                username: "user".to_string(),
+                password: "pass".to_string(),
+            }),
+            ..Rules::default()
+        };
+        assert_eq!(
+            rules.patterns(),
+            [json!({ "urlPattern": "*", "requestStage": "Request" })]
+        );
+    }
+
+    #[test]
+    fn test_challenge_response() {
+        let credentials = ProxyCredentials {
+            username: "user".to_string(),
+            password: "pass".to_string(),
+        };
+        let proxy = json!({ "requestId": "1", "authChallenge": { "source": "Proxy" } });
+        let server = json!({ "requestId": "1", "authChallenge": { "source": "Server" } });
+
+        let answer = challenge_response(&proxy, Some(&credentials), true);
+        assert_eq!(answer["response"], "ProvideCredentials");
+        assert_eq!(answer["username"], "user");
+        assert_eq!(answer["password"], "pass");
+        assert_eq!(
+            challenge_response(&proxy, Some(&credentials), false)["response"],
+            "CancelAuth"
+        );
+        assert_eq!(
+            challenge_response(&server, Some(&credentials), true)["response"],
+            "Default"
+        );
+        assert_eq!(
+            challenge_response(&proxy, None, true)["response"],
+            "Default"
+        );
+    }
+
+    #[test]
+    fn test_response_stage_detection() {
+        assert!(!is_response_stage(&paused("https://a.test/", "Document")));
+        let mut params = paused("https://a.test/", "Document");
+        params["responseStatusCode"] = json!(200);
+        assert!(is_response_stage(&params));
+    }
+
+    #[test]
+    fn test_header_rules_intercept_every_request() {
+        let patterns = api_auth().patterns();
+        assert_eq!(patterns.len(), 1);
+        assert!(patterns[0].get("resourceType").is_none());
+    }
+
+    #[test]
+    fn test_header_rules_rewrite_matching_requests() {
+        let mut params = paused("https://api.example.com/v1/items", "XHR");
+        params["request"]["headers"] = json!({
+            "authorization": "old",
+            "X-Debug": "1",
+            "Accept": "*/*"
+        });
+        let Decision::ContinueWithHeaders(headers) = api_auth().decide(&params) else {
+            panic!("headers should be rewritten");
+        };
+        assert_eq!(
+            headers,
+            [
+                ("Accept".to_string(), "*/*".to_string()),
+                ("Authorization".to_string(), "Bearer token".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_replace_only_touches_existing_headers() {
+        let mut params = paused("https://cdn.example.com/app.js", "Script");
+        params["request"]["headers"] = json!({ "User-Agent": "Chrome" });
+        assert_eq!(
+            api_auth().decide(&params),
+            Decision::ContinueWithHeaders(vec![("User-Agent".to_string(), "Bot/1.0".to_string())])
+        );
+
+        let rules = Rules {
+            header_rules: vec![HeaderRule::add(
+                RequestMatcher::new().url_glob("https://api.example.com/*"),
+                "Authorization",
+                "Bearer token",
+            )],
+            ..Default::default()
+        };
+        assert_eq!(
+            rules.decide(&paused("https://cdn.example.com/app.js", "Script")),
+            Decision::Continue
+        );
+    }
+
+    #[tokio::test]
+    async fn test_navigation_hook_rewrites_and_blocks() {
+        let (resolved_tx, mut resolved) = tokio::sync::mpsc::unbounded_channel();
+        let url = mock_server(|mut ws| async move {
+            let enable = server_recv(&mut ws).await;
+            server_send(&mut ws, json!({"id": enable["id"], "result": {}})).await;
+            let _ = resolved_tx.send(enable);
+
+            for (id, url) in [
+                ("1", "https://a.example/old"),
+                ("2", "https://a.example/logout"),
+            ] {
+                let mut params = paused(url, "Document");
+                params["requestId"] = json!(id);
+                params["request"]["headers"] = json!({"Accept": "text/html"});
+                server_send(
+                    &mut ws,
+                    json!({"method": "Fetch.requestPaused", "params": params}),
+                )
+                .await;
+                let command = server_recv(&mut ws).await;
+                server_send(&mut ws, json!({"id": command["id"], "result": {}})).await;
+                let _ = resolved_tx.send(command);
+            }
+        })
+        .await;
+        let interceptor = Interceptor::new(CdpConnection::connect(&url).await.unwrap());
+        let hook = navigation_hook(|request: NavigationRequest| async move {
+            if request.url.ends_with("/logout") {
+                return NavigationAction::Block;
+            }
+            NavigationAction::Rewrite(
+                request
+                    .with_url("https://a.example/new")
+                    .with_header("Authorization", "Bearer t"),
+            )
+        });
+        interceptor.set_navigation_hook(Some(hook)).await.unwrap();
+
+        let enable = resolved.recv().await.unwrap();
+        assert_eq!(enable["params"]["patterns"][0]["resourceType"], "Document");
+        let rewritten = resolved.recv().await.unwrap();
+        assert_eq!(rewritten["method"], "Fetch.continueRequest");
+        assert_eq!(rewritten["params"]["url"], "https://a.example/new");
+        assert_eq!(
+            rewritten["params"]["headers"],
+            json!([
+                {"name": "Accept", "value": "text/html"},
+                {"name": "Authorization", "value": "Bearer t"}
+            ])
+        );
+        let blocked = resolved.recv().await.unwrap();
+        assert_eq!(blocked["method"], "Fetch.failRequest");
+        assert_eq!(blocked["params"]["errorReason"], "BlockedByClient");
+    }
+
+    #[tokio::test]
+    async fn test_dry_run_still_continues_paused_requests() {
+        let (resolved_tx, mut resolved) = tokio::sync::mpsc::unbounded_channel();
+        let url = mock_server(|mut ws| async move {
+            let enable = server_recv(&mut ws).await;
+            server_send(&mut ws, json!({"id": enable["id"], "result": {}})).await;
+            server_send(
+                &mut ws,
+                json!({
+                    "method": "Fetch.requestPaused",
+                    "params": paused("https://example.com/", "Document")
+                }),
+            )
+            .await;
+            let command = server_recv(&mut ws).await;
+            server_send(&mut ws, json!({"id": command["id"], "result": {}})).await;
+            let _ = resolved_tx.send(command);
+        })
+        .await;
+        let connection = CdpConnection::connect(&url).await.unwrap();
+        connection.set_dry_run(Some(crate::DryRun::with_reporter(|_| {})));
+        let interceptor = Interceptor::new(connection);
+        interceptor
+            .set_navigation_policy(same_domain().navigation_policy)
+            .await
+            .unwrap();
+
+        let continued = tokio::time::timeout(std::time::Duration::from_secs(5), resolved.recv())
+            .await
+            .expect("paused request was never resolved")
+            .unwrap();
+        assert_eq!(continued["method"], "Fetch.continueRequest");
+        assert_eq!(continued["params"]["requestId"], "interception-1");
+    }
+}
@@ -2,15 +2,112 @@
 //!
 //! A Rust library for interacting with Chrome via DevTools Protocol.
 
+mod android;
+mod archive;
+#[cfg(feature = "bidi")]
+mod bidi;
 mod browser;
+mod cache;
+mod clock;
 mod connection;
+mod container;
+mod context;
+mod devtools_http;
+mod discovery;
+mod domain;
+mod driver;
+mod dry_run;
+mod emulation;
+mod environment;
 mod error;
+mod failure;
+#[cfg(feature = "fetcher")]
+mod fetcher;
+mod frame;
+mod interception;
+mod limiter;
+mod logging;
+mod middleware;
+mod network;
 mod page;
+mod pool;
+mod process;
+mod protocol;
+mod proxy;
+mod region;
+mod registry;
+#[cfg(feature = "repl")]
+mod repl;
+mod route;
+mod scenario;
+mod screenshot;
+mod snapshot;
+mod storage;
+mod timeline;
+mod timeouts;
+mod timing;
+mod trace;
+mod transfer;
+mod visual;
+mod watchdog;
 
-pub use browser::{BrowserManager, CdpBrowser};
-pub use connection::CdpConnection;
-pub use error::{Error, Result};
-pub use page::CdpPage;
+pub use android::{android_devices, AndroidDevice, AndroidOptions, CHROME_DEVTOOLS_SOCKET};
+#[cfg(feature = "bidi")]
+pub use bidi::{BidiBrowser, BidiLaunchOptions, BidiPage};
+pub use browser::{
+    BrowserExit, BrowserManager, BrowserManagerBuilder, CdpBrowser, CiFlags, HeadlessMode,
+    LaunchOptions, TargetInfo, Viewport,
+};
+pub use cache::ResponseCache;
+pub use clock::VirtualTimePolicy;
+pub use connection::{
+    BrowserConnection, CdpConnection, CdpEvent, ConnectOptions, ConnectionMetrics, ConnectionState,
+    EventStream, PendingCommand,
+};
+pub use container::{running_in_container, shared_memory_size, ResourceLimits, ResourceTuning};
+pub use context::{BrowserContext, ContextEventStream, ContextOptions, DownloadEvents};
+pub use devtools_http::{DevToolsHttpClient, HttpTarget};
+pub use discovery::{discover_executables, BrowserExecutable, BrowserFlavor, Channel};
+pub use domain::Domain;
+pub use driver::PageDriver;
+pub use dry_run::{DryRun, DryRunCommand};
+pub use environment::EnvironmentReport;
+pub use error::{CdpError, CdpErrorCode, Error, LaunchFailure, Result};
+pub use failure::{FailureArtifacts, FailureCapture};
+#[cfg(feature = "fetcher")]
+pub use fetcher::{BrowserFetcher, CHROME_FOR_TESTING_VERSION};
+pub use frame::Frame;
+pub use interception::{
+    HeaderAction, HeaderRule, NavigationAction, NavigationHook, NavigationPolicy, NavigationRequest,
+};
+pub use limiter::{HostLimiter, HostPermit};
+pub use logging::{ChromeLogging, ChromeOutput, OutputStream};
+pub use middleware::{Command, Middleware, Next, Retry, SlowCommand, SlowCommandLog};
+pub use network::{RequestInfo, RequestMatcher, UrlPattern};
+pub use page::{CdpPage, NavigationEntry, NavigationHistory, PageOptions};
+pub use pool::{PagePool, PagePoolOptions, PooledPage};
+pub use protocol::{ProtocolCompat, VersionInfo, PROTOCOL_VERSION};
+pub use proxy::{ProxyConfig, ProxyCredentials};
+pub use region::RegionProfile;
+pub use registry::{
+    BrowserEndpoint, BrowserRegistry, BrowserRegistryBuilder, EndpointStatus, RegistryPage,
+};
+#[cfg(feature = "repl")]
+pub use repl::Repl;
+pub use route::{RouteChange, RouteChangeKind};
+pub use scenario::Scenario;
+pub use screenshot::TiledScreenshotOptions;
+pub use snapshot::{Action, InteractiveElement, InteractiveSnapshot, Rect};
+#[cfg(feature = "encryption")]
+pub use storage::StorageKey;
+pub use storage::{OriginStorage, StorageState};
+pub use timeline::{EventTimeline, TimelineCategory, TimelineEvent};
+pub use timeouts::Timeouts;
+pub use timing::{NetworkTimingRecorder, Percentiles, RequestTiming, TimingSummary};
+pub use trace::{RunTrace, TraceCommand, TraceStep};
+pub use transfer::{Download, DownloadEvent};
+pub use visual::{ScreenshotComparison, ScreenshotDiff};
+pub use watchdog::{PageHealth, PageWatchdog, WatchdogOptions};
 
 /// Returns the library version
 pub fn version() -> &'static str {
@@ -0,0 +1,147 @@
+//! Per-host limits on simultaneous navigations
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// Caps simultaneous navigations to the same host across pages
+///
+/// Share one limiter between pages with [`CdpPage::set_host_limiter`](crate::CdpPage::set_host_limiter),
+/// or let [`BrowserManagerBuilder::host_limit`](crate::BrowserManagerBuilder::host_limit)
+/// do it for pages it opens. Hosts are told apart by scheme, name and port;
+/// URLs without a host, such as `data:` URLs, are never limited.
+#[derive(Debug)]
+pub struct HostLimiter {
+    per_host: usize,
+    hosts: Mutex<HashMap<String, Arc<Semaphore>>>,
+}
+
+impl HostLimiter {
+    /// Allow at most `per_host` simultaneous navigations to each host
+    pub fn new(per_host: usize) -> Self {
+        Self {
+            per_host: per_host.max(1),
+            hosts: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Maximum simultaneous navigations per host
+    pub fn per_host(&self) -> usize {
+        self.per_host
+    }
+
+    /// Number of navigations to the URL's host currently in progress
+    pub fn in_flight(&self, url: &str) -> usize {
+        let Some(host) = host_key(url) else {
+            return 0;
+        };
+        self.hosts
+            .lock()
+            .ok()
+            .and_then(|hosts| {
+                hosts
+                    .get(&host)
+                    .map(|s| self.per_host - s.available_permits())
+            })
+            .unwrap_or(0)
+    }
+
+    /// Wait until a navigation to the URL's host may start
+    ///
+    /// The slot is released when the returned permit is dropped.
+    pub async fn acquire(self: &Arc<Self>, url: &str) -> HostPermit {
+        let Some(host) = host_key(url) else {
+            return HostPermit(None);
+        };
+        let semaphore = match self.hosts.lock() {
+            Ok(mut hosts) => hosts
+                .entry(host.clone())
+                .or_insert_with(|| Arc::new(Semaphore::new(self.per_host)))
+                .clone(),
+            Err(_) => return HostPermit(None),
+        };
+        match semaphore.acquire_owned().await {
+            Ok(permit) => HostPermit(Some((self.clone(), host, permit))),
+            // Semaphores are never closed
+            Err(_) => HostPermit(None),
+        }
+    }
+}
+
+/// Slot for one navigation, from [`HostLimiter::acquire`]
+pub struct HostPermit(Option<(Arc<HostLimiter>, String, OwnedSemaphorePermit)>);
+
+impl Drop for HostPermit {
+    fn drop(&mut self) {
+        let Some((limiter, host, permit)) = self.0.take() else {
+            return;
+        };
+        drop(permit);
+        let Ok(mut hosts) = limiter.hosts.lock() else {
+            return;
+        };
+        // Forget idle hosts so crawls over many hosts do not accumulate entries
+        let idle = hosts.get(&host).is_some_and(|semaphore| {
+            Arc::strong_count(semaphore) == 1 && semaphore.available_permits() == limiter.per_host
+        });
+        if idle {
+            hosts.remove(&host);
+        }
+    }
+}
+
+/// `scheme://host:port` of a URL, or `None` if it has no host
+fn host_key(url: &str) -> Option<String> {
+    let url = reqwest::Url::parse(url).ok()?;
+    let host = url.host_str()?;
+    Some(match url.port_or_known_default() {
+        Some(port) => format!("{}://{}:{}", url.scheme(), host, port),
+        None => format!("{}://{}", url.scheme(), host),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_host_key() {
+        assert_eq!(
+            host_key("https://Example.com/a?b").as_deref(),
+            Some("https://example.com:443")
+        );
+        assert_eq!(
+            host_key("http://example.com:8080/").as_deref(),
+            Some("http://example.com:8080")
+        );
+        assert_eq!(host_key("data:text/html,hi"), None);
+        assert_eq!(host_key("not a url"), None);
+    }
+
+    #[tokio::test]
+    async fn test_acquire_limits_per_host() {
+        let limiter = Arc::new(HostLimiter::new(1));
+        let first = limiter.acquire("https://a.example/1").await;
+        // Other hosts and host-less URLs are not held up
+        let _other = limiter.acquire("https://b.example/").await;
+        let _data = limiter.acquire("data:text/html,x").await;
+        assert_eq!(limiter.in_flight("https://a.example/"), 1);
+
+        let waiting = tokio::spawn({
+            let limiter = limiter.clone();
+            async move { limiter.acquire("https://a.example/2").await }
+        });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(!waiting.is_finished());
+
+        drop(first);
+        let second = tokio::time::timeout(Duration::from_secs(1), waiting)
+            .await
+            .unwrap()
+            .unwrap();
+        drop(second);
+        assert_eq!(limiter.in_flight("https://a.example/"), 0);
+        assert_eq!(limiter.hosts.lock().unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,174 @@
+//! Chrome's own log output, kept in a managed directory
+
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::mpsc;
+
+/// Prefix of log files created in [`ChromeLogging::dir`]
+const LOG_PREFIX: &str = "chrome-";
+
+/// Where and how verbosely Chrome writes its own log
+///
+/// Chrome logs to stderr, which is captured into one file per launch in
+/// `dir`. The oldest files are removed so at most `max_files` remain, and the
+/// end of the current file is included in launch and crash errors.
+///
+/// ```
+/// use chrome_cdp::{ChromeLogging, LaunchOptions};
+///
+/// let options = LaunchOptions {
+///     logging: Some(ChromeLogging { verbosity: Some(1), ..ChromeLogging::new("logs/chrome") }),
+///     ..Default::default()
+/// };
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChromeLogging {
+    /// Directory receiving the log files, created if needed
+    pub dir: PathBuf,
+    /// Minimum severity passed as `--log-level`: 0 info, 1 warning, 2 error, 3 fatal
+    pub level: u8,
+    /// Verbose logging level passed as `--v`, if any
+    pub verbosity: Option<u32>,
+    /// Number of log files kept in `dir`, including the current one (default: 10)
+    pub max_files: usize,
+}
+
+impl ChromeLogging {
+    /// Log at info level into `dir`, keeping the last 10 files
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            level: 0,
+            verbosity: None,
+            max_files: 10,
+        }
+    }
+
+    /// Command-line arguments enabling the log
+    pub(crate) fn args(&self) -> Vec<String> {
+        let mut args = vec![
+            "--enable-logging=stderr".to_string(),
+            format!("--log-level={}", self.level),
+        ];
+        if let Some(verbosity) = self.verbosity {
+            args.push(format!("--v={}", verbosity));
+        }
+        args
+    }
+
+    /// Make room for a new log file and return its path
+    pub(crate) fn prepare(&self) -> std::io::Result<PathBuf> {
+        std::fs::create_dir_all(&self.dir)?;
+        rotate(&self.dir, self.max_files.saturating_sub(1))?;
+        let millis = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_millis());
+        let suffix = &uuid::Uuid::new_v4().simple().to_string()[..8];
+        // Zero-padded timestamps keep file names in chronological order
+        Ok(self
+            .dir
+            .join(format!("{}{:015}-{}.log", LOG_PREFIX, millis, suffix)))
+    }
+}
+
+/// Stream of the Chrome process a line was printed on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputStream {
+    /// Standard output
+    Stdout,
+    /// Standard error, where Chrome logs GPU, crashpad and other diagnostics
+    Stderr,
+}
+
+/// One line Chrome printed, tagged with the browser it came from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChromeOutput {
+    /// Process id of the browser, as returned by [`CdpBrowser::pid`](crate::CdpBrowser::pid)
+    pub pid: u32,
+    /// Stream the line was printed on
+    pub stream: OutputStream,
+    /// The line, without its line break
+    pub line: String,
+}
+
+/// Sender tagging Chrome's output lines with the browser's process id
+#[derive(Clone)]
+pub(crate) struct OutputTap {
+    pub(crate) pid: u32,
+    pub(crate) sender: mpsc::UnboundedSender<ChromeOutput>,
+}
+
+impl OutputTap {
+    /// Pass a line on; a dropped receiver only means nobody listens anymore
+    pub(crate) fn send(&self, stream: OutputStream, line: &str) {
+        let _ = self.sender.send(ChromeOutput {
+            pid: self.pid,
+            stream,
+            line: line.to_string(),
+        });
+    }
+}
+
+/// Remove the oldest log files in `dir` until at most `keep` remain
+fn rotate(dir: &Path, keep: usize) -> std::io::Result<()> {
+    let mut logs: Vec<PathBuf> = std::fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with(LOG_PREFIX) && name.ends_with(".log"))
+        })
+        .collect();
+    logs.sort();
+    let excess = logs.len().saturating_sub(keep);
+    for path in &logs[..excess] {
+        std::fs::remove_file(path)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_logging(max_files: usize) -> ChromeLogging {
+        let dir = std::env::temp_dir().join(format!("chrome-cdp-logs-{}", uuid::Uuid::new_v4()));
+        ChromeLogging {
+            max_files,
+            ..ChromeLogging::new(dir)
+        }
+    }
+
+    #[test]
+    fn test_args() {
+        let logging = ChromeLogging {
+            level: 1,
+            verbosity: Some(2),
+            ..ChromeLogging::new("logs")
+        };
+        assert_eq!(
+            logging.args(),
+            ["--enable-logging=stderr", "--log-level=1", "--v=2"]
+        );
+        assert_eq!(ChromeLogging::new("logs").args().len(), 2);
+    }
+
+    #[test]
+    fn test_prepare_rotates_old_logs() {
+        let logging = temp_logging(2);
+        let other = logging.dir.join("notes.txt");
+        let mut paths = Vec::new();
+        for _ in 0..3 {
+            let path = logging.prepare().unwrap();
+            std::fs::write(&path, "log").unwrap();
+            std::fs::write(&other, "keep").unwrap();
+            paths.push(path);
+            std::thread::sleep(std::time::Duration::from_millis(2));
+        }
+
+        assert!(!paths[0].exists());
+        assert!(paths[1].exists() && paths[2].exists());
+        assert!(other.exists());
+        std::fs::remove_dir_all(&logging.dir).unwrap();
+    }
+}
@@ -0,0 +1,386 @@
+//! Per-command middleware for CDP connections
+
+use crate::{CdpConnection, CdpErrorCode, Error, Result};
+use futures::future::BoxFuture;
+use serde_json::Value;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Longest parameter summary reported for a slow command
+const PARAMS_SUMMARY_LEN: usize = 200;
+
+/// Outgoing CDP command as seen by middleware
+#[derive(Debug, Clone, PartialEq)]
+pub struct Command {
+    /// Method name, e.g. `Page.navigate`
+    pub method: String,
+    /// Command parameters
+    pub params: Value,
+    /// Flattened target session to send the command to
+    pub session_id: Option<String>,
+}
+
+impl Command {
+    /// Command without a session
+    pub fn new(method: &str, params: Value) -> Self {
+        Self {
+            method: method.to_string(),
+            params,
+            session_id: None,
+        }
+    }
+}
+
+/// Middleware observing or modifying commands and their responses
+///
+/// Middleware run in the order they were added; each one decides whether and
+/// how often to call the rest of the chain through `next`.
+///
+/// ```
+/// use chrome_cdp::{Command, Middleware, Next, Result};
+/// use futures::future::BoxFuture;
+/// use serde_json::Value;
+///
+/// struct Log;
+///
+/// impl Middleware for Log {
+///     fn handle<'a>(&'a self, command: Command, next: Next<'a>) -> BoxFuture<'a, Result<Value>> {
+///         Box::pin(async move {
+///             let method = command.method.clone();
+///             let result = next.run(command).await;
+///             println!("{} -> {}", method, if result.is_ok() { "ok" } else { "error" });
+///             result
+///         })
+///     }
+/// }
+/// ```
+pub trait Middleware: Send + Sync {
+    /// Handle a command, usually by calling `next.run` with it
+    fn handle<'a>(&'a self, command: Command, next: Next<'a>) -> BoxFuture<'a, Result<Value>>;
+}
+
+/// Remainder of the middleware chain
+#[derive(Clone, Copy)]
+pub struct Next<'a> {
+    chain: &'a [Arc<dyn Middleware>],
+    connection: &'a CdpConnection,
+}
+
+impl<'a> Next<'a> {
+    pub(crate) fn new(chain: &'a [Arc<dyn Middleware>], connection: &'a CdpConnection) -> Self {
+        Self { chain, connection }
+    }
+
+    /// Pass the command to the next middleware, or send it if none is left
+    pub fn run(self, command: Command) -> BoxFuture<'a, Result<Value>> {
+        match self.chain.split_first() {
+            Some((first, rest)) => first.handle(
+                command,
+                Next {
+                    chain: rest,
+                    connection: self.connection,
+                },
+            ),
+            None => Box::pin(self.connection.send_unchained(command)),
+        }
+    }
+
+    /// Connection the chain belongs to
+    pub fn connection(&self) -> &'a CdpConnection {
+        self.connection
+    }
+}
+
+/// Retries commands that fail with transient errors
+///
+/// By default a command is retried when the browser answers with a generic
+/// server error (-32000) that does not indicate a closed target or a missing
+/// node, e.g. "Execution context was destroyed" during a navigation.
+pub struct Retry {
+    attempts: usize,
+    delay: Duration,
+    retryable: Box<dyn Fn(&Error) -> bool + Send + Sync>,
+}
+
+impl Retry {
+    /// Retry up to `attempts` times, waiting `delay` between tries
+    pub fn new(attempts: usize, delay: Duration) -> Self {
+        Self {
+            attempts,
+            delay,
+            retryable: Box::new(is_transient),
+        }
+    }
+
+    /// Use a custom predicate to decide which errors are retried
+    pub fn when<F>(mut self, retryable: F) -> Self
+    where
+        F: Fn(&Error) -> bool + Send + Sync + 'static,
+    {
+        self.retryable = Box::new(retryable);
+        self
+    }
+}
+
+impl Middleware for Retry {
+    fn handle<'a>(&'a self, command: Command, next: Next<'a>) -> BoxFuture<'a, Result<Value>> {
+        Box::pin(async move {
+            let mut attempt = 0;
+            loop {
+                match next.run(command.clone()).await {
+                    Err(e) if attempt < self.attempts && (self.retryable)(&e) => {
+                        attempt += 1;
+                        tokio::time::sleep(self.delay).await;
+                    }
+                    result => return result,
+                }
+            }
+        })
+    }
+}
+
+/// Whether an error is a generic server error worth retrying
+fn is_transient(error: &Error) -> bool {
+    match error {
+        Error::Protocol(e) => {
+            e.code == CdpErrorCode::ServerError && !e.is_target_closed() && !e.is_node_not_found()
+        }
+        _ => false,
+    }
+}
+
+/// Command that took longer than the [`SlowCommandLog`] threshold
+#[derive(Debug, Clone, PartialEq)]
+pub struct SlowCommand {
+    /// Method name, e.g. `DOM.getDocument`
+    pub method: String,
+    /// Parameters as JSON, truncated to keep logs readable
+    pub params: String,
+    /// Time the command had been waiting for a response when reported
+    pub elapsed: Duration,
+    /// Commands already waiting for a response when this one was sent
+    pub queue_depth: usize,
+}
+
+/// Reports commands whose response takes longer than a threshold
+///
+/// A command is reported as soon as the threshold passes, while it is still
+/// waiting, so a command that never gets a response shows up as well. The
+/// queue depth in each report tells a slow command from one stuck
+/// behind many others, e.g. a burst of `Runtime.callFunctionOn` calls.
+///
+/// ```
+/// use chrome_cdp::SlowCommandLog;
+/// use std::time::Duration;
+///
+/// let log = SlowCommandLog::with_reporter(Duration::from_secs(1), |slow| {
+///     eprintln!("{} took {:?} ({} queued)", slow.method, slow.elapsed, slow.queue_depth);
+/// });
+/// ```
+pub struct SlowCommandLog {
+    threshold: Duration,
+    report: Box<dyn Fn(&SlowCommand) + Send + Sync>,
+}
+
+impl SlowCommandLog {
+    /// Log commands slower than `threshold` as `tracing` warnings
+    #[cfg(feature = "tracing")]
+    pub fn new(threshold: Duration) -> Self {
+        Self::with_reporter(threshold, |slow| {
+            tracing::warn!(
+                method = %slow.method,
+                params = %slow.params,
+                elapsed_ms = slow.elapsed.as_millis() as u64,
+                queue_depth = slow.queue_depth,
+                "slow CDP command"
+            );
+        })
+    }
+
+    /// Pass commands slower than `threshold` to `report`
+    pub fn with_reporter<F>(threshold: Duration, report: F) -> Self
+    where
+        F: Fn(&SlowCommand) + Send + Sync + 'static,
+    {
+        Self {
+            threshold,
+            report: Box::new(report),
+        }
+    }
+}
+
+impl Middleware for SlowCommandLog {
+    fn handle<'a>(&'a self, command: Command, next: Next<'a>) -> BoxFuture<'a, Result<Value>> {
+        Box::pin(async move {
+            let queue_depth = next.connection().pending_count();
+            let method = command.method.clone();
+            let params = command.params.clone();
+            let start = Instant::now();
+            let mut response = next.run(command);
+            tokio::select! {
+                result = &mut response => return result,
+                _ = tokio::time::sleep(self.threshold) => {}
+            }
+            (self.report)(&SlowCommand {
+                method,
+                params: summarize(&params),
+                elapsed: start.elapsed(),
+                queue_depth,
+            });
+            response.await
+        })
+    }
+}
+
+/// JSON of `params`, cut at [`PARAMS_SUMMARY_LEN`] characters
+fn summarize(params: &Value) -> String {
+    let json = params.to_string();
+    match json.char_indices().nth(PARAMS_SUMMARY_LEN) {
+        Some((end, _)) => format!("{}...", &json[..end]),
+        None => json,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connection::tests::{mock_server, server_recv, server_send};
+    use crate::CdpError;
+    use serde_json::json;
+
+    struct InjectSession(&'static str);
+
+    impl Middleware for InjectSession {
+        fn handle<'a>(
+            &'a self,
+            mut command: Command,
+            next: Next<'a>,
+        ) -> BoxFuture<'a, Result<Value>> {
+            command.session_id = Some(self.0.to_string());
+            next.run(command)
+        }
+    }
+
+    fn server_error(message: &str) -> Error {
+        Error::Protocol(CdpError {
+            code: CdpErrorCode::ServerError,
+            message: message.to_string(),
+            data: None,
+        })
+    }
+
+    #[test]
+    fn test_is_transient() {
+        assert!(is_transient(&server_error(
+            "Execution context was destroyed."
+        )));
+        assert!(!is_transient(&server_error("Target closed")));
+        assert!(!is_transient(&server_error(
+            "Could not find node with given id"
+        )));
+        assert!(!is_transient(&Error::Timeout("x".to_string())));
+    }
+
+    #[tokio::test]
+    async fn test_chain_injects_session_and_retries() {
+        let url = mock_server(|mut ws| async move {
+            let first = server_recv(&mut ws).await;
+            assert_eq!(first["sessionId"], "S1");
+            server_send(
+                &mut ws,
+                json!({
+                    "id": first["id"],
+                    "error": {"code": -32000, "message": "Execution context was destroyed."}
+                }),
+            )
+            .await;
+            let second = server_recv(&mut ws).await;
+            assert_eq!(second["sessionId"], "S1");
+            assert_eq!(second["method"], "Runtime.evaluate");
+            server_send(&mut ws, json!({"id": second["id"], "result": {"ok": true}})).await;
+            server_recv(&mut ws).await;
+        })
+        .await;
+
+        let conn = CdpConnection::connect(&url).await.unwrap();
+        conn.add_middleware(Retry::new(2, Duration::from_millis(1)));
+        conn.add_middleware(InjectSession("S1"));
+
+        let result = conn
+            .send_command("Runtime.evaluate", json!({"expression": "1"}))
+            .await
+            .unwrap();
+        assert_eq!(result["ok"], true);
+    }
+
+    #[test]
+    fn test_summarize_truncates_long_params() {
+        assert_eq!(summarize(&json!({"a": 1})), r#"{"a":1}"#);
+        let long = summarize(&json!({ "expression": "é".repeat(500) }));
+        assert!(long.ends_with("..."));
+        assert_eq!(long.chars().count(), PARAMS_SUMMARY_LEN + 3);
+    }
+
+    #[tokio::test]
+    async fn test_slow_command_log_reports_slow_commands() {
+        let url = mock_server(|mut ws| async move {
+            let fast = server_recv(&mut ws).await;
+            server_send(&mut ws, json!({"id": fast["id"], "result": {}})).await;
+            let slow = server_recv(&mut ws).await;
+            tokio::time::sleep(Duration::from_millis(60)).await;
+            server_send(&mut ws, json!({"id": slow["id"], "result": {}})).await;
+        })
+        .await;
+        let reports = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let conn = CdpConnection::connect(&url).await.unwrap();
+        conn.add_middleware(SlowCommandLog::with_reporter(Duration::from_millis(50), {
+            let reports = reports.clone();
+            move |slow| reports.lock().unwrap().push(slow.clone())
+        }));
+
+        conn.send_command("Page.enable", json!({})).await.unwrap();
+        conn.send_command("DOM.getDocument", json!({"depth": -1}))
+            .await
+            .unwrap();
+
+        let reports = reports.lock().unwrap();
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].method, "DOM.getDocument");
+        assert_eq!(reports[0].params, r#"{"depth":-1}"#);
+        assert_eq!(reports[0].queue_depth, 0);
+        assert!(reports[0].elapsed >= Duration::from_millis(50));
+    }
+
+    #[tokio::test]
+    async fn test_slow_command_log_reports_before_response() {
+        let url = mock_server(|mut ws| async move {
+            // Never answered
+            server_recv(&mut ws).await;
+            std::future::pending::<()>().await;
+        })
+        .await;
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let conn = CdpConnection::connect(&url).await.unwrap();
+        conn.add_middleware(SlowCommandLog::with_reporter(
+            Duration::from_millis(50),
+            move |slow| {
+                let _ = tx.send(slow.clone());
+            },
+        ));
+
+        let command = conn.send_command("Runtime.evaluate", json!({"expression": "1"}));
+        let report = tokio::select! {
+            _ = command => panic!("the command was never answered"),
+            report = rx.recv() => report.unwrap(),
+        };
+        assert_eq!(report.method, "Runtime.evaluate");
+        assert!(report.elapsed >= Duration::from_millis(50));
+    }
+
+    #[test]
+    fn test_command_new() {
+        let command = Command::new("Page.enable", Value::Null);
+        assert_eq!(command.method, "Page.enable");
+        assert!(command.session_id.is_none());
+    }
+}
@@ -0,0 +1,328 @@
+//! Network request matching
+
+use crate::{Error, Result};
+use regex::Regex;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+/// Attributes of a network request, as seen by a [`RequestMatcher`]
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RequestInfo {
+    /// Request id assigned by the browser
+    pub request_id: String,
+    /// Request URL
+    pub url: String,
+    /// HTTP method
+    pub method: String,
+    /// Resource type, e.g. `Document`, `XHR`, `Image`
+    pub resource_type: Option<String>,
+    /// Request headers, keyed by lowercase name
+    pub headers: HashMap<String, String>,
+}
+
+impl RequestInfo {
+    /// Build from `Network.requestWillBeSent` or `Fetch.requestPaused` params
+    pub fn from_event(params: &Value) -> Self {
+        let request = &params["request"];
+        let headers = request["headers"]
+            .as_object()
+            .map(|headers| {
+                headers
+                    .iter()
+                    .map(|(name, value)| {
+                        (
+                            name.to_ascii_lowercase(),
+                            value.as_str().unwrap_or_default().to_string(),
+                        )
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Self {
+            request_id: params["requestId"].as_str().unwrap_or_default().to_string(),
+            url: request["url"].as_str().unwrap_or_default().to_string(),
+            method: request["method"].as_str().unwrap_or_default().to_string(),
+            resource_type: params["type"]
+                .as_str()
+                .or_else(|| params["resourceType"].as_str())
+                .map(String::from),
+            headers,
+        }
+    }
+}
+
+/// Pattern for matching request URLs
+#[derive(Debug, Clone)]
+pub enum UrlPattern {
+    /// Glob where `*` matches any sequence and `?` matches one character
+    Glob(String),
+    /// Regular expression, matched anywhere in the URL
+    Regex(Regex),
+}
+
+impl UrlPattern {
+    /// Whether the URL matches the pattern
+    pub fn matches(&self, url: &str) -> bool {
+        match self {
+            UrlPattern::Glob(glob) => glob_match(glob, url),
+            UrlPattern::Regex(regex) => regex.is_match(url),
+        }
+    }
+}
+
+type HeaderPredicate = Arc<dyn Fn(&str) -> bool + Send + Sync>;
+
+/// Reusable request matcher
+///
+/// Every configured criterion must match; an empty matcher matches every
+/// request. The same matcher type is used wherever the crate filters
+/// requests, so matching semantics stay consistent.
+#[derive(Clone, Default)]
+pub struct RequestMatcher {
+    url: Option<UrlPattern>,
+    methods: Vec<String>,
+    resource_types: Vec<String>,
+    headers: Vec<(String, HeaderPredicate)>,
+}
+
+impl fmt::Debug for RequestMatcher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RequestMatcher")
+            .field("url", &self.url)
+            .field("methods", &self.methods)
+            .field("resource_types", &self.resource_types)
+            .field(
+                "headers",
+                &self
+                    .headers
+                    .iter()
+                    .map(|(name, _)| name)
+                    .collect::<Vec<_>>(),
+            )
+            .finish()
+    }
+}
+
+impl RequestMatcher {
+    /// Matcher that matches every request
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Match URLs against a glob (`*` any sequence, `?` one character)
+    pub fn url_glob(mut self, glob: &str) -> Self {
+        self.url = Some(UrlPattern::Glob(glob.to_string()));
+        self
+    }
+
+    /// Match URLs against a regular expression
+    pub fn url_regex(mut self, pattern: &str) -> Result<Self> {
+        let regex = Regex::new(pattern)
+            .map_err(|e| Error::Browser(format!("Invalid URL regex '{}': {}", pattern, e)))?;
+        self.url = Some(UrlPattern::Regex(regex));
+        Ok(self)
+    }
+
+    /// Match one of the given HTTP methods (case-insensitive); may be called repeatedly
+    pub fn method(mut self, method: &str) -> Self {
+        self.methods.push(method.to_ascii_uppercase());
+        self
+    }
+
+    /// Match one of the given resource types (case-insensitive); may be called repeatedly
+    pub fn resource_type(mut self, resource_type: &str) -> Self {
+        self.resource_types.push(resource_type.to_string());
+        self
+    }
+
+    /// Require a header whose value satisfies the predicate
+    pub fn header<F>(mut self, name: &str, predicate: F) -> Self
+    where
+        F: Fn(&str) -> bool + Send + Sync + 'static,
+    {
+        self.headers
+            .push((name.to_ascii_lowercase(), Arc::new(predicate)));
+        self
+    }
+
+    /// Require a header with exactly the given value
+    pub fn header_equals(self, name: &str, value: &str) -> Self {
+        let value = value.to_string();
+        self.header(name, move |v| v == value)
+    }
+
+    /// Whether the request satisfies every criterion
+    pub fn matches(&self, request: &RequestInfo) -> bool {
+        if let Some(url) = &self.url {
+            if !url.matches(&request.url) {
+                return false;
+            }
+        }
+
+        if !self.methods.is_empty()
+            && !self
+                .methods
+                .iter()
+                .any(|m| m.eq_ignore_ascii_case(&request.method))
+        {
+            return false;
+        }
+
+        if !self.resource_types.is_empty() {
+            let Some(resource_type) = &request.resource_type else {
+                return false;
+            };
+            if !self
+                .resource_types
+                .iter()
+                .any(|t| t.eq_ignore_ascii_case(resource_type))
+            {
+                return false;
+            }
+        }
+
+        self.headers.iter().all(|(name, predicate)| {
+            request
+                .headers
+                .get(name)
+                .is_some_and(|value| predicate(value))
+        })
+    }
+}
+
+/// Match text against a glob where `*` matches any sequence and `?` one character
+fn glob_match(glob: &str, text: &str) -> bool {
+    let pattern: Vec<char> = glob.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, t));
+            p += 1;
+        } else if let Some((star_p, star_t)) = backtrack {
+            // Let the last `*` absorb one more character
+            p = star_p + 1;
+            t = star_t + 1;
+            backtrack = Some((star_p, star_t + 1));
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn request(url: &str, method: &str, resource_type: Option<&str>) -> RequestInfo {
+        RequestInfo {
+            url: url.to_string(),
+            method: method.to_string(),
+            resource_type: resource_type.map(String::from),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("*", "https://example.com/"));
+        assert!(glob_match(
+            "https://*.example.com/*",
+            "https://api.example.com/v1"
+        ));
+        assert!(glob_match("*.png", "https://cdn.example.com/a.png"));
+        assert!(glob_match("https://example.com/?", "https://example.com/a"));
+        assert!(!glob_match("*.png", "https://cdn.example.com/a.png?x=1"));
+        assert!(!glob_match("https://example.com/", "https://example.com/a"));
+        assert!(glob_match("", ""));
+        assert!(!glob_match("", "a"));
+    }
+
+    #[test]
+    fn test_empty_matcher_matches_everything() {
+        let matcher = RequestMatcher::new();
+        assert!(matcher.matches(&request("https://example.com/", "GET", None)));
+    }
+
+    #[test]
+    fn test_matcher_url_and_method() {
+        let matcher = RequestMatcher::new()
+            .url_glob("https://api.example.com/*")
+            .method("post");
+        assert!(matcher.matches(&request("https://api.example.com/login", "POST", None)));
+        assert!(!matcher.matches(&request("https://api.example.com/login", "GET", None)));
+        assert!(!matcher.matches(&request("https://example.com/login", "POST", None)));
+    }
+
+    #[test]
+    fn test_matcher_url_regex() {
+        let matcher = RequestMatcher::new().url_regex(r"/v\d+/users").unwrap();
+        assert!(matcher.matches(&request("https://x.test/v2/users?id=1", "GET", None)));
+        assert!(!matcher.matches(&request("https://x.test/users", "GET", None)));
+        assert!(RequestMatcher::new().url_regex("(").is_err());
+    }
+
+    #[test]
+    fn test_matcher_resource_type() {
+        let matcher = RequestMatcher::new()
+            .resource_type("Image")
+            .resource_type("Font");
+        assert!(matcher.matches(&request("https://x.test/a.woff", "GET", Some("font"))));
+        assert!(!matcher.matches(&request("https://x.test/", "GET", Some("Document"))));
+        assert!(!matcher.matches(&request("https://x.test/", "GET", None)));
+    }
+
+    #[test]
+    fn test_matcher_headers() {
+        let matcher = RequestMatcher::new()
+            .header_equals("Content-Type", "application/json")
+            .header("authorization", |v| v.starts_with("Bearer "));
+        let mut req = request("https://x.test/", "POST", None);
+        req.headers
+            .insert("content-type".to_string(), "application/json".to_string());
+        assert!(!matcher.matches(&req));
+        req.headers
+            .insert("authorization".to_string(), "Bearer abc".to_string());
+        assert!(matcher.matches(&req));
+    }
+
+    #[test]
+    fn test_request_info_from_request_will_be_sent() {
+        let params = json!({
+            "requestId": "1000.1",
+            "type": "XHR",
+            "request": {
+                "url": "https://x.test/api",
+                "method": "GET",
+                "headers": {"Accept": "application/json"}
+            }
+        });
+        let info = RequestInfo::from_event(&params);
+        assert_eq!(info.request_id, "1000.1");
+        assert_eq!(info.resource_type.as_deref(), Some("XHR"));
+        assert_eq!(info.headers["accept"], "application/json");
+    }
+
+    #[test]
+    fn test_request_info_from_request_paused() {
+        let params = json!({
+            "requestId": "interception-1",
+            "resourceType": "Document",
+            "request": {"url": "https://x.test/", "method": "GET", "headers": {}}
+        });
+        let info = RequestInfo::from_event(&params);
+        assert_eq!(info.resource_type.as_deref(), Some("Document"));
+    }
+}
@@ -1,139 +1,2344 @@
 //! CDP Page automation
 
-use crate::{connection::CdpConnection, Error, Result};
+use crate::archive::{InlineResources, INLINE_FN};
+use crate::browser::ws_endpoint_http;
+use crate::clock::{clock_script, tick_script};
+use crate::domain::Domains;
+use crate::emulation::{Emulation, Override};
+use crate::environment::ENVIRONMENT_EXPR;
+use crate::failure::FailureRecorder;
+use crate::frame::FrameTracker;
+use crate::interception::{navigation_hook, Interceptor};
+use crate::region::USER_AGENT_METADATA_EXPR;
+use crate::route::{PendingRoutes, ROUTE_BINDING, ROUTE_HOOK_SCRIPT};
+use crate::screenshot::{stitch_vertical, MASK_FN};
+use crate::snapshot::{PREPARE_FN, SNAPSHOT_FN};
+use crate::storage::{OriginStorage, LOCAL_STORAGE_FN, SET_LOCAL_STORAGE_FN};
+use crate::transfer::{
+    is_local_endpoint, mime_type, APPEND_UPLOAD_FN, BEGIN_UPLOAD_FN, SET_FILES_FN,
+    UPLOAD_CHUNK_SIZE,
+};
+use crate::visual::{compare_png, scale_rect, ELEMENT_RECTS_FN};
+use crate::{
+    connection::CdpConnection, Action, ConnectionState, DevToolsHttpClient, Domain, Download,
+    EnvironmentReport, Error, EventTimeline, FailureCapture, Frame, HeaderRule, HostLimiter,
+    InteractiveSnapshot, NavigationAction, NavigationPolicy, NavigationRequest,
+    NetworkTimingRecorder, PageWatchdog, ProxyCredentials, Rect, RegionProfile, RequestInfo,
+    RequestMatcher, ResponseCache, Result, RouteChange, ScreenshotComparison, ScreenshotDiff,
+    StorageState, TiledScreenshotOptions, Timeouts, VirtualTimePolicy, WatchdogOptions,
+};
+use base64::Engine;
+use serde::Deserialize;
 use serde_json::{json, Value};
+use std::path::Path;
+use std::sync::Arc;
 use std::time::Duration;
 use tokio::time::sleep;
 
+/// Object group used for remote objects held only for the duration of a call
+const OBJECT_GROUP: &str = "chrome-cdp";
+
+/// Function returning whether `this` document contains an element matching the selector
+pub(crate) const ELEMENT_EXISTS_FN: &str =
+    "function(selector) { return !!this.querySelector(selector); }";
+
+/// Time allowed past an evaluate timeout before execution is terminated explicitly
+const EVALUATE_GRACE: Duration = Duration::from_millis(500);
+
+/// Longest wait for the screenshot of a failed operation
+const FAILURE_SCREENSHOT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Whether a Runtime result reports that execution was terminated
+fn is_terminated(result: &Value) -> bool {
+    let exception = &result["exceptionDetails"];
+    [&exception["exception"]["description"], &exception["text"]]
+        .iter()
+        .filter_map(|text| text.as_str())
+        .any(|text| text.contains("Execution was terminated"))
+}
+
+/// Build `Runtime.callFunctionOn` call arguments from JSON values
+fn call_arguments(args: &[Value]) -> Value {
+    Value::Array(args.iter().map(|arg| json!({ "value": arg })).collect())
+}
+
+/// Whether an error means the target no longer exists
+fn is_target_gone(error: &Error) -> bool {
+    match error {
+        Error::WebSocket(_) | Error::TargetCrashed | Error::TargetDetached(_) => true,
+        Error::Protocol(e) => e.is_target_closed(),
+        _ => false,
+    }
+}
+
+/// Convert `exceptionDetails` of a Runtime result into an error
+pub(crate) fn exception_error(result: &Value) -> Option<Error> {
+    let exception = result.get("exceptionDetails")?;
+    let exception_text = exception["exception"]["description"]
+        .as_str()
+        .or_else(|| exception["text"].as_str())
+        .unwrap_or("unknown error");
+
+    let column_number = exception["columnNumber"].as_i64().unwrap_or(-1);
+    let line_number = exception["lineNumber"].as_i64().unwrap_or(-1);
+
+    Some(Error::Browser(format!(
+        "JavaScript execution error at line {}, column {}: {}",
+        line_number, column_number, exception_text
+    )))
+}
+
+/// Decode the base64 image data of a `Page.captureScreenshot` result
+pub(crate) fn decode_screenshot(result: &Value) -> Result<Vec<u8>> {
+    let data = result["data"]
+        .as_str()
+        .ok_or_else(|| Error::Browser("Screenshot response contained no data".to_string()))?;
+    base64::engine::general_purpose::STANDARD
+        .decode(data)
+        .map_err(|e| Error::Browser(format!("Failed to decode screenshot: {}", e)))
+}
+
+/// Absolute DevTools URL of a target, from the `devtoolsFrontendUrl` Chrome
+/// listed, which is usually relative to the DevTools endpoint
+fn frontend_url(http: &DevToolsHttpClient, target_id: &str, listed: Option<&str>) -> String {
+    let (scheme, host, port) = (http.scheme(), http.host(), http.port());
+    match listed {
+        Some(path) if path.starts_with('/') => format!("{}://{}:{}{}", scheme, host, port, path),
+        Some(url) => url.to_string(),
+        None => {
+            let ws = if scheme == "https" { "wss" } else { "ws" };
+            format!(
+                "{0}://{1}:{2}/devtools/inspector.html?{3}={1}:{2}/devtools/page/{4}",
+                scheme, host, port, ws, target_id
+            )
+        }
+    }
+}
+
+/// Entry in the page's session history
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NavigationEntry {
+    /// Unique id of the entry
+    pub id: i64,
+    /// Position of the entry in the history list
+    #[serde(skip)]
+    pub index: usize,
+    /// URL of the entry
+    pub url: String,
+    /// URL the user typed, before redirects
+    #[serde(default, rename = "userTypedURL")]
+    pub user_typed_url: String,
+    /// Title of the entry
+    #[serde(default)]
+    pub title: String,
+    /// Transition type, e.g. `link`, `typed`, `reload`
+    #[serde(default)]
+    pub transition_type: String,
+}
+
+/// Session history of a page
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NavigationHistory {
+    /// Index of the current entry
+    pub current_index: usize,
+    /// History entries, oldest first
+    pub entries: Vec<NavigationEntry>,
+}
+
+impl NavigationHistory {
+    /// Parse a `Page.getNavigationHistory` result
+    fn from_result(result: Value) -> Result<Self> {
+        let mut history: Self = serde_json::from_value(result)?;
+        for (index, entry) in history.entries.iter_mut().enumerate() {
+            entry.index = index;
+        }
+        Ok(history)
+    }
+
+    /// The entry currently displayed
+    pub fn current(&self) -> Option<&NavigationEntry> {
+        self.entries.get(self.current_index)
+    }
+}
+
+/// Settings for pages opened by [`BrowserManager`](crate::BrowserManager)
+#[derive(Debug, Clone, PartialEq)]
+pub struct PageOptions {
+    /// Domains enabled when the page is created (default: [`Domain::DEFAULTS`])
+    pub domains: Vec<Domain>,
+    /// Page timeouts; unset fields fall back to the browser's
+    pub timeouts: Timeouts,
+}
+
+impl Default for PageOptions {
+    fn default() -> Self {
+        Self {
+            domains: Domain::DEFAULTS.to_vec(),
+            timeouts: Timeouts::default(),
+        }
+    }
+}
+
 /// CDP Page for browser automation
 pub struct CdpPage {
     connection: CdpConnection,
+    /// WebSocket debugger URL the page was attached with
+    ws_url: String,
+    interceptor: Interceptor,
+    /// Identifier of the fake clock init script, if installed
+    clock_script_id: std::sync::Mutex<Option<String>>,
+    timeouts: std::sync::Mutex<Timeouts>,
+    frames: FrameTracker,
+    /// Element ids of the last interactive snapshot, by index
+    snapshot_ids: std::sync::Mutex<Vec<String>>,
+    emulation: Emulation,
+    domains: Domains,
+    /// Whether the target is known to be destroyed
+    closed: tokio::sync::Mutex<bool>,
+    host_limiter: std::sync::Mutex<Option<Arc<HostLimiter>>>,
+    /// Whether the browser shares this machine's filesystem
+    local_files: std::sync::atomic::AtomicBool,
+    /// Whether the history hook reporting route changes is installed
+    route_hook: tokio::sync::OnceCell<()>,
+    failure_capture: std::sync::Mutex<Option<Arc<FailureRecorder>>>,
+    /// Browser-level connection reporting the target's destruction, if known
+    browser: std::sync::Mutex<Option<CdpConnection>>,
 }
 
 impl CdpPage {
     /// Create a new page with the given connection
     pub async fn new(ws_url: &str) -> Result<Self> {
-        let connection = CdpConnection::connect(ws_url).await?;
+        Self::new_with_timeouts(ws_url, Timeouts::default()).await
+    }
+
+    /// Create a new page with the given connection and timeouts
+    ///
+    /// Pass the browser's [`CdpBrowser::timeouts`](crate::CdpBrowser::timeouts)
+    /// to inherit them.
+    pub async fn new_with_timeouts(ws_url: &str, timeouts: Timeouts) -> Result<Self> {
+        Self::new_with_domains(ws_url, timeouts, Domain::DEFAULTS).await
+    }
+
+    /// Create a new page enabling only the given domains up front
+    ///
+    /// Methods that rely on another domain's events enable it on first use.
+    /// Pass an empty slice for pure network automation, so that page scripts
+    /// cannot observe an attached Runtime. Without [`Domain::Page`], the
+    /// frames returned by [`frames`](Self::frames) are not kept up to date.
+    pub async fn new_with_domains(
+        ws_url: &str,
+        timeouts: Timeouts,
+        domains: &[Domain],
+    ) -> Result<Self> {
+        let connection = CdpConnection::connect_with_timeouts(ws_url, timeouts).await?;
+        let frames = FrameTracker::spawn(connection.subscribe());
+
+        let enabled = Domains::new(connection.clone());
+        for &domain in domains {
+            enabled.enable(domain).await?;
+        }
+        // Reports renderer crashes and detaches to the connection
+        connection
+            .send_command("Inspector.enable", json!({}))
+            .await
+            .map_err(|e| e.context("Failed to enable Inspector domain"))?;
+        let tree = connection
+            .send_command("Page.getFrameTree", json!({}))
+            .await
+            .map_err(|e| e.context("Failed to get frame tree"))?;
+        frames.seed(&tree);
+
+        Ok(Self {
+            interceptor: Interceptor::new(connection.clone()),
+            connection,
+            ws_url: ws_url.to_string(),
+            clock_script_id: std::sync::Mutex::new(None),
+            timeouts: std::sync::Mutex::new(timeouts),
+            frames,
+            snapshot_ids: std::sync::Mutex::new(Vec::new()),
+            emulation: Emulation::default(),
+            domains: enabled,
+            closed: tokio::sync::Mutex::new(false),
+            host_limiter: std::sync::Mutex::new(None),
+            local_files: std::sync::atomic::AtomicBool::new(is_local_endpoint(ws_url)),
+            route_hook: tokio::sync::OnceCell::new(),
+            failure_capture: std::sync::Mutex::new(None),
+            browser: std::sync::Mutex::new(None),
+        })
+    }
+
+    /// Timeouts currently applied to this page
+    pub fn timeouts(&self) -> Timeouts {
+        self.timeouts.lock().map(|t| *t).unwrap_or_default()
+    }
+
+    /// Override timeouts for this page; unset fields keep their current value
+    pub fn set_timeouts(&self, timeouts: Timeouts) {
+        if let Ok(mut current) = self.timeouts.lock() {
+            *current = timeouts.or(*current);
+            self.connection
+                .set_command_timeout(Some(current.command_timeout()));
+        }
+    }
+
+    /// Underlying CDP connection of the page
+    pub fn connection(&self) -> &CdpConnection {
+        &self.connection
+    }
+
+    /// URL opening Chrome's DevTools for this page, from `/json/list`
+    ///
+    /// Open it in a regular browser to inspect a page of a headless or remote
+    /// Chrome while automation runs, e.g. after
+    /// [`set_failure_capture`](Self::set_failure_capture) reported a failure.
+    pub async fn devtools_frontend_url(&self) -> Result<String> {
+        let http = ws_endpoint_http(&self.ws_url)?;
+        let target_id = self.target_id();
+        let target = http
+            .list()
+            .await?
+            .into_iter()
+            .find(|target| target.id == target_id)
+            .ok_or_else(|| {
+                Error::Browser(format!("Target {} is not listed by /json/list", target_id))
+            })?;
+        Ok(frontend_url(
+            &http,
+            target_id,
+            target.devtools_frontend_url.as_deref(),
+        ))
+    }
+
+    /// Enable a domain's events; does nothing if it is already enabled
+    pub async fn enable_domain(&self, domain: Domain) -> Result<()> {
+        self.domains.enable(domain).await
+    }
+
+    /// Disable a domain's events; does nothing if it is not enabled
+    pub async fn disable_domain(&self, domain: Domain) -> Result<()> {
+        self.domains.disable(domain).await
+    }
+
+    /// Domains currently enabled on the page, in no particular order
+    pub fn enabled_domains(&self) -> Vec<Domain> {
+        self.domains.enabled()
+    }
+
+    /// All frames of the page, main frame first
+    ///
+    /// Out-of-process iframes are served by separate targets and are not included.
+    pub fn frames(&self) -> Vec<Frame> {
+        self.frames.frames()
+    }
+
+    /// Frame with the given id, e.g. from a `Page.frameAttached` event
+    pub fn frame(&self, frame_id: &str) -> Option<Frame> {
+        self.frames.frame(frame_id)
+    }
+
+    /// Start probing the renderer periodically to detect hangs and crashes
+    ///
+    /// The watchdog runs until the returned handle is dropped.
+    pub fn watchdog(&self, options: WatchdogOptions) -> PageWatchdog {
+        PageWatchdog::spawn(self.connection.clone(), options)
+    }
+
+    /// Navigate to a URL
+    ///
+    /// Returns once the navigation committed. Navigations Chrome reports as
+    /// failed, e.g. with `net::ERR_NAME_NOT_RESOLVED` or a blocked URL, are
+    /// errors. With a host limiter set, the host's slot stays taken until the
+    /// page fires its load event or the navigation timeout passes.
+    ///
+    /// With [failure capture](Self::set_failure_capture) on, errors come as
+    /// [`Error::WithArtifacts`].
+    pub async fn goto(&self, url: &str) -> Result<()> {
+        self.captured("goto", self.goto_inner(url)).await
+    }
+
+    /// [`goto`](Self::goto) without failure capture
+    async fn goto_inner(&self, url: &str) -> Result<()> {
+        let limiter = self.host_limiter.lock().ok().and_then(|l| l.clone());
+        // Waiting for a slot does not count towards the navigation timeout
+        let permit = match &limiter {
+            Some(limiter) => {
+                // The slot is released on the load event, which needs the Page domain
+                self.domains.enable(Domain::Page).await?;
+                Some(limiter.acquire(url).await)
+            }
+            None => None,
+        };
+        // Subscribe before navigating so the load event cannot be missed
+        let events = permit.as_ref().map(|_| self.connection.subscribe());
+        let timeout = self.timeouts().navigation_timeout();
+        let started = std::time::Instant::now();
+        let navigate = self
+            .connection
+            .send_command("Page.navigate", json!({ "url": url }));
+        let result = tokio::time::timeout(timeout, navigate)
+            .await
+            .map_err(|_| {
+                Error::Timeout(format!(
+                    "Navigation to '{}' did not start within {:?}",
+                    url, timeout
+                ))
+            })?
+            .map_err(|e| e.context(format!("Failed to navigate to '{}'", url)))?;
+        if let Some(error_text) = result["errorText"].as_str().filter(|t| !t.is_empty()) {
+            return Err(Error::Browser(format!(
+                "Failed to navigate to '{}': {}",
+                url, error_text
+            )));
+        }
+        // Same-document navigations have no loader and fire no load event
+        if let (Some(permit), Some(mut events)) = (permit, events) {
+            if result["loaderId"].is_string() {
+                let remaining = timeout.saturating_sub(started.elapsed());
+                tokio::spawn(async move {
+                    let loaded = async {
+                        while let Some(event) = events.recv().await {
+                            if event.method == "Page.loadEventFired" {
+                                return;
+                            }
+                        }
+                    };
+                    let _ = tokio::time::timeout(remaining, loaded).await;
+                    drop(permit);
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Save a screenshot, the URL and recent console output whenever a
+    /// high-level operation fails, or stop with `None`
+    ///
+    /// Covers [`goto`](Self::goto), [`navigate_to_entry`](Self::navigate_to_entry),
+    /// [`wait_for_element`](Self::wait_for_element),
+    /// [`wait_for_selector`](Self::wait_for_selector),
+    /// [`wait_for_route_change`](Self::wait_for_route_change),
+    /// [`wait_for_request`](Self::wait_for_request),
+    /// [`wait_for_download`](Self::wait_for_download) and [`act_on`](Self::act_on).
+    /// Their errors come back as [`Error::WithArtifacts`] around the original
+    /// one, except when the target crashed or closed and there is nothing
+    /// left to capture; [`Error::root`] gets the original one back.
+    pub async fn set_failure_capture(&self, capture: Option<FailureCapture>) -> Result<()> {
+        let recorder = match capture {
+            Some(capture) => {
+                let recorder = FailureRecorder::spawn(capture, self.connection.subscribe());
+                // Console messages and exceptions are Runtime events
+                self.domains.enable(Domain::Runtime).await?;
+                Some(Arc::new(recorder))
+            }
+            None => None,
+        };
+        if let Ok(mut current) = self.failure_capture.lock() {
+            *current = recorder;
+        }
+        Ok(())
+    }
+
+    /// Run a high-level operation, attaching failure artifacts to its error if capture is on
+    async fn captured<T>(
+        &self,
+        operation: &str,
+        run: impl std::future::Future<Output = Result<T>>,
+    ) -> Result<T> {
+        let error = match run.await {
+            Ok(value) => return Ok(value),
+            Err(error) => error,
+        };
+        let recorder = self.failure_capture.lock().ok().and_then(|r| r.clone());
+        let Some(recorder) = recorder.filter(|_| !is_target_gone(&error)) else {
+            return Err(error);
+        };
+        let url = self
+            .frames()
+            .into_iter()
+            .find(Frame::is_main)
+            .map(|frame| frame.url().to_string());
+        let screenshot = tokio::time::timeout(FAILURE_SCREENSHOT_TIMEOUT, self.screenshot())
+            .await
+            .ok()
+            .and_then(Result::ok);
+        match recorder.save(operation, &error, url, screenshot).await {
+            Ok(artifacts) => Err(Error::WithArtifacts {
+                error: Box::new(error),
+                artifacts: Box::new(artifacts),
+            }),
+            // Failing to save them must not hide the operation's error
+            Err(_) => Err(error),
+        }
+    }
+
+    /// Share a per-host navigation limit with other pages, or remove it with `None`
+    ///
+    /// [`goto`](Self::goto) then waits while the target host already has
+    /// as many navigations in progress as the limiter allows.
+    pub fn set_host_limiter(&self, limiter: Option<Arc<HostLimiter>>) {
+        if let Ok(mut current) = self.host_limiter.lock() {
+            *current = limiter;
+        }
+    }
+
+    /// Cancel document navigations to URLs the policy rejects
+    ///
+    /// Blocked navigations fail with `net::ERR_BLOCKED_BY_CLIENT`. Same-document
+    /// navigations (history API, fragments) never hit the network and are not
+    /// checked.
+    pub async fn set_navigation_policy<F>(&self, policy: F) -> Result<()>
+    where
+        F: Fn(&str) -> bool + Send + Sync + 'static,
+    {
+        self.set_shared_navigation_policy(Some(Arc::new(policy)))
+            .await
+    }
+
+    /// Remove the navigation policy, allowing every navigation again
+    pub async fn clear_navigation_policy(&self) -> Result<()> {
+        self.set_shared_navigation_policy(None).await
+    }
+
+    pub(crate) async fn set_shared_navigation_policy(
+        &self,
+        policy: Option<NavigationPolicy>,
+    ) -> Result<()> {
+        self.interceptor.set_navigation_policy(policy).await
+    }
+
+    /// Run an async hook before each navigation is sent, to rewrite or veto it
+    ///
+    /// The hook sees every document request, of the main frame and of
+    /// iframes, and may change its URL or headers or block it. It runs before
+    /// the navigation policy and header rules, which apply to the rewritten
+    /// request. Other requests keep flowing while a hook is pending. Setting
+    /// a hook replaces the previous one.
+    ///
+    /// ```no_run
+    /// # async fn run(page: &chrome_cdp::CdpPage) -> chrome_cdp::Result<()> {
+    /// use chrome_cdp::NavigationAction;
+    ///
+    /// page.before_navigation(|request| async move {
+    ///     if request.url.contains("/logout") {
+    ///         return NavigationAction::Block;
+    ///     }
+    ///     let token = "fresh-token"; // e.g. refreshed from an auth server
+    ///     NavigationAction::Rewrite(
+    ///         request.with_header("Authorization", &format!("Bearer {}", token)),
+    ///     )
+    /// })
+    /// .await
+    /// # }
+    /// ```
+    pub async fn before_navigation<F, Fut>(&self, hook: F) -> Result<()>
+    where
+        F: Fn(NavigationRequest) -> Fut + Send + Sync + 'static,
+        Fut: std::future::Future<Output = NavigationAction> + Send + 'static,
+    {
+        self.interceptor
+            .set_navigation_hook(Some(navigation_hook(hook)))
+            .await
+    }
+
+    /// Remove the hook installed by [`before_navigation`](Self::before_navigation)
+    pub async fn clear_before_navigation(&self) -> Result<()> {
+        self.interceptor.set_navigation_hook(None).await
+    }
+
+    /// Rewrite headers of requests matching the rule's matcher
+    ///
+    /// Rules apply in the order they were added, after the navigation policy
+    /// allowed the request.
+    pub async fn add_header_rule(&self, rule: HeaderRule) -> Result<()> {
+        self.interceptor.add_header_rule(rule).await
+    }
+
+    /// Remove every header rewrite rule
+    pub async fn clear_header_rules(&self) -> Result<()> {
+        self.interceptor.clear_header_rules().await
+    }
+
+    /// Serve repeated requests from an on-disk cache, storing misses
+    ///
+    /// See [`ResponseCache`] for what is cached and for how long.
+    pub async fn set_response_cache(&self, cache: ResponseCache) -> Result<()> {
+        self.interceptor.set_response_cache(Some(cache)).await
+    }
+
+    /// Stop using the response cache; stored entries stay on disk
+    pub async fn clear_response_cache(&self) -> Result<()> {
+        self.interceptor.set_response_cache(None).await
+    }
+
+    /// Save files the page downloads into `dir` on this machine
+    ///
+    /// Download bodies are streamed over the DevTools connection and the
+    /// browser-side download is cancelled, so this works the same for remote
+    /// browsers, whose own download directory is on another host.
+    pub async fn set_download_capture(&self, dir: impl AsRef<Path>) -> Result<()> {
+        self.interceptor
+            .set_download_dir(Some(dir.as_ref().to_path_buf()))
+            .await
+    }
+
+    /// Stop capturing downloads, leaving them to the browser again
+    pub async fn clear_download_capture(&self) -> Result<()> {
+        self.interceptor.set_download_dir(None).await
+    }
+
+    /// Answer proxy authentication challenges with `credentials`
+    ///
+    /// Pages of a browser launched with [`ProxyConfig::with_credentials`](crate::ProxyConfig::with_credentials)
+    /// have this set already when connected through the browser or a context.
+    /// Pages built with [`CdpPage::new`] from a raw WebSocket URL, e.g. from
+    /// [`CdpBrowser::new_page`](crate::CdpBrowser::new_page), are not. While
+    /// set, every request is intercepted so challenges can be answered; pass
+    /// `None` to stop.
+    pub async fn set_proxy_credentials(&self, credentials: Option<ProxyCredentials>) -> Result<()> {
+        self.interceptor.set_proxy_credentials(credentials).await
+    }
+
+    /// Wait until a download captured by [`set_download_capture`](Self::set_download_capture) is saved
+    ///
+    /// Start waiting before triggering the download, e.g. with `tokio::join!`.
+    /// Fails if the next download could not be saved, e.g. because the
+    /// browser stopped streaming it.
+    ///
+    /// With [failure capture](Self::set_failure_capture) on, errors come as
+    /// [`Error::WithArtifacts`].
+    pub async fn wait_for_download(&self, timeout: Duration) -> Result<Download> {
+        self.captured("wait_for_download", self.wait_for_download_inner(timeout))
+            .await
+    }
+
+    /// [`wait_for_download`](Self::wait_for_download) without failure capture
+    async fn wait_for_download_inner(&self, timeout: Duration) -> Result<Download> {
+        let mut downloads = self.interceptor.downloads();
+        tokio::time::timeout(timeout, async {
+            loop {
+                match downloads.recv().await {
+                    Ok(Ok(download)) => return Ok(download),
+                    Ok(Err(e)) => {
+                        return Err(Error::Browser(format!("Failed to save download: {}", e)))
+                    }
+                    Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(tokio::sync::broadcast::error::RecvError::Closed) => {
+                        return Err(Error::Browser("Page was dropped".to_string()))
+                    }
+                }
+            }
+        })
+        .await
+        .map_err(|_| Error::Timeout(format!("No download finished within {:?}", timeout)))?
+    }
+
+    /// Choose how [`set_input_files`](Self::set_input_files) hands files to the browser
+    ///
+    /// By default, browsers reached over loopback are given local paths and
+    /// others are sent the file contents. Override this when, e.g., a browser
+    /// in a container is reached through a forwarded local port.
+    pub fn set_remote_filesystem(&self, remote: bool) {
+        self.local_files
+            .store(!remote, std::sync::atomic::Ordering::Relaxed);
+    }
+
+    /// Select local files in the file input matching `selector`
+    ///
+    /// Remote browsers cannot open our paths, so their files are streamed
+    /// into the page in chunks and assigned to the input, which fires the
+    /// usual `input` and `change` events.
+    pub async fn set_input_files<P: AsRef<Path>>(&self, selector: &str, files: &[P]) -> Result<()> {
+        if self.local_files.load(std::sync::atomic::Ordering::Relaxed) {
+            let paths = files
+                .iter()
+                .map(|path| Ok(path.as_ref().canonicalize()?.to_string_lossy().into_owned()))
+                .collect::<Result<Vec<String>>>()?;
+            return self.set_file_input_paths(selector, &paths).await;
+        }
+
+        let mut uploaded = Vec::new();
+        for path in files {
+            let path = path.as_ref();
+            let id = uuid::Uuid::new_v4().to_string();
+            self.call_function(BEGIN_UPLOAD_FN, &[json!(id)]).await?;
+            let bytes = tokio::fs::read(path).await?;
+            for chunk in bytes.chunks(UPLOAD_CHUNK_SIZE) {
+                let data = base64::engine::general_purpose::STANDARD.encode(chunk);
+                self.call_function(APPEND_UPLOAD_FN, &[json!(id), json!(data)])
+                    .await?;
+            }
+            uploaded.push(json!({
+                "id": id,
+                "name": path.file_name().map(|n| n.to_string_lossy()).unwrap_or_default(),
+                "type": mime_type(path),
+            }));
+        }
+        self.call_function(SET_FILES_FN, &[json!(selector), json!(uploaded)])
+            .await?;
+        Ok(())
+    }
+
+    /// Assign paths on the browser's filesystem to a file input
+    async fn set_file_input_paths(&self, selector: &str, paths: &[String]) -> Result<()> {
+        let document = self
+            .connection
+            .send_command(
+                "Runtime.evaluate",
+                json!({ "expression": "document", "objectGroup": OBJECT_GROUP }),
+            )
+            .await?;
+        let document_id = document["result"]["objectId"].clone();
+        let element = self
+            .connection
+            .send_command(
+                "Runtime.callFunctionOn",
+                json!({
+                    "functionDeclaration": "function(selector) { return this.querySelector(selector); }",
+                    "objectId": document_id,
+                    "arguments": call_arguments(&[json!(selector)]),
+                }),
+            )
+            .await;
+        let _ = self
+            .connection
+            .send_command("Runtime.releaseObject", json!({ "objectId": document_id }))
+            .await;
+
+        let element_id = element?["result"]["objectId"].clone();
+        if element_id.is_null() {
+            return Err(Error::Browser(format!(
+                "No element matches selector: {}",
+                selector
+            )));
+        }
+        let result = self
+            .connection
+            .send_command(
+                "DOM.setFileInputFiles",
+                json!({ "files": paths, "objectId": element_id }),
+            )
+            .await
+            .map_err(|e| e.context("Failed to set input files"));
+        let _ = self
+            .connection
+            .send_command("Runtime.releaseObject", json!({ "objectId": element_id }))
+            .await;
+        result.map(|_| ())
+    }
+
+    /// Wait for a same-document navigation of the main frame, as single-page apps do
+    ///
+    /// Route changes through `history.pushState`/`replaceState` and fragment
+    /// changes fire no load event, so [`goto`](Self::goto)-style waits cannot
+    /// see them. Returns once the new URL is committed. Start waiting before
+    /// triggering the change, e.g. with `tokio::join!`, as changes that
+    /// happened earlier are not reported.
+    ///
+    /// With [failure capture](Self::set_failure_capture) on, errors come as
+    /// [`Error::WithArtifacts`].
+    pub async fn wait_for_route_change(&self, timeout: Duration) -> Result<RouteChange> {
+        self.captured(
+            "wait_for_route_change",
+            self.wait_for_route_change_inner(timeout),
+        )
+        .await
+    }
+
+    /// [`wait_for_route_change`](Self::wait_for_route_change) without failure capture
+    async fn wait_for_route_change_inner(&self, timeout: Duration) -> Result<RouteChange> {
+        // Subscribe first so a change right after the hook is installed is not missed
+        let mut events = self.connection.subscribe();
+        self.install_route_hook().await?;
+        let main_frame = self.main_frame_id().await?;
+
+        let wait = async {
+            let mut pending = PendingRoutes::default();
+            while let Some(event) = events.recv().await {
+                match event.method.as_str() {
+                    "Runtime.bindingCalled" => pending.record(&event.params),
+                    "Page.navigatedWithinDocument" if event.params["frameId"] == main_frame => {
+                        return Ok(pending.commit(&event.params));
+                    }
+                    _ => {}
+                }
+            }
+            Err(Error::WebSocket(
+                "Connection closed while waiting for a route change".to_string(),
+            ))
+        };
+        tokio::time::timeout(timeout, wait)
+            .await
+            .map_err(|_| Error::Timeout(format!("No route change within {:?}", timeout)))?
+    }
+
+    /// Install the history hook in the current and all future documents, once
+    async fn install_route_hook(&self) -> Result<()> {
+        self.route_hook
+            .get_or_try_init(|| async {
+                self.domains.enable(Domain::Page).await?;
+                self.domains.enable(Domain::Runtime).await?;
+                self.connection
+                    .send_command("Runtime.addBinding", json!({ "name": ROUTE_BINDING }))
+                    .await
+                    .map_err(|e| e.context("Failed to add route binding"))?;
+                self.connection
+                    .send_command(
+                        "Page.addScriptToEvaluateOnNewDocument",
+                        json!({ "source": ROUTE_HOOK_SCRIPT }),
+                    )
+                    .await
+                    .map_err(|e| e.context("Failed to install route hook"))?;
+                self.connection
+                    .send_command(
+                        "Runtime.evaluate",
+                        json!({ "expression": ROUTE_HOOK_SCRIPT }),
+                    )
+                    .await?;
+                Ok(())
+            })
+            .await
+            .map(|_| ())
+    }
+
+    /// Wait for the page to send a request satisfying the matcher
+    ///
+    /// With [failure capture](Self::set_failure_capture) on, errors come as
+    /// [`Error::WithArtifacts`].
+    pub async fn wait_for_request(
+        &self,
+        matcher: &RequestMatcher,
+        timeout: Duration,
+    ) -> Result<RequestInfo> {
+        self.captured(
+            "wait_for_request",
+            self.wait_for_request_inner(matcher, timeout),
+        )
+        .await
+    }
+
+    /// [`wait_for_request`](Self::wait_for_request) without failure capture
+    async fn wait_for_request_inner(
+        &self,
+        matcher: &RequestMatcher,
+        timeout: Duration,
+    ) -> Result<RequestInfo> {
+        self.domains.enable(Domain::Network).await?;
+
+        let params = self
+            .connection
+            .wait_for_event(
+                "Network.requestWillBeSent",
+                |params| matcher.matches(&RequestInfo::from_event(params)),
+                timeout,
+            )
+            .await?;
+        Ok(RequestInfo::from_event(&params))
+    }
+
+    /// Start recording DNS, connect, TLS, TTFB and download times of the page's requests
+    ///
+    /// Compare the recorder's [`summary`](NetworkTimingRecorder::summary) with
+    /// the total load time to see whether a slow page waits on the network or
+    /// on rendering. Recording stops when the recorder is dropped.
+    pub async fn record_network_timing(&self) -> Result<NetworkTimingRecorder> {
+        // Subscribe first so responses right after enabling are not missed
+        let recorder = NetworkTimingRecorder::spawn(self.connection.subscribe());
+        self.domains.enable(Domain::Network).await?;
+        Ok(recorder)
+    }
+
+    /// Start recording lifecycle, network, console and action events in one timeline
+    ///
+    /// Actions are the navigation and `Input.*` commands sent through this
+    /// page. Export the timeline with [`EventTimeline::save`] or, to inspect
+    /// it in Perfetto, [`EventTimeline::save_perfetto`]. Recording stops when
+    /// the timeline is dropped.
+    pub async fn event_timeline(&self) -> Result<EventTimeline> {
+        let timeline = EventTimeline::spawn(&self.connection);
+        for domain in [Domain::Page, Domain::Network, Domain::Runtime, Domain::Log] {
+            self.domains.enable(domain).await?;
+        }
+        self.connection
+            .send_command("Page.setLifecycleEventsEnabled", json!({ "enabled": true }))
+            .await
+            .map_err(|e| e.context("Failed to enable lifecycle events"))?;
+        Ok(timeline)
+    }
+
+    /// Get the session history of the page
+    pub async fn navigation_history(&self) -> Result<NavigationHistory> {
+        let result = self
+            .connection
+            .send_command("Page.getNavigationHistory", json!({}))
+            .await
+            .map_err(|e| e.context("Failed to get navigation history"))?;
+        NavigationHistory::from_result(result)
+    }
+
+    /// Navigate to the history entry at the given index
+    ///
+    /// With [failure capture](Self::set_failure_capture) on, errors come as
+    /// [`Error::WithArtifacts`].
+    pub async fn navigate_to_entry(&self, index: usize) -> Result<()> {
+        self.captured("navigate_to_entry", self.navigate_to_entry_inner(index))
+            .await
+    }
+
+    /// [`navigate_to_entry`](Self::navigate_to_entry) without failure capture
+    async fn navigate_to_entry_inner(&self, index: usize) -> Result<()> {
+        let history = self.navigation_history().await?;
+        let entry = history.entries.get(index).ok_or_else(|| {
+            Error::Browser(format!(
+                "History index {} out of range ({} entries)",
+                index,
+                history.entries.len()
+            ))
+        })?;
+
+        self.connection
+            .send_command(
+                "Page.navigateToHistoryEntry",
+                json!({ "entryId": entry.id }),
+            )
+            .await
+            .map_err(|e| e.context(format!("Failed to navigate to history entry {}", index)))?;
+        Ok(())
+    }
+
+    /// Wait for an element to appear on the page
+    ///
+    /// With [failure capture](Self::set_failure_capture) on, errors come as
+    /// [`Error::WithArtifacts`].
+    pub async fn wait_for_element(&self, selector: &str, timeout_secs: u64) -> Result<bool> {
+        self.captured(
+            "wait_for_element",
+            self.wait_for_element_inner(selector, Duration::from_secs(timeout_secs)),
+        )
+        .await
+    }
+
+    /// [`wait_for_element`](Self::wait_for_element) without failure capture
+    async fn wait_for_element_inner(&self, selector: &str, timeout: Duration) -> Result<bool> {
+        let start = std::time::Instant::now();
+
+        loop {
+            let result = self
+                .call_function(ELEMENT_EXISTS_FN, &[json!(selector)])
+                .await?;
+            if result.as_bool().unwrap_or(false) {
+                return Ok(true);
+            }
+            if start.elapsed() >= timeout {
+                return Ok(false);
+            }
+
+            sleep(Duration::from_millis(500)).await;
+        }
+    }
+
+    /// Wait until an element matching the selector exists, bounded by the wait timeout
+    ///
+    /// With [failure capture](Self::set_failure_capture) on, errors come as
+    /// [`Error::WithArtifacts`].
+    pub async fn wait_for_selector(&self, selector: &str) -> Result<()> {
+        self.captured("wait_for_selector", self.wait_for_selector_inner(selector))
+            .await
+    }
+
+    /// [`wait_for_selector`](Self::wait_for_selector) without failure capture
+    async fn wait_for_selector_inner(&self, selector: &str) -> Result<()> {
+        let timeout = self.timeouts().wait_timeout();
+        if self.wait_for_element_inner(selector, timeout).await? {
+            return Ok(());
+        }
+        Err(Error::Timeout(format!(
+            "No element matched '{}' within {:?}",
+            selector, timeout
+        )))
+    }
+
+    /// Replace the document with `html` without navigating
+    ///
+    /// Returns once the document is replaced, with no load event to wait for,
+    /// which keeps DOM tests on a [`CdpBrowser::blank_page`](crate::CdpBrowser::blank_page)
+    /// fast. Relative URLs resolve against the current URL.
+    pub async fn set_content(&self, html: &str) -> Result<()> {
+        let frame_id = self.main_frame_id().await?;
+        self.connection
+            .send_command(
+                "Page.setDocumentContent",
+                json!({ "frameId": frame_id, "html": html }),
+            )
+            .await
+            .map_err(|e| e.context("Failed to set content"))?;
+        Ok(())
+    }
+
+    /// Id of the main frame, from the frame tracker or else the frame tree
+    async fn main_frame_id(&self) -> Result<String> {
+        if let Some(frame) = self.frames().into_iter().find(|f| f.parent_id().is_none()) {
+            return Ok(frame.id().to_string());
+        }
+        let tree = self
+            .connection
+            .send_command("Page.getFrameTree", json!({}))
+            .await
+            .map_err(|e| e.context("Failed to get frame tree"))?;
+        tree["frameTree"]["frame"]["id"]
+            .as_str()
+            .map(String::from)
+            .ok_or_else(|| Error::Browser("Page has no main frame".to_string()))
+    }
+
+    /// Get full HTML content for debugging
+    pub async fn get_html(&self) -> Result<String> {
+        let script = "document.documentElement.outerHTML";
+        let result = self.evaluate(script).await?;
+        result.as_str().map(String::from).ok_or_else(|| {
+            Error::Browser("Failed to get HTML: JavaScript result was not a string".to_string())
+        })
+    }
+
+    /// List the visible interactive elements of the page
+    ///
+    /// Entries are indexed for [`CdpPage::act_on`], which always refers to the
+    /// most recent snapshot. The snapshot's `Display` output is a compact,
+    /// one-line-per-element form meant for agent prompts.
+    pub async fn interactive_snapshot(&self) -> Result<InteractiveSnapshot> {
+        let elements = self.call_function(SNAPSHOT_FN, &[]).await?;
+        let snapshot = InteractiveSnapshot {
+            elements: serde_json::from_value(elements)?,
+        };
+        if let Ok(mut ids) = self.snapshot_ids.lock() {
+            *ids = snapshot.elements.iter().map(|e| e.id.clone()).collect();
+        }
+        Ok(snapshot)
+    }
+
+    /// Perform an action on an entry of the last interactive snapshot
+    ///
+    /// Clicks and hovers use real mouse events at the element's center after
+    /// scrolling it into view; fills select the current text and type over it.
+    ///
+    /// With [failure capture](Self::set_failure_capture) on, errors come as
+    /// [`Error::WithArtifacts`].
+    pub async fn act_on(&self, index: usize, action: Action) -> Result<()> {
+        self.captured("act_on", self.act_on_inner(index, action))
+            .await
+    }
+
+    /// [`act_on`](Self::act_on) without failure capture
+    async fn act_on_inner(&self, index: usize, action: Action) -> Result<()> {
+        let id = self
+            .snapshot_ids
+            .lock()
+            .ok()
+            .and_then(|ids| ids.get(index).cloned())
+            .ok_or_else(|| Error::Browser(format!("No element {} in the last snapshot", index)))?;
+        let bounds = self
+            .call_function(PREPARE_FN, &[json!(id), json!(action.name())])
+            .await?;
+        if bounds.is_null() {
+            return Err(Error::Browser(format!(
+                "Element {} is no longer in the page",
+                index
+            )));
+        }
+        let (x, y) = serde_json::from_value::<Rect>(bounds)?.center();
+
+        let mouse = |kind: &str| {
+            (
+                "Input.dispatchMouseEvent",
+                json!({ "type": kind, "x": x, "y": y, "button": "left", "clickCount": 1 }),
+            )
+        };
+        let commands = match &action {
+            Action::Click => vec![
+                mouse("mouseMoved"),
+                mouse("mousePressed"),
+                mouse("mouseReleased"),
+            ],
+            Action::Hover => vec![mouse("mouseMoved")],
+            Action::Focus => Vec::new(),
+            // Inserting nothing would keep the selection, so delete it instead
+            Action::Fill(text) if text.is_empty() => ["keyDown", "keyUp"]
+                .into_iter()
+                .map(|kind| {
+                    (
+                        "Input.dispatchKeyEvent",
+                        json!({
+                            "type": kind,
+                            "key": "Backspace",
+                            "code": "Backspace",
+                            "windowsVirtualKeyCode": 8
+                        }),
+                    )
+                })
+                .collect(),
+            Action::Fill(text) => vec![("Input.insertText", json!({ "text": text }))],
+        };
+        for result in self.connection.send_commands(&commands).await {
+            result
+                .map_err(|e| e.context(format!("Failed to {} element {}", action.name(), index)))?;
+        }
+        Ok(())
+    }
+
+    /// Serialize the rendered page into one self-contained HTML string
+    ///
+    /// Stylesheets, images, and fonts the page loaded are inlined from the
+    /// browser's resource cache, the latter two as data URIs. Scripts are
+    /// dropped, and resources of iframes or ones no longer cached keep their
+    /// original URLs.
+    pub async fn export_inlined_html(&self) -> Result<String> {
+        let tree = self
+            .connection
+            .send_command("Page.getResourceTree", json!({}))
+            .await
+            .map_err(|e| e.context("Failed to get resource tree"))?;
+        let frame_id = tree["frameTree"]["frame"]["id"].clone();
+        let listed: Vec<&Value> = tree["frameTree"]["resources"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter(|r| matches!(r["type"].as_str(), Some("Stylesheet" | "Image" | "Font")))
+            .collect();
+
+        let requests: Vec<(&str, Value)> = listed
+            .iter()
+            .map(|r| {
+                (
+                    "Page.getResourceContent",
+                    json!({ "frameId": frame_id, "url": r["url"] }),
+                )
+            })
+            .collect();
+        let contents = self.connection.send_commands(&requests).await;
+
+        let mut resources = InlineResources::default();
+        for (resource, content) in listed.iter().zip(contents) {
+            // Evicted resources simply stay linked
+            if let Ok(content) = content {
+                resources.add(
+                    resource["url"].as_str().unwrap_or_default(),
+                    resource["type"].as_str().unwrap_or_default(),
+                    resource["mimeType"].as_str().unwrap_or_default(),
+                    &content,
+                );
+            }
+        }
+        resources.inline_stylesheets();
+
+        let html = self
+            .call_function(
+                INLINE_FN,
+                &[json!(resources.data_uris), json!(resources.stylesheets)],
+            )
+            .await?;
+        html.as_str().map(String::from).ok_or_else(|| {
+            Error::Browser("Failed to export HTML: JavaScript result was not a string".to_string())
+        })
+    }
+
+    /// Export the browser's cookies and the local storage of the current origin
+    ///
+    /// Cookies of every site in the page's browser context are included.
+    pub async fn storage_state(&self) -> Result<StorageState> {
+        let cookies = self
+            .connection
+            .send_command("Network.getAllCookies", json!({}))
+            .await
+            .map_err(|e| e.context("Failed to get cookies"))?;
+        let local = self.call_function(LOCAL_STORAGE_FN, &[]).await?;
+
+        let mut origins = Vec::new();
+        let origin = local["origin"].as_str().unwrap_or_default();
+        // Opaque origins such as about:blank have no storage to restore
+        if origin != "null" && !origin.is_empty() {
+            origins.push(OriginStorage {
+                origin: origin.to_string(),
+                local_storage: serde_json::from_value(local["items"].clone())?,
+            });
+        }
+        Ok(StorageState {
+            cookies: cookies["cookies"].as_array().cloned().unwrap_or_default(),
+            origins,
+        })
+    }
+
+    /// Restore cookies and the local storage of the current origin
+    ///
+    /// Local storage of other origins is skipped, so navigate to the site
+    /// before restoring and reload afterwards if it reads storage on load.
+    pub async fn set_storage_state(&self, state: &StorageState) -> Result<()> {
+        if !state.cookies.is_empty() {
+            self.connection
+                .send_command("Network.setCookies", state.set_cookies_params())
+                .await
+                .map_err(|e| e.context("Failed to set cookies"))?;
+        }
+        for origin in &state.origins {
+            self.call_function(
+                SET_LOCAL_STORAGE_FN,
+                &[json!(origin.origin), json!(origin.local_storage)],
+            )
+            .await?;
+        }
+        Ok(())
+    }
+
+    /// Evaluate JavaScript and return the result
+    pub async fn evaluate(&self, script: &str) -> Result<Value> {
+        let result = self
+            .connection
+            .send_command(
+                "Runtime.evaluate",
+                json!({
+                    "expression": script,
+                    "returnByValue": true,
+                    "awaitPromise": true
+                }),
+            )
+            .await?;
+
+        if let Some(error) = exception_error(&result) {
+            return Err(error);
+        }
+
+        Ok(result["result"]["value"].clone())
+    }
+
+    /// Check how the page's environment looks to fingerprinting scripts
+    ///
+    /// Run it after applying emulation settings; [`EnvironmentReport::issues`]
+    /// lists what gives the browser away.
+    pub async fn environment_report(&self) -> Result<EnvironmentReport> {
+        Ok(serde_json::from_value(
+            self.evaluate(ENVIRONMENT_EXPR).await?,
+        )?)
+    }
+
+    /// Evaluate JavaScript, interrupting it if it runs longer than `timeout`
+    ///
+    /// Synchronous code such as an infinite loop is stopped by the renderer
+    /// itself. If no result arrives shortly after the deadline, e.g. because
+    /// a returned promise never settles, `Runtime.terminateExecution` is sent.
+    /// Both cases fail with [`Error::Timeout`].
+    pub async fn evaluate_with_timeout(&self, script: &str, timeout: Duration) -> Result<Value> {
+        let evaluate = self.connection.send_command(
+            "Runtime.evaluate",
+            json!({
+                "expression": script,
+                "returnByValue": true,
+                "awaitPromise": true,
+                "timeout": timeout.as_millis() as u64
+            }),
+        );
+        let timed_out = || Error::Timeout(format!("Script did not finish within {:?}", timeout));
+
+        let result = match tokio::time::timeout(timeout + EVALUATE_GRACE, evaluate).await {
+            Ok(result) => result?,
+            Err(_) => {
+                let _ = self
+                    .connection
+                    .send_command("Runtime.terminateExecution", json!({}))
+                    .await;
+                return Err(timed_out());
+            }
+        };
+
+        if is_terminated(&result) {
+            return Err(timed_out());
+        }
+        if let Some(error) = exception_error(&result) {
+            return Err(error);
+        }
+
+        Ok(result["result"]["value"].clone())
+    }
+
+    /// Call a JavaScript function with `this` bound to `document` and return the result
+    ///
+    /// Arguments are passed as JSON values rather than interpolated into source,
+    /// so untrusted strings such as selectors cannot inject script.
+    pub async fn call_function(&self, function_declaration: &str, args: &[Value]) -> Result<Value> {
+        let document = self
+            .connection
+            .send_command(
+                "Runtime.evaluate",
+                json!({ "expression": "document", "objectGroup": OBJECT_GROUP }),
+            )
+            .await?;
+        let object_id = document["result"]["objectId"]
+            .as_str()
+            .ok_or_else(|| Error::Browser("Failed to resolve document object".to_string()))?
+            .to_string();
+
+        let result = self
+            .connection
+            .send_command(
+                "Runtime.callFunctionOn",
+                json!({
+                    "functionDeclaration": function_declaration,
+                    "objectId": object_id,
+                    "arguments": call_arguments(args),
+                    "returnByValue": true,
+                    "awaitPromise": true
+                }),
+            )
+            .await;
+
+        // Release the document handle regardless of the call outcome
+        let _ = self
+            .connection
+            .send_command("Runtime.releaseObject", json!({ "objectId": object_id }))
+            .await;
+
+        let result = result?;
+        if let Some(error) = exception_error(&result) {
+            return Err(error);
+        }
+
+        Ok(result["result"]["value"].clone())
+    }
+
+    /// Accept or reject invalid TLS certificates, e.g. self-signed ones, on this page
+    pub async fn set_ignore_certificate_errors(&self, ignore: bool) -> Result<()> {
+        self.connection
+            .send_command(
+                "Security.setIgnoreCertificateErrors",
+                json!({ "ignore": ignore }),
+            )
+            .await
+            .map_err(|e| e.context("Failed to set certificate error handling"))?;
+        Ok(())
+    }
 
-        // Enable necessary domains
-        connection
-            .send_command("Page.enable", json!({}))
-            .await
-            .map_err(|e| Error::Browser(format!("Failed to enable Page domain: {}", e)))?;
-        connection
-            .send_command("Runtime.enable", json!({}))
+    /// Send these headers with every request of the page, replacing earlier ones
+    pub async fn set_extra_headers(&self, headers: &[(String, String)]) -> Result<()> {
+        let headers: serde_json::Map<String, Value> = headers
+            .iter()
+            .map(|(name, value)| (name.clone(), json!(value)))
+            .collect();
+        self.domains.enable(Domain::Network).await?;
+        self.connection
+            .send_command("Network.setExtraHTTPHeaders", json!({ "headers": headers }))
             .await
-            .map_err(|e| Error::Browser(format!("Failed to enable Runtime domain: {}", e)))?;
+            .map_err(|e| e.context("Failed to set extra headers"))?;
+        Ok(())
+    }
 
-        Ok(Self { connection })
+    /// Force Chrome's automatic dark theme on or off, independently of `prefers-color-scheme`
+    pub async fn set_auto_dark_mode(&self, enabled: bool) -> Result<()> {
+        self.set_override(
+            Override::AutoDarkMode,
+            "Emulation.setAutoDarkModeOverride",
+            json!({ "enabled": enabled }),
+        )
+        .await
+        .map_err(|e| e.context("Failed to set auto dark mode"))
     }
 
-    /// Navigate to a URL
-    pub async fn goto(&self, url: &str) -> Result<()> {
+    /// Remove the automatic dark theme override
+    pub async fn clear_auto_dark_mode(&self) -> Result<()> {
         self.connection
-            .send_command("Page.navigate", json!({ "url": url }))
+            .send_command("Emulation.setAutoDarkModeOverride", json!({}))
             .await
-            .map_err(|e| Error::Browser(format!("Failed to navigate to '{}': {}", url, e)))?;
+            .map_err(|e| e.context("Failed to clear auto dark mode"))?;
+        self.emulation.forget(Override::AutoDarkMode);
         Ok(())
     }
 
-    /// Wait for an element to appear on the page
-    pub async fn wait_for_element(&self, selector: &str, timeout_secs: u64) -> Result<bool> {
-        let start = std::time::Instant::now();
+    /// Emulate a viewport of the given size in CSS pixels
+    pub async fn set_viewport(
+        &self,
+        width: u32,
+        height: u32,
+        device_scale_factor: f64,
+        mobile: bool,
+    ) -> Result<()> {
+        self.set_override(
+            Override::DeviceMetrics,
+            "Emulation.setDeviceMetricsOverride",
+            json!({
+                "width": width,
+                "height": height,
+                "deviceScaleFactor": device_scale_factor,
+                "mobile": mobile
+            }),
+        )
+        .await
+        .map_err(|e| e.context("Failed to set viewport"))
+    }
 
-        while start.elapsed().as_secs() < timeout_secs {
-            let script = format!(
-                "!!document.querySelector(\"{}\")",
-                selector.replace('"', "\\\"")
-            );
+    /// Emulate a CSS media type (e.g. `print`) and media features
+    /// (e.g. `("prefers-color-scheme", "dark")`)
+    pub async fn set_emulated_media(&self, media: &str, features: &[(&str, &str)]) -> Result<()> {
+        let features: Vec<Value> = features
+            .iter()
+            .map(|(name, value)| json!({ "name": name, "value": value }))
+            .collect();
+        self.set_override(
+            Override::Media,
+            "Emulation.setEmulatedMedia",
+            json!({ "media": media, "features": features }),
+        )
+        .await
+        .map_err(|e| e.context("Failed to emulate media"))
+    }
 
-            let result = self.evaluate(&script).await?;
-            if result.as_bool().unwrap_or(false) {
-                return Ok(true);
-            }
+    /// Send `user_agent` instead of the browser's, optionally with an `Accept-Language` header
+    pub async fn set_user_agent(
+        &self,
+        user_agent: &str,
+        accept_language: Option<&str>,
+    ) -> Result<()> {
+        let mut params = json!({ "userAgent": user_agent });
+        if let Some(accept_language) = accept_language {
+            params["acceptLanguage"] = json!(accept_language);
+        }
+        self.set_override(
+            Override::UserAgent,
+            "Emulation.setUserAgentOverride",
+            params,
+        )
+        .await
+        .map_err(|e| e.context("Failed to set user agent"))
+    }
 
-            sleep(Duration::from_millis(500)).await;
+    /// Emulate an ICU locale such as `de-DE` for `Intl` formatting and `navigator.language`
+    pub async fn set_locale(&self, locale: &str) -> Result<()> {
+        self.set_override(
+            Override::Locale,
+            "Emulation.setLocaleOverride",
+            json!({ "locale": locale }),
+        )
+        .await
+        .map_err(|e| e.context("Failed to set locale"))
+    }
+
+    /// User agent string of the browser, without this page's override
+    pub(crate) async fn browser_user_agent(&self) -> Result<String> {
+        let version = self
+            .connection
+            .send_command("Browser.getVersion", json!({}))
+            .await
+            .map_err(|e| e.context("Failed to get user agent"))?;
+        Ok(version["userAgent"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string())
+    }
+
+    /// Slow the CPU down by the given factor (1 is no throttling)
+    pub async fn set_cpu_throttling(&self, rate: f64) -> Result<()> {
+        self.set_override(
+            Override::CpuThrottling,
+            "Emulation.setCPUThrottlingRate",
+            json!({ "rate": rate }),
+        )
+        .await
+        .map_err(|e| e.context("Failed to throttle CPU"))
+    }
+
+    /// Throttle the network to the given latency and throughputs in bytes per
+    /// second, or cut it off with `offline`
+    pub async fn set_network_throttling(
+        &self,
+        latency: Duration,
+        download_throughput: f64,
+        upload_throughput: f64,
+        offline: bool,
+    ) -> Result<()> {
+        // Network conditions only apply while the Network domain is enabled
+        self.domains.enable(Domain::Network).await?;
+        self.set_override(
+            Override::NetworkConditions,
+            "Network.emulateNetworkConditions",
+            json!({
+                "offline": offline,
+                "latency": latency.as_millis() as u64,
+                "downloadThroughput": download_throughput,
+                "uploadThroughput": upload_throughput
+            }),
+        )
+        .await
+        .map_err(|e| e.context("Failed to throttle network"))
+    }
+
+    /// Clear every emulation override set through this page
+    ///
+    /// Covers the viewport, user agent, geolocation, timezone, locale, media,
+    /// automatic dark mode, and CPU and network throttling, so a reused page
+    /// starts the next job from the browser's defaults.
+    pub async fn reset_emulation(&self) -> Result<()> {
+        let commands = self.emulation.take_clear_commands();
+        for result in self.connection.send_commands(&commands).await {
+            result.map_err(|e| e.context("Failed to reset emulation"))?;
         }
+        Ok(())
+    }
 
-        Ok(false)
+    /// Send an override command and record it for [`CdpPage::reset_emulation`]
+    async fn set_override(&self, kind: Override, method: &str, params: Value) -> Result<()> {
+        self.connection.send_command(method, params.clone()).await?;
+        self.emulation.record(kind, params);
+        Ok(())
     }
 
-    /// Get full HTML content for debugging
-    pub async fn get_html(&self) -> Result<String> {
-        let script = "document.documentElement.outerHTML";
-        let result = self.evaluate(script).await?;
-        result.as_str().map(String::from).ok_or_else(|| {
-            Error::Browser("Failed to get HTML: JavaScript result was not a string".to_string())
-        })
+    /// Re-apply the emulated viewport after a temporary change, or clear the override
+    async fn restore_device_metrics(&self) -> Result<Value> {
+        match self.emulation.get(Override::DeviceMetrics) {
+            Some(params) => {
+                self.connection
+                    .send_command("Emulation.setDeviceMetricsOverride", params)
+                    .await
+            }
+            None => {
+                self.connection
+                    .send_command("Emulation.clearDeviceMetricsOverride", json!({}))
+                    .await
+            }
+        }
     }
 
-    /// Evaluate JavaScript and return the result
-    pub async fn evaluate(&self, script: &str) -> Result<Value> {
+    /// Apply a region's timezone, locale, `Accept-Language`, and geolocation together
+    ///
+    /// The user agent string and its client hints stay unchanged. Pages still
+    /// need the geolocation permission to read the overridden position.
+    pub async fn apply_region(&self, region: &RegionProfile) -> Result<()> {
+        let user_agent = self.browser_user_agent().await?;
+        // Client hints are unavailable in insecure contexts; the override then omits them
+        let metadata = self
+            .evaluate(USER_AGENT_METADATA_EXPR)
+            .await
+            .ok()
+            .filter(|metadata| !metadata.is_null());
+
+        let (kinds, commands): (Vec<_>, Vec<_>) = region
+            .commands(&user_agent, metadata)
+            .into_iter()
+            .map(|(kind, method, params)| (kind, (method, params)))
+            .unzip();
+        let results = self.connection.send_commands(&commands).await;
+        for ((kind, (_, params)), result) in kinds.into_iter().zip(commands).zip(results) {
+            result.map_err(|e| e.context("Failed to apply region"))?;
+            self.emulation.record(kind, params);
+        }
+        Ok(())
+    }
+
+    /// Capture a PNG screenshot of the viewport
+    pub async fn screenshot(&self) -> Result<Vec<u8>> {
         let result = self
             .connection
+            .send_command("Page.captureScreenshot", json!({ "format": "png" }))
+            .await
+            .map_err(|e| e.context("Failed to capture screenshot"))?;
+        decode_screenshot(&result)
+    }
+
+    /// Capture the viewport and compare it with a baseline PNG
+    ///
+    /// Masks and ignored elements are resolved in CSS pixels and scaled to
+    /// the screenshot, so the comparison holds at any device scale factor.
+    pub async fn compare_screenshot(
+        &self,
+        baseline: &[u8],
+        options: &ScreenshotComparison,
+    ) -> Result<ScreenshotDiff> {
+        let mut masks = options.masks.clone();
+        if !options.ignore_selectors.is_empty() {
+            let rects = self
+                .call_function(ELEMENT_RECTS_FN, &[json!(options.ignore_selectors)])
+                .await?;
+            masks.extend(serde_json::from_value::<Vec<Rect>>(rects)?);
+        }
+        let scale = self
+            .evaluate("window.devicePixelRatio")
+            .await?
+            .as_f64()
+            .unwrap_or(1.0);
+        let masks: Vec<Rect> = masks.iter().map(|mask| scale_rect(mask, scale)).collect();
+
+        let screenshot = self.screenshot().await?;
+        compare_png(baseline, &screenshot, &masks, options.tolerance)
+    }
+
+    /// Capture a PNG of a document region at the given device scale factor
+    ///
+    /// The viewport is enlarged to fit the region and scrolled to it, and both
+    /// the viewport and the scroll position are restored afterwards.
+    pub async fn capture_region(
+        &self,
+        x: f64,
+        y: f64,
+        width: f64,
+        height: f64,
+        scale: f64,
+    ) -> Result<Vec<u8>> {
+        let state = self
+            .evaluate("[window.scrollX, window.scrollY, window.innerWidth, window.innerHeight]")
+            .await?;
+        let saved_x = state[0].as_f64().unwrap_or(0.0);
+        let saved_y = state[1].as_f64().unwrap_or(0.0);
+        let viewport_width = state[2].as_f64().unwrap_or(width).max(width);
+        let viewport_height = state[3].as_f64().unwrap_or(height).max(height);
+
+        self.connection
             .send_command(
-                "Runtime.evaluate",
+                "Emulation.setDeviceMetricsOverride",
                 json!({
-                    "expression": script,
-                    "returnByValue": true,
-                    "awaitPromise": true
+                    "width": viewport_width.ceil() as u64,
+                    "height": viewport_height.ceil() as u64,
+                    "deviceScaleFactor": scale,
+                    "mobile": false
                 }),
             )
+            .await
+            .map_err(|e| e.context("Failed to override viewport"))?;
+
+        let capture = async {
+            self.evaluate(&format!("window.scrollTo({}, {})", x, y))
+                .await?;
+            let result = self
+                .connection
+                .send_command(
+                    "Page.captureScreenshot",
+                    json!({
+                        "format": "png",
+                        "clip": { "x": x, "y": y, "width": width, "height": height, "scale": 1 },
+                        "captureBeyondViewport": true
+                    }),
+                )
+                .await
+                .map_err(|e| e.context("Failed to capture region"))?;
+            decode_screenshot(&result)
+        }
+        .await;
+
+        // Restore state even when the capture failed; a scroll position that
+        // cannot be restored must not hide the capture's result
+        let restored = self.restore_device_metrics().await;
+        let _ = self
+            .evaluate(&format!("window.scrollTo({}, {})", saved_x, saved_y))
+            .await;
+
+        let image = capture?;
+        restored.map_err(|e| e.context("Failed to restore viewport"))?;
+        Ok(image)
+    }
+
+    /// Capture the full page as one PNG by stitching viewport-sized tiles
+    ///
+    /// Use this instead of a single full-page capture for documents taller than
+    /// Chrome's maximum texture size. The viewport, scroll position, and masked
+    /// elements are restored afterwards.
+    pub async fn screenshot_tiled(&self, options: &TiledScreenshotOptions) -> Result<Vec<u8>> {
+        let state = self
+            .evaluate(
+                "[window.scrollX, window.scrollY, window.innerWidth, \
+                 document.documentElement.scrollHeight]",
+            )
             .await?;
+        let saved_x = state[0].as_f64().unwrap_or(0.0);
+        let saved_y = state[1].as_f64().unwrap_or(0.0);
+        let width = state[2].as_f64().unwrap_or(800.0).ceil();
+        let total_height = state[3].as_f64().unwrap_or(0.0).ceil();
+        let tile_height = f64::from(options.tile_height.max(1));
+        let masks = json!(options.mask_selectors);
 
-        if let Some(exception) = result.get("exceptionDetails") {
-            let exception_text = exception["exception"]["description"]
-                .as_str()
-                .or_else(|| exception["text"].as_str())
-                .unwrap_or("unknown error");
+        let capture = async {
+            let mut tiles = Vec::new();
+            let mut y = 0.0;
+            while y < total_height {
+                let height = tile_height.min(total_height - y);
+                self.connection
+                    .send_command(
+                        "Emulation.setDeviceMetricsOverride",
+                        json!({
+                            "width": width as u64,
+                            "height": height as u64,
+                            "deviceScaleFactor": 0,
+                            "mobile": false
+                        }),
+                    )
+                    .await
+                    .map_err(|e| e.context("Failed to override viewport"))?;
+                if !tiles.is_empty() && !options.mask_selectors.is_empty() {
+                    self.call_function(MASK_FN, &[masks.clone(), json!(true)])
+                        .await?;
+                }
+                self.evaluate(&format!("window.scrollTo(0, {})", y)).await?;
+                tiles.push(self.screenshot().await?);
+                y += height;
+            }
+            stitch_vertical(&tiles)
+        }
+        .await;
 
-            let column_number = exception["columnNumber"].as_i64().unwrap_or(-1);
-            let line_number = exception["lineNumber"].as_i64().unwrap_or(-1);
+        // Restore state even when the capture failed; masks and a scroll
+        // position that cannot be restored must not hide the capture's result
+        let restored = self.restore_device_metrics().await;
+        if !options.mask_selectors.is_empty() {
+            let _ = self.call_function(MASK_FN, &[masks, json!(false)]).await;
+        }
+        let _ = self
+            .evaluate(&format!("window.scrollTo({}, {})", saved_x, saved_y))
+            .await;
 
-            return Err(Error::Browser(format!(
-                "JavaScript execution error at line {}, column {}: {}",
-                line_number, column_number, exception_text
-            )));
+        let image = capture?;
+        restored.map_err(|e| e.context("Failed to restore viewport"))?;
+        Ok(image)
+    }
+
+    /// Freeze `Date`, `performance.now` and timers at the given time (ms since the Unix epoch)
+    ///
+    /// Timers only fire when time is moved with [`CdpPage::advance_time`].
+    /// The fake clock also applies to documents loaded later.
+    pub async fn freeze_time(&self, epoch_ms: f64) -> Result<()> {
+        self.install_clock(Some(epoch_ms), 0.0).await
+    }
+
+    /// Run the page clock at `rate` times real speed, e.g. `10.0` to fast-forward countdowns
+    ///
+    /// The current document keeps its fake time; documents loaded later start
+    /// from the real time.
+    pub async fn set_time_rate(&self, rate: f64) -> Result<()> {
+        if !rate.is_finite() || rate < 0.0 {
+            return Err(Error::Browser(format!("Invalid time rate: {}", rate)));
         }
+        self.install_clock(None, rate).await
+    }
 
-        Ok(result["result"]["value"].clone())
+    /// Move the fake clock forward, firing timers that become due
+    pub async fn advance_time(&self, by: Duration) -> Result<()> {
+        let installed = self
+            .evaluate(&tick_script(by.as_secs_f64() * 1000.0))
+            .await?;
+        if installed.as_bool() != Some(true) {
+            return Err(Error::Browser(
+                "No fake clock installed; call freeze_time or set_time_rate first".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Set the browser's virtual time policy, optionally limited to a budget
+    ///
+    /// Unlike the fake clock, virtual time is driven by the browser itself and
+    /// also covers animations and network timing; it is not supported by every
+    /// Chrome build.
+    pub async fn set_virtual_time_policy(
+        &self,
+        policy: VirtualTimePolicy,
+        budget: Option<Duration>,
+    ) -> Result<()> {
+        let mut params = json!({ "policy": policy.as_str() });
+        if let Some(budget) = budget {
+            params["budget"] = json!(budget.as_secs_f64() * 1000.0);
+        }
+        self.connection
+            .send_command("Emulation.setVirtualTimePolicy", params)
+            .await
+            .map_err(|e| e.context("Failed to set virtual time policy"))?;
+        Ok(())
+    }
+
+    /// Install or reconfigure the fake clock in the current and future documents
+    async fn install_clock(&self, start_ms: Option<f64>, rate: f64) -> Result<()> {
+        let script = clock_script(start_ms, rate);
+
+        let previous = self
+            .clock_script_id
+            .lock()
+            .ok()
+            .and_then(|mut id| id.take());
+        if let Some(identifier) = previous {
+            let _ = self
+                .connection
+                .send_command(
+                    "Page.removeScriptToEvaluateOnNewDocument",
+                    json!({ "identifier": identifier }),
+                )
+                .await;
+        }
+
+        let result = self
+            .connection
+            .send_command(
+                "Page.addScriptToEvaluateOnNewDocument",
+                json!({ "source": script }),
+            )
+            .await
+            .map_err(|e| e.context("Failed to install fake clock"))?;
+        if let Ok(mut id) = self.clock_script_id.lock() {
+            *id = result["identifier"].as_str().map(String::from);
+        }
+
+        self.evaluate(&script).await?;
+        Ok(())
+    }
+
+    /// Freeze the page like Chrome does with background tabs
+    ///
+    /// Timers, script tasks and network loading stop until
+    /// [`resume`](Self::resume), so commands that run script, e.g.
+    /// [`evaluate`](Self::evaluate), do not finish on a frozen page.
+    pub async fn freeze(&self) -> Result<()> {
+        self.connection
+            .send_command("Page.setWebLifecycleState", json!({ "state": "frozen" }))
+            .await
+            .map_err(|e| e.context("Failed to freeze page"))?;
+        Ok(())
+    }
+
+    /// Resume a page stopped with [`freeze`](Self::freeze)
+    ///
+    /// The page is also brought to the front, so it is visible again rather
+    /// than a background tab that the browser throttles.
+    pub async fn resume(&self) -> Result<()> {
+        self.connection
+            .send_command("Page.setWebLifecycleState", json!({ "state": "active" }))
+            .await
+            .map_err(|e| e.context("Failed to resume page"))?;
+        self.connection
+            .send_command("Page.bringToFront", json!({}))
+            .await
+            .map_err(|e| e.context("Failed to bring page to front"))?;
+        Ok(())
     }
 
     /// Close the page/tab
+    ///
+    /// Returns once the browser has destroyed the target. Closing a page that
+    /// is already closed, or was closed by the browser, succeeds; concurrent
+    /// calls wait for the first one.
     pub async fn close(&self) -> Result<()> {
-        self.connection
-            .send_command("Page.close", json!({}))
+        let mut closed = self.closed.lock().await;
+        if *closed || self.is_gone() {
+            *closed = true;
+            return Ok(());
+        }
+
+        let timeout = self.timeouts().close_timeout();
+        let browser = self.browser.lock().ok().and_then(|browser| browser.clone());
+        let mut state = self.connection.state_changes();
+        let close = async {
+            // Target lifecycle events are only sent once discovery is enabled
+            let mut destroyed = match &browser {
+                Some(browser) => {
+                    let events = browser.subscribe();
+                    browser
+                        .send_command("Target.setDiscoverTargets", json!({ "discover": true }))
+                        .await
+                        .ok()
+                        .map(|_| events)
+                }
+                None => None,
+            };
+            match self.connection.send_command("Page.close", json!({})).await {
+                // The target may disappear before answering
+                Ok(_) => {}
+                Err(e) if self.is_gone() || is_target_gone(&e) => return Ok(()),
+                Err(e) => return Err(e.context("Failed to close page")),
+            }
+            match &mut destroyed {
+                Some(events) => {
+                    while let Some(event) = events.recv().await {
+                        if event.method == "Target.targetDestroyed"
+                            && event.params["targetId"] == self.target_id()
+                        {
+                            break;
+                        }
+                    }
+                }
+                // Without a browser connection, the page connection dropping is the only sign
+                None => {
+                    let _ = state
+                        .wait_for(|state| *state != ConnectionState::Connected)
+                        .await;
+                }
+            }
+            Ok(())
+        };
+        tokio::time::timeout(timeout, close)
             .await
-            .map_err(|e| Error::Browser(format!("Failed to close page: {}", e)))?;
+            .map_err(|_| Error::Timeout(format!("Page did not close within {:?}", timeout)))??;
+        *closed = true;
         Ok(())
     }
+
+    /// Let [`close`](Self::close) wait for `Target.targetDestroyed` on the browser connection
+    pub(crate) fn set_browser_connection(&self, connection: CdpConnection) {
+        if let Ok(mut browser) = self.browser.lock() {
+            *browser = Some(connection);
+        }
+    }
+
+    /// WebSocket debugger URL the page was attached with
+    pub(crate) fn ws_url(&self) -> &str {
+        &self.ws_url
+    }
+
+    /// Id of the page's target, the last segment of its WebSocket URL
+    fn target_id(&self) -> &str {
+        self.ws_url.rsplit('/').next().unwrap_or_default()
+    }
+
+    /// Whether the connection is closed or the target crashed or detached
+    fn is_gone(&self) -> bool {
+        self.connection.state() != ConnectionState::Connected
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::connection::tests::{mock_server, server_recv, server_send};
+    use crate::CdpError;
 
     #[test]
-    fn test_wait_for_element_script_format() {
-        let selector = "div.main";
-        let script = format!(
-            "!!document.querySelector(\"{}\")",
-            selector.replace('"', "\\\"")
-        );
-        assert_eq!(script, "!!document.querySelector(\"div.main\")");
+    fn test_is_target_gone() {
+        assert!(is_target_gone(&Error::TargetDetached(
+            "target_closed".to_string()
+        )));
+        assert!(is_target_gone(&Error::Protocol(CdpError::from_response(
+            &json!({"code": -32001, "message": "Session with given id not found."})
+        ))));
+        assert!(!is_target_gone(&Error::Timeout("slow".to_string())));
     }
 
     #[test]
-    fn test_wait_for_element_script_with_quotes() {
-        let selector = r#"div[data-attr="test"]"#;
-        let script = format!(
-            "!!document.querySelector(\"{}\")",
-            selector.replace('"', "\\\"")
+    fn test_frontend_url() {
+        let http = DevToolsHttpClient::new("10.0.0.5", 9222);
+        assert_eq!(
+            frontend_url(
+                &http,
+                "T1",
+                Some("/devtools/inspector.html?ws=10.0.0.5:9222/devtools/page/T1")
+            ),
+            "http://10.0.0.5:9222/devtools/inspector.html?ws=10.0.0.5:9222/devtools/page/T1"
+        );
+        let hosted = "https://chrome-devtools-frontend.appspot.com/serve_rev/@abc/inspector.html?ws=10.0.0.5:9222/devtools/page/T1";
+        assert_eq!(frontend_url(&http, "T1", Some(hosted)), hosted);
+        assert_eq!(
+            frontend_url(&http, "T1", None),
+            "http://10.0.0.5:9222/devtools/inspector.html?ws=10.0.0.5:9222/devtools/page/T1"
+        );
+        assert_eq!(
+            frontend_url(&http.https(true), "T1", None),
+            "https://10.0.0.5:9222/devtools/inspector.html?wss=10.0.0.5:9222/devtools/page/T1"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_capture_region_keeps_its_result_when_scroll_restore_fails() {
+        use base64::Engine;
+
+        let url = mock_server(|mut ws| async move {
+            loop {
+                let request = server_recv(&mut ws).await;
+                let expression = request["params"]["expression"].as_str().unwrap_or_default();
+                let reply = match request["method"].as_str().unwrap() {
+                    "Runtime.evaluate" if expression.starts_with('[') => {
+                        json!({"result": {"result": {"value": [0, 40, 800, 600]}}})
+                    }
+                    // Restoring the scroll position fails, e.g. after a navigation
+                    "Runtime.evaluate" if expression.ends_with("(0, 40)") => {
+                        json!({"error": {"code": -32000, "message": "Execution context was destroyed."}})
+                    }
+                    "Page.captureScreenshot" => json!({"result": {
+                        "data": base64::engine::general_purpose::STANDARD.encode(b"png")
+                    }}),
+                    _ => json!({"result": {}}),
+                };
+                let mut reply = reply;
+                reply["id"] = request["id"].clone();
+                server_send(&mut ws, reply).await;
+            }
+        })
+        .await;
+        let page = CdpPage::new_with_domains(&url, Timeouts::default(), &[])
+            .await
+            .unwrap();
+
+        let image = page.capture_region(0.0, 900.0, 400.0, 300.0, 1.0).await;
+        assert_eq!(image.unwrap(), b"png");
+    }
+
+    #[tokio::test]
+    async fn test_close_is_idempotent() {
+        let url = mock_server(|mut ws| async move {
+            loop {
+                let request = server_recv(&mut ws).await;
+                server_send(&mut ws, json!({"id": request["id"], "result": {}})).await;
+                if request["method"] == "Page.close" {
+                    break;
+                }
+            }
+            // Destroying the target closes the socket
+        })
+        .await;
+        let page = CdpPage::new_with_domains(&url, Timeouts::default(), &[])
+            .await
+            .unwrap();
+
+        let (first, second) = tokio::join!(page.close(), page.close());
+        first.unwrap();
+        second.unwrap();
+        page.close().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_close_waits_for_target_destroyed() {
+        let (closed_tx, closed_rx) = tokio::sync::oneshot::channel();
+        let browser_url = mock_server(|mut ws| async move {
+            let request = server_recv(&mut ws).await;
+            assert_eq!(request["method"], "Target.setDiscoverTargets");
+            server_send(&mut ws, json!({"id": request["id"], "result": {}})).await;
+            closed_rx.await.unwrap();
+            for target_id in ["other", "mock"] {
+                let destroyed = json!({
+                    "method": "Target.targetDestroyed",
+                    "params": {"targetId": target_id}
+                });
+                server_send(&mut ws, destroyed).await;
+            }
+            std::future::pending::<()>().await;
+        })
+        .await;
+        let url = mock_server(|mut ws| async move {
+            let mut closed_tx = Some(closed_tx);
+            // The page socket stays open after closing
+            loop {
+                let request = server_recv(&mut ws).await;
+                server_send(&mut ws, json!({"id": request["id"], "result": {}})).await;
+                if request["method"] == "Page.close" {
+                    closed_tx.take().unwrap().send(()).unwrap();
+                }
+            }
+        })
+        .await;
+        let page = CdpPage::new_with_domains(&url, Timeouts::default(), &[])
+            .await
+            .unwrap();
+        page.set_browser_connection(CdpConnection::connect(&browser_url).await.unwrap());
+
+        tokio::time::timeout(Duration::from_secs(5), page.close())
+            .await
+            .unwrap()
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_goto_reports_navigation_error_text() {
+        let url = mock_server(|mut ws| async move {
+            loop {
+                let request = server_recv(&mut ws).await;
+                let result = match request["method"].as_str() {
+                    Some("Page.navigate") => json!({
+                        "frameId": "MAIN",
+                        "loaderId": "L1",
+                        "errorText": "net::ERR_BLOCKED_BY_CLIENT"
+                    }),
+                    _ => json!({}),
+                };
+                server_send(&mut ws, json!({"id": request["id"], "result": result})).await;
+            }
+        })
+        .await;
+        let page = CdpPage::new_with_domains(&url, Timeouts::default(), &[])
+            .await
+            .unwrap();
+
+        let err = page.goto("https://blocked.example/").await.unwrap_err();
+        assert!(err.to_string().contains("net::ERR_BLOCKED_BY_CLIENT"));
+    }
+
+    #[tokio::test]
+    async fn test_goto_holds_host_slot_until_load() {
+        let (load_tx, load_rx) = tokio::sync::oneshot::channel::<()>();
+        let (sent_tx, mut sent) = tokio::sync::mpsc::unbounded_channel();
+        let url = mock_server(|mut ws| async move {
+            let mut load_rx = Some(load_rx);
+            loop {
+                let request = server_recv(&mut ws).await;
+                let _ = sent_tx.send(request["method"].clone());
+                let result = match request["method"].as_str() {
+                    Some("Page.navigate") => json!({"frameId": "MAIN", "loaderId": "L1"}),
+                    _ => json!({}),
+                };
+                server_send(&mut ws, json!({"id": request["id"], "result": result})).await;
+                if request["method"] == "Page.navigate" {
+                    load_rx.take().unwrap().await.unwrap();
+                    let load = json!({"method": "Page.loadEventFired", "params": {}});
+                    server_send(&mut ws, load).await;
+                }
+            }
+        })
+        .await;
+        let page = CdpPage::new_with_domains(&url, Timeouts::default(), &[])
+            .await
+            .unwrap();
+        let limiter = Arc::new(HostLimiter::new(1));
+        page.set_host_limiter(Some(limiter.clone()));
+
+        page.goto("https://app.example/").await.unwrap();
+        let next = limiter.acquire("https://app.example/other");
+        tokio::pin!(next);
+        assert!(tokio::time::timeout(Duration::from_millis(100), &mut next)
+            .await
+            .is_err());
+        load_tx.send(()).unwrap();
+        tokio::time::timeout(Duration::from_secs(5), next)
+            .await
+            .unwrap();
+        // Lazily enabled, or the load event releasing the slot never fires
+        let mut before_navigate = Vec::new();
+        while let Some(method) = sent.recv().await.filter(|m| m != "Page.navigate") {
+            before_navigate.push(method);
+        }
+        assert!(before_navigate.contains(&json!("Page.enable")));
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_download_reports_failed_stream() {
+        let url = mock_server(|mut ws| async move {
+            loop {
+                let request = server_recv(&mut ws).await;
+                let reply = match request["method"].as_str() {
+                    Some("Fetch.takeResponseBodyAsStream") => json!({"error": {
+                        "code": -32000, "message": "Can only get response body on HeadersReceived pattern matched requests."
+                    }}),
+                    _ => json!({"result": {}}),
+                };
+                let mut reply = reply;
+                reply["id"] = request["id"].clone();
+                server_send(&mut ws, reply).await;
+                if request["method"] == "Fetch.enable" {
+                    let paused = json!({"method": "Fetch.requestPaused", "params": {
+                        "requestId": "interception-1",
+                        "request": {"url": "https://a.example/report.zip"},
+                        "resourceType": "Document",
+                        "responseStatusCode": 200,
+                        "responseHeaders": [{"name": "Content-Type", "value": "application/zip"}]
+                    }});
+                    server_send(&mut ws, paused).await;
+                }
+            }
+        })
+        .await;
+        let page = CdpPage::new_with_domains(&url, Timeouts::default(), &[])
+            .await
+            .unwrap();
+        let dir = std::env::temp_dir().join(format!("cdp-downloads-{}", uuid::Uuid::new_v4()));
+
+        let (captured, download) = tokio::join!(
+            page.set_download_capture(&dir),
+            page.wait_for_download(Duration::from_secs(5))
+        );
+        captured.unwrap();
+        let err = download.unwrap_err();
+        assert!(
+            err.to_string().contains("Failed to save download"),
+            "{}",
+            err
+        );
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn test_failed_goto_captures_artifacts() {
+        let url = mock_server(|mut ws| async move {
+            loop {
+                let request = server_recv(&mut ws).await;
+                let reply = match request["method"].as_str() {
+                    Some("Page.getFrameTree") => json!({"result": {"frameTree": {
+                        "frame": {"id": "MAIN", "url": "https://app.example/login"}
+                    }}}),
+                    Some("Page.navigate") => json!({"error": {
+                        "code": -32000, "message": "Cannot navigate to invalid URL"
+                    }}),
+                    Some("Page.captureScreenshot") => json!({"result": {
+                        "data": base64::engine::general_purpose::STANDARD.encode(b"png")
+                    }}),
+                    _ => json!({"result": {}}),
+                };
+                let mut reply = reply;
+                reply["id"] = request["id"].clone();
+                server_send(&mut ws, reply).await;
+            }
+        })
+        .await;
+        let page = CdpPage::new_with_domains(&url, Timeouts::default(), &[])
+            .await
+            .unwrap();
+        let dir = std::env::temp_dir().join(format!("cdp-failures-{}", uuid::Uuid::new_v4()));
+        page.set_failure_capture(Some(FailureCapture::new(&dir)))
+            .await
+            .unwrap();
+
+        let err = page.goto("not a url").await.unwrap_err();
+        let artifacts = err.artifacts().unwrap();
+        assert_eq!(artifacts.operation, "goto");
+        assert_eq!(artifacts.url.as_deref(), Some("https://app.example/login"));
+        assert_eq!(
+            std::fs::read(artifacts.screenshot.as_ref().unwrap()).unwrap(),
+            b"png"
+        );
+        let Error::WithArtifacts { error, .. } = &err else {
+            panic!("expected artifacts, got {:?}", err);
+        };
+        assert!(error.to_string().contains("Cannot navigate to invalid URL"));
+        assert!(matches!(err.root(), Error::Protocol(_)));
+
+        // Without capture the error is returned as is
+        page.set_failure_capture(None).await.unwrap();
+        let err = page.goto("not a url").await.unwrap_err();
+        assert!(err.artifacts().is_none());
+        // The browser's error stays typed behind the context
+        let Error::Context { source, .. } = &err else {
+            panic!("expected context, got {:?}", err);
+        };
+        assert!(
+            matches!(&**source, Error::Protocol(e) if e.code == crate::CdpErrorCode::ServerError)
+        );
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_set_content_targets_main_frame() {
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        let url = mock_server(|mut ws| async move {
+            loop {
+                let request = server_recv(&mut ws).await;
+                let result = match request["method"].as_str() {
+                    Some("Page.getFrameTree") => {
+                        json!({"frameTree": {"frame": {"id": "MAIN", "url": "about:blank"}}})
+                    }
+                    _ => json!({}),
+                };
+                server_send(&mut ws, json!({"id": request["id"], "result": result})).await;
+                if request["method"] == "Page.setDocumentContent" {
+                    let _ = tx.send(request["params"].clone());
+                    break;
+                }
+            }
+        })
+        .await;
+        let page = CdpPage::new_with_domains(&url, Timeouts::default(), &[])
+            .await
+            .unwrap();
+
+        page.set_content("<p>hi</p>").await.unwrap();
+        assert_eq!(
+            rx.await.unwrap(),
+            json!({"frameId": "MAIN", "html": "<p>hi</p>"})
+        );
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_route_change_reports_main_frame_history_calls() {
+        let url = mock_server(|mut ws| async move {
+            loop {
+                let request = server_recv(&mut ws).await;
+                let result = match request["method"].as_str() {
+                    Some("Page.getFrameTree") => {
+                        json!({"frameTree": {"frame": {"id": "MAIN", "url": "https://app.example/"}}})
+                    }
+                    _ => json!({}),
+                };
+                server_send(&mut ws, json!({"id": request["id"], "result": result})).await;
+                // Evaluating the hook is the last command before the wait starts
+                if request["method"] == "Runtime.evaluate" {
+                    break;
+                }
+            }
+            let payload = json!({"kind": "push", "url": "https://app.example/users"});
+            let events = [
+                json!({"method": "Runtime.bindingCalled", "params": {
+                    "name": ROUTE_BINDING, "payload": payload.to_string(), "executionContextId": 1
+                }}),
+                json!({"method": "Page.navigatedWithinDocument", "params": {
+                    "frameId": "CHILD", "url": "https://ads.example/#x", "navigationType": "fragment"
+                }}),
+                json!({"method": "Page.navigatedWithinDocument", "params": {
+                    "frameId": "MAIN", "url": "https://app.example/users", "navigationType": "historyApi"
+                }}),
+            ];
+            for event in events {
+                server_send(&mut ws, event).await;
+            }
+            let _ = server_recv(&mut ws).await;
+        })
+        .await;
+        let page = CdpPage::new_with_domains(&url, Timeouts::default(), &[])
+            .await
+            .unwrap();
+
+        let change = page
+            .wait_for_route_change(Duration::from_secs(5))
+            .await
+            .unwrap();
+        assert_eq!(
+            change,
+            RouteChange {
+                url: "https://app.example/users".to_string(),
+                kind: crate::RouteChangeKind::PushState,
+            }
         );
-        // The replace function escapes the quotes with backslash
+    }
+
+    #[test]
+    fn test_call_arguments_format() {
+        let args = call_arguments(&[json!("div.main")]);
+        assert_eq!(args, json!([{ "value": "div.main" }]));
+    }
+
+    #[test]
+    fn test_call_arguments_keep_selector_verbatim() {
+        let selector = r#"div[data-attr="te\st"] `x`"#;
+        let args = call_arguments(&[json!(selector)]);
+        // The selector is passed as a value and never spliced into script source
+        assert_eq!(args[0]["value"].as_str(), Some(selector));
+        assert!(!ELEMENT_EXISTS_FN.contains(selector));
+    }
+
+    #[test]
+    fn test_is_terminated() {
+        let terminated = json!({
+            "exceptionDetails": {"text": "Uncaught", "exception": {"description": "Error: Execution was terminated"}}
+        });
+        assert!(is_terminated(&terminated));
+        let thrown = json!({
+            "exceptionDetails": {"text": "Uncaught", "exception": {"description": "Error: boom"}}
+        });
+        assert!(!is_terminated(&thrown));
+        assert!(!is_terminated(&json!({"result": {"value": 1}})));
+    }
+
+    #[test]
+    fn test_exception_error_none_without_details() {
+        let result = json!({ "result": { "type": "boolean", "value": true } });
+        assert!(exception_error(&result).is_none());
+    }
+
+    #[test]
+    fn test_exception_error_message() {
+        let result = json!({
+            "exceptionDetails": {
+                "exception": { "description": "SyntaxError: bad selector" },
+                "lineNumber": 0,
+                "columnNumber": 3
+            }
+        });
+        let err = exception_error(&result).unwrap();
         assert_eq!(
-            script,
-            "!!document.querySelector(\"div[data-attr=\\\"test\\\"]\")"
+            err.to_string(),
+            "Browser error: JavaScript execution error at line 0, column 3: SyntaxError: bad selector"
         );
     }
 
@@ -245,6 +2450,44 @@ mod tests {
         assert_eq!(description, "Error message");
     }
 
+    #[test]
+    fn test_decode_screenshot() {
+        let result = json!({ "data": "iVBORw0KGgo=" });
+        let image = decode_screenshot(&result).unwrap();
+        assert_eq!(&image[..4], b"\x89PNG");
+    }
+
+    #[test]
+    fn test_decode_screenshot_missing_data() {
+        assert!(decode_screenshot(&json!({})).is_err());
+        assert!(decode_screenshot(&json!({ "data": "***" })).is_err());
+    }
+
+    #[test]
+    fn test_navigation_history_parsing() {
+        let result = json!({
+            "currentIndex": 1,
+            "entries": [
+                {"id": 3, "url": "about:blank", "userTypedURL": "about:blank", "title": "", "transitionType": "typed"},
+                {"id": 5, "url": "https://example.com/", "userTypedURL": "https://example.com", "title": "Example", "transitionType": "link"}
+            ]
+        });
+        let history = NavigationHistory::from_result(result).unwrap();
+        assert_eq!(history.current_index, 1);
+        assert_eq!(history.entries.len(), 2);
+        assert_eq!(history.entries[1].index, 1);
+        assert_eq!(history.entries[1].id, 5);
+        assert_eq!(history.entries[1].transition_type, "link");
+        assert_eq!(history.entries[1].user_typed_url, "https://example.com");
+        assert_eq!(history.current().unwrap().title, "Example");
+    }
+
+    #[test]
+    fn test_navigation_history_invalid() {
+        let result = json!({ "entries": "nope" });
+        assert!(NavigationHistory::from_result(result).is_err());
+    }
+
     #[tokio::test]
     async fn test_sleep_duration() {
         let start = std::time::Instant::now();
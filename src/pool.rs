@@ -0,0 +1,363 @@
+//! Warm tabs kept open between jobs
+
+use crate::{CdpBrowser, CdpPage, ConnectionState, Error, Result};
+use serde_json::json;
+use std::fmt;
+use std::ops::Deref;
+use std::sync::{Arc, Mutex};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// Settings for [`PagePool`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PagePoolOptions {
+    /// Most pages open at once, idle or checked out
+    pub size: usize,
+    /// Freeze idle pages like background tabs, and resume them on checkout
+    pub freeze_idle: bool,
+    /// URL new pages load before their first checkout, e.g. the app under test
+    pub url: Option<String>,
+}
+
+impl Default for PagePoolOptions {
+    fn default() -> Self {
+        Self {
+            size: 8,
+            freeze_idle: true,
+            url: None,
+        }
+    }
+}
+
+/// Tabs of one browser reused across checkouts instead of being reopened
+///
+/// Pages keep their document and state between checkouts. Idle pages are
+/// frozen with `Page.setWebLifecycleState`, so a large pool of warm tabs
+/// costs next to no CPU while it waits, and resumed when checked out again.
+/// Pages that crashed or were closed are replaced. Cloning is cheap; clones
+/// share the same pages.
+///
+/// ```no_run
+/// # async fn run(browser: std::sync::Arc<chrome_cdp::CdpBrowser>) -> chrome_cdp::Result<()> {
+/// use chrome_cdp::{PagePool, PagePoolOptions};
+///
+/// let pool = PagePool::new(
+///     browser,
+///     PagePoolOptions {
+///         size: 32,
+///         url: Some("https://app.example/".into()),
+///         ..Default::default()
+///     },
+/// );
+/// pool.warm_up().await?;
+///
+/// let page = pool.checkout().await?;
+/// page.evaluate("window.app.reset()").await?;
+/// page.release().await;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct PagePool {
+    shared: Arc<Shared>,
+}
+
+impl PagePool {
+    /// Pool of up to `options.size` pages on `browser`, opened on demand
+    pub fn new(browser: Arc<CdpBrowser>, options: PagePoolOptions) -> Self {
+        Self {
+            shared: Arc::new(Shared {
+                browser,
+                slots: Arc::new(Semaphore::new(options.size.max(1))),
+                options,
+                idle: Mutex::new(Vec::new()),
+            }),
+        }
+    }
+
+    /// Open pages until the pool is full, leaving them idle
+    pub async fn warm_up(&self) -> Result<()> {
+        let mut pages = Vec::new();
+        while let Ok(permit) = self.shared.slots.clone().try_acquire_owned() {
+            pages.push(self.checkout_with(permit).await?);
+        }
+        for page in pages {
+            page.release().await;
+        }
+        Ok(())
+    }
+
+    /// Take an idle page, or open one if there is none, waiting while all
+    /// pages are checked out
+    pub async fn checkout(&self) -> Result<PooledPage> {
+        let permit = self
+            .shared
+            .slots
+            .clone()
+            .acquire_owned()
+            .await
+            .map_err(|_| Error::Browser("Page pool was closed".to_string()))?;
+        self.checkout_with(permit).await
+    }
+
+    /// Browser the pages are opened on
+    pub(crate) fn browser(&self) -> &Arc<CdpBrowser> {
+        &self.shared.browser
+    }
+
+    /// Number of pages waiting for a checkout
+    pub fn idle(&self) -> usize {
+        self.shared.idle.lock().map(|idle| idle.len()).unwrap_or(0)
+    }
+
+    /// Close the idle pages; checked-out pages are closed when released
+    pub async fn close(&self) -> Result<()> {
+        self.shared.slots.close();
+        let idle = self
+            .shared
+            .idle
+            .lock()
+            .map(|mut idle| std::mem::take(&mut *idle))
+            .unwrap_or_default();
+        for page in idle {
+            page.close().await?;
+        }
+        Ok(())
+    }
+
+    /// Check out a page into a slot that is already reserved
+    async fn checkout_with(&self, permit: OwnedSemaphorePermit) -> Result<PooledPage> {
+        while let Some(page) = self.shared.take_idle() {
+            if !is_alive(&page) {
+                continue;
+            }
+            if self.shared.options.freeze_idle && page.resume().await.is_err() {
+                let _ = page.close().await;
+                continue;
+            }
+            return Ok(PooledPage::new(page, self.shared.clone(), permit));
+        }
+        let page = self
+            .shared
+            .browser
+            .new_page_connected(self.shared.options.url.as_deref())
+            .await?;
+        Ok(PooledPage::new(page, self.shared.clone(), permit))
+    }
+}
+
+impl fmt::Debug for PagePool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PagePool")
+            .field("options", &self.shared.options)
+            .field("idle", &self.idle())
+            .finish_non_exhaustive()
+    }
+}
+
+/// Page checked out of a [`PagePool`], returned to it when dropped
+pub struct PooledPage {
+    page: Option<CdpPage>,
+    shared: Arc<Shared>,
+    permit: Option<OwnedSemaphorePermit>,
+}
+
+impl PooledPage {
+    fn new(page: CdpPage, shared: Arc<Shared>, permit: OwnedSemaphorePermit) -> Self {
+        Self {
+            page: Some(page),
+            shared,
+            permit: Some(permit),
+        }
+    }
+
+    /// Return the page to the pool, waiting until it is frozen
+    pub async fn release(mut self) {
+        if let Some(page) = self.page.take() {
+            self.shared.check_in(page).await;
+        }
+    }
+
+    /// Close the page instead of returning it, e.g. after it got into a bad state
+    pub async fn discard(mut self) -> Result<()> {
+        match self.page.take() {
+            Some(page) => page.close().await,
+            None => Ok(()),
+        }
+    }
+}
+
+impl Deref for PooledPage {
+    type Target = CdpPage;
+
+    fn deref(&self) -> &CdpPage {
+        self.page
+            .as_ref()
+            .expect("the page is only taken when the checkout ends")
+    }
+}
+
+impl Drop for PooledPage {
+    fn drop(&mut self) {
+        let Some(page) = self.page.take() else {
+            return;
+        };
+        if let Ok(handle) = tokio::runtime::Handle::try_current() {
+            let shared = self.shared.clone();
+            // Keep the slot until the page is back, so the pool never exceeds its size
+            let permit = self.permit.take();
+            handle.spawn(async move {
+                shared.check_in(page).await;
+                drop(permit);
+            });
+        }
+    }
+}
+
+impl fmt::Debug for PooledPage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PooledPage").finish_non_exhaustive()
+    }
+}
+
+/// State shared between a pool and its checked-out pages
+struct Shared {
+    browser: Arc<CdpBrowser>,
+    options: PagePoolOptions,
+    /// Limits the pages open at once; only checked-out pages hold a permit
+    slots: Arc<Semaphore>,
+    /// Idle pages, most recently used last
+    idle: Mutex<Vec<CdpPage>>,
+}
+
+impl Shared {
+    fn take_idle(&self) -> Option<CdpPage> {
+        self.idle.lock().ok()?.pop()
+    }
+
+    /// Freeze a released page and keep it for the next checkout
+    async fn check_in(&self, page: CdpPage) {
+        if !is_alive(&page) {
+            return;
+        }
+        if self.slots.is_closed() || (self.options.freeze_idle && page.freeze().await.is_err()) {
+            let _ = page.close().await;
+            return;
+        }
+        if let Ok(mut idle) = self.idle.lock() {
+            idle.push(page);
+        }
+    }
+}
+
+impl Drop for Shared {
+    fn drop(&mut self) {
+        let Ok(idle) = self.idle.get_mut() else {
+            return;
+        };
+        let connections: Vec<_> = idle
+            .drain(..)
+            .map(|page| page.connection().clone())
+            .collect();
+        if let Ok(handle) = tokio::runtime::Handle::try_current() {
+            handle.spawn(async move {
+                for connection in connections {
+                    let _ = connection.send_command("Page.close", json!({})).await;
+                }
+            });
+        }
+    }
+}
+
+/// Whether a page can still be used, i.e. did not crash or close
+fn is_alive(page: &CdpPage) -> bool {
+    page.connection().state() == ConnectionState::Connected
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connection::tests::{created, fake_chrome};
+    use serde_json::Value;
+    use std::time::Duration;
+
+    /// States set with `Page.setWebLifecycleState`, and pages brought to the front, in order
+    fn lifecycle_states(commands: &Mutex<Vec<Value>>) -> Vec<String> {
+        commands
+            .lock()
+            .unwrap()
+            .iter()
+            .filter_map(|command| match command["method"].as_str() {
+                Some("Page.setWebLifecycleState") => {
+                    Some(command["params"]["state"].as_str().unwrap().to_string())
+                }
+                Some("Page.bringToFront") => Some("front".to_string()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_idle_pages_are_frozen_and_reused() {
+        let (endpoint, commands) = fake_chrome().await;
+        let browser = CdpBrowser::connect(&endpoint.host, endpoint.port)
+            .await
+            .unwrap();
+        let pool = PagePool::new(
+            Arc::new(browser),
+            PagePoolOptions {
+                size: 2,
+                ..Default::default()
+            },
+        );
+
+        let page = pool.checkout().await.unwrap();
+        page.release().await;
+        assert_eq!(pool.idle(), 1);
+        assert_eq!(lifecycle_states(&commands), ["frozen"]);
+
+        let first = pool.checkout().await.unwrap();
+        assert_eq!(created(&commands), 1);
+        assert_eq!(lifecycle_states(&commands), ["frozen", "active", "front"]);
+        let second = pool.checkout().await.unwrap();
+        assert_eq!(created(&commands), 2);
+        // Both pages are checked out
+        assert!(
+            tokio::time::timeout(Duration::from_millis(100), pool.checkout())
+                .await
+                .is_err()
+        );
+
+        drop(first);
+        let third = tokio::time::timeout(Duration::from_secs(5), pool.checkout())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(created(&commands), 2);
+        second.discard().await.unwrap();
+        third.release().await;
+        assert_eq!(pool.idle(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_warm_up_without_freezing() {
+        let (endpoint, commands) = fake_chrome().await;
+        let browser = CdpBrowser::connect(&endpoint.host, endpoint.port)
+            .await
+            .unwrap();
+        let pool = PagePool::new(
+            Arc::new(browser),
+            PagePoolOptions {
+                size: 3,
+                freeze_idle: false,
+                url: None,
+            },
+        );
+
+        pool.warm_up().await.unwrap();
+        assert_eq!(pool.idle(), 3);
+        assert_eq!(created(&commands), 3);
+        let _page = pool.checkout().await.unwrap();
+        assert_eq!(created(&commands), 3);
+        assert!(lifecycle_states(&commands).is_empty());
+    }
+}
@@ -0,0 +1,297 @@
+//! Launched browser processes and the helpers they start
+
+use std::io;
+use std::process::{Child, Command, ExitStatus};
+
+/// Browser process owning its renderer, GPU and crashpad helpers
+///
+/// On Unix the browser leads its own process group, which its helpers
+/// inherit. On Windows it is assigned to a Job Object that kills every
+/// process in it when closed.
+pub(crate) struct BrowserProcess {
+    child: Child,
+    #[cfg(windows)]
+    job: Option<JobObject>,
+}
+
+impl BrowserProcess {
+    /// Spawn `cmd` so that its helpers can be killed with it
+    ///
+    /// On Linux the browser is also killed when this process dies, since a
+    /// group of its own no longer receives the terminal's Ctrl-C and an
+    /// interrupted program runs no destructors.
+    pub(crate) fn spawn(cmd: &mut Command) -> io::Result<Self> {
+        #[cfg(unix)]
+        {
+            use std::os::unix::process::CommandExt;
+            cmd.process_group(0);
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            {
+                let parent = std::process::id() as libc::pid_t;
+                // SAFETY: the closure only calls async-signal-safe functions.
+                // The death signal follows the spawning thread, so the child
+                // is spawned on a thread living as long as this process.
+                unsafe {
+                    cmd.pre_exec(move || {
+                        if libc::prctl(libc::PR_SET_PDEATHSIG, libc::SIGKILL) != 0 {
+                            return Err(io::Error::last_os_error());
+                        }
+                        // The parent may have died before the signal was armed
+                        if libc::getppid() != parent {
+                            libc::_exit(1);
+                        }
+                        Ok(())
+                    });
+                }
+            }
+        }
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        let child = spawn_on_launcher_thread(cmd)?;
+        #[cfg(not(any(target_os = "linux", target_os = "android")))]
+        let child = cmd.spawn()?;
+        Ok(Self {
+            // Without a job, e.g. where nested jobs are not allowed, helpers
+            // are found through their parent ids instead
+            #[cfg(windows)]
+            job: JobObject::assign(&child).ok(),
+            child,
+        })
+    }
+
+    /// Process id of the browser
+    pub(crate) fn id(&self) -> u32 {
+        self.child.id()
+    }
+
+    /// The child process, for taking its output pipes
+    ///
+    /// Reap it through [`try_reap`](Self::try_reap) or
+    /// [`kill_tree`](Self::kill_tree) only, so its group is signalled while
+    /// its id is still reserved.
+    pub(crate) fn child(&mut self) -> &mut Child {
+        &mut self.child
+    }
+
+    /// Whether the browser exited, without reaping it
+    pub(crate) fn has_exited(&mut self) -> bool {
+        #[cfg(unix)]
+        {
+            // SAFETY: siginfo_t is plain data that waitid fills in
+            let mut info: libc::siginfo_t = unsafe { std::mem::zeroed() };
+            // SAFETY: WNOWAIT leaves the child waitable, so std still reaps it
+            let rc = unsafe {
+                libc::waitid(
+                    libc::P_PID,
+                    self.child.id() as libc::id_t,
+                    &mut info,
+                    libc::WEXITED | libc::WNOHANG | libc::WNOWAIT,
+                )
+            };
+            // Fails once the child has been reaped
+            rc != 0 || siginfo_pid(&info) != 0
+        }
+        #[cfg(not(unix))]
+        {
+            // The open process handle keeps the id from being reused
+            matches!(self.child.try_wait(), Ok(Some(_)))
+        }
+    }
+
+    /// Reap the browser if it exited, first killing the helpers it left behind
+    pub(crate) fn try_reap(&mut self) -> Option<ExitStatus> {
+        if !self.has_exited() {
+            return None;
+        }
+        self.kill_group();
+        self.child.try_wait().ok().flatten()
+    }
+
+    /// Ask the browser to terminate
+    pub(crate) fn terminate(&self) {
+        #[cfg(unix)]
+        // SAFETY: kill(2) has no memory safety requirements; the pid belongs to
+        // a child that has not been reaped yet, so it cannot have been reused
+        unsafe {
+            libc::kill(self.child.id() as libc::pid_t, libc::SIGTERM);
+        }
+    }
+
+    /// Kill the browser and every process it started, then reap it
+    pub(crate) fn kill_tree(&mut self) -> io::Result<ExitStatus> {
+        self.kill_group();
+        // Fails if the browser already exited, which is fine
+        let _ = self.child.kill();
+        self.child.wait()
+    }
+
+    /// Kill the browser's helpers, and the browser unless it already exited
+    ///
+    /// Must only be called before the browser is reaped: a group id is
+    /// reserved while its leader is running or a zombie.
+    fn kill_group(&self) {
+        #[cfg(unix)]
+        // SAFETY: kill(2) has no memory safety requirements; the leader has
+        // not been reaped, so the group id still belongs to it
+        unsafe {
+            libc::kill(-(self.child.id() as libc::pid_t), libc::SIGKILL);
+        }
+        #[cfg(windows)]
+        match &self.job {
+            Some(job) => job.terminate(),
+            None => {
+                let _ = Command::new("taskkill")
+                    .args(["/T", "/F", "/PID", &self.child.id().to_string()])
+                    .stdout(std::process::Stdio::null())
+                    .stderr(std::process::Stdio::null())
+                    .status();
+            }
+        }
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn siginfo_pid(info: &libc::siginfo_t) -> libc::pid_t {
+    // SAFETY: waitid filled in the SIGCHLD fields, or left them zeroed
+    unsafe { info.si_pid() }
+}
+
+#[cfg(all(unix, not(any(target_os = "linux", target_os = "android"))))]
+fn siginfo_pid(info: &libc::siginfo_t) -> libc::pid_t {
+    info.si_pid
+}
+
+/// Job Object killing its processes when its last handle is closed
+#[cfg(windows)]
+struct JobObject(windows_sys::Win32::Foundation::HANDLE);
+
+// SAFETY: a job handle may be used and closed from any thread
+#[cfg(windows)]
+unsafe impl Send for JobObject {}
+#[cfg(windows)]
+unsafe impl Sync for JobObject {}
+
+#[cfg(windows)]
+impl JobObject {
+    /// Put `child` and the processes it starts from now on into a new job
+    fn assign(child: &Child) -> io::Result<Self> {
+        use std::os::windows::io::AsRawHandle;
+        use windows_sys::Win32::System::JobObjects::{
+            AssignProcessToJobObject, CreateJobObjectW, JobObjectExtendedLimitInformation,
+            SetInformationJobObject, JOBOBJECT_EXTENDED_LIMIT_INFORMATION,
+            JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE,
+        };
+
+        // SAFETY: null attributes and name create an anonymous job
+        let handle = unsafe { CreateJobObjectW(std::ptr::null(), std::ptr::null()) };
+        if handle.is_null() {
+            return Err(io::Error::last_os_error());
+        }
+        let job = Self(handle);
+        let mut info = JOBOBJECT_EXTENDED_LIMIT_INFORMATION::default();
+        info.BasicLimitInformation.LimitFlags = JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE;
+        // SAFETY: `info` is the structure the information class expects
+        let set = unsafe {
+            SetInformationJobObject(
+                job.0,
+                JobObjectExtendedLimitInformation,
+                &info as *const _ as *const core::ffi::c_void,
+                std::mem::size_of_val(&info) as u32,
+            )
+        };
+        if set == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: both handles are open for the duration of the call
+        if unsafe { AssignProcessToJobObject(job.0, child.as_raw_handle() as _) } == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(job)
+    }
+
+    fn terminate(&self) {
+        // SAFETY: the handle is open until drop
+        unsafe {
+            windows_sys::Win32::System::JobObjects::TerminateJobObject(self.0, 1);
+        }
+    }
+}
+
+#[cfg(windows)]
+impl Drop for JobObject {
+    fn drop(&mut self) {
+        // SAFETY: the handle is owned and closed exactly once
+        unsafe {
+            windows_sys::Win32::Foundation::CloseHandle(self.0);
+        }
+    }
+}
+
+/// Spawn `cmd` on a thread that lives as long as this process
+///
+/// A launch from a short-lived thread, e.g. in `spawn_blocking` or a thread
+/// running `block_on`, would otherwise arm the parent-death signal on a
+/// thread that exits while the program still runs.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn spawn_on_launcher_thread(cmd: &mut Command) -> io::Result<Child> {
+    use std::sync::mpsc;
+    use std::sync::OnceLock;
+
+    type Launch = (Command, mpsc::Sender<(Command, io::Result<Child>)>);
+    static LAUNCHER: OnceLock<Option<mpsc::Sender<Launch>>> = OnceLock::new();
+
+    let launcher = LAUNCHER.get_or_init(|| {
+        let (tx, rx) = mpsc::channel::<Launch>();
+        std::thread::Builder::new()
+            .name("chrome-cdp-launcher".to_string())
+            .spawn(move || {
+                for (mut cmd, reply) in rx {
+                    let child = cmd.spawn();
+                    let _ = reply.send((cmd, child));
+                }
+            })
+            .ok()
+            .map(|_| tx)
+    });
+    let Some(launcher) = launcher else {
+        return cmd.spawn();
+    };
+    let (reply, spawned) = mpsc::channel();
+    let owned = std::mem::replace(cmd, Command::new(""));
+    launcher
+        .send((owned, reply))
+        .map_err(|_| io::Error::other("Launcher thread is gone"))?;
+    let (owned, child) = spawned
+        .recv()
+        .map_err(|_| io::Error::other("Launcher thread is gone"))?;
+    *cmd = owned;
+    child
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exit_is_seen_before_reaping() {
+        let mut process = BrowserProcess::spawn(Command::new("sh").args(["-c", "exit 3"])).unwrap();
+        while !process.has_exited() {
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        // Still a zombie: its pid, and so its group id, is not reused yet
+        let pid = process.id() as libc::pid_t;
+        assert_eq!(unsafe { libc::kill(pid, 0) }, 0);
+        assert_eq!(process.try_reap().unwrap().code(), Some(3));
+        assert_eq!(unsafe { libc::kill(pid, 0) }, -1);
+    }
+
+    #[test]
+    fn test_outlives_the_spawning_thread() {
+        let mut process =
+            std::thread::spawn(|| BrowserProcess::spawn(Command::new("sleep").arg("30")).unwrap())
+                .join()
+                .unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(200));
+        assert!(!process.has_exited());
+        process.kill_tree().unwrap();
+    }
+}
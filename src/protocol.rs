@@ -0,0 +1,279 @@
+//! CDP protocol version pinning and compatibility shims
+
+use crate::{Error, Result};
+use serde::Deserialize;
+use serde_json::Value;
+
+/// CDP protocol version this crate is written against
+pub const PROTOCOL_VERSION: &str = "1.3";
+
+/// Method that was renamed or moved between Chrome releases
+struct MethodShim {
+    /// Name used by older Chrome releases
+    legacy: &'static str,
+    /// Name used by current Chrome releases
+    current: &'static str,
+    /// First Chrome major version that supports the current name
+    since_major: u32,
+    /// Convert legacy parameters to current ones
+    to_current: fn(Value) -> Value,
+    /// Convert current parameters to legacy ones
+    to_legacy: fn(Value) -> Value,
+}
+
+fn same(params: Value) -> Value {
+    params
+}
+
+/// `Page.addScriptToEvaluateOnLoad` used `scriptSource` instead of `source`
+fn script_source_to_source(mut params: Value) -> Value {
+    if let Some(source) = params
+        .as_object_mut()
+        .and_then(|p| p.remove("scriptSource"))
+    {
+        params["source"] = source;
+    }
+    params
+}
+
+fn source_to_script_source(mut params: Value) -> Value {
+    if let Some(source) = params.as_object_mut().and_then(|p| p.remove("source")) {
+        params["scriptSource"] = source;
+    }
+    params
+}
+
+const METHOD_SHIMS: &[MethodShim] = &[
+    MethodShim {
+        legacy: "Page.setDeviceMetricsOverride",
+        current: "Emulation.setDeviceMetricsOverride",
+        since_major: 61,
+        to_current: same,
+        to_legacy: same,
+    },
+    MethodShim {
+        legacy: "Page.clearDeviceMetricsOverride",
+        current: "Emulation.clearDeviceMetricsOverride",
+        since_major: 61,
+        to_current: same,
+        to_legacy: same,
+    },
+    MethodShim {
+        legacy: "Page.setGeolocationOverride",
+        current: "Emulation.setGeolocationOverride",
+        since_major: 61,
+        to_current: same,
+        to_legacy: same,
+    },
+    MethodShim {
+        legacy: "Page.clearGeolocationOverride",
+        current: "Emulation.clearGeolocationOverride",
+        since_major: 61,
+        to_current: same,
+        to_legacy: same,
+    },
+    MethodShim {
+        legacy: "Page.setTouchEmulationEnabled",
+        current: "Emulation.setTouchEmulationEnabled",
+        since_major: 61,
+        to_current: same,
+        to_legacy: same,
+    },
+    MethodShim {
+        legacy: "Page.addScriptToEvaluateOnLoad",
+        current: "Page.addScriptToEvaluateOnNewDocument",
+        since_major: 63,
+        to_current: script_source_to_source,
+        to_legacy: source_to_script_source,
+    },
+    MethodShim {
+        legacy: "Page.removeScriptToEvaluateOnLoad",
+        current: "Page.removeScriptToEvaluateOnNewDocument",
+        since_major: 63,
+        to_current: same,
+        to_legacy: same,
+    },
+    MethodShim {
+        legacy: "Page.setDownloadBehavior",
+        current: "Browser.setDownloadBehavior",
+        since_major: 77,
+        to_current: same,
+        to_legacy: same,
+    },
+];
+
+/// Browser build information, as reported by `Browser.getVersion`
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VersionInfo {
+    /// Product name and version, e.g. `HeadlessChrome/120.0.6099.71`
+    pub product: String,
+    /// Chromium revision
+    pub revision: String,
+    /// CDP protocol version, e.g. `1.3`
+    pub protocol_version: String,
+    /// Default user agent
+    pub user_agent: String,
+    /// V8 version
+    pub js_version: String,
+}
+
+impl VersionInfo {
+    /// Major Chrome version, e.g. `120`
+    pub fn major(&self) -> Option<u32> {
+        parse_browser_major(&self.product)
+    }
+}
+
+/// Protocol compatibility information for a connected browser
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ProtocolCompat {
+    /// Chrome major version, if it could be detected
+    pub browser_major: Option<u32>,
+    /// Protocol version reported by the browser
+    pub protocol_version: Option<String>,
+}
+
+impl ProtocolCompat {
+    /// Build from a `/json/version` response
+    pub fn from_version_info(info: &Value) -> Self {
+        Self {
+            browser_major: info["Browser"].as_str().and_then(parse_browser_major),
+            protocol_version: info["Protocol-Version"].as_str().map(String::from),
+        }
+    }
+
+    /// Verify the browser speaks the pinned protocol version
+    pub fn check(&self) -> Result<()> {
+        let expected = PROTOCOL_VERSION.split('.').next();
+        match &self.protocol_version {
+            Some(version) if version.split('.').next() != expected => Err(Error::Cdp(format!(
+                "Unsupported protocol version {} (expected {})",
+                version, PROTOCOL_VERSION
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    /// Translate a command to the name and parameters understood by the browser
+    ///
+    /// Commands are left untouched when the browser version is unknown.
+    pub fn translate(&self, method: &str, params: Value) -> (String, Value) {
+        let Some(major) = self.browser_major else {
+            return (method.to_string(), params);
+        };
+
+        for shim in METHOD_SHIMS {
+            if major >= shim.since_major && method == shim.legacy {
+                return (shim.current.to_string(), (shim.to_current)(params));
+            }
+            if major < shim.since_major && method == shim.current {
+                return (shim.legacy.to_string(), (shim.to_legacy)(params));
+            }
+        }
+        (method.to_string(), params)
+    }
+}
+
+/// Parse the major version out of a product string like `Chrome/120.0.6099.71`
+pub(crate) fn parse_browser_major(product: &str) -> Option<u32> {
+    product.split('/').nth(1)?.split('.').next()?.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_version_info_from_get_version() {
+        let info: VersionInfo = serde_json::from_value(json!({
+            "protocolVersion": "1.3",
+            "product": "HeadlessChrome/120.0.6099.71",
+            "revision": "@9729082fe6174c0a371fc66501f5efc5d69d3d2b",
+            "userAgent": "Mozilla/5.0 HeadlessChrome/120.0.6099.71",
+            "jsVersion": "12.0.267.8"
+        }))
+        .unwrap();
+        assert_eq!(info.major(), Some(120));
+        assert_eq!(info.js_version, "12.0.267.8");
+    }
+
+    #[test]
+    fn test_parse_browser_major() {
+        assert_eq!(parse_browser_major("Chrome/120.0.6099.71"), Some(120));
+        assert_eq!(parse_browser_major("HeadlessChrome/89.0.4389.0"), Some(89));
+        assert_eq!(parse_browser_major("Chrome"), None);
+        assert_eq!(parse_browser_major("Chrome/beta"), None);
+    }
+
+    #[test]
+    fn test_from_version_info() {
+        let info = json!({
+            "Browser": "HeadlessChrome/120.0.6099.71",
+            "Protocol-Version": "1.3"
+        });
+        let compat = ProtocolCompat::from_version_info(&info);
+        assert_eq!(compat.browser_major, Some(120));
+        assert_eq!(compat.protocol_version.as_deref(), Some("1.3"));
+        assert!(compat.check().is_ok());
+    }
+
+    #[test]
+    fn test_check_rejects_other_major_protocol() {
+        let compat = ProtocolCompat {
+            browser_major: Some(200),
+            protocol_version: Some("2.0".to_string()),
+        };
+        assert!(matches!(compat.check(), Err(Error::Cdp(_))));
+    }
+
+    #[test]
+    fn test_translate_legacy_to_current() {
+        let compat = ProtocolCompat {
+            browser_major: Some(120),
+            protocol_version: None,
+        };
+        let (method, params) = compat.translate(
+            "Page.addScriptToEvaluateOnLoad",
+            json!({ "scriptSource": "1" }),
+        );
+        assert_eq!(method, "Page.addScriptToEvaluateOnNewDocument");
+        assert_eq!(params, json!({ "source": "1" }));
+    }
+
+    #[test]
+    fn test_translate_current_to_legacy() {
+        let compat = ProtocolCompat {
+            browser_major: Some(60),
+            protocol_version: None,
+        };
+        let (method, params) = compat.translate(
+            "Page.addScriptToEvaluateOnNewDocument",
+            json!({ "source": "1" }),
+        );
+        assert_eq!(method, "Page.addScriptToEvaluateOnLoad");
+        assert_eq!(params, json!({ "scriptSource": "1" }));
+        // Fetch has a different event flow than legacy interception, so it is not translated
+        let (method, _) = compat.translate("Fetch.enable", json!({ "patterns": [] }));
+        assert_eq!(method, "Fetch.enable");
+    }
+
+    #[test]
+    fn test_translate_unknown_version_is_identity() {
+        let compat = ProtocolCompat::default();
+        let (method, params) = compat.translate("Page.setDownloadBehavior", json!({}));
+        assert_eq!(method, "Page.setDownloadBehavior");
+        assert_eq!(params, json!({}));
+    }
+
+    #[test]
+    fn test_translate_unrelated_method_is_identity() {
+        let compat = ProtocolCompat {
+            browser_major: Some(120),
+            protocol_version: None,
+        };
+        let (method, _) = compat.translate("Page.navigate", json!({ "url": "about:blank" }));
+        assert_eq!(method, "Page.navigate");
+    }
+}
@@ -0,0 +1,120 @@
+//! Routing browser traffic through a proxy
+
+/// Proxy the launched browser sends its traffic through
+///
+/// Credentials are never passed on the command line, where Chrome ignores
+/// them; instead pages answer the proxy's authentication challenges over
+/// the Fetch domain. Only pages connected through the browser or a context,
+/// e.g. `new_page_connected` or `attach_page`, are set up this way; a page
+/// built with `CdpPage::new` from a raw WebSocket URL, including a popup's,
+/// needs [`CdpPage::set_proxy_credentials`](crate::CdpPage::set_proxy_credentials).
+///
+/// ```
+/// use chrome_cdp::{LaunchOptions, ProxyConfig};
+///
+/// let options = LaunchOptions {
+///     proxy: Some(
+///         ProxyConfig::new("http://proxy.example:3128")
+///             .bypass(["localhost", "*.internal"])
+///             .with_credentials("scraper", "s3cret"),
+///     ),
+///     ..Default::default()
+/// };
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProxyConfig {
+    /// Proxy server passed as `--proxy-server`, e.g. `http://host:3128` or `socks5://host:1080`
+    pub server: String,
+    /// Hosts reached directly, passed as `--proxy-bypass-list`
+    pub bypass_list: Vec<String>,
+    /// Credentials answering the proxy's authentication challenges
+    pub credentials: Option<ProxyCredentials>,
+}
+
+impl ProxyConfig {
+    /// Send all traffic through `server`
+    pub fn new(server: impl Into<String>) -> Self {
+        Self {
+            server: server.into(),
+            bypass_list: Vec::new(),
+            credentials: None,
+        }
+    }
+
+    /// Reach these hosts directly, e.g. `localhost` or `*.internal`
+    pub fn bypass<I, S>(mut self, hosts: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.bypass_list.extend(hosts.into_iter().map(Into::into));
+        self
+    }
+
+    /// Authenticate to the proxy with a username and password
+    pub fn with_credentials(
+        mut self,
+        username: impl Into<String>,
+        password: impl Into<String>,
+    ) -> Self {
+        self.credentials = Some(ProxyCredentials {
+            username: username.into(),
+            password: password.into(),
+        });
+        self
+    }
+
+    /// Command-line arguments routing traffic through the proxy
+    pub(crate) fn args(&self) -> Vec<String> {
+        let mut args = vec![format!("--proxy-server={}", self.server)];
+        if !self.bypass_list.is_empty() {
+            args.push(format!(
+                "--proxy-bypass-list={}",
+                self.bypass_list.join(";")
+            ));
+        }
+        args
+    }
+}
+
+/// Username and password for an authenticating proxy
+#[derive(Clone, PartialEq, Eq)]
+pub struct ProxyCredentials {
+    /// User name
+    pub username: String,
+    /// Password
+    pub password: String,
+}
+
+impl std::fmt::Debug for ProxyCredentials {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ProxyCredentials")
+            .field("username", &self.username)
+            .field("password", &"..")
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_proxy_args() {
+        assert_eq!(
+            ProxyConfig::new("socks5://127.0.0.1:1080").args(),
+            ["--proxy-server=socks5://127.0.0.1:1080"]
+        );
+        let proxy = ProxyConfig::new("http://proxy:3128")
+            .bypass(["localhost", "*.internal"])
+            .with_credentials("user", "s3cret");
+        assert_eq!(
+            proxy.args(),
+            [
+                "--proxy-server=http://proxy:3128",
+                "--proxy-bypass-list=localhost;*.internal"
+            ]
+        );
+        assert!(!format!("{:?}", proxy).contains("s3cret"));
+    }
+}
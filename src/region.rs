@@ -0,0 +1,234 @@
+//! Region presets bundling timezone, locale, languages, and geolocation
+
+use crate::emulation::Override;
+use serde_json::{json, Value};
+
+/// Consistent set of regional overrides for [`CdpPage::apply_region`](crate::CdpPage::apply_region)
+///
+/// Presets use the region's largest city; adjust fields for other places.
+///
+/// ```
+/// use chrome_cdp::RegionProfile;
+///
+/// let osaka = RegionProfile { latitude: 34.6937, longitude: 135.5023, ..RegionProfile::japan() };
+/// assert_eq!(osaka.timezone_id, "Asia/Tokyo");
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct RegionProfile {
+    /// IANA timezone, e.g. `Europe/Berlin`
+    pub timezone_id: String,
+    /// ICU locale used by `Intl` and `navigator.language`, e.g. `de-DE`
+    pub locale: String,
+    /// `Accept-Language` header and `navigator.languages`
+    pub accept_language: String,
+    /// Geolocation latitude in degrees
+    pub latitude: f64,
+    /// Geolocation longitude in degrees
+    pub longitude: f64,
+    /// Geolocation accuracy in meters
+    pub accuracy: f64,
+}
+
+impl RegionProfile {
+    fn preset(
+        timezone_id: &str,
+        locale: &str,
+        accept_language: &str,
+        latitude: f64,
+        longitude: f64,
+    ) -> Self {
+        Self {
+            timezone_id: timezone_id.to_string(),
+            locale: locale.to_string(),
+            accept_language: accept_language.to_string(),
+            latitude,
+            longitude,
+            accuracy: 100.0,
+        }
+    }
+
+    /// United States (New York)
+    pub fn united_states() -> Self {
+        Self::preset(
+            "America/New_York",
+            "en-US",
+            "en-US,en;q=0.9",
+            40.7128,
+            -74.0060,
+        )
+    }
+
+    /// United Kingdom (London)
+    pub fn united_kingdom() -> Self {
+        Self::preset("Europe/London", "en-GB", "en-GB,en;q=0.9", 51.5074, -0.1278)
+    }
+
+    /// Germany (Berlin)
+    pub fn germany() -> Self {
+        Self::preset(
+            "Europe/Berlin",
+            "de-DE",
+            "de-DE,de;q=0.9,en;q=0.8",
+            52.5200,
+            13.4050,
+        )
+    }
+
+    /// France (Paris)
+    pub fn france() -> Self {
+        Self::preset(
+            "Europe/Paris",
+            "fr-FR",
+            "fr-FR,fr;q=0.9,en;q=0.8",
+            48.8566,
+            2.3522,
+        )
+    }
+
+    /// Japan (Tokyo)
+    pub fn japan() -> Self {
+        Self::preset(
+            "Asia/Tokyo",
+            "ja-JP",
+            "ja-JP,ja;q=0.9,en;q=0.8",
+            35.6762,
+            139.6503,
+        )
+    }
+
+    /// Brazil (São Paulo)
+    pub fn brazil() -> Self {
+        Self::preset(
+            "America/Sao_Paulo",
+            "pt-BR",
+            "pt-BR,pt;q=0.9,en;q=0.8",
+            -23.5505,
+            -46.6333,
+        )
+    }
+
+    /// India (Mumbai)
+    pub fn india() -> Self {
+        Self::preset(
+            "Asia/Kolkata",
+            "en-IN",
+            "en-IN,en;q=0.9,hi;q=0.8",
+            19.0760,
+            72.8777,
+        )
+    }
+
+    /// Commands applying the profile, with the override each one sets
+    ///
+    /// The user agent is kept as is, and `metadata` carries the current UA-CH
+    /// values so client hints stay consistent with it after the override.
+    pub(crate) fn commands(
+        &self,
+        user_agent: &str,
+        metadata: Option<Value>,
+    ) -> Vec<(Override, &'static str, Value)> {
+        let mut user_agent = json!({
+            "userAgent": user_agent,
+            "acceptLanguage": self.accept_language,
+        });
+        if let Some(metadata) = metadata {
+            user_agent["userAgentMetadata"] = metadata;
+        }
+        vec![
+            (
+                Override::Timezone,
+                "Emulation.setTimezoneOverride",
+                json!({ "timezoneId": self.timezone_id }),
+            ),
+            (
+                Override::Locale,
+                "Emulation.setLocaleOverride",
+                json!({ "locale": self.locale }),
+            ),
+            (
+                Override::UserAgent,
+                "Emulation.setUserAgentOverride",
+                user_agent,
+            ),
+            (
+                Override::Geolocation,
+                "Emulation.setGeolocationOverride",
+                json!({
+                    "latitude": self.latitude,
+                    "longitude": self.longitude,
+                    "accuracy": self.accuracy,
+                }),
+            ),
+        ]
+    }
+}
+
+/// Expression resolving to the page's UA-CH values in `UserAgentMetadata` form, or `null`
+pub(crate) const USER_AGENT_METADATA_EXPR: &str = r#"(async () => {
+  const data = navigator.userAgentData;
+  if (!data) return null;
+  const high = await data.getHighEntropyValues(
+    ["architecture", "bitness", "fullVersionList", "model", "platformVersion", "wow64"]);
+  return {
+    brands: data.brands,
+    fullVersionList: high.fullVersionList,
+    platform: data.platform,
+    platformVersion: high.platformVersion || "",
+    architecture: high.architecture || "",
+    model: high.model || "",
+    mobile: data.mobile,
+    bitness: high.bitness || "",
+    wow64: !!high.wow64,
+  };
+})()"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_commands_cover_every_override() {
+        let commands = RegionProfile::germany().commands("UA/1.0", None);
+        let methods: Vec<_> = commands.iter().map(|(_, method, _)| *method).collect();
+        assert_eq!(
+            methods,
+            [
+                "Emulation.setTimezoneOverride",
+                "Emulation.setLocaleOverride",
+                "Emulation.setUserAgentOverride",
+                "Emulation.setGeolocationOverride",
+            ]
+        );
+        assert_eq!(commands[0].0, Override::Timezone);
+        assert_eq!(commands[0].2["timezoneId"], "Europe/Berlin");
+        assert_eq!(commands[1].2["locale"], "de-DE");
+        assert_eq!(commands[2].2["userAgent"], "UA/1.0");
+        assert_eq!(commands[2].2["acceptLanguage"], "de-DE,de;q=0.9,en;q=0.8");
+        assert!(commands[2].2.get("userAgentMetadata").is_none());
+        assert_eq!(commands[3].2["latitude"], 52.52);
+    }
+
+    #[test]
+    fn test_commands_keep_client_hints() {
+        let metadata = json!({ "platform": "Linux", "mobile": false });
+        let commands = RegionProfile::japan().commands("UA/1.0", Some(metadata.clone()));
+        assert_eq!(commands[2].2["userAgentMetadata"], metadata);
+    }
+
+    #[test]
+    fn test_presets_are_consistent() {
+        for profile in [
+            RegionProfile::united_states(),
+            RegionProfile::united_kingdom(),
+            RegionProfile::germany(),
+            RegionProfile::france(),
+            RegionProfile::japan(),
+            RegionProfile::brazil(),
+            RegionProfile::india(),
+        ] {
+            assert!(profile.accept_language.starts_with(&profile.locale));
+            assert!((-90.0..=90.0).contains(&profile.latitude));
+            assert!((-180.0..=180.0).contains(&profile.longitude));
+        }
+    }
+}
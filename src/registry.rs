@@ -0,0 +1,645 @@
+//! Pages spread over a fleet of remote browsers
+
+use crate::{CdpBrowser, CdpPage, Error, Result, Timeouts};
+use futures::future::BoxFuture;
+use serde_json::json;
+use std::fmt;
+use std::future::Future;
+use std::net::IpAddr;
+use std::ops::Deref;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+
+/// Default time between two health checks of the fleet
+const DEFAULT_HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Callback listing the endpoints currently making up the fleet
+type Discover = Arc<dyn Fn() -> BoxFuture<'static, Result<Vec<BrowserEndpoint>>> + Send + Sync>;
+
+/// DevTools HTTP endpoint of a remote browser, e.g. `chrome-0.chrome:9222`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct BrowserEndpoint {
+    /// Host name or IP address
+    pub host: String,
+    /// Port Chrome was started with via `--remote-debugging-port`
+    pub port: u16,
+}
+
+impl BrowserEndpoint {
+    /// Endpoint at `host:port`
+    pub fn new(host: impl Into<String>, port: u16) -> Self {
+        Self {
+            host: host.into(),
+            port,
+        }
+    }
+}
+
+impl fmt::Display for BrowserEndpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.host, self.port)
+    }
+}
+
+impl FromStr for BrowserEndpoint {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (host, port) = s
+            .rsplit_once(':')
+            .filter(|(host, _)| !host.is_empty())
+            .ok_or_else(|| Error::Browser(format!("Expected host:port, got '{}'", s)))?;
+        let port = port
+            .parse()
+            .map_err(|e| Error::Browser(format!("Invalid port in '{}': {}", s, e)))?;
+        Ok(Self::new(host, port))
+    }
+}
+
+/// State of one endpoint, as reported by [`BrowserRegistry::endpoints`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EndpointStatus {
+    /// The endpoint
+    pub endpoint: BrowserEndpoint,
+    /// Whether the last health check succeeded; only healthy endpoints get pages
+    pub healthy: bool,
+    /// Pages handed out by the registry and not yet closed
+    pub active_pages: usize,
+    /// Why the endpoint was last found unhealthy
+    pub last_error: Option<String>,
+}
+
+/// Builder for [`BrowserRegistry`]
+///
+/// ```no_run
+/// # async fn run() -> chrome_cdp::Result<()> {
+/// use chrome_cdp::BrowserRegistry;
+/// use std::time::Duration;
+///
+/// let registry = BrowserRegistry::builder()
+///     .endpoints(["chrome-0.chrome:9222".parse()?, "chrome-1.chrome:9222".parse()?])
+///     .health_check_interval(Duration::from_secs(5))
+///     .build();
+/// let page = registry.get_page().await?;
+/// page.goto("https://example.com").await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct BrowserRegistryBuilder {
+    endpoints: Vec<BrowserEndpoint>,
+    discover: Option<Discover>,
+    health_check_interval: Duration,
+    timeouts: Timeouts,
+}
+
+impl Default for BrowserRegistryBuilder {
+    fn default() -> Self {
+        Self {
+            endpoints: Vec::new(),
+            discover: None,
+            health_check_interval: DEFAULT_HEALTH_CHECK_INTERVAL,
+            timeouts: Timeouts::default(),
+        }
+    }
+}
+
+impl BrowserRegistryBuilder {
+    /// Add the browser at `host:port`
+    pub fn endpoint(mut self, host: &str, port: u16) -> Self {
+        self.endpoints.push(BrowserEndpoint::new(host, port));
+        self
+    }
+
+    /// Add several browsers
+    pub fn endpoints<I>(mut self, endpoints: I) -> Self
+    where
+        I: IntoIterator<Item = BrowserEndpoint>,
+    {
+        self.endpoints.extend(endpoints);
+        self
+    }
+
+    /// Look the fleet up again before every health check, e.g. by resolving
+    /// a headless Kubernetes service
+    ///
+    /// The endpoints returned replace the configured ones. Pages already
+    /// handed out keep running on endpoints that disappear. When the callback
+    /// fails, the previous endpoints are kept.
+    pub fn discover<F, Fut>(mut self, discover: F) -> Self
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Vec<BrowserEndpoint>>> + Send + 'static,
+    {
+        self.discover = Some(Arc::new(move || Box::pin(discover())));
+        self
+    }
+
+    /// Time between two health checks (default: 10 seconds)
+    pub fn health_check_interval(mut self, interval: Duration) -> Self {
+        self.health_check_interval = interval;
+        self
+    }
+
+    /// Timeouts for the browsers and the pages opened on them
+    pub fn timeouts(mut self, timeouts: Timeouts) -> Self {
+        self.timeouts = timeouts;
+        self
+    }
+
+    /// Build the registry and start checking its endpoints in the background
+    ///
+    /// # Panics
+    ///
+    /// Panics when called outside of a Tokio runtime, which runs the health
+    /// checks.
+    pub fn build(self) -> BrowserRegistry {
+        let fleet = Arc::new(Fleet {
+            members: Mutex::new(self.endpoints.into_iter().map(Member::new).collect()),
+            discover: self.discover,
+            timeouts: self.timeouts,
+            next: AtomicUsize::new(0),
+        });
+        let health_monitor = tokio::spawn({
+            let fleet = fleet.clone();
+            let mut interval =
+                tokio::time::interval(self.health_check_interval.max(Duration::from_millis(1)));
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            async move {
+                loop {
+                    interval.tick().await;
+                    // Endpoints and errors are kept for the next round
+                    let _ = fleet.refresh().await;
+                }
+            }
+        });
+        BrowserRegistry {
+            fleet,
+            health_monitor,
+        }
+    }
+}
+
+impl fmt::Debug for BrowserRegistryBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BrowserRegistryBuilder")
+            .field("endpoints", &self.endpoints)
+            .field("discover", &self.discover.is_some())
+            .field("health_check_interval", &self.health_check_interval)
+            .field("timeouts", &self.timeouts)
+            .finish()
+    }
+}
+
+/// Connections to several remote browsers, handing out pages on the least busy one
+///
+/// Each endpoint is attached with [`CdpBrowser::connect`] and checked
+/// periodically; endpoints that fail are skipped until they answer again.
+/// Pages are opened on the healthy endpoint with the fewest pages still open.
+/// Dropping the registry stops the health checks and leaves the remote
+/// browsers running.
+pub struct BrowserRegistry {
+    fleet: Arc<Fleet>,
+    health_monitor: JoinHandle<()>,
+}
+
+impl BrowserRegistry {
+    /// Create a new registry builder
+    pub fn builder() -> BrowserRegistryBuilder {
+        BrowserRegistryBuilder::default()
+    }
+
+    /// Open a page on the least busy healthy browser
+    ///
+    /// An endpoint failing to open the page is marked unhealthy and the next
+    /// one is tried. When no endpoint is known to be healthy, e.g. right
+    /// after [`build`](BrowserRegistryBuilder::build), the fleet is checked
+    /// once before giving up.
+    pub async fn get_page(&self) -> Result<RegistryPage> {
+        let mut tried = Vec::new();
+        let mut last_error = None;
+        let mut refreshed = false;
+        loop {
+            let Some((endpoint, browser, active)) = self.fleet.pick(&tried) else {
+                if tried.is_empty() && !refreshed {
+                    refreshed = true;
+                    self.fleet.refresh().await?;
+                    continue;
+                }
+                break;
+            };
+            // Count the page before opening it so concurrent calls spread out
+            let lease = PageLease::new(active);
+            match browser.new_page_connected(None).await {
+                Ok(page) => {
+                    return Ok(RegistryPage {
+                        page,
+                        endpoint,
+                        closed: false,
+                        _lease: lease,
+                    })
+                }
+                Err(e) => {
+                    self.fleet.mark_unhealthy(&endpoint, &e);
+                    last_error = Some(format!("{}: {}", endpoint, e));
+                    tried.push(endpoint);
+                }
+            }
+        }
+        let count = self.fleet.members.lock().map(|m| m.len()).unwrap_or(0);
+        Err(Error::Browser(match last_error {
+            Some(e) => format!(
+                "No healthy browser among {} endpoints, last error: {}",
+                count, e
+            ),
+            None => format!("No healthy browser among {} endpoints", count),
+        }))
+    }
+
+    /// Check every endpoint now, running discovery first if configured
+    ///
+    /// Fails only if discovery does; unhealthy endpoints are reported by
+    /// [`endpoints`](Self::endpoints).
+    pub async fn check_health(&self) -> Result<()> {
+        self.fleet.refresh().await
+    }
+
+    /// Current endpoints with their health and load
+    pub fn endpoints(&self) -> Vec<EndpointStatus> {
+        self.fleet
+            .members
+            .lock()
+            .map(|members| {
+                members
+                    .iter()
+                    .map(|member| EndpointStatus {
+                        endpoint: member.endpoint.clone(),
+                        healthy: member.browser.is_some(),
+                        active_pages: member.active.load(Ordering::SeqCst),
+                        last_error: member.last_error.clone(),
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+impl Drop for BrowserRegistry {
+    fn drop(&mut self) {
+        self.health_monitor.abort();
+    }
+}
+
+impl fmt::Debug for BrowserRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BrowserRegistry")
+            .field("endpoints", &self.endpoints())
+            .finish_non_exhaustive()
+    }
+}
+
+/// Page handed out by [`BrowserRegistry::get_page`], closed when dropped
+pub struct RegistryPage {
+    page: CdpPage,
+    endpoint: BrowserEndpoint,
+    closed: bool,
+    _lease: PageLease,
+}
+
+impl RegistryPage {
+    /// Endpoint of the browser the page was opened on
+    pub fn endpoint(&self) -> &BrowserEndpoint {
+        &self.endpoint
+    }
+
+    /// Close the page, waiting for the browser to destroy it
+    pub async fn close(mut self) -> Result<()> {
+        self.closed = true;
+        self.page.close().await
+    }
+}
+
+impl Deref for RegistryPage {
+    type Target = CdpPage;
+
+    fn deref(&self) -> &CdpPage {
+        &self.page
+    }
+}
+
+impl Drop for RegistryPage {
+    fn drop(&mut self) {
+        if self.closed {
+            return;
+        }
+        let connection = self.page.connection().clone();
+        if let Ok(handle) = tokio::runtime::Handle::try_current() {
+            handle.spawn(async move {
+                let _ = connection.send_command("Page.close", json!({})).await;
+            });
+        }
+    }
+}
+
+impl fmt::Debug for RegistryPage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RegistryPage")
+            .field("endpoint", &self.endpoint)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Counts a page against its endpoint until dropped
+struct PageLease(Arc<AtomicUsize>);
+
+impl PageLease {
+    fn new(active: Arc<AtomicUsize>) -> Self {
+        active.fetch_add(1, Ordering::SeqCst);
+        Self(active)
+    }
+}
+
+impl Drop for PageLease {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// One endpoint of the fleet, connected while healthy
+struct Member {
+    endpoint: BrowserEndpoint,
+    browser: Option<Arc<CdpBrowser>>,
+    active: Arc<AtomicUsize>,
+    last_error: Option<String>,
+}
+
+impl Member {
+    fn new(endpoint: BrowserEndpoint) -> Self {
+        Self {
+            endpoint,
+            browser: None,
+            active: Arc::new(AtomicUsize::new(0)),
+            last_error: None,
+        }
+    }
+}
+
+/// State shared between a registry and its health monitor
+struct Fleet {
+    members: Mutex<Vec<Member>>,
+    discover: Option<Discover>,
+    timeouts: Timeouts,
+    /// Rotates the first candidate so equally loaded endpoints take turns
+    next: AtomicUsize,
+}
+
+impl Fleet {
+    /// Run discovery, then connect to or health-check every endpoint
+    async fn refresh(&self) -> Result<()> {
+        let discovered = match &self.discover {
+            Some(discover) => Some(discover().await),
+            None => None,
+        };
+        if let Some(Ok(endpoints)) = &discovered {
+            self.replace_endpoints(endpoints);
+        }
+
+        let snapshot: Vec<_> = self
+            .members
+            .lock()
+            .map(|members| {
+                members
+                    .iter()
+                    .map(|member| (member.endpoint.clone(), member.browser.clone()))
+                    .collect()
+            })
+            .unwrap_or_default();
+        let probes = snapshot.into_iter().map(|(endpoint, browser)| async move {
+            let result = self.probe(&endpoint, browser).await;
+            (endpoint, result)
+        });
+        let results = futures::future::join_all(probes).await;
+
+        if let Ok(mut members) = self.members.lock() {
+            for (endpoint, result) in results {
+                let Some(member) = members.iter_mut().find(|m| m.endpoint == endpoint) else {
+                    continue;
+                };
+                match result {
+                    Ok(browser) => {
+                        member.browser = Some(browser);
+                        member.last_error = None;
+                    }
+                    Err(e) => {
+                        member.browser = None;
+                        member.last_error = Some(e.to_string());
+                    }
+                }
+            }
+        }
+        match discovered {
+            Some(Err(e)) => Err(Error::Browser(format!(
+                "Failed to discover browser endpoints: {}",
+                e
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    /// Check a connected browser, or attach to the endpoint if there is none
+    async fn probe(
+        &self,
+        endpoint: &BrowserEndpoint,
+        browser: Option<Arc<CdpBrowser>>,
+    ) -> Result<Arc<CdpBrowser>> {
+        if let Some(browser) = browser {
+            browser.health_check().await?;
+            return Ok(browser);
+        }
+        let connect_timeout = self.timeouts.connect_timeout();
+        let connect = async {
+            // Chrome refuses DevTools requests whose Host header is neither an
+            // IP address nor localhost, so service names are resolved first
+            let address = tokio::net::lookup_host((endpoint.host.as_str(), endpoint.port))
+                .await
+                .map_err(|e| Error::Browser(format!("Failed to resolve {}: {}", endpoint, e)))?
+                .min_by_key(|address| address.is_ipv6())
+                .ok_or_else(|| Error::Browser(format!("{} resolved to no address", endpoint)))?;
+            let host = match address.ip() {
+                IpAddr::V4(ip) => ip.to_string(),
+                IpAddr::V6(ip) => format!("[{}]", ip),
+            };
+            CdpBrowser::connect(&host, endpoint.port).await
+        };
+        let mut browser = tokio::time::timeout(connect_timeout, connect)
+            .await
+            .map_err(|_| {
+                Error::Timeout(format!(
+                    "Connecting to {} took longer than {:?}",
+                    endpoint, connect_timeout
+                ))
+            })??;
+        browser.set_timeouts(self.timeouts);
+        Ok(Arc::new(browser))
+    }
+
+    /// Keep the members still listed, in the new order, and add new ones
+    fn replace_endpoints(&self, endpoints: &[BrowserEndpoint]) {
+        let Ok(mut members) = self.members.lock() else {
+            return;
+        };
+        let mut previous = std::mem::take(&mut *members);
+        for endpoint in endpoints {
+            if members.iter().any(|m| &m.endpoint == endpoint) {
+                continue;
+            }
+            let member = match previous.iter().position(|m| &m.endpoint == endpoint) {
+                Some(index) => previous.swap_remove(index),
+                None => Member::new(endpoint.clone()),
+            };
+            members.push(member);
+        }
+    }
+
+    /// Healthy endpoint with the fewest active pages, skipping `exclude`
+    fn pick(
+        &self,
+        exclude: &[BrowserEndpoint],
+    ) -> Option<(BrowserEndpoint, Arc<CdpBrowser>, Arc<AtomicUsize>)> {
+        let members = self.members.lock().ok()?;
+        let count = members.len();
+        if count == 0 {
+            return None;
+        }
+        let start = self.next.fetch_add(1, Ordering::Relaxed) % count;
+        (0..count)
+            .map(|offset| &members[(start + offset) % count])
+            .filter(|member| !exclude.contains(&member.endpoint))
+            .filter_map(|member| Some((member, member.browser.clone()?)))
+            .min_by_key(|(member, _)| member.active.load(Ordering::SeqCst))
+            .map(|(member, browser)| (member.endpoint.clone(), browser, member.active.clone()))
+    }
+
+    /// Take an endpoint out of rotation until its next successful health check
+    fn mark_unhealthy(&self, endpoint: &BrowserEndpoint, error: &Error) {
+        if let Ok(mut members) = self.members.lock() {
+            if let Some(member) = members.iter_mut().find(|m| &m.endpoint == endpoint) {
+                member.browser = None;
+                member.last_error = Some(error.to_string());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connection::tests::{created, fake_chrome};
+
+    /// Endpoint nothing listens on
+    async fn dead_endpoint() -> BrowserEndpoint {
+        let listener = tokio::net::TcpListener::bind(("127.0.0.1", 0))
+            .await
+            .unwrap();
+        BrowserEndpoint::new("127.0.0.1", listener.local_addr().unwrap().port())
+    }
+
+    #[test]
+    fn test_parse_endpoint() {
+        let endpoint: BrowserEndpoint = "chrome-0.chrome:9222".parse().unwrap();
+        assert_eq!(endpoint, BrowserEndpoint::new("chrome-0.chrome", 9222));
+        assert_eq!(endpoint.to_string(), "chrome-0.chrome:9222");
+        assert!("chrome".parse::<BrowserEndpoint>().is_err());
+        assert!(":9222".parse::<BrowserEndpoint>().is_err());
+        assert!("chrome:http".parse::<BrowserEndpoint>().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_host_names_are_resolved_before_connecting() {
+        let (endpoint, commands) = fake_chrome().await;
+        let by_name = BrowserEndpoint::new("localhost", endpoint.port);
+        assert!(CdpBrowser::connect(&by_name.host, by_name.port)
+            .await
+            .is_err());
+        let registry = BrowserRegistry::builder()
+            .endpoints([by_name])
+            .health_check_interval(Duration::from_secs(3600))
+            .build();
+
+        let page = registry.get_page().await.unwrap();
+        assert_eq!(created(&commands), 1);
+        page.close().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_get_page_balances_across_healthy_endpoints() {
+        let (first, first_commands) = fake_chrome().await;
+        let (second, second_commands) = fake_chrome().await;
+        let dead = dead_endpoint().await;
+        let registry = BrowserRegistry::builder()
+            .endpoints([dead.clone(), first.clone(), second.clone()])
+            .health_check_interval(Duration::from_secs(3600))
+            .build();
+
+        let a = registry.get_page().await.unwrap();
+        let b = registry.get_page().await.unwrap();
+        assert_ne!(a.endpoint(), b.endpoint());
+        assert_eq!(created(&first_commands), 1);
+        assert_eq!(created(&second_commands), 1);
+
+        let status = registry.endpoints();
+        assert_eq!(status[0].endpoint, dead);
+        assert!(!status[0].healthy);
+        assert!(status[0].last_error.is_some());
+        assert!(status[1..].iter().all(|s| s.healthy && s.active_pages == 1));
+
+        drop(a);
+        b.close().await.unwrap();
+        assert!(registry.endpoints().iter().all(|s| s.active_pages == 0));
+    }
+
+    #[tokio::test]
+    async fn test_discovery_replaces_endpoints() {
+        let (first, _) = fake_chrome().await;
+        let (second, second_commands) = fake_chrome().await;
+        let listed = Arc::new(Mutex::new(vec![first.clone()]));
+        let registry = BrowserRegistry::builder()
+            .discover({
+                let listed = listed.clone();
+                move || {
+                    let endpoints = listed.lock().unwrap().clone();
+                    async move { Ok(endpoints) }
+                }
+            })
+            .health_check_interval(Duration::from_secs(3600))
+            .build();
+
+        registry.check_health().await.unwrap();
+        assert_eq!(registry.endpoints()[0].endpoint, first);
+
+        *listed.lock().unwrap() = vec![second.clone()];
+        registry.check_health().await.unwrap();
+        let status = registry.endpoints();
+        assert_eq!(status.len(), 1);
+        assert_eq!(status[0].endpoint, second);
+        assert!(status[0].healthy);
+
+        let page = registry.get_page().await.unwrap();
+        assert_eq!(page.endpoint(), &second);
+        assert_eq!(created(&second_commands), 1);
+    }
+
+    #[tokio::test]
+    async fn test_get_page_fails_without_healthy_endpoint() {
+        let registry = BrowserRegistry::builder()
+            .endpoints([dead_endpoint().await])
+            .build();
+        let err = registry.get_page().await.unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("No healthy browser among 1 endpoints"));
+    }
+}
@@ -0,0 +1,363 @@
+//! Interactive prompt for sending raw CDP commands
+
+use crate::{CdpConnection, Error, Result};
+use serde_json::{json, Value};
+use std::io::{BufRead, Write};
+
+/// Interactive prompt sending raw CDP commands over a connection
+///
+/// Each line is a method followed by optional JSON parameters, e.g.
+/// `Runtime.evaluate {"expression": "document.title"}`. Method names complete
+/// with Tab on Unix terminals, using the protocol schema the browser serves
+/// at `/json/protocol` (see [`CdpBrowser::protocol_schema`](crate::CdpBrowser::protocol_schema)).
+/// Type `help` for the commands of the prompt itself.
+///
+/// ```no_run
+/// # async fn run() -> chrome_cdp::Result<()> {
+/// use chrome_cdp::{CdpBrowser, Repl};
+///
+/// let browser = CdpBrowser::connect("127.0.0.1", 9222).await?;
+/// let page = browser.new_page_connected(None).await?;
+/// let schema = browser.protocol_schema().await?;
+/// Repl::new(page.connection().clone(), &schema).run().await
+/// # }
+/// ```
+pub struct Repl {
+    connection: CdpConnection,
+    methods: Vec<String>,
+}
+
+impl Repl {
+    /// Prompt on `connection`, completing method names from `schema`
+    pub fn new(connection: CdpConnection, schema: &Value) -> Self {
+        Self {
+            connection,
+            methods: schema_methods(schema),
+        }
+    }
+
+    /// Commands known from the schema, e.g. `Page.navigate`
+    pub fn methods(&self) -> &[String] {
+        &self.methods
+    }
+
+    /// Run one line and return the command's result, or `None` for blank lines
+    pub async fn execute(&self, line: &str) -> Result<Option<Value>> {
+        let line = line.trim();
+        if line.is_empty() {
+            return Ok(None);
+        }
+        let (method, params) = match line.split_once(char::is_whitespace) {
+            Some((method, params)) => (method, serde_json::from_str(params.trim())?),
+            None => (line, json!({})),
+        };
+        if !method.contains('.') {
+            return Err(Error::Browser(format!(
+                "'{}' is not a CDP method; expected Domain.method",
+                method
+            )));
+        }
+        Ok(Some(self.connection.send_command(method, params).await?))
+    }
+
+    /// Read lines from the terminal until `exit` or end of input
+    pub async fn run(self) -> Result<()> {
+        let mut editor = LineEditor::new(self.methods.clone());
+        loop {
+            let (returned, line) = tokio::task::spawn_blocking(move || {
+                let line = editor.read_line("cdp> ");
+                (editor, line)
+            })
+            .await
+            .map_err(|e| Error::Browser(format!("Task failed: {}", e)))?;
+            editor = returned;
+
+            let Some(line) = line? else {
+                return Ok(());
+            };
+            match line.trim() {
+                "exit" | "quit" => return Ok(()),
+                "help" => println!("{}", HELP),
+                "methods" => println!("{}", self.methods.join("\n")),
+                _ => match self.execute(&line).await {
+                    Ok(Some(result)) => println!("{}", serde_json::to_string_pretty(&result)?),
+                    Ok(None) => {}
+                    Err(e) => println!("error: {}", e),
+                },
+            }
+        }
+    }
+}
+
+const HELP: &str = "\
+Domain.method [json params]   send a command, e.g. Page.navigate {\"url\": \"https://example.com\"}
+methods                       list the commands of the protocol schema
+help                          show this help
+exit, quit                    leave the prompt";
+
+/// `Domain.command` names declared by a protocol schema, sorted
+fn schema_methods(schema: &Value) -> Vec<String> {
+    let mut methods: Vec<String> = schema["domains"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .flat_map(|domain| {
+            let name = domain["domain"].as_str().unwrap_or_default();
+            domain["commands"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(move |command| Some(format!("{}.{}", name, command["name"].as_str()?)))
+        })
+        .collect();
+    methods.sort();
+    methods
+}
+
+/// Outcome of completing a partial method name
+#[derive(Debug, PartialEq)]
+enum Completion {
+    /// The line was extended
+    Extended(String),
+    /// Several methods share the typed prefix
+    Candidates(Vec<String>),
+    /// Nothing to complete
+    None,
+}
+
+/// Complete the method name at the start of `line`
+fn complete(line: &str, methods: &[String]) -> Completion {
+    if line.contains(char::is_whitespace) {
+        return Completion::None;
+    }
+    let matches: Vec<&String> = methods.iter().filter(|m| m.starts_with(line)).collect();
+    let Some(first) = matches.first() else {
+        return Completion::None;
+    };
+    if matches.len() == 1 {
+        return Completion::Extended(format!("{} ", first));
+    }
+    let common = matches.iter().fold(first.len(), |len, method| {
+        first
+            .bytes()
+            .zip(method.bytes())
+            .take(len)
+            .take_while(|(a, b)| a == b)
+            .count()
+    });
+    if common > line.len() {
+        Completion::Extended(first[..common].to_string())
+    } else {
+        Completion::Candidates(matches.into_iter().cloned().collect())
+    }
+}
+
+/// Minimal line editor with Tab completion on Unix terminals
+struct LineEditor {
+    methods: Vec<String>,
+}
+
+impl LineEditor {
+    fn new(methods: Vec<String>) -> Self {
+        Self { methods }
+    }
+
+    /// Read a line, or `None` at end of input
+    fn read_line(&mut self, prompt: &str) -> Result<Option<String>> {
+        print!("{}", prompt);
+        std::io::stdout().flush()?;
+        #[cfg(unix)]
+        if let Some(_raw) = RawMode::enable() {
+            return self.read_raw(prompt);
+        }
+        let mut line = String::new();
+        if std::io::stdin().lock().read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        Ok(Some(line.trim_end_matches(['\r', '\n']).to_string()))
+    }
+
+    /// Read a line byte by byte, handling editing keys and Tab
+    #[cfg(unix)]
+    fn read_raw(&mut self, prompt: &str) -> Result<Option<String>> {
+        use std::io::Read;
+
+        let mut stdout = std::io::stdout();
+        let mut line: Vec<u8> = Vec::new();
+        let mut byte = [0u8; 1];
+        loop {
+            if std::io::stdin().read(&mut byte)? == 0 {
+                return Ok(None);
+            }
+            match byte[0] {
+                b'\r' | b'\n' => {
+                    write!(stdout, "\r\n")?;
+                    return Ok(Some(String::from_utf8_lossy(&line).into_owned()));
+                }
+                // Ctrl-C discards the line
+                3 => {
+                    write!(stdout, "^C\r\n")?;
+                    return Ok(Some(String::new()));
+                }
+                // Ctrl-D ends input on an empty line
+                4 if line.is_empty() => {
+                    write!(stdout, "\r\n")?;
+                    return Ok(None);
+                }
+                8 | 127 => {
+                    // Drop a whole UTF-8 character
+                    while let Some(last) = line.pop() {
+                        if last & 0xC0 != 0x80 {
+                            write!(stdout, "\x08 \x08")?;
+                            break;
+                        }
+                    }
+                }
+                b'\t' => {
+                    let text = String::from_utf8_lossy(&line).into_owned();
+                    match complete(&text, &self.methods) {
+                        Completion::Extended(extended) => {
+                            write!(stdout, "{}", &extended[text.len()..])?;
+                            line = extended.into_bytes();
+                        }
+                        Completion::Candidates(candidates) => {
+                            write!(
+                                stdout,
+                                "\r\n{}\r\n{}{}",
+                                candidates.join("  "),
+                                prompt,
+                                text
+                            )?;
+                        }
+                        Completion::None => {}
+                    }
+                }
+                // Escape sequences such as arrow keys are not supported
+                27 => {
+                    let mut sequence = [0u8; 2];
+                    std::io::stdin().read_exact(&mut sequence)?;
+                }
+                b if b >= 0x20 => {
+                    line.push(b);
+                    stdout.write_all(&byte)?;
+                }
+                _ => {}
+            }
+            stdout.flush()?;
+        }
+    }
+}
+
+/// Terminal switched to unbuffered input without echo, restored on drop
+#[cfg(unix)]
+struct RawMode(libc::termios);
+
+#[cfg(unix)]
+impl RawMode {
+    /// Enable raw input, or `None` if stdin is not a terminal
+    fn enable() -> Option<Self> {
+        // SAFETY: termios is plain old data filled in by tcgetattr, and
+        // STDIN_FILENO stays valid for the life of the process
+        unsafe {
+            if libc::isatty(libc::STDIN_FILENO) != 1 {
+                return None;
+            }
+            let mut original: libc::termios = std::mem::zeroed();
+            if libc::tcgetattr(libc::STDIN_FILENO, &mut original) != 0 {
+                return None;
+            }
+            let mut raw = original;
+            raw.c_lflag &= !(libc::ICANON | libc::ECHO | libc::ISIG);
+            raw.c_cc[libc::VMIN] = 1;
+            raw.c_cc[libc::VTIME] = 0;
+            if libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &raw) != 0 {
+                return None;
+            }
+            Some(Self(original))
+        }
+    }
+}
+
+#[cfg(unix)]
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        // SAFETY: restores the settings read in `enable`
+        unsafe {
+            libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &self.0);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connection::tests::{mock_server, server_recv, server_send};
+
+    fn methods() -> Vec<String> {
+        schema_methods(&json!({"domains": [
+            {"domain": "Page", "commands": [{"name": "navigate"}, {"name": "navigateToHistoryEntry"}, {"name": "reload"}]},
+            {"domain": "Runtime", "commands": [{"name": "evaluate"}]}
+        ]}))
+    }
+
+    #[test]
+    fn test_schema_methods() {
+        assert_eq!(
+            methods(),
+            [
+                "Page.navigate",
+                "Page.navigateToHistoryEntry",
+                "Page.reload",
+                "Runtime.evaluate"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_complete() {
+        let methods = methods();
+        assert_eq!(
+            complete("Ru", &methods),
+            Completion::Extended("Runtime.evaluate ".to_string())
+        );
+        assert_eq!(
+            complete("Page.n", &methods),
+            Completion::Extended("Page.navigate".to_string())
+        );
+        assert_eq!(
+            complete("Page.navigate", &methods),
+            Completion::Candidates(vec![
+                "Page.navigate".to_string(),
+                "Page.navigateToHistoryEntry".to_string()
+            ])
+        );
+        assert_eq!(complete("Network", &methods), Completion::None);
+        assert_eq!(complete("Page.reload {}", &methods), Completion::None);
+    }
+
+    #[tokio::test]
+    async fn test_execute_sends_raw_command() {
+        let url = mock_server(|mut ws| async move {
+            let request = server_recv(&mut ws).await;
+            assert_eq!(request["method"], "Runtime.evaluate");
+            assert_eq!(request["params"]["expression"], "1 + 1");
+            server_send(
+                &mut ws,
+                json!({"id": request["id"], "result": {"result": {"value": 2}}}),
+            )
+            .await;
+        })
+        .await;
+        let repl = Repl::new(CdpConnection::connect(&url).await.unwrap(), &json!({}));
+
+        assert_eq!(repl.execute("   ").await.unwrap(), None);
+        assert!(repl.execute("evaluate").await.is_err());
+        assert!(repl.execute("Runtime.evaluate {oops").await.is_err());
+        let result = repl
+            .execute(r#"Runtime.evaluate {"expression": "1 + 1"}"#)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(result["result"]["value"], 2);
+    }
+}
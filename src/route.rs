@@ -0,0 +1,130 @@
+//! Same-document navigations of single-page apps
+
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// Binding the route hook reports history API calls through
+pub(crate) const ROUTE_BINDING: &str = "__cdpRouteChange";
+
+/// Script wrapping `history.pushState` and `replaceState` to report their target URL
+///
+/// Reporting before the original call runs makes the binding call reach
+/// the client ahead of the `Page.navigatedWithinDocument` event it causes.
+pub(crate) const ROUTE_HOOK_SCRIPT: &str = r#"(() => {
+    if (window.__cdpRouteHook) return;
+    window.__cdpRouteHook = true;
+    const report = (kind, url) => {
+        try {
+            const href = new URL(url ?? location.href, location.href).href;
+            window.__cdpRouteChange(JSON.stringify({ kind, url: href }));
+        } catch (e) {}
+    };
+    for (const [name, kind] of [['pushState', 'push'], ['replaceState', 'replace']]) {
+        const original = history[name];
+        history[name] = function (state, title, url) {
+            report(kind, url);
+            return original.apply(this, arguments);
+        };
+    }
+})()"#;
+
+/// How a single-page app changed its route
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RouteChangeKind {
+    /// `history.pushState`
+    PushState,
+    /// `history.replaceState`
+    ReplaceState,
+    /// Change of the URL fragment, e.g. following an `#anchor` link
+    Fragment,
+    /// Back/forward traversal, or a history call made before the hook was installed
+    Other,
+}
+
+/// Same-document navigation, from [`CdpPage::wait_for_route_change`](crate::CdpPage::wait_for_route_change)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RouteChange {
+    /// URL after the change
+    pub url: String,
+    /// What caused the change
+    pub kind: RouteChangeKind,
+}
+
+/// History API calls reported by the hook, by target URL, until their navigation commits
+#[derive(Debug, Default)]
+pub(crate) struct PendingRoutes(HashMap<String, RouteChangeKind>);
+
+impl PendingRoutes {
+    /// Remember a `Runtime.bindingCalled` event of the route hook
+    pub(crate) fn record(&mut self, params: &Value) {
+        if params["name"] != ROUTE_BINDING {
+            return;
+        }
+        let Some(payload) = params["payload"]
+            .as_str()
+            .and_then(|payload| serde_json::from_str::<Value>(payload).ok())
+        else {
+            return;
+        };
+        let kind = match payload["kind"].as_str() {
+            Some("push") => RouteChangeKind::PushState,
+            Some("replace") => RouteChangeKind::ReplaceState,
+            _ => return,
+        };
+        if let Some(url) = payload["url"].as_str() {
+            self.0.insert(url.to_string(), kind);
+        }
+    }
+
+    /// Route change committed by a `Page.navigatedWithinDocument` event
+    pub(crate) fn commit(&mut self, params: &Value) -> RouteChange {
+        let url = params["url"].as_str().unwrap_or_default().to_string();
+        let kind = self
+            .0
+            .remove(&url)
+            .unwrap_or_else(|| match params["navigationType"].as_str() {
+                Some("fragment") => RouteChangeKind::Fragment,
+                _ => RouteChangeKind::Other,
+            });
+        RouteChange { url, kind }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn binding(kind: &str, url: &str) -> Value {
+        json!({
+            "name": ROUTE_BINDING,
+            "payload": json!({"kind": kind, "url": url}).to_string(),
+            "executionContextId": 1
+        })
+    }
+
+    #[test]
+    fn test_history_calls_classify_commits() {
+        let mut pending = PendingRoutes::default();
+        pending.record(&binding("push", "https://app.example/users"));
+        pending.record(&binding("replace", "https://app.example/users?page=2"));
+        pending.record(&json!({"name": "other", "payload": "{}"}));
+
+        let push = pending.commit(&json!({
+            "url": "https://app.example/users", "navigationType": "historyApi"
+        }));
+        assert_eq!(push.kind, RouteChangeKind::PushState);
+        let replace = pending.commit(&json!({"url": "https://app.example/users?page=2"}));
+        assert_eq!(replace.kind, RouteChangeKind::ReplaceState);
+
+        // Without a reported history call the event's type decides
+        let hash = pending.commit(&json!({
+            "url": "https://app.example/users#top", "navigationType": "fragment"
+        }));
+        assert_eq!(hash.kind, RouteChangeKind::Fragment);
+        let back = pending.commit(&json!({
+            "url": "https://app.example/users", "navigationType": "historyApi"
+        }));
+        assert_eq!(back.kind, RouteChangeKind::Other);
+    }
+}
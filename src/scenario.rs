@@ -0,0 +1,245 @@
+//! Coordinated multi-tab scenarios
+
+use crate::{BrowserContext, CdpBrowser, CdpConnection, CdpPage, Domain, Error, Result};
+use serde_json::Value;
+use std::future::Future;
+use std::time::Duration;
+
+/// Named tabs sharing one browser context, for testing realtime apps
+///
+/// Tabs share cookies and storage with each other but not with the rest of
+/// the browser. The `expect_*` methods start listening on one tab before an
+/// action runs, typically in another tab, so a fast reaction is not missed.
+///
+/// ```no_run
+/// # async fn run(browser: &chrome_cdp::CdpBrowser) -> chrome_cdp::Result<()> {
+/// use chrome_cdp::Scenario;
+/// use std::time::Duration;
+///
+/// let mut scenario = Scenario::new(browser).await?;
+/// scenario.open("alice", Some("https://chat.example/room")).await?;
+/// scenario.open("bob", Some("https://chat.example/room")).await?;
+///
+/// let (_, message) = scenario
+///     .expect_websocket_message(
+///         "bob",
+///         |payload| payload.contains("hello"),
+///         Duration::from_secs(5),
+///         scenario.tab("alice")?.evaluate("send('hello')"),
+///     )
+///     .await?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct Scenario {
+    context: BrowserContext,
+    tabs: Vec<(String, CdpPage)>,
+}
+
+impl Scenario {
+    /// Start a scenario in a fresh context of the browser
+    pub async fn new(browser: &CdpBrowser) -> Result<Self> {
+        Ok(Self::from_context(browser.new_context().await?))
+    }
+
+    /// Start a scenario in an existing context
+    pub fn from_context(context: BrowserContext) -> Self {
+        Self {
+            context,
+            tabs: Vec::new(),
+        }
+    }
+
+    /// Context the tabs belong to
+    pub fn context(&self) -> &BrowserContext {
+        &self.context
+    }
+
+    /// Open a tab under a new name, navigating to `url` if given
+    pub async fn open(&mut self, name: &str, url: Option<&str>) -> Result<&CdpPage> {
+        if self.tabs.iter().any(|(n, _)| n == name) {
+            return Err(Error::Browser(format!(
+                "Scenario already has a tab named '{}'",
+                name
+            )));
+        }
+        let page = self.context.new_page().await?;
+        if let Some(url) = url {
+            page.goto(url).await?;
+        }
+        self.tabs.push((name.to_string(), page));
+        Ok(&self.tabs[self.tabs.len() - 1].1)
+    }
+
+    /// Tab with the given name
+    pub fn tab(&self, name: &str) -> Result<&CdpPage> {
+        self.tabs
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, page)| page)
+            .ok_or_else(|| Error::Browser(format!("Scenario has no tab named '{}'", name)))
+    }
+
+    /// Names of the open tabs, in the order they were opened
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.tabs.iter().map(|(name, _)| name.as_str())
+    }
+
+    /// Run `action` and wait for a matching event on the named tab
+    ///
+    /// The event's domain is enabled first if needed. Listening starts before
+    /// the action, so events it triggers immediately are observed. Returns the action's output and the event parameters.
+    pub async fn expect_event<T, F>(
+        &self,
+        tab: &str,
+        method: &str,
+        predicate: F,
+        timeout: Duration,
+        action: impl Future<Output = Result<T>>,
+    ) -> Result<(T, Value)>
+    where
+        F: Fn(&Value) -> bool,
+    {
+        let page = self.tab(tab)?;
+        if let Some(domain) = Domain::of_event(method) {
+            page.enable_domain(domain).await?;
+        }
+        expect_on(page.connection(), tab, method, predicate, timeout, action).await
+    }
+
+    /// Run `action` and wait for the named tab to receive a WebSocket message
+    ///
+    /// `predicate` sees the frame payload; binary frames are base64-encoded.
+    pub async fn expect_websocket_message<T, F>(
+        &self,
+        tab: &str,
+        predicate: F,
+        timeout: Duration,
+        action: impl Future<Output = Result<T>>,
+    ) -> Result<(T, String)>
+    where
+        F: Fn(&str) -> bool,
+    {
+        let page = self.tab(tab)?;
+        // WebSocket frames are only reported with the Network domain enabled
+        page.enable_domain(Domain::Network).await?;
+        let (output, params) = expect_on(
+            page.connection(),
+            tab,
+            "Network.webSocketFrameReceived",
+            |params| predicate(websocket_payload(params)),
+            timeout,
+            action,
+        )
+        .await?;
+        Ok((output, websocket_payload(&params).to_string()))
+    }
+
+    /// Close every tab
+    pub async fn close(self) -> Result<()> {
+        for (_, page) in self.tabs {
+            page.close().await?;
+        }
+        Ok(())
+    }
+}
+
+/// Subscribe to `connection`, run `action`, then wait for a matching event
+async fn expect_on<T, F>(
+    connection: &CdpConnection,
+    tab: &str,
+    method: &str,
+    predicate: F,
+    timeout: Duration,
+    action: impl Future<Output = Result<T>>,
+) -> Result<(T, Value)>
+where
+    F: Fn(&Value) -> bool,
+{
+    let mut events = connection.subscribe();
+    let output = action.await?;
+
+    let wait = async {
+        while let Some(event) = events.recv().await {
+            if event.method == method && predicate(&event.params) {
+                return Ok(event.params);
+            }
+        }
+        Err(Error::Browser(format!(
+            "Tab '{}' closed while waiting for {}",
+            tab, method
+        )))
+    };
+    let params = tokio::time::timeout(timeout, wait).await.map_err(|_| {
+        Error::Timeout(format!(
+            "Tab '{}' did not receive {} within {:?}",
+            tab, method, timeout
+        ))
+    })??;
+    Ok((output, params))
+}
+
+/// Payload of a `Network.webSocketFrameReceived` event
+fn websocket_payload(params: &Value) -> &str {
+    params["response"]["payloadData"]
+        .as_str()
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connection::tests::{mock_server, server_recv, server_send};
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_expect_on_listens_before_action() {
+        let url = mock_server(|mut ws| async move {
+            let request = server_recv(&mut ws).await;
+            // The event arrives before the action's response
+            server_send(
+                &mut ws,
+                json!({"method": "Network.webSocketFrameReceived",
+                       "params": {"response": {"payloadData": "hello"}}}),
+            )
+            .await;
+            server_send(&mut ws, json!({"id": request["id"], "result": {}})).await;
+        })
+        .await;
+        let connection = CdpConnection::connect(&url).await.unwrap();
+
+        let (output, params) = expect_on(
+            &connection,
+            "bob",
+            "Network.webSocketFrameReceived",
+            |params| websocket_payload(params) == "hello",
+            Duration::from_secs(1),
+            connection.send_command("Runtime.evaluate", json!({"expression": "send()"})),
+        )
+        .await
+        .unwrap();
+        assert_eq!(output, json!({}));
+        assert_eq!(websocket_payload(&params), "hello");
+    }
+
+    #[tokio::test]
+    async fn test_expect_on_times_out() {
+        let url = mock_server(|ws| async move {
+            tokio::time::sleep(Duration::from_secs(1)).await;
+            drop(ws);
+        })
+        .await;
+        let connection = CdpConnection::connect(&url).await.unwrap();
+
+        let result = expect_on(
+            &connection,
+            "bob",
+            "Network.webSocketFrameReceived",
+            |_| true,
+            Duration::from_millis(50),
+            async { Ok(()) },
+        )
+        .await;
+        assert!(matches!(result, Err(Error::Timeout(message)) if message.contains("'bob'")));
+    }
+}
@@ -0,0 +1,177 @@
+//! Tiled full-page screenshots stitched into a single PNG
+
+use crate::{Error, Result};
+
+/// Options for [`CdpPage::screenshot_tiled`](crate::CdpPage::screenshot_tiled)
+#[derive(Debug, Clone)]
+pub struct TiledScreenshotOptions {
+    /// Height of each captured tile in CSS pixels (default: 4000)
+    ///
+    /// Keep `tile_height * device scale factor` below Chrome's maximum texture
+    /// size (16384 on most GPUs).
+    pub tile_height: u32,
+    /// Selectors of elements hidden in every tile but the first, such as
+    /// sticky headers that would otherwise repeat in each segment
+    pub mask_selectors: Vec<String>,
+}
+
+impl Default for TiledScreenshotOptions {
+    fn default() -> Self {
+        Self {
+            tile_height: 4000,
+            mask_selectors: Vec::new(),
+        }
+    }
+}
+
+/// Function hiding (`hide = true`) or restoring elements matching the selectors
+pub(crate) const MASK_FN: &str = r#"function(selectors, hide) {
+  for (const selector of selectors) {
+    for (const el of this.querySelectorAll(selector)) {
+      if (hide) {
+        if (el.dataset.cdpMask === undefined) el.dataset.cdpMask = el.style.visibility;
+        el.style.visibility = "hidden";
+      } else if (el.dataset.cdpMask !== undefined) {
+        el.style.visibility = el.dataset.cdpMask;
+        delete el.dataset.cdpMask;
+      }
+    }
+  }
+}"#;
+
+/// Decoded RGBA image
+pub(crate) struct Rgba {
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+    pub(crate) pixels: Vec<u8>,
+}
+
+/// Decode a PNG into 8-bit RGBA pixels
+pub(crate) fn decode_png(data: &[u8]) -> Result<Rgba> {
+    let mut decoder = png::Decoder::new(data);
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder
+        .read_info()
+        .map_err(|e| Error::Browser(format!("Failed to decode PNG: {}", e)))?;
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let info = reader
+        .next_frame(&mut buffer)
+        .map_err(|e| Error::Browser(format!("Failed to decode PNG: {}", e)))?;
+    buffer.truncate(info.buffer_size());
+
+    let pixels = match info.color_type {
+        png::ColorType::Rgba => buffer,
+        png::ColorType::Rgb => buffer
+            .chunks_exact(3)
+            .flat_map(|p| [p[0], p[1], p[2], 255])
+            .collect(),
+        png::ColorType::GrayscaleAlpha => buffer
+            .chunks_exact(2)
+            .flat_map(|p| [p[0], p[0], p[0], p[1]])
+            .collect(),
+        png::ColorType::Grayscale => buffer.iter().flat_map(|&g| [g, g, g, 255]).collect(),
+        png::ColorType::Indexed => {
+            return Err(Error::Browser(
+                "Unexpected indexed PNG after expansion".to_string(),
+            ))
+        }
+    };
+
+    Ok(Rgba {
+        width: info.width,
+        height: info.height,
+        pixels,
+    })
+}
+
+/// Encode 8-bit RGBA pixels as PNG
+pub(crate) fn encode_png(image: &Rgba) -> Result<Vec<u8>> {
+    let mut out = Vec::new();
+    let mut encoder = png::Encoder::new(&mut out, image.width, image.height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder
+        .write_header()
+        .and_then(|mut writer| writer.write_image_data(&image.pixels))
+        .map_err(|e| Error::Browser(format!("Failed to encode PNG: {}", e)))?;
+    Ok(out)
+}
+
+/// Stack PNG tiles vertically into one PNG
+///
+/// Tiles narrower than the widest one are padded with transparent pixels.
+pub(crate) fn stitch_vertical(tiles: &[Vec<u8>]) -> Result<Vec<u8>> {
+    let tiles = tiles
+        .iter()
+        .map(|tile| decode_png(tile))
+        .collect::<Result<Vec<_>>>()?;
+    let width = tiles.iter().map(|t| t.width).max().unwrap_or(0);
+    let height = tiles.iter().map(|t| t.height).sum();
+    if width == 0 || height == 0 {
+        return Err(Error::Browser("Nothing to stitch".to_string()));
+    }
+
+    let row_bytes = width as usize * 4;
+    let mut pixels = Vec::with_capacity(row_bytes * height as usize);
+    for tile in &tiles {
+        let tile_row = tile.width as usize * 4;
+        for row in tile.pixels.chunks_exact(tile_row) {
+            pixels.extend_from_slice(row);
+            pixels.resize(pixels.len() + row_bytes - tile_row, 0);
+        }
+    }
+
+    encode_png(&Rgba {
+        width,
+        height,
+        pixels,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn solid(width: u32, height: u32, rgba: [u8; 4]) -> Vec<u8> {
+        let pixels = rgba.repeat((width * height) as usize);
+        encode_png(&Rgba {
+            width,
+            height,
+            pixels,
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_png_roundtrip() {
+        let image = decode_png(&solid(3, 2, [1, 2, 3, 4])).unwrap();
+        assert_eq!((image.width, image.height), (3, 2));
+        assert_eq!(&image.pixels[..4], &[1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn test_stitch_vertical_stacks_tiles() {
+        let stitched =
+            stitch_vertical(&[solid(2, 2, [255, 0, 0, 255]), solid(2, 1, [0, 0, 255, 255])])
+                .unwrap();
+        let image = decode_png(&stitched).unwrap();
+        assert_eq!((image.width, image.height), (2, 3));
+        assert_eq!(&image.pixels[..4], &[255, 0, 0, 255]);
+        assert_eq!(&image.pixels[2 * 2 * 4..2 * 2 * 4 + 4], &[0, 0, 255, 255]);
+    }
+
+    #[test]
+    fn test_stitch_vertical_pads_narrow_tiles() {
+        let stitched =
+            stitch_vertical(&[solid(2, 1, [9, 9, 9, 255]), solid(1, 1, [7, 7, 7, 255])]).unwrap();
+        let image = decode_png(&stitched).unwrap();
+        assert_eq!((image.width, image.height), (2, 2));
+        assert_eq!(&image.pixels[8..12], &[7, 7, 7, 255]);
+        assert_eq!(&image.pixels[12..16], &[0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_stitch_vertical_rejects_empty() {
+        assert!(stitch_vertical(&[]).is_err());
+    }
+}
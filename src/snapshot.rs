@@ -0,0 +1,251 @@
+//! Compact snapshots of interactive elements for agent-style automation
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Function listing visible interactive elements of `this` document
+///
+/// Elements get ids that stay the same across snapshots for as long as the
+/// element is alive; `window.__cdpElements` maps them back to elements.
+pub(crate) const SNAPSHOT_FN: &str = r#"function() {
+  const registry = window.__cdpElements || (window.__cdpElements = {
+    ids: new WeakMap(), byId: new Map(), next: 1,
+  });
+  const idOf = (el) => {
+    let id = registry.ids.get(el);
+    if (!id) {
+      id = "e" + registry.next++;
+      registry.ids.set(el, id);
+      registry.byId.set(id, new WeakRef(el));
+    }
+    return id;
+  };
+
+  const selector = [
+    "a[href]", "button", "input:not([type=hidden])", "select", "textarea", "summary",
+    "[role=button]", "[role=link]", "[role=checkbox]", "[role=radio]", "[role=tab]",
+    "[role=menuitem]", "[role=option]", "[role=switch]", "[role=textbox]", "[role=combobox]",
+    "[onclick]", "[contenteditable=''], [contenteditable=true]", "[tabindex]:not([tabindex='-1'])",
+  ].join(",");
+
+  const implicitRole = (el) => {
+    const tag = el.tagName.toLowerCase();
+    if (tag === "a") return "link";
+    if (tag === "button" || tag === "summary") return "button";
+    if (tag === "select") return el.multiple ? "listbox" : "combobox";
+    if (tag === "textarea" || el.isContentEditable) return "textbox";
+    if (tag === "input") {
+      const type = (el.getAttribute("type") || "text").toLowerCase();
+      if (["button", "submit", "reset", "image"].includes(type)) return "button";
+      if (type === "checkbox" || type === "radio") return type;
+      if (type === "range") return "slider";
+      if (type === "search") return "searchbox";
+      return "textbox";
+    }
+    return "generic";
+  };
+
+  const text = (value) => (value || "").replace(/\s+/g, " ").trim();
+  const accessibleName = (el) => {
+    const labelledBy = el.getAttribute("aria-labelledby");
+    const candidates = [
+      el.getAttribute("aria-label"),
+      labelledBy && labelledBy.split(/\s+/)
+        .map((id) => this.getElementById(id)?.textContent).join(" "),
+      el.labels && Array.from(el.labels).map((label) => label.textContent).join(" "),
+      el.getAttribute("alt"),
+      el.tagName === "INPUT" && ["button", "submit", "reset"].includes(el.type) && el.value,
+      el.innerText,
+      el.getAttribute("title"),
+      el.getAttribute("placeholder"),
+    ];
+    for (const candidate of candidates) {
+      const name = text(candidate);
+      if (name) return name.length > 80 ? name.slice(0, 79) + "…" : name;
+    }
+    return "";
+  };
+
+  const visible = (el) => {
+    const rect = el.getBoundingClientRect();
+    if (rect.width <= 0 || rect.height <= 0) return null;
+    const style = getComputedStyle(el);
+    if (style.visibility === "hidden" || style.display === "none" || +style.opacity === 0) {
+      return null;
+    }
+    return rect;
+  };
+
+  const elements = [];
+  for (const el of this.querySelectorAll(selector)) {
+    if (el.disabled) continue;
+    const rect = visible(el);
+    if (!rect) continue;
+    elements.push({
+      index: elements.length,
+      id: idOf(el),
+      role: el.getAttribute("role") || implicitRole(el),
+      name: accessibleName(el),
+      bounds: { x: rect.x, y: rect.y, width: rect.width, height: rect.height },
+    });
+  }
+  return elements;
+}"#;
+
+/// Function preparing the element with the given id for an action
+///
+/// Called with `(id, action)`. Scrolls the element into view, focuses it for
+/// `focus` and `fill` (selecting existing text for the latter), and returns
+/// its viewport bounds, or `null` if the element is gone.
+pub(crate) const PREPARE_FN: &str = r#"function(id, action) {
+  const el = window.__cdpElements?.byId.get(id)?.deref();
+  if (!el || !el.isConnected) return null;
+  el.scrollIntoView({ block: "center", inline: "center" });
+  if (action === "focus" || action === "fill") {
+    el.focus();
+    if (action === "fill") {
+      if (typeof el.select === "function") {
+        el.select();
+      } else {
+        const range = this.createRange();
+        range.selectNodeContents(el);
+        const selection = getSelection();
+        selection.removeAllRanges();
+        selection.addRange(range);
+      }
+    }
+  }
+  const rect = el.getBoundingClientRect();
+  return { x: rect.x, y: rect.y, width: rect.width, height: rect.height };
+}"#;
+
+/// Bounding box in CSS pixels relative to the viewport
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Rect {
+    /// Left edge
+    pub x: f64,
+    /// Top edge
+    pub y: f64,
+    /// Width
+    pub width: f64,
+    /// Height
+    pub height: f64,
+}
+
+impl Rect {
+    /// Center point of the box
+    pub fn center(&self) -> (f64, f64) {
+        (self.x + self.width / 2.0, self.y + self.height / 2.0)
+    }
+}
+
+/// Visible interactive element of a page
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InteractiveElement {
+    /// Position in the snapshot, used with [`CdpPage::act_on`](crate::CdpPage::act_on)
+    pub index: usize,
+    /// Id that stays the same for this element across snapshots
+    pub id: String,
+    /// ARIA role, explicit or implied by the tag
+    pub role: String,
+    /// Accessible name, shortened to 80 characters
+    pub name: String,
+    /// Bounds at the time of the snapshot
+    pub bounds: Rect,
+}
+
+impl fmt::Display for InteractiveElement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "[{}] {} {:?} @{},{} {}x{}",
+            self.index,
+            self.role,
+            self.name,
+            self.bounds.x.round(),
+            self.bounds.y.round(),
+            self.bounds.width.round(),
+            self.bounds.height.round()
+        )
+    }
+}
+
+/// Indexed list of the interactive elements visible on a page
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct InteractiveSnapshot {
+    /// Elements in document order
+    pub elements: Vec<InteractiveElement>,
+}
+
+impl fmt::Display for InteractiveSnapshot {
+    /// One line per element, compact enough to put into a prompt
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for element in &self.elements {
+            writeln!(f, "{}", element)?;
+        }
+        Ok(())
+    }
+}
+
+/// Action performed on a snapshot entry
+#[derive(Debug, Clone, PartialEq)]
+pub enum Action {
+    /// Click the element's center with the mouse
+    Click,
+    /// Move the mouse over the element
+    Hover,
+    /// Focus the element
+    Focus,
+    /// Replace the element's text by typing the given text
+    Fill(String),
+}
+
+impl Action {
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            Self::Click => "click",
+            Self::Hover => "hover",
+            Self::Focus => "focus",
+            Self::Fill(_) => "fill",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_snapshot_deserializes_and_renders() {
+        let elements: Vec<InteractiveElement> = serde_json::from_value(json!([
+            {"index": 0, "id": "e1", "role": "link", "name": "Home",
+             "bounds": {"x": 10.2, "y": 5.0, "width": 40.0, "height": 18.6}},
+            {"index": 1, "id": "e7", "role": "textbox", "name": "Card \"number\"",
+             "bounds": {"x": 10.0, "y": 50.0, "width": 200.0, "height": 30.0}}
+        ]))
+        .unwrap();
+        let snapshot = InteractiveSnapshot { elements };
+        assert_eq!(
+            snapshot.to_string(),
+            "[0] link \"Home\" @10,5 40x19\n[1] textbox \"Card \\\"number\\\"\" @10,50 200x30\n"
+        );
+    }
+
+    #[test]
+    fn test_rect_center() {
+        let rect = Rect {
+            x: 10.0,
+            y: 20.0,
+            width: 100.0,
+            height: 40.0,
+        };
+        assert_eq!(rect.center(), (60.0, 40.0));
+    }
+
+    #[test]
+    fn test_action_names() {
+        assert_eq!(Action::Click.name(), "click");
+        assert_eq!(Action::Fill("x".to_string()).name(), "fill");
+    }
+}
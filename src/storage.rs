@@ -0,0 +1,301 @@
+//! Saved cookies and local storage, for reusing logged-in sessions
+
+use crate::{Error, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::path::Path;
+
+/// Function returning the page's origin and local storage entries
+pub(crate) const LOCAL_STORAGE_FN: &str = r#"function() {
+  const items = [];
+  try {
+    for (let i = 0; i < localStorage.length; i++) {
+      const name = localStorage.key(i);
+      items.push([name, localStorage.getItem(name)]);
+    }
+  } catch (e) {}
+  return { origin: location.origin, items };
+}"#;
+
+/// Function writing local storage entries if the page is on the given origin
+pub(crate) const SET_LOCAL_STORAGE_FN: &str = r#"function(origin, items) {
+  if (location.origin !== origin) return false;
+  for (const [name, value] of items) localStorage.setItem(name, value);
+  return true;
+}"#;
+
+/// First bytes of an encrypted storage state file
+const ENCRYPTED_MAGIC: &[u8] = b"CDPSTATE\x01";
+
+/// Cookies and local storage of a browser session
+///
+/// Export it with [`CdpPage::storage_state`](crate::CdpPage::storage_state)
+/// after logging in and restore it with
+/// [`CdpPage::set_storage_state`](crate::CdpPage::set_storage_state) to skip
+/// the login next time. Saved files hold live credentials; with the
+/// `encryption` feature, [`save_encrypted`](Self::save_encrypted) keeps them
+/// out of plaintext.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct StorageState {
+    /// Cookies as reported by `Network.getAllCookies`
+    pub cookies: Vec<Value>,
+    /// Local storage per origin
+    pub origins: Vec<OriginStorage>,
+}
+
+/// Local storage entries of one origin
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OriginStorage {
+    /// Origin, e.g. `https://example.com`
+    pub origin: String,
+    /// Name and value of each entry
+    pub local_storage: Vec<(String, String)>,
+}
+
+impl StorageState {
+    /// Write the state as JSON
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        std::fs::write(path, serde_json::to_vec_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Read a state written by [`save`](Self::save)
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let bytes = std::fs::read(path)?;
+        if bytes.starts_with(ENCRYPTED_MAGIC) {
+            return Err(Error::Browser(
+                "Storage state is encrypted; use StorageState::load_encrypted".to_string(),
+            ));
+        }
+        Ok(serde_json::from_slice(&bytes)?)
+    }
+
+    /// Write the state encrypted with AES-256-GCM
+    #[cfg(feature = "encryption")]
+    pub fn save_encrypted(&self, path: impl AsRef<Path>, key: &StorageKey) -> Result<()> {
+        std::fs::write(path, key.seal(&serde_json::to_vec(self)?)?)?;
+        Ok(())
+    }
+
+    /// Read a state written by [`save_encrypted`](Self::save_encrypted)
+    ///
+    /// Fails if the key is wrong or the file was modified.
+    #[cfg(feature = "encryption")]
+    pub fn load_encrypted(path: impl AsRef<Path>, key: &StorageKey) -> Result<Self> {
+        Ok(serde_json::from_slice(&key.open(&std::fs::read(path)?)?)?)
+    }
+
+    /// `Network.setCookies` params restoring the cookies
+    pub(crate) fn set_cookies_params(&self) -> Value {
+        let cookies: Vec<Value> = self.cookies.iter().map(cookie_param).collect();
+        json!({ "cookies": cookies })
+    }
+}
+
+/// `Network.CookieParam` for a `Network.Cookie`, dropping read-only fields
+fn cookie_param(cookie: &Value) -> Value {
+    let mut param = serde_json::Map::new();
+    for field in [
+        "name",
+        "value",
+        "domain",
+        "path",
+        "secure",
+        "httpOnly",
+        "sameSite",
+        "priority",
+        "sourceScheme",
+        "sourcePort",
+        "partitionKey",
+    ] {
+        if let Some(value) = cookie.get(field) {
+            param.insert(field.to_string(), value.clone());
+        }
+    }
+    // Session cookies report an expiry of -1
+    if cookie["session"] != true {
+        if let Some(expires) = cookie.get("expires").filter(|e| e.as_f64() > Some(0.0)) {
+            param.insert("expires".to_string(), expires.clone());
+        }
+    }
+    Value::Object(param)
+}
+
+/// 256-bit key encrypting saved storage states
+#[cfg(feature = "encryption")]
+#[derive(Clone)]
+pub struct StorageKey([u8; 32]);
+
+#[cfg(feature = "encryption")]
+impl StorageKey {
+    /// Key from raw bytes
+    pub fn from_bytes(bytes: [u8; 32]) -> Self {
+        Self(bytes)
+    }
+
+    /// Key from its base64 encoding, e.g. read from a secret store
+    pub fn from_base64(encoded: &str) -> Result<Self> {
+        use base64::Engine;
+
+        let bytes = base64::engine::general_purpose::STANDARD
+            .decode(encoded.trim())
+            .map_err(|e| Error::Browser(format!("Invalid storage key: {}", e)))?;
+        let bytes: [u8; 32] = bytes.try_into().map_err(|bytes: Vec<u8>| {
+            Error::Browser(format!(
+                "Invalid storage key: expected 32 bytes, got {}",
+                bytes.len()
+            ))
+        })?;
+        Ok(Self(bytes))
+    }
+
+    /// Random key from the operating system's generator
+    pub fn generate() -> Result<Self> {
+        use ring::rand::{SecureRandom, SystemRandom};
+
+        let mut bytes = [0u8; 32];
+        SystemRandom::new()
+            .fill(&mut bytes)
+            .map_err(|_| Error::Browser("Failed to generate storage key".to_string()))?;
+        Ok(Self(bytes))
+    }
+
+    /// Base64 encoding of the key, accepted by [`from_base64`](Self::from_base64)
+    pub fn to_base64(&self) -> String {
+        use base64::Engine;
+
+        base64::engine::general_purpose::STANDARD.encode(self.0)
+    }
+
+    fn aead_key(&self) -> ring::aead::LessSafeKey {
+        let key = ring::aead::UnboundKey::new(&ring::aead::AES_256_GCM, &self.0)
+            .expect("AES-256 keys are 32 bytes");
+        ring::aead::LessSafeKey::new(key)
+    }
+
+    /// Encrypt into `magic | nonce | ciphertext and tag`
+    fn seal(&self, plaintext: &[u8]) -> Result<Vec<u8>> {
+        use ring::aead::{Aad, Nonce, NONCE_LEN};
+        use ring::rand::{SecureRandom, SystemRandom};
+
+        let mut nonce = [0u8; NONCE_LEN];
+        SystemRandom::new()
+            .fill(&mut nonce)
+            .map_err(|_| Error::Browser("Failed to generate nonce".to_string()))?;
+        let mut sealed = plaintext.to_vec();
+        self.aead_key()
+            .seal_in_place_append_tag(
+                Nonce::assume_unique_for_key(nonce),
+                Aad::from(ENCRYPTED_MAGIC),
+                &mut sealed,
+            )
+            .map_err(|_| Error::Browser("Failed to encrypt storage state".to_string()))?;
+
+        let mut blob = ENCRYPTED_MAGIC.to_vec();
+        blob.extend_from_slice(&nonce);
+        blob.extend_from_slice(&sealed);
+        Ok(blob)
+    }
+
+    /// Decrypt a blob written by [`seal`](Self::seal)
+    fn open(&self, blob: &[u8]) -> Result<Vec<u8>> {
+        use ring::aead::{Aad, Nonce, NONCE_LEN};
+
+        let rest = blob
+            .strip_prefix(ENCRYPTED_MAGIC)
+            .filter(|rest| rest.len() >= NONCE_LEN)
+            .ok_or_else(|| Error::Browser("Not an encrypted storage state".to_string()))?;
+        let (nonce, sealed) = rest.split_at(NONCE_LEN);
+        let nonce = Nonce::try_assume_unique_for_key(nonce)
+            .map_err(|_| Error::Browser("Not an encrypted storage state".to_string()))?;
+        let mut sealed = sealed.to_vec();
+        let plaintext = self
+            .aead_key()
+            .open_in_place(nonce, Aad::from(ENCRYPTED_MAGIC), &mut sealed)
+            .map_err(|_| {
+                Error::Browser(
+                    "Failed to decrypt storage state: wrong key or corrupted file".to_string(),
+                )
+            })?;
+        Ok(plaintext.to_vec())
+    }
+}
+
+#[cfg(feature = "encryption")]
+impl std::fmt::Debug for StorageKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("StorageKey(..)")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state() -> StorageState {
+        StorageState {
+            cookies: vec![json!({
+                "name": "sid",
+                "value": "secret",
+                "domain": "example.com",
+                "path": "/",
+                "expires": -1,
+                "size": 9,
+                "httpOnly": true,
+                "secure": true,
+                "session": true
+            })],
+            origins: vec![OriginStorage {
+                origin: "https://example.com".to_string(),
+                local_storage: vec![("token".to_string(), "abc".to_string())],
+            }],
+        }
+    }
+
+    #[test]
+    fn test_cookie_param_drops_read_only_fields() {
+        let params = state().set_cookies_params();
+        let cookie = &params["cookies"][0];
+        assert_eq!(cookie["name"], "sid");
+        assert_eq!(cookie["httpOnly"], true);
+        assert!(cookie.get("size").is_none());
+        assert!(cookie.get("expires").is_none());
+
+        let persistent = cookie_param(&json!({"name": "a", "expires": 1.7e9, "session": false}));
+        assert_eq!(persistent["expires"], 1.7e9);
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let path = std::env::temp_dir().join(format!("state-{}.json", uuid::Uuid::new_v4()));
+        state().save(&path).unwrap();
+        let json: Value = serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+        assert_eq!(json["origins"][0]["localStorage"][0][1], "abc");
+        assert_eq!(StorageState::load(&path).unwrap(), state());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[cfg(feature = "encryption")]
+    #[test]
+    fn test_encrypted_round_trip() {
+        let key = StorageKey::generate().unwrap();
+        let path = std::env::temp_dir().join(format!("state-{}.bin", uuid::Uuid::new_v4()));
+        state().save_encrypted(&path, &key).unwrap();
+
+        let blob = std::fs::read(&path).unwrap();
+        assert!(!blob.windows(6).any(|w| w == b"secret"));
+        assert!(StorageState::load(&path).is_err());
+        assert_eq!(StorageState::load_encrypted(&path, &key).unwrap(), state());
+
+        let other = StorageKey::generate().unwrap();
+        assert!(StorageState::load_encrypted(&path, &other).is_err());
+        let decoded = StorageKey::from_base64(&key.to_base64()).unwrap();
+        assert_eq!(
+            StorageState::load_encrypted(&path, &decoded).unwrap(),
+            state()
+        );
+        assert!(StorageKey::from_base64("c2hvcnQ=").is_err());
+        std::fs::remove_file(path).unwrap();
+    }
+}
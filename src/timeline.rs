@@ -0,0 +1,399 @@
+//! Chronological timeline of a page's lifecycle, network, console and action events
+
+use crate::connection::{CdpConnection, CdpEvent, MiddlewareGuard};
+use crate::middleware::{Command, Middleware, Next};
+use crate::trace::console_line;
+use crate::Result;
+use futures::future::BoxFuture;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::path::Path;
+use std::sync::{Arc, Mutex, Weak};
+use std::time::Instant;
+use tokio::task::JoinHandle;
+
+/// Kind of a [`TimelineEvent`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TimelineCategory {
+    /// Navigations, lifecycle milestones and loads
+    Lifecycle,
+    /// Requests, responses and their completion
+    Network,
+    /// Console output and uncaught exceptions
+    Console,
+    /// Navigation and input commands sent by the automation
+    Action,
+}
+
+impl TimelineCategory {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Lifecycle => "lifecycle",
+            Self::Network => "network",
+            Self::Console => "console",
+            Self::Action => "action",
+        }
+    }
+}
+
+/// One entry of an [`EventTimeline`]
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TimelineEvent {
+    /// Milliseconds since the timeline started
+    pub at_ms: f64,
+    /// Kind of event
+    pub category: TimelineCategory,
+    /// CDP event or command, e.g. `Network.responseReceived` or `Input.dispatchMouseEvent`
+    pub name: String,
+    /// Most relevant fields, such as the URL and status of a response
+    pub detail: Value,
+}
+
+impl TimelineEvent {
+    /// Timeline entry for a CDP event, or `None` if the event is not recorded
+    fn from_event(event: &CdpEvent, at_ms: f64) -> Option<Self> {
+        let params = &event.params;
+        let (category, detail) = match event.method.as_str() {
+            "Page.lifecycleEvent" => (
+                TimelineCategory::Lifecycle,
+                json!({ "name": params["name"], "frameId": params["frameId"] }),
+            ),
+            "Page.frameNavigated" => (
+                TimelineCategory::Lifecycle,
+                json!({ "url": params["frame"]["url"], "frameId": params["frame"]["id"] }),
+            ),
+            "Page.navigatedWithinDocument" => (
+                TimelineCategory::Lifecycle,
+                json!({ "url": params["url"], "frameId": params["frameId"] }),
+            ),
+            "Page.domContentEventFired" | "Page.loadEventFired" => {
+                (TimelineCategory::Lifecycle, json!({}))
+            }
+            "Network.requestWillBeSent" => (
+                TimelineCategory::Network,
+                json!({
+                    "requestId": params["requestId"],
+                    "method": params["request"]["method"],
+                    "url": params["request"]["url"],
+                    "type": params["type"]
+                }),
+            ),
+            "Network.responseReceived" => (
+                TimelineCategory::Network,
+                json!({
+                    "requestId": params["requestId"],
+                    "url": params["response"]["url"],
+                    "status": params["response"]["status"]
+                }),
+            ),
+            "Network.loadingFinished" => (
+                TimelineCategory::Network,
+                json!({
+                    "requestId": params["requestId"],
+                    "encodedDataLength": params["encodedDataLength"]
+                }),
+            ),
+            "Network.loadingFailed" => (
+                TimelineCategory::Network,
+                json!({ "requestId": params["requestId"], "errorText": params["errorText"] }),
+            ),
+            "Runtime.consoleAPICalled" | "Runtime.exceptionThrown" => (
+                TimelineCategory::Console,
+                json!({ "text": console_line(&event.method, params)? }),
+            ),
+            "Log.entryAdded" => (
+                TimelineCategory::Console,
+                json!({
+                    "level": params["entry"]["level"],
+                    "text": params["entry"]["text"],
+                    "url": params["entry"]["url"]
+                }),
+            ),
+            _ => return None,
+        };
+        Some(Self {
+            at_ms,
+            category,
+            name: event.method.clone(),
+            detail,
+        })
+    }
+
+    /// Timeline entry for an outgoing command, or `None` if it is not a user action
+    fn from_command(command: &Command, at_ms: f64) -> Option<Self> {
+        let recorded = command.method.starts_with("Input.")
+            || matches!(
+                command.method.as_str(),
+                "Page.navigate" | "Page.reload" | "Page.navigateToHistoryEntry"
+            );
+        recorded.then(|| Self {
+            at_ms,
+            category: TimelineCategory::Action,
+            name: command.method.clone(),
+            detail: command.params.clone(),
+        })
+    }
+}
+
+/// Events shared by the recording task and the action middleware
+struct Recorded {
+    started: Instant,
+    events: Mutex<Vec<TimelineEvent>>,
+}
+
+impl Recorded {
+    fn elapsed_ms(&self) -> f64 {
+        self.started.elapsed().as_secs_f64() * 1000.0
+    }
+
+    fn push(&self, event: TimelineEvent) {
+        if let Ok(mut events) = self.events.lock() {
+            events.push(event);
+        }
+    }
+}
+
+/// Middleware recording user actions while the timeline is alive
+struct ActionRecorder(Weak<Recorded>);
+
+impl Middleware for ActionRecorder {
+    fn handle<'a>(&'a self, command: Command, next: Next<'a>) -> BoxFuture<'a, Result<Value>> {
+        if let Some(recorded) = self.0.upgrade() {
+            if let Some(event) = TimelineEvent::from_command(&command, recorded.elapsed_ms()) {
+                recorded.push(event);
+            }
+        }
+        next.run(command)
+    }
+}
+
+/// Recorder of a page's events, from [`CdpPage::event_timeline`](crate::CdpPage::event_timeline)
+///
+/// Timestamps are taken as events arrive, on one clock for events and
+/// actions alike. Recording stops when the timeline is dropped.
+///
+/// ```no_run
+/// # async fn run(page: &chrome_cdp::CdpPage) -> chrome_cdp::Result<()> {
+/// let timeline = page.event_timeline().await?;
+/// page.goto("https://example.com").await?;
+/// page.wait_for_selector("h1").await?;
+/// timeline.save_perfetto("traces/example.json")?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct EventTimeline {
+    recorded: Arc<Recorded>,
+    task: JoinHandle<()>,
+    /// Keeps the action recorder in the connection's chain
+    _actions: MiddlewareGuard,
+}
+
+impl EventTimeline {
+    /// Record the connection's events and the actions sent through it
+    pub(crate) fn spawn(connection: &CdpConnection) -> Self {
+        let mut events = connection.subscribe();
+        let recorded = Arc::new(Recorded {
+            started: Instant::now(),
+            events: Mutex::new(Vec::new()),
+        });
+        let state = recorded.clone();
+        let task = tokio::spawn(async move {
+            while let Some(event) = events.recv().await {
+                if let Some(event) = TimelineEvent::from_event(&event, state.elapsed_ms()) {
+                    state.push(event);
+                }
+            }
+        });
+        let actions = connection.add_scoped_middleware(ActionRecorder(Arc::downgrade(&recorded)));
+        Self {
+            recorded,
+            task,
+            _actions: actions,
+        }
+    }
+
+    /// Events recorded so far, in chronological order
+    pub fn events(&self) -> Vec<TimelineEvent> {
+        let mut events = self
+            .recorded
+            .events
+            .lock()
+            .map(|events| events.clone())
+            .unwrap_or_default();
+        events.sort_by(|a, b| a.at_ms.total_cmp(&b.at_ms));
+        events
+    }
+
+    /// Events as a JSON array, in chronological order
+    pub fn to_json(&self) -> Value {
+        json!(self.events())
+    }
+
+    /// Events in the Chrome trace event format, which Perfetto and `chrome://tracing` open
+    ///
+    /// Each category becomes its own track of instant events.
+    pub fn to_perfetto(&self) -> Value {
+        let tracks = [
+            TimelineCategory::Lifecycle,
+            TimelineCategory::Network,
+            TimelineCategory::Console,
+            TimelineCategory::Action,
+        ];
+        let mut trace_events: Vec<Value> = tracks
+            .iter()
+            .enumerate()
+            .map(|(tid, category)| {
+                json!({
+                    "name": "thread_name", "ph": "M", "pid": 1, "tid": tid + 1,
+                    "args": { "name": category.as_str() }
+                })
+            })
+            .collect();
+        trace_events.extend(self.events().into_iter().map(|event| {
+            let tid = tracks
+                .iter()
+                .position(|c| *c == event.category)
+                .unwrap_or(0)
+                + 1;
+            json!({
+                "name": event.name,
+                "cat": event.category.as_str(),
+                "ph": "i",
+                "s": "t",
+                "ts": (event.at_ms * 1000.0).round() as u64,
+                "pid": 1,
+                "tid": tid,
+                "args": event.detail
+            })
+        }));
+        json!({ "traceEvents": trace_events, "displayTimeUnit": "ms" })
+    }
+
+    /// Write the events to a file as a JSON array
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        std::fs::write(path, serde_json::to_vec_pretty(&self.to_json())?)?;
+        Ok(())
+    }
+
+    /// Write the events to a file in the trace event format, for Perfetto
+    pub fn save_perfetto(&self, path: impl AsRef<Path>) -> Result<()> {
+        std::fs::write(path, serde_json::to_vec(&self.to_perfetto())?)?;
+        Ok(())
+    }
+}
+
+impl Drop for EventTimeline {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connection::tests::{mock_server, server_recv, server_send};
+
+    fn event(method: &str, params: Value) -> CdpEvent {
+        CdpEvent {
+            method: method.to_string(),
+            params,
+            session_id: None,
+        }
+    }
+
+    #[test]
+    fn test_events_and_actions_are_classified() {
+        let response = event(
+            "Network.responseReceived",
+            json!({"requestId": "7", "response": {"url": "https://a.example/", "status": 200, "headers": {}}}),
+        );
+        let entry = TimelineEvent::from_event(&response, 12.5).unwrap();
+        assert_eq!(entry.category, TimelineCategory::Network);
+        assert_eq!(
+            entry.detail,
+            json!({"requestId": "7", "url": "https://a.example/", "status": 200})
+        );
+
+        let lifecycle = event(
+            "Page.lifecycleEvent",
+            json!({"name": "load", "frameId": "F"}),
+        );
+        let entry = TimelineEvent::from_event(&lifecycle, 0.0).unwrap();
+        assert_eq!(entry.category, TimelineCategory::Lifecycle);
+        assert_eq!(entry.detail["name"], "load");
+
+        let console = event(
+            "Runtime.consoleAPICalled",
+            json!({"type": "log", "args": [{"type": "string", "value": "hi"}]}),
+        );
+        let entry = TimelineEvent::from_event(&console, 0.0).unwrap();
+        assert_eq!(entry.detail["text"], "[log] hi");
+        assert!(TimelineEvent::from_event(&event("DOM.documentUpdated", json!({})), 0.0).is_none());
+
+        let click = Command::new("Input.dispatchMouseEvent", json!({"type": "mousePressed"}));
+        let entry = TimelineEvent::from_command(&click, 3.0).unwrap();
+        assert_eq!(entry.category, TimelineCategory::Action);
+        assert!(
+            TimelineEvent::from_command(&Command::new("Runtime.evaluate", json!({})), 0.0)
+                .is_none()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_records_actions_and_events_in_order() {
+        let url = mock_server(|mut ws| async move {
+            let request = server_recv(&mut ws).await;
+            server_send(&mut ws, json!({"id": request["id"], "result": {}})).await;
+            let sent = json!({"requestId": "1", "request": {"method": "GET", "url": "https://a.example/"}});
+            server_send(
+                &mut ws,
+                json!({"method": "Network.requestWillBeSent", "params": sent}),
+            )
+            .await;
+            server_send(
+                &mut ws,
+                json!({"method": "Page.lifecycleEvent", "params": {"name": "load", "frameId": "F"}}),
+            )
+            .await;
+            let _ = server_recv(&mut ws).await;
+        })
+        .await;
+        let connection = CdpConnection::connect(&url).await.unwrap();
+        let timeline = EventTimeline::spawn(&connection);
+
+        connection
+            .send_command("Page.navigate", json!({"url": "https://a.example/"}))
+            .await
+            .unwrap();
+        for _ in 0..100 {
+            if timeline.events().len() == 3 {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        let events = timeline.events();
+        let names: Vec<&str> = events.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(
+            names,
+            [
+                "Page.navigate",
+                "Network.requestWillBeSent",
+                "Page.lifecycleEvent"
+            ]
+        );
+        assert_eq!(events[0].detail["url"], "https://a.example/");
+
+        let trace = timeline.to_perfetto();
+        let trace_events = trace["traceEvents"].as_array().unwrap();
+        let navigate = trace_events.iter().find(|e| e["ph"] == "i").unwrap();
+        assert_eq!(navigate["cat"], "action");
+        assert_eq!(navigate["tid"], 4);
+        assert_eq!(trace_events[3]["args"]["name"], "action");
+
+        // The action recorder leaves the chain with the timeline
+        assert_eq!(connection.middleware_chain().len(), 1);
+        drop(timeline);
+        assert!(connection.middleware_chain().is_empty());
+    }
+}
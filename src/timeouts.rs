@@ -0,0 +1,118 @@
+//! Timeout configuration shared by browsers and pages
+
+use std::time::Duration;
+
+const DEFAULT_LAUNCH: Duration = Duration::from_secs(30);
+const DEFAULT_CONNECT: Duration = Duration::from_secs(5);
+const DEFAULT_COMMAND: Duration = Duration::from_secs(60);
+const DEFAULT_NAVIGATION: Duration = Duration::from_secs(30);
+const DEFAULT_WAIT: Duration = Duration::from_secs(30);
+const DEFAULT_CLOSE: Duration = Duration::from_secs(5);
+
+/// Timeouts for browser and page operations
+///
+/// Unset fields fall back to the next level: page settings override browser
+/// settings, which override manager settings, which override the defaults.
+///
+/// ```
+/// use chrome_cdp::Timeouts;
+/// use std::time::Duration;
+///
+/// let manager = Timeouts { navigation: Some(Duration::from_secs(60)), ..Default::default() };
+/// let page = Timeouts { wait: Some(Duration::from_secs(5)), ..Default::default() };
+/// let effective = page.or(manager);
+/// assert_eq!(effective.navigation_timeout(), Duration::from_secs(60));
+/// assert_eq!(effective.wait_timeout(), Duration::from_secs(5));
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Timeouts {
+    /// Starting Chrome until its DevTools endpoint is up (default: 30 seconds)
+    pub launch: Option<Duration>,
+    /// Reaching the DevTools endpoint and opening WebSockets (default: 5 seconds)
+    pub connect: Option<Duration>,
+    /// Any single CDP command (default: 60 seconds)
+    pub command: Option<Duration>,
+    /// Starting a navigation (default: 30 seconds)
+    pub navigation: Option<Duration>,
+    /// Waiting for elements and other page conditions (default: 30 seconds)
+    pub wait: Option<Duration>,
+    /// Closing pages and browsers (default: 5 seconds)
+    pub close: Option<Duration>,
+}
+
+impl Timeouts {
+    /// Fill unset fields from `fallback`
+    pub fn or(self, fallback: Timeouts) -> Timeouts {
+        Timeouts {
+            launch: self.launch.or(fallback.launch),
+            connect: self.connect.or(fallback.connect),
+            command: self.command.or(fallback.command),
+            navigation: self.navigation.or(fallback.navigation),
+            wait: self.wait.or(fallback.wait),
+            close: self.close.or(fallback.close),
+        }
+    }
+
+    /// Effective launch timeout
+    pub fn launch_timeout(&self) -> Duration {
+        self.launch.unwrap_or(DEFAULT_LAUNCH)
+    }
+
+    /// Effective connect timeout
+    pub fn connect_timeout(&self) -> Duration {
+        self.connect.unwrap_or(DEFAULT_CONNECT)
+    }
+
+    /// Effective command timeout
+    pub fn command_timeout(&self) -> Duration {
+        self.command.unwrap_or(DEFAULT_COMMAND)
+    }
+
+    /// Effective navigation timeout
+    pub fn navigation_timeout(&self) -> Duration {
+        self.navigation.unwrap_or(DEFAULT_NAVIGATION)
+    }
+
+    /// Effective wait timeout
+    pub fn wait_timeout(&self) -> Duration {
+        self.wait.unwrap_or(DEFAULT_WAIT)
+    }
+
+    /// Effective close timeout
+    pub fn close_timeout(&self) -> Duration {
+        self.close.unwrap_or(DEFAULT_CLOSE)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_defaults() {
+        let timeouts = Timeouts::default();
+        assert_eq!(timeouts.launch_timeout(), DEFAULT_LAUNCH);
+        assert_eq!(timeouts.connect_timeout(), DEFAULT_CONNECT);
+        assert_eq!(timeouts.command_timeout(), DEFAULT_COMMAND);
+        assert_eq!(timeouts.navigation_timeout(), DEFAULT_NAVIGATION);
+        assert_eq!(timeouts.wait_timeout(), DEFAULT_WAIT);
+        assert_eq!(timeouts.close_timeout(), DEFAULT_CLOSE);
+    }
+
+    #[test]
+    fn test_or_prefers_own_values() {
+        let page = Timeouts {
+            command: Some(Duration::from_secs(1)),
+            ..Default::default()
+        };
+        let browser = Timeouts {
+            command: Some(Duration::from_secs(2)),
+            close: Some(Duration::from_secs(3)),
+            ..Default::default()
+        };
+        let effective = page.or(browser);
+        assert_eq!(effective.command_timeout(), Duration::from_secs(1));
+        assert_eq!(effective.close_timeout(), Duration::from_secs(3));
+        assert_eq!(effective.launch_timeout(), DEFAULT_LAUNCH);
+    }
+}
@@ -0,0 +1,301 @@
+//! Per-request network timing, to tell slow networks from slow rendering
+
+use crate::connection::{CdpEvent, EventStream};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+
+/// Phases of one request, from `Network.responseReceived`'s `timing`
+///
+/// Phases the request skipped, e.g. DNS and connect on a reused connection,
+/// are `None`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RequestTiming {
+    /// Request id, as in `Network.*` events
+    pub request_id: String,
+    /// URL of the response
+    pub url: String,
+    /// Resolving the host name
+    pub dns: Option<Duration>,
+    /// Establishing the TCP connection, excluding the TLS handshake
+    pub connect: Option<Duration>,
+    /// TLS handshake
+    pub tls: Option<Duration>,
+    /// From sending the request until the response headers arrived
+    pub ttfb: Option<Duration>,
+    /// From the response headers until the body was fully loaded
+    pub download: Option<Duration>,
+    /// `requestTime` baseline in seconds, on the same clock as event timestamps
+    request_time: f64,
+    /// Offset of the response headers from the baseline, in milliseconds
+    headers_end: Option<f64>,
+}
+
+impl RequestTiming {
+    /// Parse a `Network.responseReceived` event, or `None` if it carries no timing
+    pub(crate) fn from_event(params: &Value) -> Option<Self> {
+        let timing = params["response"].get("timing")?;
+        let at = |field: &str| timing[field].as_f64().filter(|ms| *ms >= 0.0);
+        let span = |start: Option<f64>, end: Option<f64>| {
+            let (start, end) = (start?, end?);
+            Some(Duration::from_secs_f64((end - start).max(0.0) / 1000.0))
+        };
+        let tls_start = at("sslStart");
+        Some(Self {
+            request_id: params["requestId"].as_str()?.to_string(),
+            url: params["response"]["url"]
+                .as_str()
+                .unwrap_or_default()
+                .to_string(),
+            dns: span(at("dnsStart"), at("dnsEnd")),
+            connect: span(at("connectStart"), tls_start.or(at("connectEnd"))),
+            tls: span(tls_start, at("sslEnd")),
+            ttfb: span(at("sendStart"), at("receiveHeadersEnd")),
+            download: None,
+            request_time: timing["requestTime"].as_f64()?,
+            headers_end: at("receiveHeadersEnd"),
+        })
+    }
+
+    /// Record the body as loaded at `timestamp`, from `Network.loadingFinished`
+    fn finish(&mut self, timestamp: f64) {
+        if let Some(headers_end) = self.headers_end {
+            let headers_at = self.request_time + headers_end / 1000.0;
+            self.download = Some(Duration::from_secs_f64((timestamp - headers_at).max(0.0)));
+        }
+    }
+}
+
+/// Distribution of one phase over the recorded requests
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Percentiles {
+    /// Median
+    pub p50: Duration,
+    /// 90th percentile
+    pub p90: Duration,
+    /// 99th percentile
+    pub p99: Duration,
+    /// Slowest request
+    pub max: Duration,
+}
+
+impl Percentiles {
+    /// Nearest-rank percentiles of `samples`, or `None` if there are none
+    fn of(mut samples: Vec<Duration>) -> Option<Self> {
+        if samples.is_empty() {
+            return None;
+        }
+        samples.sort();
+        let rank = |p: f64| samples[((p * samples.len() as f64).ceil() as usize).max(1) - 1];
+        Some(Self {
+            p50: rank(0.5),
+            p90: rank(0.9),
+            p99: rank(0.99),
+            max: samples[samples.len() - 1],
+        })
+    }
+}
+
+/// Percentiles of each phase over a page's requests
+///
+/// A phase is `None` when no recorded request went through it, e.g. `tls`
+/// on a plain-HTTP site.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimingSummary {
+    /// Number of requests with timing information
+    pub requests: usize,
+    /// Resolving host names
+    pub dns: Option<Percentiles>,
+    /// Establishing TCP connections
+    pub connect: Option<Percentiles>,
+    /// TLS handshakes
+    pub tls: Option<Percentiles>,
+    /// Waiting for response headers
+    pub ttfb: Option<Percentiles>,
+    /// Downloading response bodies
+    pub download: Option<Percentiles>,
+}
+
+impl TimingSummary {
+    /// Summarize the given timings
+    pub fn of(timings: &[RequestTiming]) -> Self {
+        let phase = |pick: fn(&RequestTiming) -> Option<Duration>| {
+            Percentiles::of(timings.iter().filter_map(pick).collect())
+        };
+        Self {
+            requests: timings.len(),
+            dns: phase(|t| t.dns),
+            connect: phase(|t| t.connect),
+            tls: phase(|t| t.tls),
+            ttfb: phase(|t| t.ttfb),
+            download: phase(|t| t.download),
+        }
+    }
+}
+
+/// Timings in the order responses arrived, with the index of each request id
+#[derive(Default)]
+struct Recorded {
+    timings: Vec<RequestTiming>,
+    by_id: HashMap<String, usize>,
+}
+
+impl Recorded {
+    fn apply(&mut self, event: &CdpEvent) {
+        match event.method.as_str() {
+            "Network.responseReceived" => {
+                if let Some(timing) = RequestTiming::from_event(&event.params) {
+                    self.by_id
+                        .insert(timing.request_id.clone(), self.timings.len());
+                    self.timings.push(timing);
+                }
+            }
+            "Network.loadingFinished" => {
+                let request_id = event.params["requestId"].as_str().unwrap_or_default();
+                let index = self.by_id.remove(request_id);
+                if let (Some(index), Some(timestamp)) = (index, event.params["timestamp"].as_f64())
+                {
+                    self.timings[index].finish(timestamp);
+                }
+            }
+            "Network.loadingFailed" => {
+                let request_id = event.params["requestId"].as_str().unwrap_or_default();
+                self.by_id.remove(request_id);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Recorder of network timing, from [`CdpPage::record_network_timing`](crate::CdpPage::record_network_timing)
+///
+/// Recording stops when the recorder is dropped.
+pub struct NetworkTimingRecorder {
+    recorded: Arc<Mutex<Recorded>>,
+    task: JoinHandle<()>,
+}
+
+impl NetworkTimingRecorder {
+    pub(crate) fn spawn(mut events: EventStream) -> Self {
+        let recorded = Arc::new(Mutex::new(Recorded::default()));
+        let state = recorded.clone();
+        let task = tokio::spawn(async move {
+            while let Some(event) = events.recv().await {
+                if let Ok(mut state) = state.lock() {
+                    state.apply(&event);
+                }
+            }
+        });
+        Self { recorded, task }
+    }
+
+    /// Timings of the responses received so far, in arrival order
+    ///
+    /// `download` stays `None` until the body has finished loading.
+    pub fn timings(&self) -> Vec<RequestTiming> {
+        self.recorded
+            .lock()
+            .map(|recorded| recorded.timings.clone())
+            .unwrap_or_default()
+    }
+
+    /// Percentiles of each phase over the responses received so far
+    pub fn summary(&self) -> TimingSummary {
+        TimingSummary::of(&self.timings())
+    }
+}
+
+impl Drop for NetworkTimingRecorder {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn response(id: &str, timing: Value) -> CdpEvent {
+        CdpEvent {
+            method: "Network.responseReceived".to_string(),
+            params: json!({
+                "requestId": id,
+                "response": {"url": format!("https://a.example/{}", id), "timing": timing}
+            }),
+            session_id: None,
+        }
+    }
+
+    fn ms(millis: u64) -> Option<Duration> {
+        Some(Duration::from_millis(millis))
+    }
+
+    #[test]
+    fn test_phases_from_timing() {
+        let event = response(
+            "1",
+            json!({
+                "requestTime": 100.0,
+                "dnsStart": 0.0, "dnsEnd": 20.0,
+                "connectStart": 20.0, "sslStart": 50.0, "sslEnd": 90.0, "connectEnd": 90.0,
+                "sendStart": 90.0, "sendEnd": 91.0, "receiveHeadersEnd": 190.0
+            }),
+        );
+        let mut recorded = Recorded::default();
+        recorded.apply(&event);
+        recorded.apply(&CdpEvent {
+            method: "Network.loadingFinished".to_string(),
+            params: json!({"requestId": "1", "timestamp": 100.5}),
+            session_id: None,
+        });
+
+        let timing = &recorded.timings[0];
+        assert_eq!(timing.url, "https://a.example/1");
+        assert_eq!(timing.dns, ms(20));
+        assert_eq!(timing.connect, ms(30));
+        assert_eq!(timing.tls, ms(40));
+        assert_eq!(timing.ttfb, ms(100));
+        assert_eq!(timing.download, ms(310));
+        assert!(recorded.by_id.is_empty());
+    }
+
+    #[test]
+    fn test_reused_connection_skips_phases() {
+        let event = response(
+            "2",
+            json!({
+                "requestTime": 5.0,
+                "dnsStart": -1, "dnsEnd": -1, "connectStart": -1, "connectEnd": -1,
+                "sslStart": -1, "sslEnd": -1, "sendStart": 1.0, "receiveHeadersEnd": 11.0
+            }),
+        );
+        let timing = RequestTiming::from_event(&event.params).unwrap();
+        assert_eq!((timing.dns, timing.connect, timing.tls), (None, None, None));
+        assert_eq!(timing.ttfb, ms(10));
+
+        // Cached and data: responses carry no timing
+        assert!(RequestTiming::from_event(&json!({"requestId": "3", "response": {}})).is_none());
+    }
+
+    #[test]
+    fn test_summary_percentiles() {
+        let mut recorded = Recorded::default();
+        for i in 1..=10u64 {
+            recorded.apply(&response(
+                &i.to_string(),
+                json!({"requestTime": 0.0, "sendStart": 0.0, "receiveHeadersEnd": (i * 10) as f64}),
+            ));
+        }
+        let summary = TimingSummary::of(&recorded.timings);
+        assert_eq!(summary.requests, 10);
+        assert_eq!(summary.dns, None);
+        let ttfb = summary.ttfb.unwrap();
+        assert_eq!(ttfb.p50, Duration::from_millis(50));
+        assert_eq!(ttfb.p90, Duration::from_millis(90));
+        assert_eq!(ttfb.p99, Duration::from_millis(100));
+        assert_eq!(ttfb.max, Duration::from_millis(100));
+    }
+}
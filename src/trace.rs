@@ -0,0 +1,286 @@
+//! Run traces recording automation steps for post-mortem inspection
+
+use crate::connection::MiddlewareGuard;
+use crate::middleware::{Command, Middleware, Next};
+use crate::{CdpPage, EventStream, Result};
+use futures::future::BoxFuture;
+use serde::Serialize;
+use serde_json::Value;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::Instant;
+
+/// File name of the step index inside a saved trace
+const TRACE_FILE: &str = "trace.json";
+
+/// One recorded automation step
+#[derive(Debug, Clone, Serialize)]
+pub struct TraceStep {
+    /// Position of the step in the run, starting at 1
+    pub index: usize,
+    /// Description of the action, e.g. `goto https://example.com`
+    pub action: String,
+    /// Milliseconds between the start of the run and the start of the step
+    pub started_ms: u64,
+    /// Duration of the step in milliseconds
+    pub duration_ms: u64,
+    /// Error message if the step failed
+    pub error: Option<String>,
+    /// Console output and uncaught exceptions seen during the step
+    pub console: Vec<String>,
+    /// CDP commands sent through the page during the step, in order
+    pub commands: Vec<TraceCommand>,
+    /// File name of the screenshot taken after the step, if one could be taken
+    pub screenshot: Option<String>,
+}
+
+/// CDP command sent during a step
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TraceCommand {
+    /// Method name, e.g. `Input.dispatchMouseEvent`
+    pub method: String,
+    /// Command parameters
+    pub params: Value,
+}
+
+/// Middleware recording commands while the trace is alive
+struct CommandRecorder(Weak<Mutex<Vec<TraceCommand>>>);
+
+impl Middleware for CommandRecorder {
+    fn handle<'a>(&'a self, command: Command, next: Next<'a>) -> BoxFuture<'a, Result<Value>> {
+        if let Some(commands) = self.0.upgrade() {
+            if let Ok(mut commands) = commands.lock() {
+                commands.push(TraceCommand {
+                    method: command.method.clone(),
+                    params: command.params.clone(),
+                });
+            }
+        }
+        next.run(command)
+    }
+}
+
+/// Recorder capturing a screenshot, console output, commands, and timing for each step
+///
+/// ```no_run
+/// # async fn run(page: &chrome_cdp::CdpPage) -> chrome_cdp::Result<()> {
+/// let mut trace = chrome_cdp::RunTrace::start(page);
+/// let result = trace.step("goto example.com", page.goto("https://example.com")).await;
+/// if result.is_err() {
+///     trace.save("traces/failed-run")?;
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub struct RunTrace<'a> {
+    page: &'a CdpPage,
+    events: EventStream,
+    commands: Arc<Mutex<Vec<TraceCommand>>>,
+    /// Keeps the command recorder in the connection's chain
+    _recorder: MiddlewareGuard,
+    started: Instant,
+    steps: Vec<TraceStep>,
+    screenshots: Vec<(String, Vec<u8>)>,
+}
+
+impl<'a> RunTrace<'a> {
+    /// Start recording steps performed on the page
+    pub fn start(page: &'a CdpPage) -> Self {
+        let commands = Arc::new(Mutex::new(Vec::new()));
+        let recorder = page
+            .connection()
+            .add_scoped_middleware(CommandRecorder(Arc::downgrade(&commands)));
+        Self {
+            page,
+            events: page.connection().subscribe(),
+            commands,
+            _recorder: recorder,
+            started: Instant::now(),
+            steps: Vec::new(),
+            screenshots: Vec::new(),
+        }
+    }
+
+    /// Run one step and record it, returning the step's own result
+    pub async fn step<T, F>(&mut self, action: &str, step: F) -> Result<T>
+    where
+        F: Future<Output = Result<T>>,
+    {
+        let index = self.steps.len() + 1;
+        let started_ms = self.started.elapsed().as_millis() as u64;
+        let step_start = Instant::now();
+        // Drop commands sent between steps, e.g. for the last screenshot
+        self.take_commands();
+        let result = step.await;
+        let duration_ms = step_start.elapsed().as_millis() as u64;
+        let commands = self.take_commands();
+
+        let mut console = Vec::new();
+        while let Some(event) = self.events.try_recv() {
+            if let Some(line) = console_line(&event.method, &event.params) {
+                console.push(line);
+            }
+        }
+
+        // A failed screenshot must not mask the outcome of the step
+        let screenshot = match self.page.screenshot().await {
+            Ok(png) => {
+                let name = format!("step-{:03}.png", index);
+                self.screenshots.push((name.clone(), png));
+                Some(name)
+            }
+            Err(_) => None,
+        };
+
+        self.steps.push(TraceStep {
+            index,
+            action: action.to_string(),
+            started_ms,
+            duration_ms,
+            error: result.as_ref().err().map(|e| e.to_string()),
+            console,
+            commands,
+            screenshot,
+        });
+        result
+    }
+
+    /// Commands recorded since the last call
+    fn take_commands(&self) -> Vec<TraceCommand> {
+        self.commands
+            .lock()
+            .map(|mut commands| std::mem::take(&mut *commands))
+            .unwrap_or_default()
+    }
+
+    /// Steps recorded so far
+    pub fn steps(&self) -> &[TraceStep] {
+        &self.steps
+    }
+
+    /// Write `trace.json` and the step screenshots into a directory
+    ///
+    /// Returns the path of the written `trace.json`.
+    pub fn save(&self, dir: impl AsRef<Path>) -> Result<PathBuf> {
+        let dir = dir.as_ref();
+        std::fs::create_dir_all(dir)?;
+        for (name, png) in &self.screenshots {
+            std::fs::write(dir.join(name), png)?;
+        }
+        let path = dir.join(TRACE_FILE);
+        std::fs::write(&path, serde_json::to_vec_pretty(&self.steps)?)?;
+        Ok(path)
+    }
+}
+
+/// Render a console or exception event as a single line
+pub(crate) fn console_line(method: &str, params: &Value) -> Option<String> {
+    match method {
+        "Runtime.consoleAPICalled" => {
+            let args: Vec<String> = params["args"]
+                .as_array()
+                .map(|args| {
+                    args.iter()
+                        .map(|arg| match &arg["value"] {
+                            Value::String(s) => s.clone(),
+                            Value::Null => arg["description"]
+                                .as_str()
+                                .unwrap_or_else(|| arg["type"].as_str().unwrap_or_default())
+                                .to_string(),
+                            value => value.to_string(),
+                        })
+                        .collect()
+                })
+                .unwrap_or_default();
+            Some(format!(
+                "[{}] {}",
+                params["type"].as_str().unwrap_or("log"),
+                args.join(" ")
+            ))
+        }
+        "Runtime.exceptionThrown" => {
+            let details = &params["exceptionDetails"];
+            let text = details["exception"]["description"]
+                .as_str()
+                .or_else(|| details["text"].as_str())
+                .unwrap_or("unknown exception");
+            Some(format!("[exception] {}", text))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connection::tests::{mock_server, server_recv, server_send};
+    use crate::Timeouts;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_step_records_its_commands() {
+        let url = mock_server(|mut ws| async move {
+            loop {
+                let request = server_recv(&mut ws).await;
+                server_send(&mut ws, json!({"id": request["id"], "result": {}})).await;
+            }
+        })
+        .await;
+        let page = CdpPage::new_with_domains(&url, Timeouts::default(), &[])
+            .await
+            .unwrap();
+        let mut trace = RunTrace::start(&page);
+        let connection = page.connection().clone();
+        let send = |method: &'static str| {
+            let connection = connection.clone();
+            async move { connection.send_command(method, json!({})).await }
+        };
+
+        trace.step("reload", send("Page.reload")).await.unwrap();
+        trace.step("stop", send("Page.stopLoading")).await.unwrap();
+        // Screenshots taken after each step are not part of the next one
+        let methods: Vec<Vec<&str>> = trace
+            .steps()
+            .iter()
+            .map(|step| step.commands.iter().map(|c| c.method.as_str()).collect())
+            .collect();
+        assert_eq!(methods, [["Page.reload"], ["Page.stopLoading"]]);
+    }
+
+    #[test]
+    fn test_console_line_formats_arguments() {
+        let params = json!({
+            "type": "warning",
+            "args": [
+                {"type": "string", "value": "count"},
+                {"type": "number", "value": 3},
+                {"type": "object", "description": "Object"},
+                {"type": "undefined"}
+            ]
+        });
+        assert_eq!(
+            console_line("Runtime.consoleAPICalled", &params).unwrap(),
+            "[warning] count 3 Object undefined"
+        );
+    }
+
+    #[test]
+    fn test_console_line_formats_exceptions() {
+        let params = json!({
+            "exceptionDetails": {
+                "text": "Uncaught",
+                "exception": {"description": "TypeError: x is undefined"}
+            }
+        });
+        assert_eq!(
+            console_line("Runtime.exceptionThrown", &params).unwrap(),
+            "[exception] TypeError: x is undefined"
+        );
+    }
+
+    #[test]
+    fn test_console_line_ignores_other_events() {
+        assert!(console_line("Page.loadEventFired", &json!({})).is_none());
+    }
+}
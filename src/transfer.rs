@@ -0,0 +1,592 @@
+//! Moving files between this process and a possibly remote browser
+
+use crate::{CdpConnection, CdpEvent, Error, Result};
+use base64::Engine;
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+use tokio::io::AsyncWriteExt;
+
+/// Raw bytes sent per command when streaming a file into the page
+pub(crate) const UPLOAD_CHUNK_SIZE: usize = 1024 * 1024;
+
+/// Bytes requested per `IO.read` when streaming a download out of the browser
+const DOWNLOAD_READ_SIZE: usize = 1024 * 1024;
+
+/// Function starting an upload buffer in the page
+pub(crate) const BEGIN_UPLOAD_FN: &str = r#"function(id) {
+  (window.__cdpUploads = window.__cdpUploads || {})[id] = [];
+}"#;
+
+/// Function appending a base64 chunk to an upload buffer
+pub(crate) const APPEND_UPLOAD_FN: &str = r#"function(id, data) {
+  const bytes = atob(data);
+  const chunk = new Uint8Array(bytes.length);
+  for (let i = 0; i < bytes.length; i++) chunk[i] = bytes.charCodeAt(i);
+  window.__cdpUploads[id].push(chunk);
+}"#;
+
+/// Function assigning the uploaded buffers to a file input as `File`s
+pub(crate) const SET_FILES_FN: &str = r#"function(selector, files) {
+  const uploads = window.__cdpUploads || {};
+  try {
+    const input = this.querySelector(selector);
+    if (!input) throw new Error('No element matches ' + selector);
+    if (input.type !== 'file') throw new Error(selector + ' is not a file input');
+    const transfer = new DataTransfer();
+    for (const file of files) {
+      transfer.items.add(new File(uploads[file.id], file.name, { type: file.type }));
+    }
+    input.files = transfer.files;
+    input.dispatchEvent(new Event('input', { bubbles: true }));
+    input.dispatchEvent(new Event('change', { bubbles: true }));
+  } finally {
+    for (const file of files) delete uploads[file.id];
+  }
+}"#;
+
+/// File saved from a download the browser started
+#[derive(Debug, Clone, PartialEq)]
+pub struct Download {
+    /// URL the file was downloaded from
+    pub url: String,
+    /// File name suggested by the server or the URL
+    pub suggested_filename: String,
+    /// Where the file was written on this machine
+    pub path: PathBuf,
+    /// Size of the file in bytes
+    pub size: u64,
+}
+
+/// Progress of a download the browser saves itself, from [`BrowserContext::download_events`](crate::BrowserContext::download_events)
+#[derive(Debug, Clone, PartialEq)]
+pub enum DownloadEvent {
+    /// The browser started a download
+    Started {
+        /// Download id, shared by all events of the download
+        guid: String,
+        /// URL the file is downloaded from
+        url: String,
+        /// File name suggested by the server or the URL
+        suggested_filename: String,
+    },
+    /// More bytes arrived
+    Progress {
+        /// Download id
+        guid: String,
+        /// Bytes received so far
+        received_bytes: u64,
+        /// Expected size in bytes, or 0 if the server did not announce it
+        total_bytes: u64,
+    },
+    /// The file is complete
+    Completed {
+        /// Download id
+        guid: String,
+        /// Size of the file in bytes
+        received_bytes: u64,
+        /// Where the browser saved the file, if known
+        path: Option<PathBuf>,
+    },
+    /// The download was canceled or failed
+    Canceled {
+        /// Download id
+        guid: String,
+    },
+}
+
+impl DownloadEvent {
+    /// Parse a `Browser.downloadWillBegin` or `Browser.downloadProgress` event
+    pub(crate) fn from_event(event: &CdpEvent) -> Option<Self> {
+        let params = &event.params;
+        let guid = params["guid"].as_str()?.to_string();
+        let bytes = |field: &str| params[field].as_f64().unwrap_or(0.0).max(0.0) as u64;
+        match event.method.as_str() {
+            "Browser.downloadWillBegin" => Some(Self::Started {
+                guid,
+                url: params["url"].as_str().unwrap_or_default().to_string(),
+                suggested_filename: params["suggestedFilename"]
+                    .as_str()
+                    .unwrap_or_default()
+                    .to_string(),
+            }),
+            "Browser.downloadProgress" => match params["state"].as_str()? {
+                "inProgress" => Some(Self::Progress {
+                    guid,
+                    received_bytes: bytes("receivedBytes"),
+                    total_bytes: bytes("totalBytes"),
+                }),
+                "completed" => Some(Self::Completed {
+                    guid,
+                    received_bytes: bytes("receivedBytes"),
+                    // Only reported by recent Chrome versions
+                    path: params["filePath"].as_str().map(PathBuf::from),
+                }),
+                _ => Some(Self::Canceled { guid }),
+            },
+            _ => None,
+        }
+    }
+
+    /// Download id the event belongs to
+    pub fn guid(&self) -> &str {
+        match self {
+            Self::Started { guid, .. }
+            | Self::Progress { guid, .. }
+            | Self::Completed { guid, .. }
+            | Self::Canceled { guid } => guid,
+        }
+    }
+}
+
+/// Whether a WebSocket debugger URL points at this machine
+///
+/// Browsers reached over loopback share our filesystem, so paths can be
+/// handed to them directly.
+pub(crate) fn is_local_endpoint(ws_url: &str) -> bool {
+    let Ok(url) = reqwest::Url::parse(ws_url) else {
+        return false;
+    };
+    let host = url.host_str().unwrap_or_default();
+    host.eq_ignore_ascii_case("localhost")
+        || host
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .parse::<std::net::IpAddr>()
+            .is_ok_and(|ip| ip.is_loopback())
+}
+
+/// MIME type for an uploaded file, guessed from its extension
+pub(crate) fn mime_type(path: &Path) -> &'static str {
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    match extension.as_str() {
+        "txt" => "text/plain",
+        "html" | "htm" => "text/html",
+        "css" => "text/css",
+        "csv" => "text/csv",
+        "js" => "text/javascript",
+        "json" => "application/json",
+        "xml" => "application/xml",
+        "pdf" => "application/pdf",
+        "zip" => "application/zip",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "svg" => "image/svg+xml",
+        "mp4" => "video/mp4",
+        "mp3" => "audio/mpeg",
+        _ => "application/octet-stream",
+    }
+}
+
+/// Value of a response header in a `Fetch.requestPaused` event
+fn response_header<'a>(params: &'a Value, name: &str) -> Option<&'a str> {
+    params["responseHeaders"].as_array()?.iter().find(|h| {
+        h["name"]
+            .as_str()
+            .is_some_and(|n| n.eq_ignore_ascii_case(name))
+    })?["value"]
+        .as_str()
+}
+
+/// Whether a paused response is a download rather than content to render
+///
+/// That is a response sent as an attachment, or a document whose type the
+/// browser cannot display, e.g. `application/zip`.
+pub(crate) fn is_download(params: &Value) -> bool {
+    let attachment = response_header(params, "content-disposition").is_some_and(|value| {
+        value
+            .trim_start()
+            .get(..10)
+            .is_some_and(|kind| kind.eq_ignore_ascii_case("attachment"))
+    });
+    let successful = params["responseStatusCode"]
+        .as_u64()
+        .is_some_and(|status| (200..300).contains(&status));
+    attachment
+        || (params["resourceType"] == "Document"
+            && successful
+            && response_header(params, "content-type").is_some_and(|mime| !is_renderable(mime)))
+}
+
+/// Whether the browser displays a document of this `Content-Type` itself
+fn is_renderable(content_type: &str) -> bool {
+    let mime = content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+    // Without a type the browser sniffs the content
+    mime.is_empty()
+        || ["text/", "image/", "audio/", "video/"]
+            .iter()
+            .any(|prefix| mime.starts_with(prefix))
+        || mime.ends_with("+xml")
+        || mime.ends_with("+json")
+        || [
+            "application/xml",
+            "application/json",
+            "application/javascript",
+            "application/x-javascript",
+            "application/ecmascript",
+            "application/pdf",
+            "multipart/x-mixed-replace",
+        ]
+        .contains(&mime.as_str())
+}
+
+/// File name for a download, from `Content-Disposition` or the URL
+pub(crate) fn download_filename(params: &Value) -> String {
+    let from_header = response_header(params, "content-disposition").and_then(|value| {
+        let mut plain = None;
+        for part in value.split(';').map(str::trim) {
+            let Some((key, raw)) = part.split_once('=') else {
+                continue;
+            };
+            match key.trim().to_ascii_lowercase().as_str() {
+                // RFC 5987 form, e.g. filename*=UTF-8''na%C3%AFve.txt
+                "filename*" => {
+                    let encoded = raw.rsplit('\'').next().unwrap_or(raw);
+                    return Some(percent_decode(encoded));
+                }
+                "filename" => plain = Some(raw.trim_matches('"').to_string()),
+                _ => {}
+            }
+        }
+        plain
+    });
+    let from_url = || {
+        let url = reqwest::Url::parse(params["request"]["url"].as_str()?).ok()?;
+        let segment = url.path_segments()?.next_back()?;
+        Some(percent_decode(segment))
+    };
+    let name = from_header.or_else(from_url).unwrap_or_default();
+    // Never let the server pick a directory
+    let name = name.rsplit(['/', '\\']).next().unwrap_or_default().trim();
+    if name.is_empty() || name == "." || name == ".." {
+        "download".to_string()
+    } else {
+        name.to_string()
+    }
+}
+
+/// Decode `%XX` escapes, keeping invalid ones as they are
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|h| std::str::from_utf8(h).ok())
+            .and_then(|h| u8::from_str_radix(h, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Create a file in `dir` for `name` that did not exist yet, e.g. `report (1).pdf`
+///
+/// The name is claimed atomically, so concurrent downloads of the same name
+/// never share a file.
+async fn create_unique(dir: &Path, name: &str) -> Result<(PathBuf, tokio::fs::File)> {
+    let (stem, extension) = match name.rsplit_once('.') {
+        Some((stem, extension)) if !stem.is_empty() => (stem, format!(".{}", extension)),
+        _ => (name, String::new()),
+    };
+    for n in 0.. {
+        let path = match n {
+            0 => dir.join(name),
+            n => dir.join(format!("{} ({}){}", stem, n, extension)),
+        };
+        match tokio::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)
+            .await
+        {
+            Ok(file) => return Ok((path, file)),
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e.into()),
+        }
+    }
+    unreachable!("some numbered name is free")
+}
+
+/// Stream the body of a paused download response into `dir`
+///
+/// The browser-side request is aborted afterwards, so the file is only
+/// written here and never on the browser's host.
+pub(crate) async fn stream_download(
+    connection: &CdpConnection,
+    params: &Value,
+    dir: &Path,
+) -> Result<Download> {
+    let request_id = &params["requestId"];
+    let result = save_stream(connection, params, dir).await;
+    let _ = connection
+        .send_command(
+            "Fetch.failRequest",
+            json!({ "requestId": request_id, "errorReason": "Aborted" }),
+        )
+        .await;
+    result
+}
+
+async fn save_stream(connection: &CdpConnection, params: &Value, dir: &Path) -> Result<Download> {
+    let stream = connection
+        .send_command(
+            "Fetch.takeResponseBodyAsStream",
+            json!({ "requestId": params["requestId"] }),
+        )
+        .await
+        .map_err(|e| Error::Browser(format!("Failed to stream download: {}", e)))?;
+    let handle = stream["stream"].clone();
+
+    tokio::fs::create_dir_all(dir).await?;
+    let suggested_filename = download_filename(params);
+    let (path, mut file) = create_unique(dir, &suggested_filename).await?;
+    let mut size = 0u64;
+    let copied = async {
+        loop {
+            let chunk = connection
+                .send_command(
+                    "IO.read",
+                    json!({ "handle": handle, "size": DOWNLOAD_READ_SIZE }),
+                )
+                .await
+                .map_err(|e| Error::Browser(format!("Failed to read download: {}", e)))?;
+            let data = chunk["data"].as_str().unwrap_or_default();
+            let bytes = if chunk["base64Encoded"] == true {
+                base64::engine::general_purpose::STANDARD
+                    .decode(data)
+                    .map_err(|e| Error::Browser(format!("Failed to decode download: {}", e)))?
+            } else {
+                data.as_bytes().to_vec()
+            };
+            file.write_all(&bytes).await?;
+            size += bytes.len() as u64;
+            if chunk["eof"] == true {
+                return file.flush().await.map_err(Error::from);
+            }
+        }
+    }
+    .await;
+    let _ = connection
+        .send_command("IO.close", json!({ "handle": handle }))
+        .await;
+    if let Err(e) = copied {
+        let _ = tokio::fs::remove_file(&path).await;
+        return Err(e);
+    }
+
+    Ok(Download {
+        url: params["request"]["url"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string(),
+        suggested_filename,
+        path,
+        size,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connection::tests::{mock_server, server_recv, server_send};
+
+    fn paused(url: &str, disposition: Option<&str>) -> Value {
+        let headers: Vec<Value> = disposition
+            .map(|d| json!({"name": "Content-Disposition", "value": d}))
+            .into_iter()
+            .collect();
+        json!({
+            "requestId": "interception-1",
+            "request": {"url": url},
+            "responseStatusCode": 200,
+            "responseHeaders": headers
+        })
+    }
+
+    #[test]
+    fn test_is_local_endpoint() {
+        assert!(is_local_endpoint("ws://127.0.0.1:9222/devtools/page/1"));
+        assert!(is_local_endpoint("ws://localhost:9222/devtools/page/1"));
+        assert!(is_local_endpoint("ws://[::1]:9222/devtools/page/1"));
+        assert!(!is_local_endpoint("ws://10.0.0.5:9222/devtools/page/1"));
+        assert!(!is_local_endpoint("wss://browsers.example/devtools/page/1"));
+    }
+
+    #[test]
+    fn test_download_detection_and_filename() {
+        let inline = paused("https://a.example/report.pdf", Some("inline"));
+        assert!(!is_download(&inline));
+        assert!(!is_download(&paused("https://a.example/", None)));
+
+        let typed = |resource_type: &str, content_type: &str| {
+            let mut params = paused("https://a.example/file", None);
+            params["resourceType"] = json!(resource_type);
+            params["responseHeaders"] = json!([{"name": "Content-Type", "value": content_type}]);
+            params
+        };
+        assert!(is_download(&typed("Document", "application/zip")));
+        assert!(is_download(&typed("Document", "application/octet-stream")));
+        assert!(!is_download(&typed("Document", "text/html; charset=utf-8")));
+        assert!(!is_download(&typed("Document", "image/svg+xml")));
+        assert!(!is_download(&typed("Document", "application/pdf")));
+        // Subresources of any type are loaded by the page itself
+        assert!(!is_download(&typed("XHR", "application/zip")));
+
+        let quoted = paused(
+            "https://a.example/get?id=1",
+            Some("attachment; filename=\"report 2024.pdf\""),
+        );
+        assert!(is_download(&quoted));
+        assert_eq!(download_filename(&quoted), "report 2024.pdf");
+
+        let encoded = paused(
+            "https://a.example/x",
+            Some("attachment; filename=\"fallback.txt\"; filename*=UTF-8''na%C3%AFve.txt"),
+        );
+        assert_eq!(download_filename(&encoded), "naïve.txt");
+
+        let traversal = paused(
+            "https://a.example/x",
+            Some("attachment; filename=\"../../etc/passwd\""),
+        );
+        assert_eq!(download_filename(&traversal), "passwd");
+
+        let from_url = paused("https://a.example/files/data%201.csv", Some("attachment"));
+        assert_eq!(download_filename(&from_url), "data 1.csv");
+        assert_eq!(
+            download_filename(&paused("https://a.example/", Some("attachment"))),
+            "download"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_create_unique_numbers_existing_files() {
+        let dir = std::env::temp_dir().join(format!("cdp-transfer-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (first, _) = create_unique(&dir, "a.txt").await.unwrap();
+        assert_eq!(first, dir.join("a.txt"));
+        let (second, _) = create_unique(&dir, "a.txt").await.unwrap();
+        assert_eq!(second, dir.join("a (1).txt"));
+        std::fs::write(dir.join("Makefile"), "").unwrap();
+        let (numbered, _) = create_unique(&dir, "Makefile").await.unwrap();
+        assert_eq!(numbered, dir.join("Makefile (1)"));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_mime_type() {
+        assert_eq!(mime_type(Path::new("photo.JPG")), "image/jpeg");
+        assert_eq!(mime_type(Path::new("blob")), "application/octet-stream");
+    }
+
+    #[test]
+    fn test_download_events_from_browser_events() {
+        let event = |method: &str, params: Value| CdpEvent {
+            method: method.to_string(),
+            params,
+            session_id: None,
+        };
+        let begin = event(
+            "Browser.downloadWillBegin",
+            json!({"guid": "g", "frameId": "F", "url": "https://a.example/r.pdf", "suggestedFilename": "r.pdf"}),
+        );
+        assert_eq!(
+            DownloadEvent::from_event(&begin),
+            Some(DownloadEvent::Started {
+                guid: "g".to_string(),
+                url: "https://a.example/r.pdf".to_string(),
+                suggested_filename: "r.pdf".to_string(),
+            })
+        );
+        let progress = event(
+            "Browser.downloadProgress",
+            json!({"guid": "g", "state": "inProgress", "receivedBytes": 512, "totalBytes": 0}),
+        );
+        assert_eq!(
+            DownloadEvent::from_event(&progress),
+            Some(DownloadEvent::Progress {
+                guid: "g".to_string(),
+                received_bytes: 512,
+                total_bytes: 0,
+            })
+        );
+        let done = event(
+            "Browser.downloadProgress",
+            json!({"guid": "g", "state": "completed", "receivedBytes": 1024, "totalBytes": 1024, "filePath": "/tmp/r.pdf"}),
+        );
+        assert_eq!(
+            DownloadEvent::from_event(&done),
+            Some(DownloadEvent::Completed {
+                guid: "g".to_string(),
+                received_bytes: 1024,
+                path: Some(PathBuf::from("/tmp/r.pdf")),
+            })
+        );
+        let canceled = event(
+            "Browser.downloadProgress",
+            json!({"guid": "g", "state": "canceled"}),
+        );
+        assert_eq!(DownloadEvent::from_event(&canceled).unwrap().guid(), "g");
+        assert!(DownloadEvent::from_event(&event("Page.loadEventFired", json!({}))).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_stream_download_reads_chunks_and_aborts_request() {
+        let url = mock_server(|mut ws| async move {
+            let take = server_recv(&mut ws).await;
+            assert_eq!(take["method"], "Fetch.takeResponseBodyAsStream");
+            server_send(&mut ws, json!({"id": take["id"], "result": {"stream": "s1"}})).await;
+            for (data, base64, eof) in [("aGVsbG8g", true, false), ("world", false, true)] {
+                let read = server_recv(&mut ws).await;
+                assert_eq!(read["method"], "IO.read");
+                assert_eq!(read["params"]["handle"], "s1");
+                server_send(
+                    &mut ws,
+                    json!({"id": read["id"], "result": {"data": data, "base64Encoded": base64, "eof": eof}}),
+                )
+                .await;
+            }
+            for expected in ["IO.close", "Fetch.failRequest"] {
+                let request = server_recv(&mut ws).await;
+                assert_eq!(request["method"], expected);
+                server_send(&mut ws, json!({"id": request["id"], "result": {}})).await;
+            }
+        })
+        .await;
+        let connection = CdpConnection::connect(&url).await.unwrap();
+        let dir = std::env::temp_dir().join(format!("cdp-downloads-{}", uuid::Uuid::new_v4()));
+
+        let params = paused(
+            "https://a.example/hello.txt",
+            Some("attachment; filename=hello.txt"),
+        );
+        let download = stream_download(&connection, &params, &dir).await.unwrap();
+        assert_eq!(download.path, dir.join("hello.txt"));
+        assert_eq!(download.size, 11);
+        assert_eq!(
+            std::fs::read_to_string(&download.path).unwrap(),
+            "hello world"
+        );
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,199 @@
+//! Screenshot comparison against baselines, with masked regions
+
+use crate::screenshot::{decode_png, encode_png, Rgba};
+use crate::{Error, Rect, Result};
+
+/// Function returning the viewport boxes of all elements matching the selectors
+pub(crate) const ELEMENT_RECTS_FN: &str = r#"function(selectors) {
+  const rects = [];
+  for (const selector of selectors) {
+    for (const el of this.querySelectorAll(selector)) {
+      const r = el.getBoundingClientRect();
+      rects.push({ x: r.x, y: r.y, width: r.width, height: r.height });
+    }
+  }
+  return rects;
+}"#;
+
+/// Options for [`CdpPage::compare_screenshot`](crate::CdpPage::compare_screenshot)
+///
+/// Masked pixels never count as differences, so dynamic content such as
+/// clocks, ads or avatars does not require updating the baseline.
+#[derive(Debug, Clone, Default)]
+pub struct ScreenshotComparison {
+    /// Regions to ignore, in CSS pixels relative to the viewport
+    pub masks: Vec<Rect>,
+    /// Selectors of elements to ignore, e.g. `.clock`
+    pub ignore_selectors: Vec<String>,
+    /// Largest per-channel difference still treated as equal, absorbing
+    /// anti-aliasing noise (default: 0)
+    pub tolerance: u8,
+}
+
+/// Result of comparing a screenshot with its baseline
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScreenshotDiff {
+    /// Pixels that differ outside the masks
+    pub differing_pixels: u64,
+    /// Pixels compared, i.e. not masked
+    pub compared_pixels: u64,
+    /// PNG of the screenshot with differences in red and masks in gray
+    pub diff_image: Vec<u8>,
+}
+
+impl ScreenshotDiff {
+    /// Share of compared pixels that differ, from 0.0 to 1.0
+    pub fn ratio(&self) -> f64 {
+        if self.compared_pixels == 0 {
+            0.0
+        } else {
+            self.differing_pixels as f64 / self.compared_pixels as f64
+        }
+    }
+
+    /// Whether no compared pixel differs
+    pub fn is_match(&self) -> bool {
+        self.differing_pixels == 0
+    }
+}
+
+/// Compare two PNGs, skipping `masks` given in image pixels
+pub(crate) fn compare_png(
+    baseline: &[u8],
+    actual: &[u8],
+    masks: &[Rect],
+    tolerance: u8,
+) -> Result<ScreenshotDiff> {
+    let baseline = decode_png(baseline)?;
+    let actual = decode_png(actual)?;
+    if (baseline.width, baseline.height) != (actual.width, actual.height) {
+        return Err(Error::Browser(format!(
+            "Screenshot is {}x{} but the baseline is {}x{}",
+            actual.width, actual.height, baseline.width, baseline.height
+        )));
+    }
+
+    let width = actual.width as usize;
+    let mut diff = actual.pixels.clone();
+    let mut differing_pixels = 0;
+    let mut compared_pixels = 0;
+    for (index, (expected, found)) in baseline
+        .pixels
+        .chunks_exact(4)
+        .zip(actual.pixels.chunks_exact(4))
+        .enumerate()
+    {
+        let (x, y) = ((index % width) as f64, (index / width) as f64);
+        let marker = if masks.iter().any(|mask| contains(mask, x, y)) {
+            [128, 128, 128, 255]
+        } else {
+            compared_pixels += 1;
+            let equal = expected
+                .iter()
+                .zip(found)
+                .all(|(a, b)| a.abs_diff(*b) <= tolerance);
+            if equal {
+                continue;
+            }
+            differing_pixels += 1;
+            [255, 0, 0, 255]
+        };
+        diff[index * 4..index * 4 + 4].copy_from_slice(&marker);
+    }
+
+    Ok(ScreenshotDiff {
+        differing_pixels,
+        compared_pixels,
+        diff_image: encode_png(&Rgba {
+            width: actual.width,
+            height: actual.height,
+            pixels: diff,
+        })?,
+    })
+}
+
+/// Scale a rectangle in CSS pixels to image pixels
+pub(crate) fn scale_rect(rect: &Rect, scale: f64) -> Rect {
+    Rect {
+        x: rect.x * scale,
+        y: rect.y * scale,
+        width: rect.width * scale,
+        height: rect.height * scale,
+    }
+}
+
+/// Whether the pixel whose top-left corner is at `(x, y)` overlaps the rectangle
+fn contains(rect: &Rect, x: f64, y: f64) -> bool {
+    x + 1.0 > rect.x && x < rect.x + rect.width && y + 1.0 > rect.y && y < rect.y + rect.height
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 4x2 image of one color with the given pixels replaced
+    fn image(changed: &[(usize, [u8; 4])]) -> Vec<u8> {
+        let mut pixels = [10, 20, 30, 255].repeat(8);
+        for (index, rgba) in changed {
+            pixels[index * 4..index * 4 + 4].copy_from_slice(rgba);
+        }
+        encode_png(&Rgba {
+            width: 4,
+            height: 2,
+            pixels,
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_compare_counts_differences_and_marks_them() {
+        let diff = compare_png(&image(&[]), &image(&[(5, [0, 0, 0, 255])]), &[], 0).unwrap();
+        assert_eq!((diff.differing_pixels, diff.compared_pixels), (1, 8));
+        assert_eq!(diff.ratio(), 0.125);
+        let marked = decode_png(&diff.diff_image).unwrap();
+        assert_eq!(&marked.pixels[20..24], &[255, 0, 0, 255]);
+    }
+
+    #[test]
+    fn test_compare_skips_masks_and_tolerates_noise() {
+        let actual = image(&[(1, [0, 0, 0, 255]), (6, [12, 18, 30, 255])]);
+        let mask = Rect {
+            x: 1.0,
+            y: 0.0,
+            width: 1.0,
+            height: 1.0,
+        };
+        let diff = compare_png(&image(&[]), &actual, &[mask], 2).unwrap();
+        assert!(diff.is_match());
+        assert_eq!(diff.compared_pixels, 7);
+        let marked = decode_png(&diff.diff_image).unwrap();
+        assert_eq!(&marked.pixels[4..8], &[128, 128, 128, 255]);
+    }
+
+    #[test]
+    fn test_compare_rejects_size_mismatch() {
+        let small = encode_png(&Rgba {
+            width: 1,
+            height: 1,
+            pixels: vec![0; 4],
+        })
+        .unwrap();
+        assert!(compare_png(&image(&[]), &small, &[], 0).is_err());
+    }
+
+    #[test]
+    fn test_partial_pixels_are_masked() {
+        let mask = scale_rect(
+            &Rect {
+                x: 0.25,
+                y: 0.25,
+                width: 0.5,
+                height: 0.5,
+            },
+            2.0,
+        );
+        assert!(contains(&mask, 0.0, 0.0));
+        assert!(contains(&mask, 1.0, 1.0));
+        assert!(!contains(&mask, 2.0, 0.0));
+    }
+}
@@ -0,0 +1,177 @@
+//! Page health watchdog
+
+use crate::{CdpConnection, Error};
+use serde_json::json;
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+
+/// Health of a watched page
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PageHealth {
+    /// The renderer answered the last probe in time
+    Healthy,
+    /// The renderer did not answer the last probe within the threshold
+    Unresponsive,
+    /// The renderer crashed or the target went away; the page must be replaced
+    Crashed,
+}
+
+/// Options for [`CdpPage::watchdog`](crate::CdpPage::watchdog)
+#[derive(Debug, Clone)]
+pub struct WatchdogOptions {
+    /// Time between probes (default: 10 seconds)
+    pub interval: Duration,
+    /// Time a probe may take before the renderer is considered hung (default: 5 seconds)
+    pub threshold: Duration,
+    /// Reload the page when it becomes unresponsive
+    pub reload: bool,
+}
+
+impl Default for WatchdogOptions {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(10),
+            threshold: Duration::from_secs(5),
+            reload: false,
+        }
+    }
+}
+
+/// Handle of a running watchdog; the watchdog stops when it is dropped
+pub struct PageWatchdog {
+    health: watch::Receiver<PageHealth>,
+    task: JoinHandle<()>,
+}
+
+impl PageWatchdog {
+    pub(crate) fn spawn(connection: CdpConnection, options: WatchdogOptions) -> Self {
+        let (tx, health) = watch::channel(PageHealth::Healthy);
+        let task = tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(options.interval).await;
+                let health = probe(&connection, options.threshold).await;
+                tx.send_replace(health);
+                match health {
+                    PageHealth::Crashed => break,
+                    PageHealth::Unresponsive if options.reload => {
+                        let _ = connection.send_command("Page.reload", json!({})).await;
+                    }
+                    _ => {}
+                }
+            }
+        });
+        Self { health, task }
+    }
+
+    /// Health reported by the most recent probe
+    pub fn health(&self) -> PageHealth {
+        *self.health.borrow()
+    }
+
+    /// Wait until a probe reports the page as unhealthy
+    ///
+    /// Callers running a pool typically replace the page once this returns.
+    pub async fn unhealthy(&mut self) -> PageHealth {
+        match self
+            .health
+            .wait_for(|health| *health != PageHealth::Healthy)
+            .await
+        {
+            Ok(health) => *health,
+            // The probe task only exits after reporting a crash
+            Err(_) => PageHealth::Crashed,
+        }
+    }
+}
+
+impl Drop for PageWatchdog {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// Evaluate a trivial expression and classify the outcome
+async fn probe(connection: &CdpConnection, threshold: Duration) -> PageHealth {
+    let evaluate = connection.send_command(
+        "Runtime.evaluate",
+        json!({ "expression": "1+1", "returnByValue": true }),
+    );
+    match tokio::time::timeout(threshold, evaluate).await {
+        Ok(Ok(_)) => PageHealth::Healthy,
+        Ok(Err(
+            Error::TargetCrashed | Error::TargetDetached(_) | Error::WebSocket(_) | Error::Cdp(_),
+        )) => PageHealth::Crashed,
+        // Errors such as a navigation racing the probe say nothing about the renderer
+        Ok(Err(_)) => PageHealth::Healthy,
+        Err(_) => PageHealth::Unresponsive,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connection::tests::{mock_server, server_recv, server_send};
+
+    fn fast() -> WatchdogOptions {
+        WatchdogOptions {
+            interval: Duration::from_millis(10),
+            threshold: Duration::from_millis(50),
+            reload: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_probe_healthy() {
+        let url = mock_server(|mut ws| async move {
+            let cmd = server_recv(&mut ws).await;
+            server_send(
+                &mut ws,
+                json!({"id": cmd["id"], "result": {"result": {"value": 2}}}),
+            )
+            .await;
+            server_recv(&mut ws).await;
+        })
+        .await;
+
+        let conn = CdpConnection::connect(&url).await.unwrap();
+        assert_eq!(
+            probe(&conn, Duration::from_secs(1)).await,
+            PageHealth::Healthy
+        );
+    }
+
+    #[tokio::test]
+    async fn test_watchdog_reports_hung_renderer() {
+        let url = mock_server(|mut ws| async move {
+            // Never answer, like a renderer stuck in a busy loop
+            loop {
+                server_recv(&mut ws).await;
+            }
+        })
+        .await;
+
+        let conn = CdpConnection::connect(&url).await.unwrap();
+        let mut watchdog = PageWatchdog::spawn(conn, fast());
+        assert_eq!(watchdog.health(), PageHealth::Healthy);
+        assert_eq!(watchdog.unhealthy().await, PageHealth::Unresponsive);
+    }
+
+    #[tokio::test]
+    async fn test_watchdog_reports_crash() {
+        let url = mock_server(|mut ws| async move {
+            server_recv(&mut ws).await;
+            server_send(
+                &mut ws,
+                json!({"method": "Inspector.targetCrashed", "params": {}}),
+            )
+            .await;
+            server_recv(&mut ws).await;
+        })
+        .await;
+
+        let conn = CdpConnection::connect(&url).await.unwrap();
+        let mut watchdog = PageWatchdog::spawn(conn, fast());
+        assert_eq!(watchdog.unhealthy().await, PageHealth::Crashed);
+    }
+}
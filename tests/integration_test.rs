@@ -9,13 +9,51 @@ mod chrome_tests {
     use std::path::PathBuf;
 
     /// Create a BrowserManager with flags for containerized environments
+    fn manager_builder() -> chrome_cdp::BrowserManagerBuilder {
+        BrowserManager::builder()
+            .executable(PathBuf::from("/usr/bin/chromium"))
+            .args(["--no-sandbox", "--disable-gpu", "--disable-setuid-sandbox"])
+    }
+
     fn create_manager() -> BrowserManager {
-        let args = vec![
-            "--no-sandbox".to_string(),
-            "--disable-gpu".to_string(),
-            "--disable-setuid-sandbox".to_string(),
-        ];
-        BrowserManager::new(Some(PathBuf::from("/usr/bin/chromium")), true, false, args)
+        manager_builder().build()
+    }
+
+    /// Start an HTTP server echoing the request head back as the body
+    ///
+    /// Returns its address and a counter of the requests it answered, not
+    /// counting favicon lookups.
+    async fn serve_echo() -> (
+        std::net::SocketAddr,
+        std::sync::Arc<std::sync::atomic::AtomicUsize>,
+    ) {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let hits = std::sync::Arc::new(AtomicUsize::new(0));
+        let counter = hits.clone();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let mut buf = vec![0; 8192];
+                let n = socket.read(&mut buf).await.unwrap_or(0);
+                if n == 0 {
+                    continue;
+                }
+                let head = String::from_utf8_lossy(&buf[..n]).to_lowercase();
+                if !head.starts_with("get /favicon.ico") {
+                    counter.fetch_add(1, Ordering::SeqCst);
+                }
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    head.len(),
+                    head
+                );
+                let _ = socket.write_all(response.as_bytes()).await;
+            }
+        });
+        (addr, hits)
     }
 
     #[tokio::test]
@@ -26,6 +64,34 @@ mod chrome_tests {
         drop(browser);
     }
 
+    #[tokio::test]
+    async fn test_browser_connection_get_targets() {
+        let manager = create_manager();
+        let browser = manager.get_browser().await.unwrap();
+
+        let connection = browser.connection().await.unwrap();
+        let result = connection
+            .send_command("Target.getTargets", serde_json::json!({}))
+            .await
+            .unwrap();
+        assert!(result["targetInfos"].is_array());
+    }
+
+    #[tokio::test]
+    async fn test_context_pages_are_scoped() {
+        let manager = create_manager();
+        let browser = manager.get_browser().await.unwrap();
+
+        let context_a = browser.new_context().await.unwrap();
+        let context_b = browser.new_context().await.unwrap();
+
+        let page = context_a.new_page().await.unwrap();
+        assert_eq!(context_a.pages().await.unwrap().len(), 1);
+        assert!(context_b.pages().await.unwrap().is_empty());
+
+        page.close().await.unwrap();
+    }
+
     #[tokio::test]
     async fn test_page_create_and_close() {
         let manager = create_manager();
@@ -117,6 +183,67 @@ mod chrome_tests {
         page.close().await.unwrap();
     }
 
+    #[tokio::test]
+    async fn test_page_wait_for_element_with_quoted_selector() {
+        let manager = create_manager();
+        let browser = manager.get_browser().await.unwrap();
+
+        let ws_url = browser.new_page().await.unwrap();
+        let page = CdpPage::new(&ws_url).await.unwrap();
+
+        page.goto("data:text/html,<html><body><div data-name='a\\\"b`c'>Found</div></body></html>")
+            .await
+            .unwrap();
+
+        // Quotes, backslashes and backticks are passed through verbatim
+        let found = page
+            .wait_for_element(r#"div[data-name='a\\"b`c']"#, 2)
+            .await
+            .unwrap();
+        assert!(found);
+
+        page.close().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_page_capture_region() {
+        let manager = create_manager();
+        let browser = manager.get_browser().await.unwrap();
+
+        let ws_url = browser.new_page().await.unwrap();
+        let page = CdpPage::new(&ws_url).await.unwrap();
+
+        page.goto("data:text/html,<html><body style='height:3000px'>Tall</body></html>")
+            .await
+            .unwrap();
+
+        let image = page
+            .capture_region(0.0, 1000.0, 200.0, 100.0, 2.0)
+            .await
+            .unwrap();
+        assert_eq!(&image[..4], b"\x89PNG");
+
+        // Scroll position is restored after the capture
+        let scroll_y = page.evaluate("window.scrollY").await.unwrap();
+        assert_eq!(scroll_y.as_f64(), Some(0.0));
+
+        page.close().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_page_auto_dark_mode() {
+        let manager = create_manager();
+        let browser = manager.get_browser().await.unwrap();
+
+        let ws_url = browser.new_page().await.unwrap();
+        let page = CdpPage::new(&ws_url).await.unwrap();
+
+        page.set_auto_dark_mode(true).await.unwrap();
+        page.clear_auto_dark_mode().await.unwrap();
+
+        page.close().await.unwrap();
+    }
+
     #[tokio::test]
     async fn test_evaluate_error_handling() {
         let manager = create_manager();
@@ -162,6 +289,1403 @@ mod chrome_tests {
 
         page.close().await.unwrap();
     }
+
+    #[tokio::test]
+    async fn test_navigation_policy_blocks_disallowed_urls() {
+        let manager = create_manager();
+        let browser = manager.get_browser().await.unwrap();
+
+        let ws_url = browser.new_page().await.unwrap();
+        let page = CdpPage::new(&ws_url).await.unwrap();
+
+        // Fetch never pauses data: URLs, so the pages come from a real server
+        let (addr, hits) = serve_echo().await;
+        page.set_navigation_policy(|url| !url.contains("blocked"))
+            .await
+            .unwrap();
+
+        page.goto(&format!("http://{}/allowed", addr))
+            .await
+            .unwrap();
+        tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+        assert!(page.get_html().await.unwrap().contains("get /allowed"));
+
+        let err = page
+            .goto(&format!("http://{}/blocked", addr))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("ERR_BLOCKED_BY_CLIENT"));
+        tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+
+        // Chrome commits an error page in place of the blocked document
+        assert!(!page.get_html().await.unwrap().contains("get /blocked"));
+        assert_eq!(hits.load(std::sync::atomic::Ordering::SeqCst), 1);
+
+        page.close().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_run_trace_records_steps() {
+        let manager = create_manager();
+        let browser = manager.get_browser().await.unwrap();
+
+        let ws_url = browser.new_page().await.unwrap();
+        let page = CdpPage::new(&ws_url).await.unwrap();
+
+        let mut trace = chrome_cdp::RunTrace::start(&page);
+        trace
+            .step("goto", page.goto("data:text/html,<p>traced</p>"))
+            .await
+            .unwrap();
+        trace
+            .step("log", page.evaluate("console.log('hello', 42)"))
+            .await
+            .unwrap();
+
+        let dir = std::env::temp_dir().join(format!("chrome-cdp-trace-{}", std::process::id()));
+        let path = trace.save(&dir).unwrap();
+        assert!(path.exists());
+        assert!(dir.join("step-001.png").exists());
+        assert!(trace.steps()[1]
+            .console
+            .contains(&"[log] hello 42".to_string()));
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        page.close().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_map_pages_collects_results_in_order() {
+        let manager = create_manager();
+        let urls: Vec<String> = (0..4)
+            .map(|i| format!("data:text/html,<title>page{}</title>", i))
+            .collect();
+
+        let results = manager
+            .map_pages(&urls, 2, |page| async move {
+                let title = page.evaluate("document.title").await?;
+                Ok(title.as_str().unwrap_or_default().to_string())
+            })
+            .await
+            .unwrap();
+
+        let titles: Vec<String> = results.into_iter().map(|r| r.unwrap()).collect();
+        assert_eq!(titles, vec!["page0", "page1", "page2", "page3"]);
+    }
+
+    #[tokio::test]
+    async fn test_freeze_and_advance_time() {
+        let manager = create_manager();
+        let browser = manager.get_browser().await.unwrap();
+
+        let ws_url = browser.new_page().await.unwrap();
+        let page = CdpPage::new(&ws_url).await.unwrap();
+
+        page.goto("data:text/html,<p>clock</p>").await.unwrap();
+        page.freeze_time(1_700_000_000_000.0).await.unwrap();
+
+        let now = page.evaluate("Date.now()").await.unwrap();
+        assert_eq!(now.as_f64(), Some(1_700_000_000_000.0));
+
+        page.evaluate("window.fired = false; setTimeout(() => window.fired = true, 5000)")
+            .await
+            .unwrap();
+        page.advance_time(std::time::Duration::from_secs(4))
+            .await
+            .unwrap();
+        assert_eq!(page.evaluate("window.fired").await.unwrap(), false);
+        page.advance_time(std::time::Duration::from_secs(1))
+            .await
+            .unwrap();
+        assert_eq!(page.evaluate("window.fired").await.unwrap(), true);
+
+        page.close().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_screenshot_tiled_stitches_full_page() {
+        let manager = create_manager();
+        let browser = manager.get_browser().await.unwrap();
+
+        let ws_url = browser.new_page().await.unwrap();
+        let page = CdpPage::new(&ws_url).await.unwrap();
+
+        page.goto(
+            "data:text/html,<body style='margin:0'>\
+             <header style='position:sticky;top:0;height:50px'>h</header>\
+             <div style='height:2500px'></div></body>",
+        )
+        .await
+        .unwrap();
+
+        let options = chrome_cdp::TiledScreenshotOptions {
+            tile_height: 1000,
+            mask_selectors: vec!["header".to_string()],
+        };
+        let png = page.screenshot_tiled(&options).await.unwrap();
+        assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+
+        let header_visible = page
+            .evaluate("getComputedStyle(document.querySelector('header')).visibility")
+            .await
+            .unwrap();
+        assert_eq!(header_visible, "visible");
+
+        page.close().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_page_inherits_and_overrides_timeouts() {
+        use chrome_cdp::{Error, Timeouts};
+        use std::time::Duration;
+
+        let manager = manager_builder()
+            .timeouts(Timeouts {
+                navigation: Some(Duration::from_secs(20)),
+                ..Default::default()
+            })
+            .build();
+        let browser = manager.get_browser().await.unwrap();
+        let ws_url = browser.new_page().await.unwrap();
+        let page = CdpPage::new_with_timeouts(&ws_url, browser.timeouts())
+            .await
+            .unwrap();
+        assert_eq!(
+            page.timeouts().navigation_timeout(),
+            Duration::from_secs(20)
+        );
+
+        page.set_timeouts(Timeouts {
+            wait: Some(Duration::from_millis(200)),
+            ..Default::default()
+        });
+        assert_eq!(
+            page.timeouts().navigation_timeout(),
+            Duration::from_secs(20)
+        );
+
+        page.goto("data:text/html,<p>no match</p>").await.unwrap();
+        let err = page.wait_for_selector("#missing").await.unwrap_err();
+        assert!(matches!(err, Error::Timeout(_)));
+
+        page.close().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_iframe_load() {
+        use std::time::Duration;
+
+        let manager = create_manager();
+        let browser = manager.get_browser().await.unwrap();
+        let ws_url = browser.new_page().await.unwrap();
+        let page = CdpPage::new(&ws_url).await.unwrap();
+
+        page.goto("data:text/html,<iframe name=pay srcdoc='<p>card</p>'></iframe>")
+            .await
+            .unwrap();
+        page.wait_for_selector("iframe").await.unwrap();
+
+        let frames = page.frames();
+        assert!(frames[0].is_main());
+        let iframe = frames
+            .iter()
+            .find(|frame| !frame.is_main())
+            .expect("iframe should be tracked");
+        iframe.wait_for_load(Duration::from_secs(10)).await.unwrap();
+        let loaded = page.frame(iframe.id()).unwrap();
+        assert_eq!(loaded.name(), "pay");
+
+        page.close().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_launch_on_fixed_port() {
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let manager = manager_builder().remote_debugging_port(port).build();
+        let browser = manager.get_browser().await.unwrap();
+        assert_eq!(browser.port(), port);
+
+        // A second browser cannot bind the same port
+        let err = chrome_cdp::CdpBrowser::launch_with_options(chrome_cdp::LaunchOptions {
+            executable_path: Some(PathBuf::from("/usr/bin/chromium")),
+            remote_debugging_port: Some(port),
+            ..Default::default()
+        })
+        .await
+        .err()
+        .expect("port should be taken");
+        assert!(err.to_string().contains("not available"));
+    }
+
+    #[tokio::test]
+    async fn test_export_inlined_html() {
+        let manager = create_manager();
+        let browser = manager.get_browser().await.unwrap();
+        let ws_url = browser.new_page().await.unwrap();
+        let page = CdpPage::new(&ws_url).await.unwrap();
+
+        page.goto("data:text/html,<h1>Archive</h1><script>document.title='x'</script>")
+            .await
+            .unwrap();
+        page.wait_for_selector("h1").await.unwrap();
+
+        let html = page.export_inlined_html().await.unwrap();
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("<h1>Archive</h1>"));
+        assert!(!html.contains("<script>"));
+
+        page.close().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_context_download_dirs() {
+        let manager = create_manager();
+        let browser = manager.get_browser().await.unwrap();
+        let root = std::env::temp_dir().join(format!("cdp-it-downloads-{}", std::process::id()));
+
+        let first = browser.new_context().await.unwrap();
+        let second = browser.new_context().await.unwrap();
+        first.set_download_dir(root.join("a")).await.unwrap();
+        second.set_download_dir(root.join("b")).await.unwrap();
+        assert_ne!(first.download_dir(), second.download_dir());
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_profile_dir_removed_on_drop() {
+        let browser = chrome_cdp::CdpBrowser::launch_with_options(chrome_cdp::LaunchOptions {
+            executable_path: Some(PathBuf::from("/usr/bin/chromium")),
+            args: vec!["--no-sandbox".to_string(), "--disable-gpu".to_string()],
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let profile = browser.user_data_dir().unwrap().to_path_buf();
+        assert!(profile.exists());
+
+        drop(browser);
+        assert!(!profile.exists());
+    }
+
+    #[tokio::test]
+    async fn test_browser_graceful_close() {
+        let browser = chrome_cdp::CdpBrowser::launch_with_options(chrome_cdp::LaunchOptions {
+            executable_path: Some(PathBuf::from("/usr/bin/chromium")),
+            args: vec!["--no-sandbox".to_string(), "--disable-gpu".to_string()],
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        browser.close().await.unwrap();
+        assert!(chrome_cdp::CdpBrowser::connect("127.0.0.1", browser.port())
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_apply_region_profile() {
+        let manager = create_manager();
+        let browser = manager.get_browser().await.unwrap();
+        let ws_url = browser.new_page().await.unwrap();
+        let page = CdpPage::new(&ws_url).await.unwrap();
+
+        page.apply_region(&chrome_cdp::RegionProfile::japan())
+            .await
+            .unwrap();
+        let timezone = page
+            .evaluate("Intl.DateTimeFormat().resolvedOptions().timeZone")
+            .await
+            .unwrap();
+        assert_eq!(timezone, "Asia/Tokyo");
+        let language = page.evaluate("navigator.language").await.unwrap();
+        assert_eq!(language, "ja-JP");
+
+        page.close().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_wait_reports_requested_exit() {
+        let browser = chrome_cdp::CdpBrowser::launch_with_options(chrome_cdp::LaunchOptions {
+            executable_path: Some(PathBuf::from("/usr/bin/chromium")),
+            args: vec!["--no-sandbox".to_string(), "--disable-gpu".to_string()],
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        browser.on_exit(move |exit| {
+            let _ = tx.send(exit.expected);
+        });
+
+        browser.close().await.unwrap();
+        let exit = browser.wait().await.unwrap();
+        assert!(exit.expected);
+        assert!(rx.await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_interactive_snapshot_and_act() {
+        use chrome_cdp::Action;
+
+        let manager = create_manager();
+        let browser = manager.get_browser().await.unwrap();
+        let ws_url = browser.new_page().await.unwrap();
+        let page = CdpPage::new(&ws_url).await.unwrap();
+
+        page.goto(
+            "data:text/html,<label>Email <input id=email></label>\
+             <button onclick=\"document.title='sent:'+email.value\">Send</button>\
+             <button style=display:none>Hidden</button>",
+        )
+        .await
+        .unwrap();
+        page.wait_for_selector("button").await.unwrap();
+
+        let snapshot = page.interactive_snapshot().await.unwrap();
+        assert_eq!(snapshot.elements.len(), 2);
+        assert_eq!(snapshot.elements[0].role, "textbox");
+        assert_eq!(snapshot.elements[0].name, "Email");
+        assert_eq!(snapshot.elements[1].name, "Send");
+
+        page.act_on(0, Action::Fill("a@example.com".to_string()))
+            .await
+            .unwrap();
+        page.act_on(1, Action::Click).await.unwrap();
+        let title = page.evaluate("document.title").await.unwrap();
+        assert_eq!(title, "sent:a@example.com");
+
+        // Ids survive a fresh snapshot
+        let again = page.interactive_snapshot().await.unwrap();
+        assert_eq!(again.elements[1].id, snapshot.elements[1].id);
+
+        page.close().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_evaluate_with_timeout_interrupts_loop() {
+        use chrome_cdp::Error;
+        use std::time::Duration;
+
+        let manager = create_manager();
+        let browser = manager.get_browser().await.unwrap();
+        let ws_url = browser.new_page().await.unwrap();
+        let page = CdpPage::new(&ws_url).await.unwrap();
+
+        let err = page
+            .evaluate_with_timeout("while (true) {}", Duration::from_millis(200))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::Timeout(_)));
+
+        let err = page
+            .evaluate_with_timeout("new Promise(() => {})", Duration::from_millis(200))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::Timeout(_)));
+
+        // The page stays usable afterwards
+        assert_eq!(page.evaluate("1 + 1").await.unwrap(), 2);
+
+        page.close().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_browser_pages_include_initial_tab() {
+        let browser = chrome_cdp::CdpBrowser::launch_with_options(chrome_cdp::LaunchOptions {
+            executable_path: Some(PathBuf::from("/usr/bin/chromium")),
+            args: vec!["--no-sandbox".to_string(), "--disable-gpu".to_string()],
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let initial = browser.pages().await.unwrap();
+        assert!(!initial.is_empty());
+
+        browser.new_page().await.unwrap();
+        let pages = browser.pages().await.unwrap();
+        assert_eq!(pages.len(), initial.len() + 1);
+        assert_eq!(pages[0].evaluate("1 + 1").await.unwrap(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_new_page_connected_navigates() {
+        let manager = create_manager();
+        let browser = manager.get_browser().await.unwrap();
+
+        let page = browser
+            .new_page_connected(Some("data:text/html,<title>ready</title>"))
+            .await
+            .unwrap();
+        assert_eq!(page.evaluate("document.title").await.unwrap(), "ready");
+        page.close().await.unwrap();
+
+        let blank = browser.new_page_connected(None).await.unwrap();
+        assert_eq!(blank.evaluate("1 + 1").await.unwrap(), 2);
+        blank.close().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_header_rules_rewrite_requests() {
+        let (addr, _) = serve_echo().await;
+
+        let manager = create_manager();
+        let browser = manager.get_browser().await.unwrap();
+        let page = browser.new_page_connected(None).await.unwrap();
+        page.add_header_rule(chrome_cdp::HeaderRule::add(
+            chrome_cdp::RequestMatcher::new().url_glob(&format!("http://{}/*", addr)),
+            "X-Api-Key",
+            "secret",
+        ))
+        .await
+        .unwrap();
+
+        page.goto(&format!("http://{}/echo", addr)).await.unwrap();
+        page.wait_for_selector("body").await.unwrap();
+        let body = page.evaluate("document.body.innerText").await.unwrap();
+        assert!(body.as_str().unwrap().contains("x-api-key: secret"));
+
+        page.clear_header_rules().await.unwrap();
+        page.close().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_targets_describe_pages() {
+        let manager = create_manager();
+        let browser = manager.get_browser().await.unwrap();
+        let page = browser
+            .new_page_connected(Some("data:text/html,<title>listed</title>"))
+            .await
+            .unwrap();
+        page.wait_for_selector("title").await.unwrap();
+
+        let targets = browser.targets().await.unwrap();
+        let target = targets
+            .iter()
+            .find(|t| t.is_page() && t.title == "listed")
+            .expect("new page is listed");
+        assert!(target.url.starts_with("data:text/html"));
+        assert!(target.attached);
+
+        page.close().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_attach_page_opened_by_script() {
+        let manager = create_manager();
+        let browser = manager.get_browser().await.unwrap();
+        let page = browser.new_page_connected(None).await.unwrap();
+
+        page.evaluate("window.open('data:text/html,<title>popup</title>'); 0")
+            .await
+            .unwrap();
+        let mut popup = None;
+        for _ in 0..20 {
+            let targets = browser.targets().await.unwrap();
+            popup = targets
+                .into_iter()
+                .find(|t| t.is_page() && t.title == "popup");
+            if popup.is_some() {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        }
+
+        let popup = browser
+            .attach_page(&popup.expect("popup is listed").target_id)
+            .await
+            .unwrap();
+        assert_eq!(popup.evaluate("document.title").await.unwrap(), "popup");
+
+        popup.close().await.unwrap();
+        page.close().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_response_cache_serves_repeated_requests() {
+        use std::sync::atomic::Ordering;
+
+        let (addr, hits) = serve_echo().await;
+        let dir = std::env::temp_dir().join(format!("chrome-cdp-it-cache-{}", addr.port()));
+        let manager = create_manager();
+        let browser = manager.get_browser().await.unwrap();
+        let page = browser.new_page_connected(None).await.unwrap();
+        page.set_response_cache(chrome_cdp::ResponseCache::new(
+            &dir,
+            std::time::Duration::from_secs(60),
+        ))
+        .await
+        .unwrap();
+
+        let url = format!("http://{}/cached", addr);
+        for _ in 0..2 {
+            page.goto(&url).await.unwrap();
+            page.wait_for_selector("body").await.unwrap();
+            tokio::time::sleep(std::time::Duration::from_millis(300)).await;
+        }
+        let body = page.evaluate("document.body.innerText").await.unwrap();
+        assert!(body.as_str().unwrap().contains("get /cached"));
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+
+        page.clear_response_cache().await.unwrap();
+        page.close().await.unwrap();
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn test_chrome_log_kept_in_managed_dir() {
+        let dir = std::env::temp_dir().join(format!("chrome-cdp-it-logs-{}", std::process::id()));
+        let browser = chrome_cdp::CdpBrowser::launch_with_options(chrome_cdp::LaunchOptions {
+            executable_path: Some(PathBuf::from("/usr/bin/chromium")),
+            args: vec!["--no-sandbox".to_string(), "--disable-gpu".to_string()],
+            logging: Some(chrome_cdp::ChromeLogging::new(&dir)),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let log = browser.log_path().unwrap().to_path_buf();
+        assert!(log.starts_with(&dir));
+
+        browser.close().await.unwrap();
+        drop(browser);
+        let content = std::fs::read_to_string(&log).unwrap();
+        assert!(content.contains("DevTools listening on"));
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn test_version_info() {
+        let manager = create_manager();
+        let browser = manager.get_browser().await.unwrap();
+
+        let info = browser.version_info().await.unwrap();
+        assert!(info.major().unwrap() >= 100);
+        assert_eq!(info.protocol_version, "1.3");
+        assert!(!info.user_agent.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_scenario_expects_events_across_tabs() {
+        let manager = create_manager();
+        let browser = manager.get_browser().await.unwrap();
+        let mut scenario = chrome_cdp::Scenario::new(&browser).await.unwrap();
+        scenario.open("alice", None).await.unwrap();
+        scenario.open("bob", None).await.unwrap();
+        assert!(scenario.open("bob", None).await.is_err());
+        assert_eq!(scenario.names().collect::<Vec<_>>(), ["alice", "bob"]);
+
+        let bob = scenario.tab("bob").unwrap();
+        let (_, event) = scenario
+            .expect_event(
+                "bob",
+                "Runtime.consoleAPICalled",
+                |params| params["args"][0]["value"] == "ping",
+                std::time::Duration::from_secs(5),
+                async {
+                    scenario.tab("alice")?.evaluate("1").await?;
+                    bob.evaluate("console.log('ping')").await
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(event["type"], "log");
+
+        scenario.close().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_environment_report_reflects_region() {
+        let manager = create_manager();
+        let browser = manager.get_browser().await.unwrap();
+        let page = browser.new_page_connected(None).await.unwrap();
+        page.apply_region(&chrome_cdp::RegionProfile::germany())
+            .await
+            .unwrap();
+
+        let report = page.environment_report().await.unwrap();
+        assert_eq!(report.timezone, "Europe/Berlin");
+        assert_eq!(report.timezone_offset, report.intl_timezone_offset);
+        assert!(!report
+            .issues()
+            .iter()
+            .any(|issue| issue.starts_with("Intl locale")));
+
+        page.close().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_reset_emulation_clears_overrides() {
+        let manager = create_manager();
+        let browser = manager.get_browser().await.unwrap();
+        let ws_url = browser.new_page().await.unwrap();
+        let page = CdpPage::new(&ws_url).await.unwrap();
+
+        page.set_viewport(500, 400, 1.0, false).await.unwrap();
+        page.apply_region(&chrome_cdp::RegionProfile::japan())
+            .await
+            .unwrap();
+        assert_eq!(page.evaluate("window.innerWidth").await.unwrap(), 500);
+
+        page.reset_emulation().await.unwrap();
+        let timezone = page
+            .evaluate("Intl.DateTimeFormat().resolvedOptions().timeZone")
+            .await
+            .unwrap();
+        assert_ne!(timezone, "Asia/Tokyo");
+        assert_ne!(page.evaluate("window.innerWidth").await.unwrap(), 500);
+
+        page.close().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_page_without_domains_enables_network_lazily() {
+        let (addr, _) = serve_echo().await;
+
+        let manager = create_manager();
+        let browser = manager.get_browser().await.unwrap();
+        let ws_url = browser.new_page().await.unwrap();
+        let page = CdpPage::new_with_domains(&ws_url, browser.timeouts(), &[])
+            .await
+            .unwrap();
+        assert!(page.enabled_domains().is_empty());
+
+        let url = format!("http://{}/lazy", addr);
+        let matcher = chrome_cdp::RequestMatcher::new().url_glob(&url);
+        let (request, _) = tokio::join!(
+            page.wait_for_request(&matcher, std::time::Duration::from_secs(10)),
+            async {
+                // Give the wait a moment to enable the Network domain
+                tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+                page.goto(&url).await
+            }
+        );
+        assert_eq!(request.unwrap().url, url);
+        assert_eq!(page.enabled_domains(), [chrome_cdp::Domain::Network]);
+
+        page.close().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_compare_screenshot_ignores_masked_elements() {
+        let manager = create_manager();
+        let browser = manager.get_browser().await.unwrap();
+        let page = browser
+            .new_page_connected(Some(
+                "data:text/html,<h1>Title</h1><p class='clock'>12:00</p>",
+            ))
+            .await
+            .unwrap();
+        let baseline = page.screenshot().await.unwrap();
+
+        page.evaluate("document.querySelector('.clock').textContent = '12:01'")
+            .await
+            .unwrap();
+        let unmasked = page
+            .compare_screenshot(&baseline, &Default::default())
+            .await
+            .unwrap();
+        assert!(!unmasked.is_match());
+
+        let options = chrome_cdp::ScreenshotComparison {
+            ignore_selectors: vec![".clock".to_string()],
+            ..Default::default()
+        };
+        let masked = page.compare_screenshot(&baseline, &options).await.unwrap();
+        assert!(
+            masked.is_match(),
+            "{} pixels differ",
+            masked.differing_pixels
+        );
+
+        page.close().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_close_twice_and_after_target_destroyed() {
+        let manager = create_manager();
+        let browser = manager.get_browser().await.unwrap();
+
+        let page = browser.new_page_connected(None).await.unwrap();
+        let (first, second) = tokio::join!(page.close(), page.close());
+        first.unwrap();
+        second.unwrap();
+        page.close().await.unwrap();
+
+        let page = browser.new_page_connected(None).await.unwrap();
+        let target_id = page
+            .connection()
+            .send_command("Target.getTargetInfo", serde_json::json!({}))
+            .await
+            .unwrap()["targetInfo"]["targetId"]
+            .as_str()
+            .unwrap()
+            .to_string();
+        browser
+            .connection()
+            .await
+            .unwrap()
+            .send_command(
+                "Target.closeTarget",
+                serde_json::json!({ "targetId": target_id }),
+            )
+            .await
+            .unwrap();
+        page.close().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_get_browser_relaunches_dead_browser() {
+        let manager = create_manager();
+        let browser = manager.get_browser().await.unwrap();
+        browser.close().await.unwrap();
+        assert!(browser.has_exited());
+
+        let relaunched = manager.get_browser().await.unwrap();
+        assert!(!std::sync::Arc::ptr_eq(&browser, &relaunched));
+        relaunched.health_check().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_manager_shutdown_closes_browser() {
+        let manager = create_manager();
+        let browser = manager.get_browser().await.unwrap();
+
+        manager.shutdown().await.unwrap();
+        assert!(browser.has_exited());
+        assert!(manager.get_browser().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_storage_state_round_trip() {
+        let (addr, _) = serve_echo().await;
+        let url = format!("http://{}/", addr);
+        let manager = create_manager();
+        let browser = manager.get_browser().await.unwrap();
+
+        let page = browser.new_page_connected(Some(&url)).await.unwrap();
+        page.evaluate(
+            "document.cookie = 'sid=abc; max-age=3600'; localStorage.setItem('token', 'xyz')",
+        )
+        .await
+        .unwrap();
+        let state = page.storage_state().await.unwrap();
+        assert!(state.cookies.iter().any(|c| c["name"] == "sid"));
+        assert_eq!(
+            state.origins[0].local_storage,
+            [("token".to_string(), "xyz".to_string())]
+        );
+
+        let context = browser.new_context().await.unwrap();
+        let restored = context.new_page().await.unwrap();
+        restored.goto(&url).await.unwrap();
+        restored.set_storage_state(&state).await.unwrap();
+        let seen = restored
+            .evaluate("document.cookie + '|' + localStorage.getItem('token')")
+            .await
+            .unwrap();
+        assert_eq!(seen, "sid=abc|xyz");
+    }
+
+    #[tokio::test]
+    async fn test_streamed_upload_and_download() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let mut buf = vec![0; 8192];
+                let _ = socket.read(&mut buf).await;
+                let body = "col\n1\n";
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: text/csv\r\nContent-Disposition: attachment; filename=\"data.csv\"\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                );
+                let _ = socket.write_all(response.as_bytes()).await;
+            }
+        });
+        let manager = create_manager();
+        let browser = manager.get_browser().await.unwrap();
+        let page = browser.new_page_connected(None).await.unwrap();
+
+        // Pretend the browser is remote so contents are streamed
+        page.set_remote_filesystem(true);
+        let upload = std::env::temp_dir().join(format!("chrome-cdp-it-upload-{}.txt", addr.port()));
+        std::fs::write(&upload, "uploaded").unwrap();
+        page.goto("data:text/html,<input type=file id=f>")
+            .await
+            .unwrap();
+        page.set_input_files("#f", &[&upload]).await.unwrap();
+        let text = page
+            .evaluate("document.querySelector('#f').files[0].text()")
+            .await
+            .unwrap();
+        assert_eq!(text, "uploaded");
+        std::fs::remove_file(upload).unwrap();
+
+        let dir = std::env::temp_dir().join(format!("chrome-cdp-it-downloads-{}", addr.port()));
+        page.set_download_capture(&dir).await.unwrap();
+        let trigger = format!("location.href = 'http://{}/export'", addr);
+        let (download, _) = tokio::join!(
+            page.wait_for_download(std::time::Duration::from_secs(10)),
+            page.evaluate(&trigger)
+        );
+        let download = download.unwrap();
+        assert_eq!(download.suggested_filename, "data.csv");
+        assert_eq!(std::fs::read_to_string(&download.path).unwrap(), "col\n1\n");
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_with_page_closes_page_on_success_error_and_panic() {
+        use futures::FutureExt;
+
+        let manager = create_manager();
+        let browser = manager.get_browser().await.unwrap();
+        let count_pages = || async {
+            let targets = browser.targets().await.unwrap();
+            targets.iter().filter(|t| t.is_page()).count()
+        };
+        let before = count_pages().await;
+
+        let title = manager
+            .with_page(|page| async move {
+                page.goto("data:text/html,<title>leased</title>").await?;
+                page.evaluate("document.title").await
+            })
+            .await
+            .unwrap();
+        assert_eq!(title, "leased");
+
+        let failed: chrome_cdp::Result<()> = manager
+            .with_page(|_| async { Err(chrome_cdp::Error::Browser("failed".to_string())) })
+            .await;
+        assert!(failed.is_err());
+
+        let panicked = std::panic::AssertUnwindSafe(
+            manager.with_page(|_| async { panic!("boom") as chrome_cdp::Result<()> }),
+        )
+        .catch_unwind()
+        .await;
+        assert!(panicked.is_err());
+
+        tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+        assert_eq!(count_pages().await, before);
+    }
+
+    #[tokio::test]
+    async fn test_context_guard_closes_pages_when_dropped() {
+        let manager = create_manager();
+        let browser = manager.get_browser().await.unwrap();
+        let count_pages = || async {
+            let targets = browser.targets().await.unwrap();
+            targets.iter().filter(|t| t.is_page()).count()
+        };
+        let before = count_pages().await;
+
+        let context = browser.new_context().await.unwrap();
+        context.new_page().await.unwrap();
+        context.new_page().await.unwrap();
+        assert_eq!(count_pages().await, before + 2);
+        // A panicking test drops its context while unwinding
+        let task = tokio::spawn(async move {
+            let _context = context;
+            panic!("test failed");
+        });
+        assert!(task.await.is_err());
+        tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+        assert_eq!(count_pages().await, before);
+
+        let context = browser.new_context().await.unwrap();
+        context.new_page().await.unwrap();
+        context.close().await.unwrap();
+        context.close().await.unwrap();
+        assert_eq!(count_pages().await, before);
+    }
+
+    #[tokio::test]
+    async fn test_proxy_with_credentials() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        // Proxy demanding Basic auth for user:pass, answering every request itself
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let proxy_addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let mut buf = vec![0; 8192];
+                let n = socket.read(&mut buf).await.unwrap_or(0);
+                let head = String::from_utf8_lossy(&buf[..n]).to_lowercase();
+                let response = if head.contains("proxy-authorization: basic dxnlcjpwyxnz") {
+                    "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nContent-Length: 7\r\nConnection: close\r\n\r\nproxied"
+                } else {
+                    "HTTP/1.1 407 Proxy Authentication Required\r\nProxy-Authenticate: Basic realm=\"test\"\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                };
+                let _ = socket.write_all(response.as_bytes()).await;
+            }
+        });
+
+        let browser = chrome_cdp::CdpBrowser::launch_with_options(chrome_cdp::LaunchOptions {
+            executable_path: Some(PathBuf::from("/usr/bin/chromium")),
+            args: vec!["--no-sandbox".to_string(), "--disable-gpu".to_string()],
+            proxy: Some(
+                chrome_cdp::ProxyConfig::new(format!("http://{}", proxy_addr))
+                    .with_credentials("user", "pass"),
+            ),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let page = browser
+            .new_page_connected(Some("http://proxied.test/"))
+            .await
+            .unwrap();
+        assert_eq!(
+            page.evaluate("document.body.innerText").await.unwrap(),
+            "proxied"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_network_timing_summary() {
+        let (addr, _) = serve_echo().await;
+        let manager = create_manager();
+        let browser = manager.get_browser().await.unwrap();
+        let page = browser.new_page_connected(None).await.unwrap();
+
+        let recorder = page.record_network_timing().await.unwrap();
+        page.goto(&format!("http://{}/timed", addr)).await.unwrap();
+        tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+
+        let timings = recorder.timings();
+        let document = timings.iter().find(|t| t.url.ends_with("/timed")).unwrap();
+        assert!(document.ttfb.is_some());
+        assert!(document.download.is_some());
+        assert_eq!(document.tls, None);
+        let summary = recorder.summary();
+        assert!(summary.requests >= 1);
+        assert!(summary.ttfb.is_some());
+        page.close().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_with_page_retries_after_browser_crash() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let manager = BrowserManager::builder()
+            .executable(PathBuf::from("/usr/bin/chromium"))
+            .args(["--no-sandbox", "--disable-gpu", "--disable-setuid-sandbox"])
+            .relaunch_on_crash(true)
+            .build();
+        let first = manager.get_browser().await.unwrap();
+        let attempts = AtomicUsize::new(0);
+
+        let title = manager
+            .with_page_retrying(|page| {
+                let attempt = attempts.fetch_add(1, Ordering::SeqCst);
+                let browser = first.clone();
+                async move {
+                    if attempt == 0 {
+                        let connection = browser.connection().await?;
+                        let _ = connection
+                            .send_command("Browser.crash", serde_json::json!({}))
+                            .await;
+                        browser.wait().await?;
+                    }
+                    page.goto("data:text/html,<title>second</title>").await?;
+                    page.evaluate("document.title").await
+                }
+            })
+            .await
+            .unwrap();
+        assert_eq!(attempts.load(Ordering::SeqCst), 2);
+        assert_eq!(title, "second");
+        assert!(!std::sync::Arc::ptr_eq(
+            &first,
+            &manager.get_browser().await.unwrap()
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_ignore_certificate_errors() {
+        let manager = BrowserManager::builder()
+            .executable(PathBuf::from("/usr/bin/chromium"))
+            .args(["--no-sandbox", "--disable-gpu", "--disable-setuid-sandbox"])
+            .ignore_certificate_errors(true)
+            .build();
+        let browser = manager.get_browser().await.unwrap();
+        browser.set_ignore_certificate_errors(true).await.unwrap();
+
+        let page = browser.new_page_connected(None).await.unwrap();
+        page.set_ignore_certificate_errors(false).await.unwrap();
+        page.close().await.unwrap();
+
+        let context = browser.new_context().await.unwrap();
+        context.set_ignore_certificate_errors(true);
+        context.new_page().await.unwrap();
+        context.close().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_before_navigation_rewrites_request() {
+        let (addr, _) = serve_echo().await;
+
+        let manager = create_manager();
+        let browser = manager.get_browser().await.unwrap();
+        let page = browser.new_page_connected(None).await.unwrap();
+        page.before_navigation(move |request| async move {
+            if request.url.contains("/admin") {
+                return chrome_cdp::NavigationAction::Block;
+            }
+            chrome_cdp::NavigationAction::Rewrite(
+                request
+                    .with_url(format!("http://{}/rewritten", addr))
+                    .with_header("X-Token", "fresh"),
+            )
+        })
+        .await
+        .unwrap();
+
+        page.goto(&format!("http://{}/original", addr))
+            .await
+            .unwrap();
+        page.wait_for_selector("body").await.unwrap();
+        let body = page.evaluate("document.body.innerText").await.unwrap();
+        let body = body.as_str().unwrap();
+        assert!(body.starts_with("get /rewritten"));
+        assert!(body.contains("x-token: fresh"));
+
+        page.goto(&format!("http://{}/admin", addr)).await.unwrap();
+        tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+        let body = page.evaluate("document.body.innerText").await.unwrap();
+        assert!(!body.as_str().unwrap_or_default().contains("/admin"));
+
+        page.clear_before_navigation().await.unwrap();
+        page.close().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_blank_page_set_content() {
+        let manager = create_manager();
+        let browser = manager.get_browser().await.unwrap();
+        let page = browser.blank_page().await.unwrap();
+        assert_eq!(page.frames()[0].url(), "about:blank");
+
+        page.set_content("<ul><li>a</li><li>b</li></ul><script>document.title = 'set'</script>")
+            .await
+            .unwrap();
+        let count = page
+            .evaluate("document.querySelectorAll('li').length")
+            .await
+            .unwrap();
+        assert_eq!(count, 2);
+        assert_eq!(page.evaluate("document.title").await.unwrap(), "set");
+        page.close().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_event_timeline() {
+        let manager = create_manager();
+        let browser = manager.get_browser().await.unwrap();
+        let ws_url = browser.new_page().await.unwrap();
+        let page = CdpPage::new(&ws_url).await.unwrap();
+        let timeline = page.event_timeline().await.unwrap();
+
+        page.goto("data:text/html,<script>console.log('ready')</script><h1>t</h1>")
+            .await
+            .unwrap();
+        page.wait_for_selector("h1").await.unwrap();
+        tokio::time::sleep(std::time::Duration::from_millis(300)).await;
+
+        let events = timeline.events();
+        assert_eq!(events[0].name, "Page.navigate");
+        assert!(events.windows(2).all(|pair| pair[0].at_ms <= pair[1].at_ms));
+        assert!(events
+            .iter()
+            .any(|e| e.category == chrome_cdp::TimelineCategory::Console
+                && e.detail["text"] == "[log] ready"));
+        assert!(
+            timeline.to_perfetto()["traceEvents"]
+                .as_array()
+                .unwrap()
+                .len()
+                > events.len()
+        );
+        page.close().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_route_change() {
+        let manager = create_manager();
+        let browser = manager.get_browser().await.unwrap();
+        let page = browser.blank_page().await.unwrap();
+        let (addr, _server) = serve_echo().await;
+        page.goto(&format!("http://{}/app", addr)).await.unwrap();
+        page.wait_for_selector("body").await.unwrap();
+
+        let (change, _) = tokio::join!(
+            page.wait_for_route_change(std::time::Duration::from_secs(5)),
+            async {
+                tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+                page.evaluate("history.pushState({}, '', '/app/users')")
+                    .await
+            }
+        );
+        let change = change.unwrap();
+        assert_eq!(change.url, format!("http://{}/app/users", addr));
+        assert_eq!(change.kind, chrome_cdp::RouteChangeKind::PushState);
+
+        let (change, _) = tokio::join!(
+            page.wait_for_route_change(std::time::Duration::from_secs(5)),
+            async {
+                tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+                page.evaluate("location.hash = 'top'").await
+            }
+        );
+        assert_eq!(change.unwrap().kind, chrome_cdp::RouteChangeKind::Fragment);
+        page.close().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_context_wait_for_download() {
+        let manager = create_manager();
+        let browser = manager.get_browser().await.unwrap();
+        let context = browser.new_context().await.unwrap();
+        let dir = std::env::temp_dir().join(format!("cdp-it-context-dl-{}", std::process::id()));
+        context.set_download_dir(&dir).await.unwrap();
+        let page = context.new_page().await.unwrap();
+
+        let trigger = "const a = document.createElement('a'); \
+            a.href = URL.createObjectURL(new Blob(['hello'])); \
+            a.download = 'note.txt'; document.body.append(a); a.click()";
+        let (download, _) = tokio::join!(
+            context.wait_for_download(std::time::Duration::from_secs(10)),
+            async {
+                tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+                page.evaluate(trigger).await
+            }
+        );
+        let download = download.unwrap();
+        assert_eq!(download.suggested_filename, "note.txt");
+        assert_eq!(
+            download.path.parent(),
+            Some(context.download_dir().unwrap().as_path())
+        );
+        assert_eq!(std::fs::read_to_string(&download.path).unwrap(), "hello");
+        context.close().await.unwrap();
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_default_viewport() {
+        let manager = BrowserManager::builder()
+            .executable(PathBuf::from("/usr/bin/chromium"))
+            .args(["--no-sandbox", "--disable-gpu", "--disable-setuid-sandbox"])
+            .viewport(chrome_cdp::Viewport::new(1024, 640).with_scale(2.0))
+            .build();
+        let browser = manager.get_browser().await.unwrap();
+        let page = browser.new_page_connected(None).await.unwrap();
+        let size = page
+            .evaluate("[innerWidth, innerHeight, devicePixelRatio]")
+            .await
+            .unwrap();
+        assert_eq!(size, serde_json::json!([1024, 640, 2]));
+        page.close().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_screenshot_all() {
+        let manager = create_manager();
+        let browser = manager.get_browser().await.unwrap();
+        let first = browser.new_page_connected(None).await.unwrap();
+        let context = browser.new_context().await.unwrap();
+        let _second = context.new_page().await.unwrap();
+
+        let images = browser.screenshot_all().await.unwrap();
+        assert!(images.len() >= 2);
+        for png in images.values() {
+            assert!(png.as_ref().unwrap().starts_with(b"\x89PNG"));
+        }
+        first.close().await.unwrap();
+        context.close().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_context_options_apply_to_pages() {
+        let (addr, _) = serve_echo().await;
+        let manager = create_manager();
+        let browser = manager.get_browser().await.unwrap();
+        let options = chrome_cdp::ContextOptions {
+            user_agent: Some("ContextBot/1.0".into()),
+            locale: Some("de-DE".into()),
+            extra_headers: vec![("X-Job-Id".into(), "42".into())],
+            ..Default::default()
+        };
+        let context = browser.new_context_with_options(options).await.unwrap();
+        let page = context.new_page().await.unwrap();
+
+        page.goto(&format!("http://{}/", addr)).await.unwrap();
+        let head = page
+            .evaluate("document.body.innerText.toLowerCase()")
+            .await
+            .unwrap();
+        let head = head.as_str().unwrap();
+        assert!(head.contains("user-agent: contextbot/1.0"));
+        assert!(head.contains("accept-language: de-de"));
+        assert!(head.contains("x-job-id: 42"));
+        let language = page.evaluate("navigator.language").await.unwrap();
+        assert_eq!(language, "de-DE");
+        context.close().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_dry_run_skips_state_changes() {
+        let manager = create_manager();
+        let browser = manager.get_browser().await.unwrap();
+        let page = browser
+            .new_page_connected(Some("data:text/html,<title>before</title>"))
+            .await
+            .unwrap();
+        let reports = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        page.connection()
+            .set_dry_run(Some(chrome_cdp::DryRun::with_reporter({
+                let reports = reports.clone();
+                move |command| reports.lock().unwrap().push(command.clone())
+            })));
+
+        page.connection()
+            .send_command(
+                "Runtime.evaluate",
+                serde_json::json!({"expression": "document.title = 'after'"}),
+            )
+            .await
+            .unwrap();
+        page.connection().set_dry_run(None);
+
+        let title = page.evaluate("document.title").await.unwrap();
+        assert_eq!(title, "before");
+        let reports = reports.lock().unwrap().clone();
+        assert_eq!(reports.len(), 1);
+        assert!(!reports[0].dispatched);
+        page.close().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_http_endpoint_target_operations() {
+        let manager = create_manager();
+        let browser = manager.get_browser().await.unwrap();
+        let http = browser.http();
+
+        let created = http.new_page(Some("about:blank#http")).await.unwrap();
+        let listed = http.list().await.unwrap();
+        assert!(listed.iter().any(|target| target.id == created.id));
+        http.activate(&created.id).await.unwrap();
+        http.close(&created.id).await.unwrap();
+        assert!(http.protocol().await.unwrap()["domains"].is_array());
+    }
+
+    #[tokio::test]
+    async fn test_failure_capture_saves_artifacts() {
+        let manager = create_manager();
+        let browser = manager.get_browser().await.unwrap();
+        let page = browser
+            .new_page_connected(Some(
+                "data:text/html,<script>console.log('booted')</script><p>empty</p>",
+            ))
+            .await
+            .unwrap();
+        let dir = std::env::temp_dir().join(format!("cdp-failures-{}", uuid::Uuid::new_v4()));
+        page.set_failure_capture(Some(chrome_cdp::FailureCapture::new(&dir)))
+            .await
+            .unwrap();
+        page.evaluate("console.warn('about to fail')")
+            .await
+            .unwrap();
+
+        page.set_timeouts(chrome_cdp::Timeouts {
+            wait: Some(std::time::Duration::from_millis(200)),
+            ..Default::default()
+        });
+        let err = page.wait_for_selector("#missing").await.unwrap_err();
+        let artifacts = err.artifacts().unwrap();
+        assert!(artifacts.screenshot.as_ref().unwrap().exists());
+        assert!(artifacts
+            .console
+            .iter()
+            .any(|line| line.contains("about to fail")));
+        page.close().await.unwrap();
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_registry_opens_pages_on_remote_browser() {
+        let manager = create_manager();
+        let browser = manager.get_browser().await.unwrap();
+        let registry = chrome_cdp::BrowserRegistry::builder()
+            .endpoint(browser.host(), browser.port())
+            .build();
+
+        let page = registry.get_page().await.unwrap();
+        page.set_content("<p id=fleet>ok</p>").await.unwrap();
+        assert_eq!(
+            page.evaluate("document.getElementById('fleet').textContent")
+                .await
+                .unwrap(),
+            "ok"
+        );
+        let status = registry.endpoints();
+        assert!(status[0].healthy);
+        assert_eq!(status[0].active_pages, 1);
+        page.close().await.unwrap();
+        assert_eq!(registry.endpoints()[0].active_pages, 0);
+    }
+
+    #[tokio::test]
+    async fn test_page_pool_freezes_idle_pages() {
+        let manager = create_manager();
+        let browser = manager.get_browser().await.unwrap();
+        let pool = chrome_cdp::PagePool::new(
+            browser,
+            chrome_cdp::PagePoolOptions {
+                size: 2,
+                ..Default::default()
+            },
+        );
+        pool.warm_up().await.unwrap();
+        assert_eq!(pool.idle(), 2);
+
+        let page = pool.checkout().await.unwrap();
+        page.set_content("<p id=warm>ok</p>").await.unwrap();
+        page.release().await;
+
+        // The most recently used page comes back with its document intact
+        let page = pool.checkout().await.unwrap();
+        assert_eq!(
+            page.evaluate("document.getElementById('warm').textContent")
+                .await
+                .unwrap(),
+            "ok"
+        );
+        page.release().await;
+        pool.close().await.unwrap();
+        assert_eq!(pool.idle(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_devtools_frontend_url() {
+        let manager = create_manager();
+        let browser = manager.get_browser().await.unwrap();
+        let page = browser.new_page_connected(None).await.unwrap();
+
+        let url = page.devtools_frontend_url().await.unwrap();
+        assert!(url.starts_with("http"));
+        assert!(url.contains("/devtools/page/"));
+        page.close().await.unwrap();
+    }
 }
 
 // Non-feature-gated test that always runs but skips if feature not enabled